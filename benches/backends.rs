@@ -0,0 +1,49 @@
+// Scanning every table of a database read through the file backend and through a memory
+// map, with the page cache off so that every page comes from the backend. The file scanned
+// is scan.db unless SQRLITE_BENCH_DB names another; for a scan that does not fit in the
+// CPU caches, make a database of about 1 GB with
+//
+//     PRAGMA page_size = 4096;
+//     CREATE TABLE big(id INTEGER PRIMARY KEY, name TEXT, value REAL, data BLOB);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 4000000)
+//     INSERT INTO big SELECT i, printf('row %d', i), i / 3.0, randomblob(230) FROM n;
+//
+// and run
+//
+//     SQRLITE_BENCH_DB=big.db cargo bench --features mmap --bench backends
+
+use std::hint::black_box;
+use std::{env, fs};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sqrlite::Database;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scan.db");
+
+fn scan_all(db: &Database) -> usize {
+    let mut fields = 0;
+    for table in db.tables().unwrap() {
+        for row in table.rows() {
+            fields += black_box(row.unwrap()).values().len();
+        }
+    }
+    fields
+}
+
+fn backends(c: &mut Criterion) {
+    let path = env::var("SQRLITE_BENCH_DB").unwrap_or_else(|_| FIXTURE.to_owned());
+    let mut file = Database::new(&path).unwrap();
+    let mut mapped = Database::open_mmap(&path).unwrap();
+    file.set_page_cache_size(0);
+    mapped.set_page_cache_size(0);
+
+    let mut group = c.benchmark_group("backends");
+    group.throughput(Throughput::Bytes(fs::metadata(&path).unwrap().len()));
+    group.sample_size(10);
+    group.bench_function("file", |b| b.iter(|| scan_all(&file)));
+    group.bench_function("mmap", |b| b.iter(|| scan_all(&mapped)));
+    group.finish();
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
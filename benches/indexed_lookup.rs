@@ -0,0 +1,40 @@
+// A query answered through an index: seeking readings_sensor to one sensor's 294 entries,
+// then fetching each of their rows from the table by rowid. Every fetch walks down from the
+// table's root, so with the page cache on, the interior pages are read from the file once
+// and then served from memory on every fetch and every repeat of the query.
+//
+//     cargo bench --bench indexed_lookup
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sqrlite::{execute, Database, Select};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scan.db");
+
+fn indexed_lookup(c: &mut Criterion) {
+    let mut db = Database::new(FIXTURE).unwrap();
+    let select = Select::parse("SELECT value FROM readings WHERE sensor = 'sensor-7'").unwrap();
+    let rows = execute(&db, &select).unwrap().rows.len();
+
+    // a repeat of the query reads no page from the file
+    let before = db.page_cache_stats();
+    execute(&db, &select).unwrap();
+    let after = db.page_cache_stats();
+    assert_eq!(after.misses, before.misses);
+    assert!(after.hits > before.hits);
+
+    let mut group = c.benchmark_group("indexed_lookup");
+    group.throughput(Throughput::Elements(rows as u64));
+    group.bench_function("cached", |b| {
+        b.iter(|| execute(&db, black_box(&select)).unwrap().rows.len())
+    });
+    db.set_page_cache_size(0);
+    group.bench_function("uncached", |b| {
+        b.iter(|| execute(&db, black_box(&select)).unwrap().rows.len())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, indexed_lookup);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+// Reading and parsing every b-tree page of a table and its index, each with a single read of
+// the whole page into a reused buffer. With the page cache off every page costs one read
+// from the file; with it on, the reads after the first come from memory.
+//
+//     cargo bench --bench page_read
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sqrlite::btree_page::{BtreePage, PageType};
+use sqrlite::cell::CellRef;
+use sqrlite::Database;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scan.db");
+
+// page numbers of every page of a b-tree
+fn tree_pages(db: &Database, root: u32) -> Vec<u32> {
+    let mut pages = vec![];
+    let mut pending = vec![root];
+    let mut buffer = vec![];
+    while let Some(page_num) = pending.pop() {
+        pages.push(page_num);
+        let mut page = BtreePage::default();
+        page.read_page(db, page_num, &mut buffer).unwrap();
+        if let PageType::InteriorTable | PageType::InteriorIndex = page.page_type {
+            for i in 0..page.cell_pointers.len() {
+                let cell = CellRef::get(&page, &buffer, i).unwrap();
+                pending.extend(cell.left_child_pointer());
+            }
+            pending.extend(page.rightmost_ptr);
+        }
+    }
+    pages
+}
+
+fn read_pages(db: &Database, pages: &[u32]) -> usize {
+    let mut page = BtreePage::default();
+    let mut buffer = vec![];
+    let mut cells = 0;
+    for &page_num in pages {
+        page.read_page(db, black_box(page_num), &mut buffer)
+            .unwrap();
+        cells += page.cell_pointers.len();
+    }
+    cells
+}
+
+fn page_read(c: &mut Criterion) {
+    let mut db = Database::new(FIXTURE).unwrap();
+    let schema = db.schema().unwrap();
+    let pages = ["readings", "readings_sensor"]
+        .iter()
+        .flat_map(|name| tree_pages(&db, schema.find(name).unwrap().rootpage))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("read_page");
+    group.throughput(Throughput::Bytes(
+        pages.len() as u64 * u64::from(db.page_size),
+    ));
+    group.bench_function("cached", |b| b.iter(|| read_pages(&db, &pages)));
+    db.set_page_cache_size(0);
+    group.bench_function("uncached", |b| b.iter(|| read_pages(&db, &pages)));
+    group.finish();
+}
+
+criterion_group!(benches, page_read);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+// Reading every row of a 5000-row table end to end: walking the b-tree, decoding each record
+// and following the overflow chains of the rows with long notes.
+//
+//     cargo bench --bench scan
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sqrlite::Database;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scan.db");
+
+fn scan(c: &mut Criterion) {
+    let db = Database::new(FIXTURE).unwrap();
+    let table = db.table("readings").unwrap();
+    let rows = table.count().unwrap();
+
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Elements(rows));
+    group.bench_function("rows", |b| {
+        b.iter(|| {
+            let mut fields = 0;
+            for row in table.rows() {
+                fields += black_box(row.unwrap()).values().len();
+            }
+            fields
+        })
+    });
+    group.bench_function("count", |b| b.iter(|| table.count().unwrap()));
+    group.bench_function("get", |b| {
+        b.iter(|| table.get(black_box(2_500)).unwrap().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+// Decoding varints of each length, from the one-byte serial types and header sizes that
+// make up most of a record to the nine-byte form of a large rowid, and encoding the varints
+// of record headers into a reused buffer, with and without allocating a vector for each.
+//
+//     cargo bench --bench varint
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sqrlite::record::encode;
+use sqrlite::varint::{decode_be, encode_be, encode_into};
+use sqrlite::FieldData;
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_be");
+    for len in [1, 2, 3, 5, 9] {
+        // the smallest value taking `len` bytes, followed by padding as in a page
+        let value: u64 = if len == 9 {
+            1 << 56
+        } else {
+            1 << (7 * (len - 1))
+        };
+        let (_, mut bytes) = encode_be(value);
+        bytes.resize(16, 0);
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &bytes, |b, bytes| {
+            b.iter(|| decode_be(black_box(bytes)).unwrap())
+        });
+    }
+    group.finish();
+
+    // a record header's worth of mixed lengths, as the scan loop sees them
+    let mut stream = Vec::new();
+    for value in [4u64, 1, 1, 7, 13, 0, 2_000, 6, 300_000, 1 << 40] {
+        stream.extend(encode_be(value).1);
+    }
+    c.bench_function("decode_be/stream", |b| {
+        b.iter(|| {
+            let mut rest = black_box(&stream[..]);
+            let mut sum = 0u64;
+            while let Ok((value, len)) = decode_be(rest) {
+                sum = sum.wrapping_add(value);
+                rest = &rest[len..];
+            }
+            sum
+        })
+    });
+}
+
+fn encode_headers(c: &mut Criterion) {
+    // the serial types of a thousand records of a header size, an integer, a short text, a
+    // real and a blob, as a record encoder writes them
+    let values = (0..1000u64)
+        .flat_map(|i| [6, 1 + i % 6, 13 + 2 * (i % 40), 7, 12 + 2 * (i * 37 % 5000)])
+        .collect::<Vec<_>>();
+    let mut out = Vec::with_capacity(values.len() * 9);
+
+    let mut group = c.benchmark_group("encode_headers");
+    group.throughput(Throughput::Elements(values.len() as u64));
+    group.bench_function("encode_be", |b| {
+        b.iter(|| {
+            out.clear();
+            for &value in black_box(&values) {
+                out.extend(encode_be(value).1);
+            }
+            out.len()
+        })
+    });
+    group.bench_function("encode_into", |b| {
+        b.iter(|| {
+            out.clear();
+            let mut buf = [0; 9];
+            for &value in black_box(&values) {
+                let len = encode_into(value, &mut buf);
+                out.extend_from_slice(&buf[..len]);
+            }
+            out.len()
+        })
+    });
+    group.finish();
+
+    let row = [
+        FieldData::Null(()),
+        FieldData::Integer(1_000_000),
+        FieldData::Text("sensor-17".to_owned()),
+        FieldData::Real(21.5),
+        FieldData::Blob(vec![0; 300]),
+    ];
+    c.bench_function("encode_record", |b| b.iter(|| encode(black_box(&row))));
+}
+
+criterion_group!(benches, decode, encode_headers);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+// Filtering the rows of a 31-column table on its third column, c2. get_field reads the
+// record header only as far as that column and decodes it alone, where decode reads every
+// field of every row. Made with
+//
+//     CREATE TABLE metrics(id INTEGER PRIMARY KEY, c1 INTEGER, c2 REAL, c3 TEXT, ..., c30 TEXT);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+//     INSERT INTO metrics SELECT i, i * 1, i * 2 / 7.0, printf('v%d', i * 3), ...,
+//         printf('v%d', i * 30) FROM n;
+//
+// the columns cycling through INTEGER, REAL and TEXT, each value i times the column number.
+//
+//     cargo bench --bench wide_filter
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sqrlite::record::encode;
+use sqrlite::{execute, Database, FieldData, Record, Select, TextEncoding};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wide_rows.db");
+const COLUMN: usize = 2;
+
+fn matches(value: &FieldData) -> bool {
+    matches!(value, FieldData::Real(r) if *r < 100.0)
+}
+
+fn wide_filter(c: &mut Criterion) {
+    let db = Database::new(FIXTURE).unwrap();
+    // the records as stored, with NULL in place of the rowid alias
+    let payloads = db
+        .table("metrics")
+        .unwrap()
+        .rows()
+        .map(|row| {
+            let mut values = row.unwrap().values().to_vec();
+            values[0] = FieldData::Null(());
+            encode(&values)
+        })
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("wide_filter");
+    group.throughput(Throughput::Elements(payloads.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            payloads
+                .iter()
+                .filter(|payload| {
+                    let values = Record::decode(black_box(payload), TextEncoding::Utf8).unwrap();
+                    matches(&values[COLUMN])
+                })
+                .count()
+        })
+    });
+    group.bench_function("get_field", |b| {
+        b.iter(|| {
+            payloads
+                .iter()
+                .filter(|payload| {
+                    let value = Record::get_field(black_box(payload), COLUMN, TextEncoding::Utf8);
+                    matches(&value.unwrap().unwrap())
+                })
+                .count()
+        })
+    });
+
+    let select = Select::parse("SELECT c1 FROM metrics WHERE c2 < 100").unwrap();
+    group.bench_function("select", |b| {
+        b.iter(|| execute(&db, black_box(&select)).unwrap().rows.len())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, wide_filter);
+criterion_main!(benches);
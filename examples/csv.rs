@@ -0,0 +1,47 @@
+// Write every row of a table as CSV, with a header line of column names. Values are quoted
+// as RFC 4180 asks when they hold a comma, a quote or a line break; NULL is an empty field
+// and a blob is written as hex.
+//
+//     cargo run --example csv -- sample.db apples > apples.csv
+
+use std::env;
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+
+use sqrlite::Database;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<_>>();
+    let (Some(path), Some(name)) = (args.get(1), args.get(2)) else {
+        return Err("usage: csv <database> <table>".into());
+    };
+    let db = Database::new(path)?;
+    let table = db.table(name)?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    let header = table
+        .def
+        .columns
+        .iter()
+        .map(|column| csv_field(&column.name))
+        .collect::<Vec<_>>();
+    writeln!(out, "{}", header.join(","))?;
+    for row in table.rows() {
+        let fields = row?
+            .values()
+            .iter()
+            .map(|value| csv_field(&value.to_string()))
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
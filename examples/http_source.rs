@@ -0,0 +1,223 @@
+// Read a database on a web server without downloading all of it, fetching the parts that are
+// needed with HTTP range requests and keeping recently used blocks in a small LRU cache.
+//
+//     cargo run --example http_source -- http://localhost:8000/big.db
+//     cargo run --example http_source -- http://localhost:8000/big.db "SELECT * FROM t WHERE id = 42"
+//
+// Only plain http:// URLs are supported, to stay within the standard library.
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use sqrlite::db::Database;
+use sqrlite::dbinfo::DBInfo;
+use sqrlite::page_source::PageSource;
+use sqrlite::query::{execute, Select};
+
+const BLOCK_SIZE: u64 = 4096;
+const CACHE_BLOCKS: usize = 256;
+
+// Blocks of the remote file, dropping the least recently used once the cache is full
+#[derive(Debug, Default)]
+struct BlockCache {
+    blocks: HashMap<u64, (u64, Vec<u8>)>, // block number to last use and contents
+    clock: u64,
+}
+
+impl BlockCache {
+    fn get(&mut self, block: u64) -> Option<&[u8]> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.blocks.get_mut(&block).map(|(used, bytes)| {
+            *used = clock;
+            bytes.as_slice()
+        })
+    }
+
+    fn insert(&mut self, block: u64, bytes: Vec<u8>) {
+        if self.blocks.len() >= CACHE_BLOCKS {
+            let oldest = self
+                .blocks
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(&block, _)| block);
+            if let Some(oldest) = oldest {
+                self.blocks.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.blocks.insert(block, (self.clock, bytes));
+    }
+}
+
+// Counters shared with main, as the source itself moves into the Database
+#[derive(Debug, Default)]
+struct FetchStats {
+    requests: AtomicU64,
+    bytes: AtomicU64,
+}
+
+#[derive(Debug)]
+struct HttpSource {
+    host: String, // with the port, as the Host header takes it
+    path: String,
+    len: u64,
+    cache: Mutex<BlockCache>,
+    stats: Arc<FetchStats>,
+}
+
+impl HttpSource {
+    fn new(url: &str) -> io::Result<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "only http:// URLs are supported")
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let mut source = Self {
+            host: host.to_owned(),
+            path: path.to_owned(),
+            len: 0,
+            cache: Mutex::default(),
+            stats: Arc::default(),
+        };
+        // the total size comes back in the Content-Range header of any range request
+        let (_, len) = source.get_range(0, 0)?;
+        source.len = len;
+        Ok(source)
+    }
+
+    // Fetch bytes `start` to `end` inclusive, returning them with the size of the whole file
+    fn get_range(&self, start: u64, end: u64) -> io::Result<(Vec<u8>, u64)> {
+        let address = if self.host.contains(':') {
+            self.host.clone()
+        } else {
+            format!("{}:80", self.host)
+        };
+        let mut stream = TcpStream::connect(address)?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nConnection: close\r\n\r\n",
+            self.path, self.host, start, end
+        )?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        self.stats.requests.fetch_add(1, Ordering::Relaxed);
+
+        let bad_response = |reason: &str| io::Error::new(ErrorKind::InvalidData, reason);
+        let split = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| bad_response("response has no end of headers"))?;
+        let head = String::from_utf8_lossy(&response[..split]).into_owned();
+        let body = response[split + 4..].to_vec();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("206") {
+            return Err(bad_response(&format!(
+                "expected a partial content response, got `{}`",
+                status
+            )));
+        }
+        // Content-Range: bytes 0-4095/1048576
+        let total = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-range"))
+            .and_then(|(_, value)| value.rsplit('/').next())
+            .and_then(|total| total.trim().parse().ok())
+            .ok_or_else(|| bad_response("response has no usable Content-Range header"))?;
+        if body.len() as u64 != end - start + 1 {
+            return Err(bad_response("response body is not the requested range"));
+        }
+        self.stats
+            .bytes
+            .fetch_add(body.len() as u64, Ordering::Relaxed);
+        Ok((body, total))
+    }
+}
+
+impl PageSource for HttpSource {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let end = offset + buf.len() as u64;
+        if end > self.len {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "read past the end of the remote file",
+            ));
+        }
+        let (first, last) = (offset / BLOCK_SIZE, (end.max(1) - 1) / BLOCK_SIZE);
+        // held for the whole read, so that blocks fetched for it are not evicted by another
+        let mut cache = self
+            .cache
+            .lock()
+            .map_err(|_| io::Error::other("block cache poisoned"))?;
+
+        // blocks missing from the cache are fetched in a single request spanning all of them
+        let missing = (first..=last)
+            .filter(|&block| cache.get(block).is_none())
+            .collect::<Vec<_>>();
+        if let (Some(&from), Some(&to)) = (missing.first(), missing.last()) {
+            let (bytes, _) =
+                self.get_range(from * BLOCK_SIZE, ((to + 1) * BLOCK_SIZE).min(self.len) - 1)?;
+            for (block, chunk) in (from..=to).zip(bytes.chunks(BLOCK_SIZE as usize)) {
+                cache.insert(block, chunk.to_vec());
+            }
+        }
+
+        for block in first..=last {
+            let bytes = cache
+                .get(block)
+                .ok_or_else(|| io::Error::other("block evicted while reading"))?;
+            let block_start = block * BLOCK_SIZE;
+            let from = offset.max(block_start);
+            let to = end.min(block_start + bytes.len() as u64);
+            buf[(from - offset) as usize..(to - offset) as usize].copy_from_slice(
+                &bytes[(from - block_start) as usize..(to - block_start) as usize],
+            );
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.len)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<_>>();
+    let url = args
+        .get(1)
+        .ok_or("usage: http_source <http://host:port/path> [SELECT statement]")?;
+
+    let source = HttpSource::new(url)?;
+    let (stats, len) = (Arc::clone(&source.stats), source.len);
+    let db = Database::from_source(source)?;
+    let info = DBInfo::read_info(&db)?;
+    println!("{:24}{}", "database page size:", info.db_page_size);
+    println!("{:24}{}", "database page count:", info.db_page_count);
+    println!("{:24}{}", "text encoding:", info.text_encoding.name());
+
+    if let Some(sql) = args.get(2) {
+        let result = execute(&db, &Select::parse(sql)?)?;
+        for row in result.rows {
+            let values = row
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            println!("{}", values.join("|"));
+        }
+    }
+    eprintln!(
+        "fetched {} bytes of {} in {} requests",
+        stats.bytes.load(Ordering::Relaxed),
+        len,
+        stats.requests.load(Ordering::Relaxed)
+    );
+    Ok(())
+}
@@ -0,0 +1,39 @@
+// List the tables and indexes of a database with their row counts, or print one row, using
+// the table handles rather than b-trees and cursors.
+//
+//     cargo run --example tables -- sample.db
+//     cargo run --example tables -- sample.db apples 3
+
+use std::env;
+use std::error::Error;
+
+use sqrlite::Database;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = env::args().collect::<Vec<_>>();
+    let path = args
+        .get(1)
+        .ok_or("usage: tables <database> [<table> <rowid>]")?;
+    let db = Database::new(path)?;
+
+    if let (Some(name), Some(rowid)) = (args.get(2), args.get(3)) {
+        let table = db.table(name)?;
+        match table.get(rowid.parse()?)? {
+            Some(row) => {
+                for (column, value) in row.iter() {
+                    println!("{} = {}", column, value);
+                }
+            }
+            None => println!("{} has no row {}", table.name, rowid),
+        }
+        return Ok(());
+    }
+
+    for table in db.tables()? {
+        println!("table {} ({} rows)", table.name, table.count()?);
+    }
+    for index in db.indexes()? {
+        println!("index {} on {}", index.name, index.def.table);
+    }
+    Ok(())
+}
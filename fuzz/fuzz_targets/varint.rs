@@ -0,0 +1,21 @@
+// Decode arbitrary bytes as varints and as a record: decoding may fail, but must not panic,
+// and a varint must end within nine bytes and re-decode the same from a reader.
+//
+//     cargo +nightly fuzz run varint
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqrlite::record::{Record, TextEncoding};
+use sqrlite::varint::{decode_be, decode_be_i64, decode_from};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((value, len)) = decode_be(data) {
+        assert!((1..=9).contains(&len) && len <= data.len());
+        assert_eq!(decode_from(&mut &data[..]).unwrap(), (value, len));
+        assert_eq!(decode_be_i64(data).unwrap(), (value as i64, len));
+    } else {
+        assert!(data.len() < 9);
+    }
+    let _ = Record::decode(data, TextEncoding::Utf8);
+});
@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+
+use crate::btree_page::{BtreePage, PageType};
+use crate::cell::CellContent;
+use crate::db::Database;
+use crate::error::SqrliteError;
+
+/// Pages and cells found at one level of a b-tree, the root being level 0
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LevelStats {
+    /// pages at this level
+    pub pages: usize,
+    /// cells on those pages
+    pub cells: usize,
+}
+
+/// The shape and size of a b-tree, gathered by `tree_stats`
+#[derive(Debug, Default, Clone)]
+pub struct TreeStats {
+    /// number of levels, so a tree that is a single leaf has depth 1
+    pub depth: usize,
+    /// pages and cells at each level, from the root down
+    pub levels: Vec<LevelStats>,
+    /// pages at the bottom level
+    pub leaf_pages: usize,
+    /// fewest cells on a leaf page
+    pub min_leaf_cells: usize,
+    /// most cells on a leaf page
+    pub max_leaf_cells: usize,
+    /// mean number of cells on a leaf page
+    pub avg_leaf_cells: f64,
+    /// rows of a table, or entries of an index, which its interior cells hold as well
+    pub entries: u64,
+    /// declared payload sizes, including bytes on overflow pages
+    pub payload_bytes: u64,
+    /// pages taken up by payloads spilling out of their cells
+    pub overflow_pages: u64,
+    /// Structural problems found on the way. B-trees are balanced, so leaves at different
+    /// depths, or a page reachable twice, mean the file is corrupt.
+    pub corruption: Vec<String>,
+}
+
+/// Gather the shape of the b-tree rooted at `root_page` in a single traversal
+pub fn tree_stats(db: &Database, root_page: u32) -> Result<TreeStats, SqrliteError> {
+    let mut stats = TreeStats::default();
+    let mut leaf_depth = None;
+    let mut leaf_cells = vec![];
+    let mut visited = HashSet::new();
+    let mut buffer = vec![];
+    let mut pending = vec![(root_page, 0)];
+
+    while let Some((page_num, level)) = pending.pop() {
+        if !visited.insert(page_num) {
+            stats
+                .corruption
+                .push(format!("page {} is reachable more than once", page_num));
+            continue;
+        }
+        let mut page = BtreePage::default();
+        page.read_page(db, page_num, &mut buffer)?;
+
+        if stats.levels.len() <= level {
+            stats.levels.resize(level + 1, LevelStats::default());
+        }
+        let num_cells = page.cell_pointers.len();
+        stats.levels[level].pages += 1;
+        stats.levels[level].cells += num_cells;
+
+        let mut children = vec![];
+        for index in 0..num_cells {
+            let content = CellContent::get_cell_data(&page, &buffer, index)?;
+            match &content {
+                CellContent::LeafTable { payload, .. }
+                | CellContent::LeafIndex { payload, .. }
+                | CellContent::InteriorIndex { payload, .. } => {
+                    stats.entries += 1;
+                    stats.payload_bytes += payload.size;
+                    stats.overflow_pages += payload.overflow_pages(db, &page);
+                }
+                CellContent::InteriorTable { .. } => {}
+            }
+            if let Ok(child) = content.get_left_child_pointer() {
+                children.push(child);
+            }
+        }
+
+        match page.page_type {
+            PageType::LeafTable | PageType::LeafIndex => {
+                leaf_cells.push(num_cells);
+                match leaf_depth {
+                    None => leaf_depth = Some(level),
+                    Some(depth) if depth != level => stats.corruption.push(format!(
+                        "leaf page {} is at level {} but other leaves are at level {}",
+                        page_num, level, depth
+                    )),
+                    Some(_) => {}
+                }
+            }
+            PageType::InteriorTable | PageType::InteriorIndex => {
+                children.extend(page.rightmost_ptr);
+                pending.extend(children.into_iter().rev().map(|child| (child, level + 1)));
+            }
+        }
+    }
+
+    stats.depth = stats.levels.len();
+    stats.leaf_pages = leaf_cells.len();
+    stats.min_leaf_cells = leaf_cells.iter().copied().min().unwrap_or_default();
+    stats.max_leaf_cells = leaf_cells.iter().copied().max().unwrap_or_default();
+    if !leaf_cells.is_empty() {
+        stats.avg_leaf_cells = leaf_cells.iter().sum::<usize>() as f64 / leaf_cells.len() as f64;
+    }
+    Ok(stats)
+}
+
+impl TreeStats {
+    /// Every page of the tree, overflow pages included
+    pub fn pages(&self) -> u64 {
+        self.levels
+            .iter()
+            .map(|level| level.pages as u64)
+            .sum::<u64>()
+            + self.overflow_pages
+    }
+}
@@ -1,72 +1,85 @@
 #![allow(dead_code)]
 
-use std::io::{prelude::*, SeekFrom};
 use std::{error::Error, fmt};
 
 use crate::cell::Cell;
 use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::ptrmap::PtrMap;
 
 const LEAF_BTREE_HEADER_SIZE: u8 = 8;
 const INTERIOR_BTREE_HEADER_SIZE: u8 = 12;
+// SQLite's smallest usable page: 512 bytes with up to 32 of them reserved
+const MIN_USABLE_SIZE: u32 = 480;
 
+/// A page number that cannot be read as a b-tree page: past the end of the database, or one
+/// of the pages SQLite sets aside for locking and pointer maps
 #[derive(Debug)]
-struct BtreeTypeError {
+pub struct InvalidPageNumber {
+    /// the page asked for
+    pub page: u32,
     details: String,
 }
 
-impl BtreeTypeError {
-    fn new() -> Self {
-        Self {
-            details: "invalid b-tree type".to_owned(),
-        }
+impl InvalidPageNumber {
+    pub(crate) fn new(page: u32, details: String) -> Self {
+        Self { page, details }
     }
 }
 
-impl fmt::Display for BtreeTypeError {
+impl fmt::Display for InvalidPageNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.details)
     }
 }
 
-impl Error for BtreeTypeError {}
+impl Error for InvalidPageNumber {}
 
+/// A page whose structure is inconsistent, such as cell pointers into the header or cells
+/// that overlap
 #[derive(Debug)]
-struct PagesExceededError {
-    details: String,
+pub struct CorruptPage {
+    /// the corrupt page
+    pub page: u32,
+    /// what is wrong with it
+    pub reason: String,
 }
 
-impl PagesExceededError {
-    fn new() -> Self {
-        Self {
-            details: "number of pages in database exceeded".to_string(),
-        }
+impl CorruptPage {
+    fn new(page: u32, reason: String) -> Self {
+        Self { page, reason }
     }
 }
 
-impl fmt::Display for PagesExceededError {
+impl fmt::Display for CorruptPage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.details)
+        write!(f, "page {} is corrupt: {}", self.page, self.reason)
     }
 }
 
-impl Error for PagesExceededError {}
+impl Error for CorruptPage {}
 
+/// The four kinds of b-tree page, told apart by the first byte of the page header
 #[derive(Debug)]
 pub enum PageType {
+    /// an interior page of an index b-tree, flag 0x02
     InteriorIndex,
+    /// an interior page of a table b-tree, flag 0x05
     InteriorTable,
+    /// a leaf page of an index b-tree, flag 0x0a
     LeafIndex,
+    /// a leaf page of a table b-tree, flag 0x0d
     LeafTable,
 }
 
 impl PageType {
-    fn get_page_type(flag: u8) -> Result<Self, BtreeTypeError> {
+    fn get_page_type(flag: u8) -> Option<Self> {
         match flag {
-            0x02 => Ok(Self::InteriorIndex),
-            0x05 => Ok(Self::InteriorTable),
-            0x0a => Ok(Self::LeafIndex),
-            0x0d => Ok(Self::LeafTable),
-            _ => Err(BtreeTypeError::new()),
+            0x02 => Some(Self::InteriorIndex),
+            0x05 => Some(Self::InteriorTable),
+            0x0a => Some(Self::LeafIndex),
+            0x0d => Some(Self::LeafTable),
+            _ => None,
         }
     }
 
@@ -78,18 +91,35 @@ impl PageType {
     }
 }
 
+/// A b-tree page with its header, cell pointer array and freeblock chain parsed. The cells
+/// themselves are read from the page's bytes as they are needed.
 #[derive(Debug)]
 pub struct BtreePage {
+    /// what kind of b-tree page this is
     pub page_type: PageType,
-    pub page_num: u32, // page numbers are indexed from ONE per SQLite convention
-    pub file_starting_position: u64, // start of the page relative to beginning of db file in bytes
+    /// page numbers are indexed from ONE per SQLite convention
+    pub page_num: u32,
+    /// start of the page relative to beginning of db file in bytes
+    pub file_starting_position: u64,
+    /// number of cells on the page, as the header gives it
     pub num_cells: u16,
-    pub first_cell_start: u16,
+    /// start of the cell content area
+    pub first_cell_start: u32,
+    /// offset of each cell within the page, in key order
     pub cell_pointers: Vec<u16>,
+    /// offset of the first freeblock, 0 if there is none
+    pub first_freeblock: u16,
+    /// (offset, size) of each freeblock, in chain order
+    pub freeblocks: Vec<(u16, u16)>,
+    /// free bytes in fragments too small for a freeblock
+    pub fragmented_bytes: u8,
+    /// 8 bytes for leaf pages, 12 for interior pages, which also hold the rightmost pointer
     pub header_size: u8,
+    /// the first 8 bytes of the page header, as stored
     pub header: [u8; 8],
+    /// the child holding keys greater than every cell's, on interior pages
     pub rightmost_ptr: Option<u32>,
-    page_size: u16, // for calculating cell sizes (from db)
+    usable_size: u32, // bytes of the page before the reserved space at its end
 }
 
 impl Default for BtreePage {
@@ -101,111 +131,558 @@ impl Default for BtreePage {
             num_cells: 0,
             first_cell_start: 0,
             cell_pointers: vec![],
+            first_freeblock: 0,
+            freeblocks: vec![],
+            fragmented_bytes: 0,
             header_size: 8,
             header: [0u8; 8],
             rightmost_ptr: None,
-            page_size: 0,
+            usable_size: 0,
         }
     }
 }
 
 impl BtreePage {
-    pub fn new(db: &mut Database) -> Result<Self, Box<dyn Error>> {
+    /// Read the b-tree page at the start of page 1, which is the root of sqlite_schema
+    pub fn new(db: &Database) -> Result<Self, SqrliteError> {
         let mut btree_pg = BtreePage::default();
-        btree_pg
-            .read_page_header(db, 1)
-            .map_err(|e| e.to_string())?;
-        btree_pg.page_size = db.page_size;
+        btree_pg.read_page_header(db, 1)?;
         Ok(btree_pg)
     }
 
-    pub fn read_page_header(&mut self, db: &mut Database, page: u32) -> Result<(), Box<dyn Error>> {
-        validate_page_num(db, page).map_err(|e| e.to_string())?;
-        self.page_num = page;
-        self.file_starting_position = ((page - 1) as u64) * (db.page_size as u64);
+    /// Read and parse a page, allocating a buffer for it; `read_page` reuses one
+    pub fn read_page_header(&mut self, db: &Database, page: u32) -> Result<(), SqrliteError> {
+        self.read_page(db, page, &mut vec![])
+    }
 
-        self.header = [0u8; 8];
-        let pg_header_start: u64 = if page == 1 {
-            100
-        } else {
-            self.file_starting_position
+    /// Read a whole page into `buffer` with a single read and parse it. The buffer can be
+    /// reused from page to page.
+    pub fn read_page(
+        &mut self,
+        db: &Database,
+        page: u32,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), SqrliteError> {
+        validate_page_num(db, page)?;
+        db.read_page_into(page, buffer)?;
+
+        *self = Self::parse(page, buffer, db.page_size - u32::from(db.reserved_space))?;
+        Ok(())
+    }
+
+    /// Parse the header, rightmost pointer, cell pointer array and freeblock chain of a page
+    /// from its bytes, of which the first `usable_size` hold b-tree content. Page 1 begins
+    /// with the 100-byte database header, which is skipped.
+    pub fn parse(page_num: u32, bytes: &[u8], usable_size: u32) -> Result<Self, SqrliteError> {
+        let usable_end = usable_size as usize;
+        // the spill thresholds of cells are only defined from this size up
+        if usable_size < MIN_USABLE_SIZE {
+            return Err(CorruptPage::new(
+                page_num,
+                format!(
+                    "usable size {} is less than the minimum of {}",
+                    usable_size, MIN_USABLE_SIZE
+                ),
+            )
+            .into());
+        }
+        if usable_end > bytes.len() {
+            return Err(CorruptPage::new(
+                page_num,
+                format!(
+                    "usable size {} exceeds the {} bytes of the page",
+                    usable_size,
+                    bytes.len()
+                ),
+            )
+            .into());
+        }
+        let mut page = BtreePage {
+            page_num,
+            file_starting_position: u64::from(page_num.saturating_sub(1)) * bytes.len() as u64,
+            usable_size,
+            ..Default::default()
         };
 
-        db.file
-            .seek(SeekFrom::Start(pg_header_start))
-            .map_err(|e| e.to_string())?;
-        db.file
-            .read_exact(&mut self.header)
-            .map_err(|e| "error reading page header: ".to_owned() + &e.to_string())?;
-        // db.file.read_exact_at(&mut page_header, pg_header_start);
+        // the first page starts with the database header
+        let pg_header_start = if page_num == 1 { 100 } else { 0 };
+        page.header = bytes
+            .get(pg_header_start..pg_header_start + 8)
+            .and_then(|header| header.try_into().ok())
+            .ok_or_else(|| {
+                CorruptPage::new(page_num, "page is too short for a header".to_owned())
+            })?;
 
         // read btree page type from first byte and get header size
-        self.page_type = PageType::get_page_type(self.header[0]).map_err(|e| e.to_string())?;
-        self.header_size = self.page_type.get_header_size();
-        self.num_cells = u16::from_be_bytes([self.header[3], self.header[4]]);
-        self.first_cell_start = u16::from_be_bytes([self.header[5], self.header[6]]);
+        page.page_type = PageType::get_page_type(page.header[0]).ok_or_else(|| {
+            CorruptPage::new(
+                page_num,
+                format!("invalid b-tree page type {:#04x}", page.header[0]),
+            )
+        })?;
+        page.header_size = page.page_type.get_header_size();
+        page.first_freeblock = u16::from_be_bytes([page.header[1], page.header[2]]);
+        page.num_cells = u16::from_be_bytes([page.header[3], page.header[4]]);
+        // a content area starting at 65536, on an empty 64 KiB page, is stored as 0
+        page.first_cell_start = match u16::from_be_bytes([page.header[5], page.header[6]]) {
+            0 => 65536,
+            start => u32::from(start),
+        };
+        page.fragmented_bytes = page.header[7];
 
-        // read the right-most pointer if the page is an interior b-tree
-        self.rightmost_ptr = match self.page_type {
+        // read the cell pointer array immediately following the page header, and before it
+        // the right-most pointer if the page is an interior b-tree
+        let pointers_start = pg_header_start + page.header_size as usize;
+        let pointers_end = pointers_start + 2 * page.num_cells as usize;
+        if pointers_end > usable_end {
+            return Err(CorruptPage::new(
+                page_num,
+                format!(
+                    "cell pointer array of {} cells overruns the page",
+                    page.num_cells
+                ),
+            )
+            .into());
+        }
+        page.rightmost_ptr = match page.page_type {
             PageType::InteriorTable | PageType::InteriorIndex => {
-                let mut pointer_buf = [0u8; 4];
-                db.file
-                    .seek(SeekFrom::Start(
-                        pg_header_start + u64::from(self.header_size) - 4,
-                    ))
-                    .map_err(|e| e.to_string())?;
-                db.file
-                    .read_exact(&mut pointer_buf)
-                    .map_err(|e| e.to_string())?;
-                Some(u32::from_be_bytes(pointer_buf))
+                let ptr = &bytes[pointers_start - 4..pointers_start];
+                Some(u32::from_be_bytes([ptr[0], ptr[1], ptr[2], ptr[3]]))
             }
             _ => None,
         };
+        page.cell_pointers = bytes[pointers_start..pointers_end]
+            .chunks_exact(2)
+            .map(|ptr| u16::from_be_bytes([ptr[0], ptr[1]]))
+            .collect();
+        let content_start = pointers_end.max(page.first_cell_start as usize);
+        for (index, &ptr) in page.cell_pointers.iter().enumerate() {
+            if (ptr as usize) < content_start || ptr as usize >= usable_end {
+                return Err(CorruptPage::new(
+                    page_num,
+                    format!(
+                        "cell pointer {} is {}, outside the cell content area {}..{}",
+                        index, ptr, content_start, usable_end
+                    ),
+                )
+                .into());
+            }
+        }
+        page.freeblocks = page.read_freeblocks(bytes, pointers_end)?;
+        page.check_cell_extents(bytes)?;
 
-        // read the cell pointer array immediately following the page header
-        self.cell_pointers = vec![];
-        let mut cell_ptr = [0u8; 2];
-        for i in (0..self.num_cells * 2).step_by(2) {
-            db.file
-                .seek(SeekFrom::Start(
-                    pg_header_start + u64::from(self.header_size) + u64::from(i),
+        Ok(page)
+    }
+
+    // Follow the chain of freeblocks, each of which starts with the offset of the next one
+    // and its own size. The chain must run forward through the content area, which also
+    // guarantees that it ends.
+    fn read_freeblocks(
+        &self,
+        page_buf: &[u8],
+        pointers_end: usize,
+    ) -> Result<Vec<(u16, u16)>, SqrliteError> {
+        let usable_end = self.usable_size() as usize;
+        let mut freeblocks = vec![];
+        let mut offset = self.first_freeblock as usize;
+        let mut previous_end = pointers_end;
+        while offset != 0 {
+            let index = freeblocks.len();
+            let corrupt = |reason: String| CorruptPage::new(self.page_num, reason);
+            if offset < previous_end {
+                return Err(corrupt(format!(
+                    "freeblock {} at offset {} does not follow the end of the previous block at {}",
+                    index, offset, previous_end
                 ))
-                .map_err(|e| e.to_string())?;
-            db.file
-                .read_exact(&mut cell_ptr)
-                .map_err(|e| e.to_string())?;
-            self.cell_pointers.push(u16::from_be_bytes(cell_ptr))
+                .into());
+            }
+            if offset + 4 > usable_end {
+                return Err(corrupt(format!(
+                    "freeblock {} at offset {} is outside the usable area",
+                    index, offset
+                ))
+                .into());
+            }
+            let next = u16::from_be_bytes([page_buf[offset], page_buf[offset + 1]]);
+            let size = u16::from_be_bytes([page_buf[offset + 2], page_buf[offset + 3]]);
+            if size < 4 || offset + size as usize > usable_end {
+                return Err(corrupt(format!(
+                    "freeblock {} at offset {} has invalid size {}",
+                    index, offset, size
+                ))
+                .into());
+            }
+            freeblocks.push((offset as u16, size));
+            previous_end = offset + size as usize;
+            offset = next as usize;
         }
+        Ok(freeblocks)
+    }
 
+    // Make sure no two cells or freeblocks share bytes. Cells are measured from their
+    // contents, which also keeps each of them inside the usable area.
+    fn check_cell_extents(&self, page_buf: &[u8]) -> Result<(), SqrliteError> {
+        let mut extents = self
+            .cells(page_buf)
+            .enumerate()
+            .map(|(index, cell)| {
+                cell.map(|cell| (cell.offset, cell.offset + cell.size as u64, "cell", index))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        extents.extend(
+            self.freeblocks
+                .iter()
+                .enumerate()
+                .map(|(index, &(offset, size))| {
+                    (
+                        offset as u64,
+                        offset as u64 + size as u64,
+                        "freeblock",
+                        index,
+                    )
+                }),
+        );
+        extents.sort_unstable();
+        for pair in extents.windows(2) {
+            let ((_, end, kind, index), (start, _, next_kind, next_index)) = (pair[0], pair[1]);
+            if start < end {
+                return Err(CorruptPage::new(
+                    self.page_num,
+                    format!(
+                        "{} {} and {} {} overlap",
+                        kind, index, next_kind, next_index
+                    ),
+                )
+                .into());
+            }
+        }
         Ok(())
     }
 
-    pub fn get_page_cells(&self) -> Vec<Cell> {
-        let mut pointers = self.cell_pointers.clone();
-        pointers.sort_unstable();
+    /// Unused bytes of the page as (bytes in freeblocks, bytes in fragments, bytes in the gap
+    /// between the cell pointer array and the cell content area)
+    pub fn free_space(&self) -> (u32, u32, u32) {
+        let freeblock_bytes = self.freeblocks.iter().map(|&(_, size)| size as u32).sum();
+        let pointers_end = if self.page_num == 1 { 100 } else { 0 }
+            + self.header_size as u32
+            + 2 * self.cell_pointers.len() as u32;
+        let gap = self.first_cell_start.saturating_sub(pointers_end);
+        (freeblock_bytes, self.fragmented_bytes as u32, gap)
+    }
 
-        pointers
-            .iter()
-            .enumerate()
-            .map(|(i, offset)| {
-                let size = if i == pointers.len() - 1 {
-                    self.page_size - offset
-                } else {
-                    pointers[i + 1] - offset
-                };
-                Cell {
-                    offset: *offset as u64,
-                    size: size as usize,
-                }
+    /// Bytes of each page available to b-tree content; the rest is reserved for extensions
+    pub fn usable_size(&self) -> u64 {
+        u64::from(self.usable_size)
+    }
+
+    /// Get the cell at a position in the cell pointer array, i.e. the index-th cell in key
+    /// order, measuring it from the contents of the page. Reads stop at the end of the usable
+    /// area, before any reserved bytes.
+    pub fn get_cell(&self, page_buf: &[u8], index: usize) -> Result<Cell, SqrliteError> {
+        let offset = *self.cell_pointers.get(index).ok_or_else(|| {
+            CorruptPage::new(self.page_num, format!("there is no cell {}", index))
+        })?;
+        let usable_end = (self.usable_size() as usize).min(page_buf.len());
+        page_buf
+            .get(offset as usize..usable_end)
+            .and_then(|bytes| Cell::measure(self, offset, bytes))
+            .map(|cell| Cell { index, ..cell })
+            .ok_or_else(|| {
+                CorruptPage::new(
+                    self.page_num,
+                    format!("cell {} extends past the usable area of the page", index),
+                )
+                .into()
             })
-            .collect::<Vec<Cell>>()
+    }
+
+    /// Iterate over the cells of a page in key order, which is the order of the cell pointer
+    /// array rather than the order of the cells in the content area
+    pub fn cells<'a>(
+        &'a self,
+        page_buf: &'a [u8],
+    ) -> impl Iterator<Item = Result<Cell, SqrliteError>> + 'a {
+        (0..self.cell_pointers.len()).map(move |index| self.get_cell(page_buf, index))
     }
 }
 
-fn validate_page_num(db: &Database, page: u32) -> Result<(), PagesExceededError> {
-    if page > db.page_count {
-        Err(PagesExceededError::new())
+pub(crate) fn validate_page_num(db: &Database, page: u32) -> Result<(), InvalidPageNumber> {
+    let details = if page == 0 {
+        "page 0 does not exist; pages are numbered from 1".to_owned()
+    } else if page > db.page_count {
+        format!(
+            "page {} is past the last page of the database, {}",
+            page, db.page_count
+        )
+    } else if page == db.lock_byte_page() {
+        format!(
+            "page {} is the lock-byte page and does not hold b-tree data",
+            page
+        )
+    } else if PtrMap::new(db).is_some_and(|map| map.is_ptrmap_page(page)) {
+        format!(
+            "page {} is a pointer-map page and does not hold b-tree data",
+            page
+        )
     } else {
-        Ok(())
+        return Ok(());
+    };
+    Err(InvalidPageNumber::new(page, details))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 512;
+    // a table leaf cell: payload size 3, rowid 1, and the record (7)
+    const CELL: [u8; 5] = [3, 1, 2, 1, 7];
+
+    // Page 2, a table leaf holding a copy of CELL at each of the given offsets, in that
+    // order, and freeblocks of (offset, next, size)
+    fn leaf(cells: &[u16], freeblocks: &[(u16, u16, u16)]) -> Vec<u8> {
+        let mut bytes = vec![0; PAGE_SIZE];
+        let first_freeblock = freeblocks.first().map_or(0, |&(offset, _, _)| offset);
+        let content_start = cells.iter().min().copied().unwrap_or(PAGE_SIZE as u16);
+        bytes[0] = 0x0d;
+        bytes[1..3].copy_from_slice(&first_freeblock.to_be_bytes());
+        bytes[3..5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+        bytes[5..7].copy_from_slice(&content_start.to_be_bytes());
+        for (i, &offset) in cells.iter().enumerate() {
+            bytes[8 + 2 * i..10 + 2 * i].copy_from_slice(&offset.to_be_bytes());
+            let offset = offset as usize;
+            let end = (offset + CELL.len()).min(PAGE_SIZE);
+            bytes[offset..end].copy_from_slice(&CELL[..end - offset]);
+        }
+        for &(offset, next, size) in freeblocks {
+            let offset = offset as usize;
+            bytes[offset..offset + 2].copy_from_slice(&next.to_be_bytes());
+            bytes[offset + 2..offset + 4].copy_from_slice(&size.to_be_bytes());
+        }
+        bytes
+    }
+
+    // (page number, page bytes, usable size, page type and cell count or error reason)
+    type Case = (
+        u32,
+        Vec<u8>,
+        u32,
+        Result<(&'static str, usize), &'static str>,
+    );
+
+    fn page_with_header(page_num: u32, len: usize, header: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        let start = if page_num == 1 { 100 } else { 0 };
+        bytes[start..start + header.len()].copy_from_slice(header);
+        bytes
+    }
+
+    #[test]
+    fn parse_table() {
+        let cases: Vec<Case> = vec![
+            (2, leaf(&[], &[]), 512, Ok(("LeafTable", 0))),
+            (2, leaf(&[507, 502], &[]), 512, Ok(("LeafTable", 2))),
+            (
+                1,
+                page_with_header(1, 512, &[0x0d, 0, 0, 0, 0, 0x02, 0, 0]),
+                512,
+                Ok(("LeafTable", 0)),
+            ),
+            (
+                3,
+                page_with_header(3, 512, &[0x05, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 7]),
+                512,
+                Ok(("InteriorTable", 0)),
+            ),
+            (
+                3,
+                page_with_header(3, 1024, &[0x0a, 0, 0, 0, 0, 0x04, 0, 0]),
+                1000,
+                Ok(("LeafIndex", 0)),
+            ),
+            (
+                3,
+                page_with_header(3, 512, &[0x02, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 9]),
+                480,
+                Ok(("InteriorIndex", 0)),
+            ),
+            (
+                2,
+                page_with_header(2, 512, &[0x00]),
+                512,
+                Err("invalid b-tree page type 0x00"),
+            ),
+            (
+                2,
+                page_with_header(2, 512, &[0x0d, 0, 0, 0x01, 0]),
+                512,
+                Err("overruns the page"),
+            ),
+            (1, vec![0; 104], 104, Err("less than the minimum")),
+            (2, leaf(&[], &[]), 479, Err("less than the minimum of 480")),
+            (2, leaf(&[], &[]), 34, Err("less than the minimum")),
+            (2, leaf(&[], &[]), 1024, Err("exceeds the 512 bytes")),
+        ];
+        for (i, (page_num, bytes, usable_size, expected)) in cases.into_iter().enumerate() {
+            let parsed = BtreePage::parse(page_num, &bytes, usable_size);
+            match (parsed, expected) {
+                (Ok(page), Ok((page_type, cells))) => {
+                    assert_eq!(format!("{:?}", page.page_type), page_type, "case {}", i);
+                    assert_eq!(page.cell_pointers.len(), cells, "case {}", i);
+                }
+                (Err(SqrliteError::CorruptPage(e)), Err(reason)) => {
+                    assert!(e.reason.contains(reason), "case {}: {}", i, e.reason);
+                }
+                (parsed, expected) => panic!("case {}: {:?}, expected {:?}", i, parsed, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn rightmost_pointer_of_interior_pages() {
+        let bytes = page_with_header(3, 512, &[0x05, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0x01, 0x02]);
+        let page = BtreePage::parse(3, &bytes, 512).unwrap();
+        assert_eq!(page.rightmost_ptr, Some(0x0102));
+        assert_eq!(page.header_size, 12);
+        assert_eq!(
+            BtreePage::parse(2, &leaf(&[], &[]), 512)
+                .unwrap()
+                .rightmost_ptr,
+            None
+        );
+    }
+
+    #[test]
+    fn empty_64k_page_content_start() {
+        let bytes = page_with_header(2, 65536, &[0x0d, 0, 0, 0, 0, 0, 0, 0]);
+        let page = BtreePage::parse(2, &bytes, 65536).unwrap();
+        assert_eq!(page.first_cell_start, 65536);
+        assert_eq!(page.free_space(), (0, 0, 65536 - 8));
+    }
+
+    #[test]
+    fn cell_pointer_before_the_content_start() {
+        let mut bytes = leaf(&[507, 502], &[]);
+        // the header claims the content area begins after the second cell
+        bytes[5..7].copy_from_slice(&505u16.to_be_bytes());
+        assert_corrupt(
+            &bytes,
+            512,
+            "cell pointer 1 is 502, outside the cell content area 505..512",
+        );
+
+        // on a 64 KiB page a stored start of 0 puts every pointer before it
+        let mut bytes = vec![0; 65536];
+        bytes[..8].copy_from_slice(&[0x0d, 0, 0, 0, 1, 0, 0, 0]);
+        bytes[8..10].copy_from_slice(&65000u16.to_be_bytes());
+        assert_corrupt(
+            &bytes,
+            65536,
+            "cell pointer 0 is 65000, outside the cell content area 65536..65536",
+        );
+    }
+
+    fn assert_corrupt(bytes: &[u8], usable_size: u32, reason: &str) {
+        match BtreePage::parse(2, bytes, usable_size) {
+            Err(SqrliteError::CorruptPage(e)) => {
+                assert_eq!(e.page, 2);
+                assert!(e.reason.contains(reason), "{}", e.reason);
+            }
+            other => panic!("expected a corrupt page, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn well_formed_leaf() {
+        let bytes = leaf(&[507, 502], &[(400, 480, 10), (480, 0, 4)]);
+        let page = BtreePage::parse(2, &bytes, PAGE_SIZE as u32).unwrap();
+        assert_eq!(page.cell_pointers, [507, 502]);
+        assert_eq!(page.freeblocks, [(400, 10), (480, 4)]);
+        assert_eq!(page.free_space(), (14, 0, 502 - 12));
+        let cells = page.cells(&bytes).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(cells[0].offset, 507);
+        assert_eq!(cells[1].size, CELL.len());
+    }
+
+    #[test]
+    fn overlapping_cells() {
+        assert_corrupt(
+            &leaf(&[500, 503], &[]),
+            PAGE_SIZE as u32,
+            "cell 0 and cell 1 overlap",
+        );
+        // the same cell pointed to twice
+        assert_corrupt(&leaf(&[500, 500], &[]), PAGE_SIZE as u32, "overlap");
+    }
+
+    #[test]
+    fn cell_overlapping_a_freeblock() {
+        let bytes = leaf(&[500], &[(496, 0, 6)]);
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "overlap");
+    }
+
+    #[test]
+    fn cell_pointer_past_the_usable_end() {
+        // 12 bytes reserved at the end of the page
+        let usable_size = PAGE_SIZE as u32 - 12;
+        assert_corrupt(
+            &leaf(&[505], &[]),
+            usable_size,
+            "outside the cell content area",
+        );
+        // starts inside the usable area but runs into the reserved bytes
+        assert_corrupt(
+            &leaf(&[498], &[]),
+            usable_size,
+            "extends past the usable area",
+        );
+    }
+
+    #[test]
+    fn cell_pointer_into_the_header() {
+        let mut bytes = leaf(&[500], &[]);
+        bytes[8..10].copy_from_slice(&4u16.to_be_bytes());
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "outside the cell content area");
+    }
+
+    #[test]
+    fn freeblock_loop() {
+        assert_corrupt(
+            &leaf(&[507], &[(400, 400, 8)]),
+            PAGE_SIZE as u32,
+            "does not follow",
+        );
+        let bytes = leaf(&[507], &[(400, 450, 8), (450, 400, 8)]);
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "does not follow");
+    }
+
+    #[test]
+    fn freeblock_past_the_page() {
+        let mut bytes = leaf(&[500], &[]);
+        bytes[1..3].copy_from_slice(&510u16.to_be_bytes());
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "outside the usable area");
+
+        bytes[1..3].copy_from_slice(&600u16.to_be_bytes());
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "outside the usable area");
+
+        // starts inside the page but its size runs past the end
+        let bytes = leaf(&[507], &[(480, 0, 40)]);
+        assert_corrupt(&bytes, PAGE_SIZE as u32, "invalid size 40");
+    }
+
+    #[test]
+    fn page_numbers_start_at_one() {
+        let db =
+            Database::from_bytes(include_bytes!("../tests/fixtures/scan.db").to_vec()).unwrap();
+        assert!(validate_page_num(&db, 1).is_ok());
+        assert!(validate_page_num(&db, db.page_count).is_ok());
+        for page in [0, db.page_count + 1] {
+            let err = validate_page_num(&db, page).unwrap_err();
+            assert_eq!(err.page, page);
+        }
+
+        let mut buffer = vec![];
+        let result = BtreePage::default().read_page(&db, 0, &mut buffer);
+        assert!(matches!(result, Err(SqrliteError::InvalidPageNumber(_))));
     }
 }
@@ -1,17 +1,21 @@
 #![allow(dead_code)]
 
 use std::{
+    borrow::Cow,
+    collections::HashSet,
     error::Error,
     fmt,
-    io::{BufReader, Read, Seek, SeekFrom},
+    io::{self, Read, Seek, SeekFrom},
 };
 
+use crate::error::SqrliteError;
 use crate::{
-    btree_page::{BtreePage, PageType},
-    db::Database,
-    varint::decode_be,
+    btree_page::{validate_page_num, BtreePage, PageType},
+    db::{CorruptData, Database},
+    varint::{decode_be, decode_be_i64, push_varint, TruncatedVarintError},
 };
 
+/// A field asked of a kind of cell that does not have it, such as the rowid of an index cell
 #[derive(Debug)]
 pub struct InvalidFieldError {
     details: String,
@@ -36,117 +40,569 @@ impl fmt::Display for InvalidFieldError {
 
 impl Error for InvalidFieldError {}
 
+/// An overflow chain that ends early, loops, or leads to a page that cannot be part of it
+#[derive(Debug)]
+pub struct OverflowError {
+    details: String,
+}
+
+impl OverflowError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for OverflowError {}
+
+/// A cell whose bytes do not parse as the kind of cell its page holds
+#[derive(Debug)]
+pub struct CellParseError {
+    /// the page holding the cell
+    pub page: u32,
+    /// position of the cell in the page's cell pointer array
+    pub cell_index: usize,
+    /// what is wrong with the cell
+    pub reason: String,
+}
+
+impl fmt::Display for CellParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cell {} of page {} is malformed: {}",
+            self.cell_index, self.page, self.reason
+        )
+    }
+}
+
+impl Error for CellParseError {}
+
+/// Where a cell lies within its page
 #[derive(Debug, Default)]
 pub struct Cell {
+    /// offset of the cell from the start of the page
     pub offset: u64,
+    /// bytes the cell takes up on the page, overflow page number included
     pub size: usize,
+    /// position in the page's cell pointer array
+    pub index: usize,
 }
 
-#[derive(Debug, Default)]
+impl Cell {
+    /// Measure the cell at `offset` of a page from its own structure: the leading child pointer
+    /// and varints, the part of the payload stored on the page and any overflow page number.
+    /// `bytes` runs from the start of the cell to the end of the page's usable area; None means
+    /// the cell would extend past it.
+    pub fn measure(page: &BtreePage, offset: u16, bytes: &[u8]) -> Option<Self> {
+        let varint_at = |start: usize| match bytes.get(start..) {
+            Some(rest) if !rest.is_empty() => decode_be(rest).ok(),
+            _ => None,
+        };
+
+        let size = match page.page_type {
+            PageType::InteriorTable => 4 + varint_at(4)?.1,
+            _ => {
+                let start = match page.page_type {
+                    PageType::InteriorIndex => 4,
+                    _ => 0,
+                };
+                let (payload_size, varint_len) = varint_at(start)?;
+                let mut header_len = start + varint_len;
+                if let PageType::LeafTable = page.page_type {
+                    header_len += varint_at(header_len)?.1;
+                }
+                let local = Payload::local_size(&page.page_type, payload_size, page.usable_size());
+                let overflow_ptr = if local < payload_size { 4 } else { 0 };
+                header_len + local as usize + overflow_ptr
+            }
+        };
+        (size <= bytes.len()).then_some(Cell {
+            offset: offset as u64,
+            size,
+            index: 0,
+        })
+    }
+}
+
+/// The payload of a cell, owning the part of it stored on the page
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Payload {
-    pub size: u64, // in bytes, including overflow
+    /// in bytes, including overflow
+    pub size: u64,
+    /// the bytes stored in the cell, which is all of them unless some spill onto overflow pages
     pub payload: Vec<u8>,
+    /// the number of the first overflow page, as stored, if the payload spills
     pub overflow: Option<[u8; 4]>,
 }
 
 impl Payload {
+    /// Number of payload bytes that do not fit in the cell and spill onto overflow pages
     pub fn calculate_spillage(&self, db: &Database, page: &BtreePage) -> u64 {
+        let usable = db.page_size as u64 - db.reserved_space as u64;
+        self.size - Self::local_size(&page.page_type, self.size, usable)
+    }
+
+    /// Number of overflow pages the spilled part of the payload takes up
+    pub fn overflow_pages(&self, db: &Database, page: &BtreePage) -> u64 {
+        self.calculate_spillage(db, page)
+            .div_ceil(overflow_capacity(db) as u64)
+    }
+
+    /// The whole payload: the bytes stored in the cell followed by those spilled onto its
+    /// chain of overflow pages. Each overflow page starts with the number of the next page in
+    /// the chain, 0 on the last one, and the rest of its usable area holds payload bytes.
+    pub fn reassemble(self, db: &Database) -> Result<Vec<u8>, SqrliteError> {
+        let Some(first) = self.overflow else {
+            return Ok(self.payload);
+        };
+        let capacity = overflow_capacity(db);
+        let max_size = self.payload.len() as u64
+            + u64::from(db.page_count.saturating_sub(1)) * capacity as u64;
+        if self.size > max_size {
+            return Err(CorruptData::new(&format!(
+                "payload of {} bytes cannot fit in a database of {} pages",
+                self.size, db.page_count
+            ))
+            .into());
+        }
+        db.check_allocation(self.size, "payload")?;
+        let size = self.size as usize;
+
+        // grown as pages are read rather than sized from the declared length up front
+        let mut bytes = self.payload;
+        let mut visited = HashSet::new();
+        let mut page = vec![];
+        let mut next = u32::from_be_bytes(first);
+        while bytes.len() < size {
+            check_overflow_page(db, next, self.size, bytes.len() as u64, &mut visited)?;
+            db.read_page_into(next, &mut page)?;
+            let take = (size - bytes.len()).min(capacity);
+            bytes.extend_from_slice(&page[4..4 + take]);
+            next = u32::from_be_bytes([page[0], page[1], page[2], page[3]]);
+        }
+        Ok(bytes)
+    }
+
+    /// Stream the payload instead of collecting it, reading overflow pages only as their
+    /// bytes are reached
+    pub fn open_reader<'a>(&'a self, db: &'a Database) -> PayloadReader<'a> {
+        PayloadReader {
+            db,
+            local: &self.payload,
+            start: 0,
+            end: self.size,
+            position: 0,
+            size: self.size,
+            chain: vec![],
+            visited: HashSet::new(),
+            next: self.overflow.map_or(0, u32::from_be_bytes),
+            buffer: vec![],
+            loaded: None,
+        }
+    }
+
+    /// Number of bytes of a payload stored on the b-tree page itself; the rest spills onto
+    /// overflow pages. `usable` must be at least 480, the smallest usable size of a valid
+    /// database, which `BtreePage::parse` enforces.
+    pub fn local_size(page_type: &PageType, size: u64, usable: u64) -> u64 {
         // Variables below are explained in SQLite documentation: https://www.sqlite.org/fileformat2.html#b_tree_pages
-        let p = self.size;
-        let u = db.page_size as u64 - db.reserved_space as u64;
-        let m = ((u - 12) * 32 / 255) - 23;
-        let k = m + ((p - m) % (u - 4));
-        let x = match page.page_type {
+        let p = size;
+        let u = usable;
+        let x = match page_type {
             PageType::LeafTable => u - 35,
             PageType::LeafIndex | PageType::InteriorIndex => ((u - 12) * 64 / 255) - 23,
-            _ => 0,
+            PageType::InteriorTable => return 0,
         };
-        match p {
-            p if (p > x && k <= x) => p - k,
-            p if (p > x && k > x) => p - m,
-            _ => 0,
+        if p <= x {
+            return p;
+        }
+        let m = ((u - 12) * 32 / 255) - 23;
+        let k = m + ((p - m) % (u - 4));
+        if k <= x {
+            k
+        } else {
+            m
         }
     }
 }
 
-#[derive(Debug)]
+// Number of payload bytes held by each overflow page, after its next-page pointer
+fn overflow_capacity(db: &Database) -> usize {
+    (db.page_size - u32::from(db.reserved_space)) as usize - 4
+}
+
+// Check that `page` can be the next page of the overflow chain of a `size`-byte payload,
+// after `read` bytes of the payload have been collected
+fn check_overflow_page(
+    db: &Database,
+    page: u32,
+    size: u64,
+    read: u64,
+    visited: &mut HashSet<u32>,
+) -> Result<(), OverflowError> {
+    let bad_chain = |reason: String| {
+        OverflowError::new(&format!(
+            "overflow chain of a {}-byte payload {}",
+            size, reason
+        ))
+    };
+    match page {
+        0 => Err(bad_chain(format!("ends after {} of its bytes", read))),
+        1 => Err(bad_chain("references page 1".to_owned())),
+        _ => {
+            validate_page_num(db, page)
+                .map_err(|e| bad_chain(format!("references an invalid page: {}", e)))?;
+            if visited.insert(page) {
+                Ok(())
+            } else {
+                Err(bad_chain(format!("loops back to page {}", page)))
+            }
+        }
+    }
+}
+
+/// Reads a payload, or a range of it, from the cell's local bytes and then its overflow
+/// chain, holding one overflow page in memory at a time. Pages of the chain are remembered
+/// as they are found, so seeking backwards doesn't follow the chain from its start again.
+pub struct PayloadReader<'a> {
+    db: &'a Database,
+    local: &'a [u8],
+    start: u64, // range being read, as offsets into the payload
+    end: u64,
+    position: u64, // offset into the payload of the next byte to read
+    size: u64,
+    chain: Vec<u32>, // overflow pages found so far, in chain order
+    visited: HashSet<u32>,
+    next: u32,             // page following the last one in `chain`
+    buffer: Vec<u8>,       // contents of one overflow page
+    loaded: Option<usize>, // position in `chain` of the page held in `buffer`
+}
+
+impl PayloadReader<'_> {
+    // Restrict the reader to `len` bytes starting at `offset` into the payload
+    pub(crate) fn range(mut self, offset: u64, len: u64) -> Result<Self, SqrliteError> {
+        if offset.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(OverflowError::new(&format!(
+                "range of {} bytes at offset {} extends past the {}-byte payload",
+                len, offset, self.size
+            ))
+            .into());
+        }
+        self.start = offset;
+        self.end = offset + len;
+        self.position = offset;
+        Ok(self)
+    }
+
+    // Load the index-th overflow page of the chain into the buffer, following the chain as
+    // far as needed
+    fn load(&mut self, index: usize) -> Result<(), SqrliteError> {
+        let capacity = overflow_capacity(self.db) as u64;
+        while self.chain.len() <= index {
+            let read = self.local.len() as u64 + self.chain.len() as u64 * capacity;
+            check_overflow_page(self.db, self.next, self.size, read, &mut self.visited)?;
+            self.db.read_page_into(self.next, &mut self.buffer)?;
+            self.loaded = Some(self.chain.len());
+            self.chain.push(self.next);
+            let next = &self.buffer[0..4];
+            self.next = u32::from_be_bytes([next[0], next[1], next[2], next[3]]);
+        }
+        if self.loaded != Some(index) {
+            self.db
+                .read_page_into(self.chain[index], &mut self.buffer)?;
+            self.loaded = Some(index);
+        }
+        Ok(())
+    }
+}
+
+impl Read for PayloadReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.end || buf.is_empty() {
+            return Ok(0);
+        }
+        let remaining = self.end - self.position;
+        let local_len = self.local.len() as u64;
+        let bytes = if self.position < local_len {
+            &self.local[self.position as usize..]
+        } else {
+            let capacity = overflow_capacity(self.db) as u64;
+            let offset = self.position - local_len;
+            self.load((offset / capacity) as usize)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            let within = (offset % capacity) as usize;
+            &self.buffer[4 + within..4 + capacity as usize]
+        };
+        let len = bytes.len().min(buf.len()).min(remaining as usize);
+        buf[..len].copy_from_slice(&bytes[..len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for PayloadReader<'_> {
+    // Positions are relative to the start of the range being read
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(self.start.saturating_add(offset)),
+            SeekFrom::End(delta) => self.end.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        match target {
+            Some(target) if target >= self.start => {
+                self.position = target;
+                Ok(target - self.start)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a position before the start of the payload",
+            )),
+        }
+    }
+}
+
+/// A payload borrowed from the contents of the page holding its cell
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadRef<'page> {
+    /// in bytes, including overflow
+    pub size: u64,
+    /// the bytes stored in the cell
+    pub local: &'page [u8],
+    /// the number of the first overflow page, as stored, if the payload spills
+    pub overflow: Option<[u8; 4]>,
+}
+
+impl<'page> PayloadRef<'page> {
+    /// Copy the part of the payload stored on the page into an owned `Payload`
+    pub fn to_payload(&self) -> Payload {
+        Payload {
+            size: self.size,
+            payload: self.local.to_vec(),
+            overflow: self.overflow,
+        }
+    }
+
+    /// The whole payload, still borrowed from the page unless part of it is on overflow pages
+    pub fn read(&self, db: &Database) -> Result<Cow<'page, [u8]>, SqrliteError> {
+        match self.overflow {
+            None => Ok(Cow::Borrowed(self.local)),
+            Some(_) => Ok(Cow::Owned(self.to_payload().reassemble(db)?)),
+        }
+    }
+}
+
+/// A cell borrowed from the contents of its page. Nothing is copied when parsing it, which
+/// suits reading through every cell of a page; `to_content` gives an owned `CellContent`
+/// for a cell that has to outlive the page buffer.
+#[derive(Debug, Clone, Copy)]
+pub enum CellRef<'page> {
+    /// a row of a table
+    LeafTable {
+        /// the row's rowid
+        row_id: i64,
+        /// the row's record
+        payload: PayloadRef<'page>,
+    },
+    /// an entry of an index
+    LeafIndex {
+        /// the entry's record
+        payload: PayloadRef<'page>,
+    },
+    /// an entry of an index with the child page of entries sorting before it
+    InteriorIndex {
+        /// page number of the child
+        left_child_ptr: u32,
+        /// the entry's record
+        payload: PayloadRef<'page>,
+    },
+    /// a divider between the subtrees of a table b-tree
+    InteriorTable {
+        /// page number of the child holding rowids up to the key
+        left_child_ptr: u32,
+        /// the largest rowid in the left child
+        integer_key: i64,
+    },
+}
+
+impl<'page> CellRef<'page> {
+    /// Parse the index-th cell, in key order, of a page from the page's contents
+    pub fn get(pg: &BtreePage, page_buf: &'page [u8], index: usize) -> Result<Self, SqrliteError> {
+        let cell = pg.get_cell(page_buf, index)?;
+        Self::parse(pg, page_buf, cell)
+    }
+
+    /// Parse a cell of the given page, slicing its bytes out of the page's contents
+    pub fn parse(pg: &BtreePage, page_buf: &'page [u8], cell: Cell) -> Result<Self, SqrliteError> {
+        let corrupt = |reason: String| CellParseError {
+            page: pg.page_num,
+            cell_index: cell.index,
+            reason,
+        };
+        let start = cell.offset as usize;
+        let cell_buf = page_buf
+            .get(start..start + cell.size)
+            .ok_or_else(|| corrupt(format!("cell at offset {} overruns the page", start)))?;
+        let mut reader = CellReader::new(cell_buf);
+        let parsed = match pg.page_type {
+            PageType::LeafTable => parse_leaf_table_cell(pg, &mut reader)
+                .map(|(row_id, payload)| CellRef::LeafTable { row_id, payload }),
+            PageType::InteriorTable => {
+                parse_interior_table_cell(&mut reader).map(|(left_child_ptr, integer_key)| {
+                    CellRef::InteriorTable {
+                        left_child_ptr,
+                        integer_key,
+                    }
+                })
+            }
+            PageType::LeafIndex => {
+                parse_leaf_index_cell(pg, &mut reader).map(|payload| CellRef::LeafIndex { payload })
+            }
+            PageType::InteriorIndex => {
+                parse_interior_index_cell(pg, &mut reader).map(|(left_child_ptr, payload)| {
+                    CellRef::InteriorIndex {
+                        left_child_ptr,
+                        payload,
+                    }
+                })
+            }
+        };
+        Ok(parsed.map_err(corrupt)?)
+    }
+
+    /// Copy the cell into an owned `CellContent`
+    pub fn to_content(&self) -> CellContent {
+        match *self {
+            CellRef::LeafTable { row_id, payload } => CellContent::LeafTable {
+                cell_type: "B-Tree Leaf Table",
+                row_id,
+                payload: payload.to_payload(),
+            },
+            CellRef::InteriorTable {
+                left_child_ptr,
+                integer_key,
+            } => CellContent::InteriorTable {
+                cell_type: "B-Tree Interior Table",
+                left_child_ptr,
+                integer_key,
+            },
+            CellRef::LeafIndex { payload } => CellContent::LeafIndex {
+                cell_type: "B-Tree Leaf Index",
+                payload: payload.to_payload(),
+            },
+            CellRef::InteriorIndex {
+                left_child_ptr,
+                payload,
+            } => CellContent::InteriorIndex {
+                cell_type: "B-Tree Interior Index",
+                left_child_ptr,
+                payload: payload.to_payload(),
+            },
+        }
+    }
+
+    /// The cell's payload, which every kind of cell but an interior table cell has
+    pub fn payload(&self) -> Option<PayloadRef<'page>> {
+        match *self {
+            CellRef::LeafTable { payload, .. }
+            | CellRef::LeafIndex { payload }
+            | CellRef::InteriorIndex { payload, .. } => Some(payload),
+            CellRef::InteriorTable { .. } => None,
+        }
+    }
+
+    /// The child page of an interior cell
+    pub fn left_child_pointer(&self) -> Option<u32> {
+        match *self {
+            CellRef::InteriorTable { left_child_ptr, .. }
+            | CellRef::InteriorIndex { left_child_ptr, .. } => Some(left_child_ptr),
+            CellRef::LeafTable { .. } | CellRef::LeafIndex { .. } => None,
+        }
+    }
+}
+
+/// Payload bytes that an encoded cell leaves for its overflow pages. Each page of the chain
+/// holds a 4-byte next page number followed by up to `page_capacity` of these bytes, and the
+/// number of the first page belongs at `pointer_offset` into the cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverflowPlan {
+    /// offset into the encoded cell of the first overflow page number
+    pub pointer_offset: usize,
+    /// payload bytes left for the overflow pages
+    pub spilled: Vec<u8>,
+    /// payload bytes each overflow page holds
+    pub page_capacity: usize,
+}
+
+impl OverflowPlan {
+    /// Number of overflow pages the spilled bytes take up
+    pub fn page_count(&self) -> usize {
+        self.spilled.len().div_ceil(self.page_capacity)
+    }
+}
+
+/// A cell of a b-tree page, owning its payload. `CellRef` borrows from the page instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CellContent {
+    /// a row of a table
     LeafTable {
+        /// a description of the kind of cell
         cell_type: &'static str,
-        row_id: u64,
+        /// the row's rowid
+        row_id: i64,
+        /// the row's record
         payload: Payload,
     },
+    /// an entry of an index
     LeafIndex {
+        /// a description of the kind of cell
         cell_type: &'static str,
+        /// the entry's record
         payload: Payload,
     },
+    /// an entry of an index with the child page of entries sorting before it
     InteriorIndex {
+        /// a description of the kind of cell
         cell_type: &'static str,
+        /// page number of the child
         left_child_ptr: u32,
+        /// the entry's record
         payload: Payload,
     },
+    /// a divider between the subtrees of a table b-tree
     InteriorTable {
+        /// a description of the kind of cell
         cell_type: &'static str,
+        /// page number of the child holding rowids up to the key
         left_child_ptr: u32,
-        integer_key: u64,
+        /// the largest rowid in the left child
+        integer_key: i64,
     },
 }
 
 impl CellContent {
+    /// Parse the index-th cell, in key order, of a page from the page's contents
     pub fn get_cell_data(
         pg: &BtreePage,
-        db: &mut Database,
-        cell: Cell,
-    ) -> Result<Self, Box<dyn Error>> {
-        let mut reader = BufReader::new(&db.file);
-        reader
-            .seek(SeekFrom::Start(pg.file_starting_position + cell.offset))
-            .map_err(|e| e.to_string())?;
-        let mut cell_buf = vec![0u8; cell.size];
-        reader
-            .read_exact(&mut cell_buf)
-            .map_err(|e| e.to_string())?;
-
-        match pg.page_type {
-            PageType::LeafTable => {
-                let cell_type = "B-Tree Leaf Table";
-                let (row_id, payload) =
-                    parse_leaf_table_cell(cell, &mut cell_buf).map_err(|e| e.to_string())?;
-                Ok(CellContent::LeafTable {
-                    cell_type,
-                    row_id,
-                    payload,
-                })
-            }
-            PageType::InteriorTable => {
-                let cell_type = "B-Tree Interior Table";
-                let (left_child_ptr, integer_key) =
-                    parse_interior_table_cell(&mut cell_buf).map_err(|e| e.to_string())?;
-                Ok(CellContent::InteriorTable {
-                    cell_type,
-                    left_child_ptr,
-                    integer_key,
-                })
-            }
-            PageType::LeafIndex => {
-                let cell_type = "B-Tree Leaf Index";
-                let payload =
-                    parse_leaf_index_cell(cell, &mut cell_buf).map_err(|e| e.to_string())?;
-                Ok(CellContent::LeafIndex { cell_type, payload })
-            }
-            PageType::InteriorIndex => {
-                let cell_type = "B-Tree Interior Index";
-                let (left_child_ptr, payload) =
-                    parse_interior_index_cell(cell, &mut cell_buf).map_err(|e| e.to_string())?;
-                Ok(CellContent::InteriorIndex {
-                    cell_type,
-                    left_child_ptr,
-                    payload,
-                })
-            }
-        }
+        page_buf: &[u8],
+        index: usize,
+    ) -> Result<Self, SqrliteError> {
+        Ok(CellRef::get(pg, page_buf, index)?.to_content())
+    }
+
+    /// Parse a cell of the given page, copying its bytes out of the page's contents
+    pub fn parse(pg: &BtreePage, page_buf: &[u8], cell: Cell) -> Result<Self, SqrliteError> {
+        Ok(CellRef::parse(pg, page_buf, cell)?.to_content())
     }
 
+    /// The part of the payload stored in the cell, for the kinds of cell that have one
     pub fn get_payload(&self) -> Result<&[u8], InvalidFieldError> {
         match self {
             CellContent::LeafTable { payload, .. }
@@ -158,6 +614,19 @@ impl CellContent {
         }
     }
 
+    /// Take the cell's whole payload, reading any part of it stored on overflow pages
+    pub fn into_payload(self, db: &Database) -> Result<Vec<u8>, SqrliteError> {
+        match self {
+            CellContent::LeafTable { payload, .. }
+            | CellContent::LeafIndex { payload, .. }
+            | CellContent::InteriorIndex { payload, .. } => payload.reassemble(db),
+            CellContent::InteriorTable { cell_type, .. } => {
+                Err(InvalidFieldError::new(cell_type, "payload").into())
+            }
+        }
+    }
+
+    /// The child page of an interior cell
     pub fn get_left_child_pointer(&self) -> Result<u32, InvalidFieldError> {
         match self {
             CellContent::InteriorTable { left_child_ptr, .. }
@@ -168,7 +637,8 @@ impl CellContent {
         }
     }
 
-    pub fn get_row_id(&self) -> Result<u64, InvalidFieldError> {
+    /// The rowid of a leaf table cell
+    pub fn get_row_id(&self) -> Result<i64, InvalidFieldError> {
         match self {
             CellContent::LeafTable { row_id, .. } => Ok(*row_id),
             CellContent::InteriorTable { cell_type, .. }
@@ -178,77 +648,298 @@ impl CellContent {
             }
         }
     }
+
+    /// The bytes of the cell as stored on a page with the given usable size: child pointer,
+    /// payload size and rowid or key varints, then as much of the payload as the spill formula
+    /// keeps on the page and, if any spills, the first overflow page number. The payload may
+    /// hold just its local part, as parsed cells do, or all of it; in the latter case the rest
+    /// is returned as the plan for the overflow pages, and the pointer is left for the caller
+    /// to fill in once those pages are allocated.
+    pub fn encode(&self, usable_size: u32) -> (Vec<u8>, Option<OverflowPlan>) {
+        let mut bytes = vec![];
+        let (page_type, payload) = match self {
+            CellContent::InteriorTable {
+                left_child_ptr,
+                integer_key,
+                ..
+            } => {
+                bytes.extend(left_child_ptr.to_be_bytes());
+                push_varint(&mut bytes, *integer_key as u64);
+                return (bytes, None);
+            }
+            CellContent::LeafTable {
+                row_id, payload, ..
+            } => {
+                push_varint(&mut bytes, payload.size);
+                push_varint(&mut bytes, *row_id as u64);
+                (PageType::LeafTable, payload)
+            }
+            CellContent::LeafIndex { payload, .. } => {
+                push_varint(&mut bytes, payload.size);
+                (PageType::LeafIndex, payload)
+            }
+            CellContent::InteriorIndex {
+                left_child_ptr,
+                payload,
+                ..
+            } => {
+                bytes.extend(left_child_ptr.to_be_bytes());
+                push_varint(&mut bytes, payload.size);
+                (PageType::InteriorIndex, payload)
+            }
+        };
+
+        let local = Payload::local_size(&page_type, payload.size, u64::from(usable_size)) as usize;
+        bytes.extend_from_slice(&payload.payload[..local.min(payload.payload.len())]);
+        if local as u64 == payload.size {
+            return (bytes, None);
+        }
+        let pointer_offset = bytes.len();
+        bytes.extend(payload.overflow.unwrap_or_default());
+        let spilled = payload.payload.get(local..).unwrap_or_default();
+        let plan = (!spilled.is_empty()).then(|| OverflowPlan {
+            pointer_offset,
+            spilled: spilled.to_vec(),
+            page_capacity: usable_size as usize - 4,
+        });
+        (bytes, plan)
+    }
 }
 
-fn parse_leaf_table_cell(
-    cell: Cell,
-    cell_buf: &mut [u8],
-) -> Result<(u64, Payload), Box<dyn Error>> {
-    let mut payload = Payload::default();
-    let mut varint_len: usize;
-    let mut position: usize = 0;
+fn parse_leaf_table_cell<'page>(
+    pg: &BtreePage,
+    cell: &mut CellReader<'page>,
+) -> Result<(i64, PayloadRef<'page>), String> {
+    let size = cell.varint()?;
+    let rowid = cell.signed_varint()?;
+    Ok((rowid, read_payload(pg, cell, size)?))
+}
 
-    (payload.size, varint_len) = decode_be(cell_buf).map_err(|e| e.to_string())?;
-    position += varint_len;
+fn parse_interior_table_cell(cell: &mut CellReader) -> Result<(u32, i64), String> {
+    let left_child_ptr = cell.u32()?;
+    let int_key = cell.signed_varint()?;
+    Ok((left_child_ptr, int_key))
+}
 
-    if payload.size > cell.size as u64 {
-        let overflow: [u8; 4] = cell_buf[cell_buf.len() - 4..].try_into()?;
-        payload.overflow = Some(overflow);
-    }
+fn parse_leaf_index_cell<'page>(
+    pg: &BtreePage,
+    cell: &mut CellReader<'page>,
+) -> Result<PayloadRef<'page>, String> {
+    let size = cell.varint()?;
+    read_payload(pg, cell, size)
+}
 
-    let rowid: u64;
-    (rowid, varint_len) = decode_be(&cell_buf[position..]).map_err(|e| e.to_string())?;
-    position += varint_len;
+fn parse_interior_index_cell<'page>(
+    pg: &BtreePage,
+    cell: &mut CellReader<'page>,
+) -> Result<(u32, PayloadRef<'page>), String> {
+    let left_child_ptr = cell.u32()?;
+    let size = cell.varint()?;
+    Ok((left_child_ptr, read_payload(pg, cell, size)?))
+}
 
-    payload.payload = match payload.overflow {
-        Some(_) => cell_buf[position..cell_buf.len() - 4].to_vec(),
-        None => cell_buf[position..].to_vec(),
+// Read the payload of a cell that declares `size` bytes in total. Whether it spills is
+// decided by the page type and usable size alone: the page keeps as many bytes as the spill
+// formula allows, followed by the first overflow page number only when some is left over.
+fn read_payload<'page>(
+    pg: &BtreePage,
+    cell: &mut CellReader<'page>,
+    size: u64,
+) -> Result<PayloadRef<'page>, String> {
+    let local = Payload::local_size(&pg.page_type, size, pg.usable_size());
+    let bytes = cell.take(local as usize)?;
+    let overflow = if local < size {
+        Some(cell.u32()?.to_be_bytes())
+    } else {
+        None
     };
-    Ok((rowid, payload))
+    Ok(PayloadRef {
+        size,
+        local: bytes,
+        overflow,
+    })
 }
 
-fn parse_interior_table_cell(cell_buf: &mut [u8]) -> Result<(u32, u64), Box<dyn Error>> {
-    let left_child_ptr_buf: [u8; 4] = cell_buf[..4].try_into()?;
-    let left_child_ptr = u32::from_be_bytes(left_child_ptr_buf);
-    let (int_key, _) = decode_be(&cell_buf[4..])?;
-    Ok((left_child_ptr, int_key))
+// Reads the fields of a cell in order, returning an error rather than panicking when the
+// cell is too short for what its fields claim
+struct CellReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
 }
 
-fn parse_leaf_index_cell(cell: Cell, cell_buf: &mut [u8]) -> Result<Payload, Box<dyn Error>> {
-    let mut payload = Payload::default();
-    let varint_len: usize;
-    (payload.size, varint_len) = decode_be(cell_buf).map_err(|e| e.to_string())?;
+impl<'a> CellReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
 
-    if payload.size > cell.size as u64 {
-        let overflow: [u8; 4] = cell_buf[cell_buf.len() - 4..].try_into()?;
-        payload.overflow = Some(overflow);
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let taken = self
+            .position
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| {
+                format!(
+                    "{} bytes at offset {} run past the end of the {}-byte cell",
+                    len,
+                    self.position,
+                    self.bytes.len()
+                )
+            })?;
+        self.position += len;
+        Ok(taken)
     }
 
-    payload.payload = match payload.overflow {
-        Some(_) => cell_buf[varint_len..cell_buf.len() - 4].to_vec(),
-        None => cell_buf[varint_len..].to_vec(),
-    };
-    Ok(payload)
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        self.decode_with(decode_be)
+    }
+
+    fn signed_varint(&mut self) -> Result<i64, String> {
+        self.decode_with(decode_be_i64)
+    }
+
+    fn decode_with<T>(
+        &mut self,
+        decode: impl Fn(&[u8]) -> Result<(T, usize), TruncatedVarintError>,
+    ) -> Result<T, String> {
+        let rest = &self.bytes[self.position.min(self.bytes.len())..];
+        let (value, len) = decode(rest).map_err(|_| {
+            format!(
+                "varint at offset {} runs past the end of the {}-byte cell",
+                self.position,
+                self.bytes.len()
+            )
+        })?;
+        self.position += len;
+        Ok(value)
+    }
 }
 
-fn parse_interior_index_cell(
-    cell: Cell,
-    cell_buf: &mut [u8],
-) -> Result<(u32, Payload), Box<dyn Error>> {
-    let left_child_ptr_buf: [u8; 4] = cell_buf[..4].try_into()?;
-    let left_child_ptr = u32::from_be_bytes(left_child_ptr_buf);
-    let mut payload = Payload::default();
-    let varint_len: usize;
-    (payload.size, varint_len) = decode_be(&cell_buf[4..]).map_err(|e| e.to_string())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const FLAGS: [u8; 4] = [0x02, 0x05, 0x0a, 0x0d];
 
-    if payload.size > cell.size as u64 {
-        let overflow: [u8; 4] = cell_buf[cell_buf.len() - 4..].try_into()?;
-        payload.overflow = Some(overflow);
+    fn page_type(flag: u8) -> PageType {
+        match flag {
+            0x02 => PageType::InteriorIndex,
+            0x05 => PageType::InteriorTable,
+            0x0a => PageType::LeafIndex,
+            _ => PageType::LeafTable,
+        }
     }
 
-    payload.payload = match payload.overflow {
-        Some(_) => cell_buf[4 + varint_len..cell_buf.len() - 4].to_vec(),
-        None => cell_buf[4 + varint_len..].to_vec(),
-    };
-    Ok((left_child_ptr, payload))
+    // A cell as parsing would give it: of the page type with the given flag, holding the
+    // local part of a payload and, if the rest spills, the stored overflow page number
+    fn cell(
+        flag: u8,
+        usable_size: u32,
+        key: i64,
+        child: u32,
+        full: &[u8],
+        overflow: u32,
+    ) -> CellContent {
+        let page_type = page_type(flag);
+        let size = full.len() as u64;
+        let local = Payload::local_size(&page_type, size, u64::from(usable_size)) as usize;
+        let payload = Payload {
+            size,
+            payload: full[..local].to_vec(),
+            overflow: (local < full.len()).then(|| overflow.to_be_bytes()),
+        };
+        match page_type {
+            PageType::LeafTable => CellContent::LeafTable {
+                cell_type: "B-Tree Leaf Table",
+                row_id: key,
+                payload,
+            },
+            PageType::InteriorTable => CellContent::InteriorTable {
+                cell_type: "B-Tree Interior Table",
+                left_child_ptr: child,
+                integer_key: key,
+            },
+            PageType::LeafIndex => CellContent::LeafIndex {
+                cell_type: "B-Tree Leaf Index",
+                payload,
+            },
+            PageType::InteriorIndex => CellContent::InteriorIndex {
+                cell_type: "B-Tree Interior Index",
+                left_child_ptr: child,
+                payload,
+            },
+        }
+    }
+
+    // Page 2 holding just the given cell, at the end of its usable area
+    fn page_holding(flag: u8, usable_size: u32, cell: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; usable_size as usize];
+        let start = bytes.len() - cell.len();
+        bytes[start..].copy_from_slice(cell);
+        bytes[0] = flag;
+        bytes[3..5].copy_from_slice(&1u16.to_be_bytes());
+        bytes[5..7].copy_from_slice(&(start as u16).to_be_bytes());
+        let pointers = if flag & 0x08 == 0 { 12 } else { 8 };
+        bytes[pointers..pointers + 2].copy_from_slice(&(start as u16).to_be_bytes());
+        bytes
+    }
+
+    proptest! {
+        #[test]
+        fn parse_inverts_encode(
+            flag in prop::sample::select(&FLAGS[..]),
+            usable_size in 480u32..=65536,
+            key: i64,
+            child: u32,
+            overflow in 2u32..,
+            full in prop::collection::vec(any::<u8>(), 0..20_000),
+        ) {
+            let content = cell(flag, usable_size, key, child, &full, overflow);
+            let (bytes, plan) = content.encode(usable_size);
+            prop_assert!(plan.is_none());
+
+            let page_bytes = page_holding(flag, usable_size, &bytes);
+            let page = BtreePage::parse(2, &page_bytes, usable_size).unwrap();
+            let parsed = CellContent::get_cell_data(&page, &page_bytes, 0).unwrap();
+            prop_assert_eq!(page.get_cell(&page_bytes, 0).unwrap().size, bytes.len());
+            prop_assert_eq!(parsed, content);
+        }
+
+        // Encoding the whole payload gives the same cell, short of the overflow page number,
+        // and leaves the spilled bytes for the overflow pages
+        #[test]
+        fn encode_plans_the_spilled_bytes(
+            flag in prop::sample::select(&[0x02, 0x0a, 0x0d][..]),
+            usable_size in 480u32..=65536,
+            key: i64,
+            full in prop::collection::vec(any::<u8>(), 0..20_000),
+        ) {
+            let local_only = cell(flag, usable_size, key, 7, &full, 0);
+            let mut whole = local_only.clone();
+            match &mut whole {
+                CellContent::LeafTable { payload, .. }
+                | CellContent::LeafIndex { payload, .. }
+                | CellContent::InteriorIndex { payload, .. } => payload.payload = full.clone(),
+                CellContent::InteriorTable { .. } => unreachable!(),
+            }
+            let (expected, _) = local_only.encode(usable_size);
+            let (bytes, plan) = whole.encode(usable_size);
+            prop_assert_eq!(&bytes, &expected);
+
+            let local = local_only.get_payload().unwrap().len();
+            match plan {
+                Some(plan) => {
+                    prop_assert_eq!(plan.pointer_offset, bytes.len() - 4);
+                    prop_assert_eq!(&plan.spilled[..], &full[local..]);
+                    prop_assert_eq!(plan.page_capacity, usable_size as usize - 4);
+                }
+                None => prop_assert_eq!(local, full.len()),
+            }
+        }
+    }
 }
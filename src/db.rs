@@ -1,111 +1,948 @@
 #![allow(dead_code)]
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::env::current_dir;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
-
-const DB_HEADER_SIZE: usize = 100;
-const HEADER_STRING_ARR: [u8; 16] = [
-    0x53, 0x51, 0x4c, 0x69, 0x74, 0x65, 0x20, 0x66, 0x6f, 0x72, 0x6d, 0x61, 0x74, 0x20, 0x33, 0x00,
-];
-// description (offset, size) per SQLite database header format
-const HEADER_STR_SZ: (usize, usize) = (0, 16);
-const PG_SIZE: (usize, usize) = (16, 2);
-const PG_COUNT: (usize, usize) = (28, 4);
-const RESERVED_SPACE: (usize, usize) = (20, 1);
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
+use crate::btree_page::InvalidPageNumber;
+use crate::error::SqrliteError;
+use crate::header::{AutoVacuum, DBHeader, JournalMode, DB_HEADER_SIZE, MAGIC};
+use crate::journal::{find_hot_journal, HotJournalError};
+use crate::page_cache::{PageBuf, PageCache, PageCacheStats, DEFAULT_PAGE_CACHE_BYTES};
+use crate::page_source::{PageSource, ReaderSource};
+use crate::record::{FieldData, TextEncoding};
+use crate::scan::TableScan;
+use crate::schema::{ObjectType, Schema, SchemaCache};
+use crate::sniff::{sniff, NotADatabaseError};
+use crate::table::{IndexHandle, NoSuchTable, TableHandle};
+use crate::wal::{WalError, WalIndex, WalInfo};
+
+const CHANGE_COUNTER: (usize, usize) = (24, 4);
+
+/// SQLite locks byte ranges starting at this file offset; the page containing it is never used
+const LOCK_BYTE_OFFSET: u64 = 0x40000000;
+
+const SEQUENCE_TABLE: &str = "sqlite_sequence";
+
+/// Default for the most memory a single value read from the file may take
+const DEFAULT_ALLOCATION_BUDGET: u64 = 1 << 30;
+
+/// The freelist trunk chain loops, runs off the end of the database, or holds a different
+/// number of pages than the header records
+#[derive(Debug)]
+pub struct FreelistError {
+    details: String,
+}
+
+impl FreelistError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for FreelistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for FreelistError {}
+
+/// A size or count recorded in the file that it cannot possibly hold, such as a payload
+/// longer than every page of the database put together
+#[derive(Debug)]
+pub struct CorruptData {
+    details: String,
+}
+
+impl CorruptData {
+    pub(crate) fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for CorruptData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for CorruptData {}
+
+/// A value read from the file would need more memory than the allocation budget allows
+#[derive(Debug)]
+pub struct AllocationBudgetError {
+    details: String,
+}
+
+impl AllocationBudgetError {
+    fn new(bytes: u64, what: &str, budget: u64) -> Self {
+        Self {
+            details: format!(
+                "{} of {} bytes exceeds the allocation budget of {} bytes",
+                what, bytes, budget
+            ),
+        }
+    }
+}
+
+impl fmt::Display for AllocationBudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for AllocationBudgetError {}
+
+/// The file's change counter moved while a query read it: another process wrote to the
+/// database, so the rows read may mix its old and new contents
+#[derive(Debug)]
+pub struct ConcurrentModification {
+    /// the change counter when the read began
+    pub before: u32,
+    /// the change counter once it was done
+    pub after: u32,
+}
+
+impl fmt::Display for ConcurrentModification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the database was modified while it was being read (change counter {} became {})",
+            self.before, self.after
+        )
+    }
+}
+
+impl Error for ConcurrentModification {}
+
+/// A page of the database lies past the end of the file, so the file was cut short
 #[derive(Debug)]
-struct InvalidDBFileError {
+pub struct TruncatedDatabase {
+    /// the page that could not be read
+    pub page: u32,
+    /// whole pages the file holds
+    pub file_pages: u32,
     details: String,
 }
 
-impl InvalidDBFileError {
-    fn new() -> Self {
+impl TruncatedDatabase {
+    fn new(page: u32, file_pages: u32) -> Self {
         Self {
-            details: "file is not a valid database".to_owned(),
+            page,
+            file_pages,
+            details: format!(
+                "page {} is past the end of the file, which holds only {} pages: the database \
+                 is truncated",
+                page, file_pages
+            ),
         }
     }
 }
 
-impl fmt::Display for InvalidDBFileError {
+impl fmt::Display for TruncatedDatabase {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.details)
     }
 }
 
-impl Error for InvalidDBFileError {}
+impl Error for TruncatedDatabase {}
+
+/// Whether to consult the files SQLite keeps beside the database while it is being written
+#[derive(Debug, Clone, Copy)]
+pub struct DatabaseOptions {
+    /// take committed pages from the -wal file of a WAL-mode database
+    pub read_wal: bool,
+    /// refuse a database left with a hot rollback journal
+    pub check_journal: bool,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        DatabaseOptions {
+            read_wal: true,
+            check_journal: true,
+        }
+    }
+}
+
+/// Where the storage of a database came from, so that another handle on it can be made
+#[derive(Debug, Clone)]
+enum Source {
+    File {
+        path: PathBuf,
+        mapped: bool,
+    }, // the path is absolute
+    Bytes(Arc<[u8]>),
+    /// given by the caller
+    Custom,
+}
 
+/// An open SQLite database file, read through a cache of its pages. Everything else in the
+/// crate reads through one: its schema, tables and indexes, queries and the reports on the
+/// file's structure.
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+/// assert_eq!(db.page_size, 4096);
+/// assert_eq!(db.schema()?.objects.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// A Database can be shared between threads, for example as an `Arc<Database>` handed to each
+/// request of a server, and any number of them may read through it at once. Each page is read
+/// as the file holds it at that moment: nothing pins a snapshot across pages, so a scan made
+/// while another process writes to a database in rollback mode may see a mix of old and new
+/// pages. execute() notices this from the file change counter and fails with
+/// ConcurrentModification rather than return such rows. A WAL is read as it was when the
+/// database was opened until refresh(), which cannot run while any thread is reading.
 #[derive(Debug)]
 pub struct Database {
-    pub file: File,
-    pub header: [u8; DB_HEADER_SIZE],
-    pub page_size: u16,
+    storage: Box<dyn PageSource>,
+    source: Source,
+    /// the 100-byte header at the start of the file
+    pub header: DBHeader,
+    /// size of every page in bytes, from the header
+    pub page_size: u32,
+    /// number of pages, from the header or, when it is out of date, worked out from the
+    /// file size
     pub page_count: u32,
+    /// bytes at the end of each page set aside for extensions, from the header
     pub reserved_space: u8,
+    /// encoding of every string in the database, from the header
+    pub text_encoding: TextEncoding,
+    allocation_budget: u64,
+    schema_cache: Mutex<SchemaCache>,
+    /// committed pages of the -wal file, which take precedence over the database file
+    wal: Option<WalIndex>,
+    options: DatabaseOptions,
+    page_cache: Mutex<PageCache>,
+    page_cache_bytes: u64,
 }
 
+// fail to build rather than quietly lose the sharing described above
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Database>();
+};
+
 impl Database {
-    pub fn new<P>(db_file: P) -> Result<Self, Box<dyn Error>>
+    /// Open a database, along with the -wal file of one in WAL mode so that commits not yet
+    /// copied into the database file are seen. A database in rollback mode is refused if a hot
+    /// journal shows a transaction was cut short, as its file may be half-written.
+    pub fn new<P>(db_file: P) -> Result<Self, SqrliteError>
     where
         P: AsRef<Path>,
     {
-        let mut path = db_file.as_ref().to_path_buf();
+        Self::with_options(db_file, DatabaseOptions::default())
+    }
+
+    /// Open a database reading only the database file, even if commits are waiting in a WAL
+    pub fn new_ignoring_wal<P>(db_file: P) -> Result<Self, SqrliteError>
+    where
+        P: AsRef<Path>,
+    {
+        let options = DatabaseOptions {
+            read_wal: false,
+            ..Default::default()
+        };
+        Self::with_options(db_file, options)
+    }
+
+    /// Open a database, choosing whether to read its WAL and look for a hot journal
+    pub fn with_options<P>(db_file: P, options: DatabaseOptions) -> Result<Self, SqrliteError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_file(db_file.as_ref(), false, options)
+    }
+
+    /// Open a database by mapping its file into memory, so that pages are copied out of the
+    /// page cache without a system call for each read. The file must not be truncated by
+    /// another process while it is mapped: reading a page past its new end kills the process
+    /// with SIGBUS rather than returning an error.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P>(db_file: P) -> Result<Self, SqrliteError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_file(db_file.as_ref(), true, DatabaseOptions::default())
+    }
+
+    fn open_file(
+        db_file: &Path,
+        mapped: bool,
+        options: DatabaseOptions,
+    ) -> Result<Self, SqrliteError> {
+        let mut path = db_file.to_path_buf();
         if !path.is_absolute() {
             let cwd = current_dir()?;
             path = cwd.join(path);
         }
 
-        let mut file = File::open(&path).map_err(|e| e.to_string())?;
-        let mut header = [0; DB_HEADER_SIZE];
-        file.read_exact(&mut header)
-            .map_err(|e| e.to_string() + " - database header might be invalid or corrupt")?;
+        let file = File::open(&path)
+            .map_err(|e| SqrliteError::io(format!("cannot open {}", path.display()), e))?;
+        let storage: Box<dyn PageSource> = match mapped {
+            #[cfg(feature = "mmap")]
+            true => {
+                // SAFETY: the map is only read, and open_mmap leaves keeping the file from
+                // being truncated while it is mapped to the caller
+                let map = unsafe { memmap2::Mmap::map(&file) }
+                    .map_err(|e| SqrliteError::io(format!("cannot map {}", path.display()), e))?;
+                Box::new(map)
+            }
+            _ => Box::new(file),
+        };
+        Self::open(storage, Source::File { path, mapped }, options)
+    }
 
-        let header_str_arr: [u8; 16] = header
-            [(HEADER_STR_SZ.0)..(HEADER_STR_SZ.0 + HEADER_STR_SZ.1)]
-            .try_into()
-            .map_err(|e: std::array::TryFromSliceError| {
-                "error reading header: ".to_owned() + &e.to_string()
-            })?;
-        validate_db_file(header_str_arr).map_err(|e| e.to_string())?;
+    /// Read a database from any seekable source, such as a file already open. Without a
+    /// path there is no WAL or journal to look for beside it.
+    pub fn from_reader<R>(reader: R) -> Result<Self, SqrliteError>
+    where
+        R: Read + Seek + Send + Sync + fmt::Debug + 'static,
+    {
+        Self::from_source(ReaderSource::new(reader)?)
+    }
 
-        let pg_size_arr = header[(PG_SIZE.0)..(PG_SIZE.0 + PG_SIZE.1)]
-            .try_into()
-            .map_err(|e: std::array::TryFromSliceError| {
-                "error reading header: ".to_owned() + &e.to_string()
-            })?;
-        let page_size = u16::from_be_bytes(pg_size_arr);
+    /// Read a database from a source of its bytes, such as one fetching them over a network
+    pub fn from_source<S>(source: S) -> Result<Self, SqrliteError>
+    where
+        S: PageSource + 'static,
+    {
+        Self::open(Box::new(source), Source::Custom, DatabaseOptions::default())
+    }
 
-        let pg_count_arr = header[(PG_COUNT.0)..(PG_COUNT.0 + PG_COUNT.1)]
-            .try_into()
-            .map_err(|e: std::array::TryFromSliceError| {
-                "error reading header: ".to_owned() + &e.to_string()
-            })?;
-        let page_count = u32::from_be_bytes(pg_count_arr);
+    /// Read a database held entirely in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, SqrliteError> {
+        let bytes = Arc::<[u8]>::from(bytes);
+        let storage = Box::new(Cursor::new(Arc::clone(&bytes)));
+        Self::open(storage, Source::Bytes(bytes), DatabaseOptions::default())
+    }
 
-        let reserved_space_arr = header[(RESERVED_SPACE.0)..(RESERVED_SPACE.0 + RESERVED_SPACE.1)]
-            .try_into()
-            .map_err(|e: std::array::TryFromSliceError| {
-                "error reading header: ".to_owned() + &e.to_string()
-            })?;
-        let reserved_space = u8::from_be_bytes(reserved_space_arr);
+    fn open(
+        storage: Box<dyn PageSource>,
+        source: Source,
+        options: DatabaseOptions,
+    ) -> Result<Self, SqrliteError> {
+        let path = match &source {
+            Source::File { path, .. } => Some(path.as_path()),
+            _ => None,
+        };
+        let (header, page_count, wal) = read_snapshot(storage.as_ref(), path, options)?;
+        let page_cache = PageCache::new(cache_capacity(DEFAULT_PAGE_CACHE_BYTES, header.page_size));
 
         Ok(Self {
-            file,
-            header,
-            page_size,
+            storage,
+            source,
+            page_size: header.page_size,
             page_count,
-            reserved_space,
+            reserved_space: header.reserved_space,
+            text_encoding: header.text_encoding,
+            header,
+            allocation_budget: DEFAULT_ALLOCATION_BUDGET,
+            schema_cache: Mutex::default(),
+            page_cache: Mutex::new(page_cache),
+            page_cache_bytes: DEFAULT_PAGE_CACHE_BYTES,
+            wal,
+            options,
         })
     }
-}
 
-fn validate_db_file(header_str_arr: [u8; 16]) -> Result<(), InvalidDBFileError> {
-    if header_str_arr == HEADER_STRING_ARR {
+    pub(crate) fn can_reopen(&self) -> bool {
+        !matches!(self.source, Source::Custom)
+    }
+
+    /// Open the database again, for another thread, reading it the same way as this handle.
+    /// A database read from a caller's reader cannot be, as there is no way to copy it.
+    pub(crate) fn reopen(&self) -> Result<Self, SqrliteError> {
+        match &self.source {
+            Source::File { path, mapped } => Self::open_file(path, *mapped, self.options),
+            Source::Bytes(bytes) => Self::open(
+                Box::new(Cursor::new(Arc::clone(bytes))),
+                self.source.clone(),
+                self.options,
+            ),
+            Source::Custom => Err(SqrliteError::Unsupported(
+                "a database read from the caller's own source cannot be opened again".to_owned(),
+            )),
+        }
+    }
+
+    /// Get the parsed schema, reading sqlite_schema only on first use or after the file
+    /// change counter shows the database was modified since the cached copy was read
+    pub fn schema(&self) -> Result<Arc<Schema>, SqrliteError> {
+        let change_counter = self.read_change_counter()?;
+        if let Some(schema) = lock(&self.schema_cache).get(change_counter) {
+            return Ok(schema);
+        }
+        // pages cached before the counter changed may be out of date too
+        lock(&self.page_cache).clear();
+
+        // threads that miss at the same time each read the schema, rather than wait on a lock
+        let schema = Arc::new(Schema::read(self)?);
+        lock(&self.schema_cache).store(change_counter, Arc::clone(&schema));
+        Ok(schema)
+    }
+
+    /// Re-read the header, returning whether another process changed the database since it
+    /// was opened or last refreshed. A change to the file change counter or the schema cookie
+    /// also drops the cached schema.
+    pub fn refresh(&mut self) -> Result<bool, SqrliteError> {
+        let path = match &self.source {
+            Source::File { path, .. } => Some(path.as_path()),
+            _ => None,
+        };
+        let (header, page_count, wal) = read_snapshot(self.storage.as_ref(), path, self.options)?;
+        let wal_changed = match (&self.wal, &wal) {
+            (Some(old), Some(new)) => {
+                old.header != new.header || old.committed_frames != new.committed_frames
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        let changed = wal_changed
+            || header.change_counter != self.header.change_counter
+            || header.schema_cookie != self.header.schema_cookie;
+        let page_cache = self
+            .page_cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if changed {
+            lock(&self.schema_cache).invalidate();
+            page_cache.clear();
+        }
+        if header.page_size != self.page_size {
+            page_cache.clear();
+            page_cache.set_capacity(cache_capacity(self.page_cache_bytes, header.page_size));
+        }
+        self.page_size = header.page_size;
+        self.page_count = page_count;
+        self.reserved_space = header.reserved_space;
+        self.text_encoding = header.text_encoding;
+        self.header = header;
+        self.wal = wal;
+        Ok(changed)
+    }
+
+    /// Fail if the file change counter no longer has the value it had when a read began
+    pub fn check_unchanged(&self, change_counter: u32) -> Result<(), SqrliteError> {
+        let current = self.read_change_counter()?;
+        if current != change_counter {
+            return Err(ConcurrentModification {
+                before: change_counter,
+                after: current,
+            }
+            .into());
+        }
         Ok(())
-    } else {
-        Err(InvalidDBFileError::new())
     }
+
+    /// Path of the database file, unless it was read from a buffer or reader
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Whether the database is in rollback or WAL mode, going by the header
+    pub fn journal_mode(&self) -> JournalMode {
+        self.header.journal_mode()
+    }
+
+    /// Number of whole pages the database file holds, whatever the header says
+    pub fn file_page_count(&self) -> Result<u32, SqrliteError> {
+        let len = self.file_size()?;
+        Ok((len / u64::from(self.page_size))
+            .try_into()
+            .unwrap_or(u32::MAX))
+    }
+
+    /// Length of the database file in bytes
+    pub fn file_size(&self) -> Result<u64, SqrliteError> {
+        Ok(self.storage.len()?)
+    }
+
+    /// Pages of the database that are neither in the file nor in the WAL, which can only be
+    /// because the file was cut short
+    pub fn missing_pages(&self) -> Result<u32, SqrliteError> {
+        let file_pages = self.file_page_count()?;
+        let in_wal = match &self.wal {
+            Some(wal) => wal
+                .pages()
+                .filter(|&page| page > file_pages && page <= self.page_count)
+                .count() as u32,
+            None => 0,
+        };
+        Ok(self
+            .page_count
+            .saturating_sub(file_pages)
+            .saturating_sub(in_wal))
+    }
+
+    /// Whether pointer-map pages are kept, which are then interleaved with the data pages
+    pub fn auto_vacuum(&self) -> AutoVacuum {
+        self.header.auto_vacuum()
+    }
+
+    /// The -wal file SQLite keeps beside a WAL-mode database
+    pub fn wal_path(&self) -> Option<PathBuf> {
+        self.path().map(|path| with_suffix(path, "-wal"))
+    }
+
+    /// The rollback journal SQLite writes beside the database during a transaction
+    pub fn journal_path(&self) -> Option<PathBuf> {
+        self.path().map(|path| with_suffix(path, "-journal"))
+    }
+
+    /// A hot journal beside the database, which only matters in rollback mode
+    pub fn hot_journal(&self) -> Result<Option<HotJournalError>, SqrliteError> {
+        match self.journal_path() {
+            Some(path) if self.journal_mode() == JournalMode::Rollback => {
+                find_hot_journal(&path, self.page_size)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// A summary of the WAL being read, if any
+    pub fn wal_info(&self) -> Option<WalInfo> {
+        self.wal.as_ref().map(WalIndex::info)
+    }
+
+    /// Size of the -wal file if the database is in WAL mode and the file holds any data
+    pub fn pending_wal_size(&self) -> Option<u64> {
+        if self.journal_mode() != JournalMode::Wal {
+            return None;
+        }
+        std::fs::metadata(self.wal_path()?)
+            .ok()
+            .map(|meta| meta.len())
+            .filter(|&len| len > 0)
+    }
+
+    /// Most bytes that may be allocated to hold a single value read from the file. Sizes
+    /// recorded in the file are untrusted, so a value claiming more is refused up front.
+    pub fn allocation_budget(&self) -> u64 {
+        self.allocation_budget
+    }
+
+    /// Change the allocation budget, which defaults to 1 GiB
+    pub fn set_allocation_budget(&mut self, bytes: u64) {
+        self.allocation_budget = bytes;
+    }
+
+    pub(crate) fn check_allocation(
+        &self,
+        bytes: u64,
+        what: &str,
+    ) -> Result<(), AllocationBudgetError> {
+        if bytes > self.allocation_budget {
+            Err(AllocationBudgetError::new(
+                bytes,
+                what,
+                self.allocation_budget,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of the page that spans the file-locking byte range. It holds no data and is
+    /// skipped by SQLite, so it only exists in databases larger than 1 GiB.
+    pub fn lock_byte_page(&self) -> u32 {
+        (LOCK_BYTE_OFFSET / u64::from(self.page_size)) as u32 + 1
+    }
+
+    /// Get the bytes of a page, without interpreting them. Pages are served from the cache
+    /// when they were read recently, and otherwise read from the WAL or the database file.
+    pub fn page(&self, page: u32) -> Result<Arc<PageBuf>, SqrliteError> {
+        // a zero page number, as a corrupt child or overflow pointer may hold, has no offset
+        if page == 0 {
+            return Err(InvalidPageNumber::new(
+                0,
+                "there is no page 0; page numbers start at 1".to_owned(),
+            )
+            .into());
+        }
+        if let Some(bytes) = lock(&self.page_cache).get(page) {
+            return Ok(bytes);
+        }
+        // the cache is not locked while reading, so other threads are not held up by it
+        let mut buffer = vec![0; self.page_size as usize];
+        let in_wal = match &self.wal {
+            Some(wal) => wal.read_page(page, &mut buffer)?,
+            None => false,
+        };
+        if !in_wal {
+            let offset = u64::from(page - 1) * u64::from(self.page_size);
+            if let Err(e) = self.storage.read(offset, &mut buffer) {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    let file_pages = self.file_page_count()?;
+                    return Err(TruncatedDatabase::new(page, file_pages).into());
+                }
+                return Err(SqrliteError::io(format!("error reading page {}", page), e));
+            }
+        }
+        let bytes = Arc::<PageBuf>::from(buffer);
+        lock(&self.page_cache).insert(page, Arc::clone(&bytes));
+        Ok(bytes)
+    }
+
+    /// Copy a whole page into `buffer`, resizing it to the page size
+    pub(crate) fn read_page_into(
+        &self,
+        page: u32,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), SqrliteError> {
+        let bytes = self.page(page)?;
+        buffer.clear();
+        buffer.extend_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Most bytes of recently read pages to keep in memory; 0 turns the cache off
+    pub fn set_page_cache_size(&mut self, bytes: u64) {
+        self.page_cache
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_capacity(cache_capacity(bytes, self.page_size));
+        self.page_cache_bytes = bytes;
+    }
+
+    /// Hits and misses of the page cache so far
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        lock(&self.page_cache).stats()
+    }
+
+    /// The value set with PRAGMA user_version, which applications use to track schema changes
+    pub fn user_version(&self) -> i32 {
+        self.header.user_version
+    }
+
+    /// The value set with PRAGMA application_id, identifying the application owning the file
+    pub fn application_id(&self) -> i32 {
+        self.header.application_id
+    }
+
+    /// The version number of the SQLite library that last wrote the file, e.g. 3045002 for
+    /// 3.45.2; dbinfo::sqlite_version_text turns it into the release
+    pub fn software_version(&self) -> u32 {
+        self.header.sqlite_version
+    }
+
+    /// First trunk page of the freelist, or 0 when it is empty
+    pub fn freelist_head(&self) -> u32 {
+        self.header.first_freelist_trunk
+    }
+
+    /// Number of pages on the freelist, trunk pages included, as the header records it
+    pub fn freelist_count(&self) -> u32 {
+        self.header.freelist_count
+    }
+
+    /// Walk the freelist trunk chain and confirm it holds as many pages as the header records
+    pub fn verify_freelist(&self) -> Result<(), SqrliteError> {
+        self.freelist_pages().map(|_| ())
+    }
+
+    /// Collect the pages on the freelist, trunk pages included. Each trunk page holds the
+    /// number of the next trunk, a count of leaf pages and then the leaf page numbers.
+    pub fn freelist_pages(&self) -> Result<BTreeSet<u32>, SqrliteError> {
+        let expected = self.freelist_count();
+        let mut trunk = self.freelist_head();
+        let max_leaves = (self.page_size - u32::from(self.reserved_space)) / 4 - 2;
+
+        let mut pages = BTreeSet::new();
+        let mut trunk_num = 0;
+        while trunk != 0 {
+            trunk_num += 1;
+            let bad_chain = |reason: String| {
+                FreelistError::new(&format!(
+                    "freelist trunk #{} (page {}) {}",
+                    trunk_num, trunk, reason
+                ))
+            };
+            if trunk > self.page_count {
+                return Err(
+                    bad_chain(format!("is beyond the page count of {}", self.page_count)).into(),
+                );
+            }
+            if !pages.insert(trunk) {
+                return Err(
+                    bad_chain("was already visited; the trunk chain loops".to_owned()).into(),
+                );
+            }
+            if trunk == self.lock_byte_page() {
+                return Err(bad_chain("is the lock-byte page".to_owned()).into());
+            }
+
+            let bytes = self.page(trunk)?;
+            let next = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let num_leaves = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+            if num_leaves > max_leaves {
+                return Err(bad_chain(format!(
+                    "claims {} leaf pages but can hold at most {}",
+                    num_leaves, max_leaves
+                ))
+                .into());
+            }
+            for leaf in bytes[8..8 + 4 * num_leaves as usize].chunks_exact(4) {
+                let leaf = u32::from_be_bytes([leaf[0], leaf[1], leaf[2], leaf[3]]);
+                if leaf == 0 || leaf > self.page_count {
+                    return Err(bad_chain(format!(
+                        "lists leaf page {} outside pages 1 to {}",
+                        leaf, self.page_count
+                    ))
+                    .into());
+                }
+                if leaf == self.lock_byte_page() {
+                    return Err(bad_chain(format!("lists the lock-byte page {}", leaf)).into());
+                }
+                if !pages.insert(leaf) {
+                    return Err(bad_chain(format!(
+                        "lists page {} which is already on the freelist",
+                        leaf
+                    ))
+                    .into());
+                }
+            }
+            trunk = next;
+        }
+
+        if pages.len() != expected as usize {
+            return Err(FreelistError::new(&format!(
+                "freelist holds {} pages but the header records {}",
+                pages.len(),
+                expected
+            ))
+            .into());
+        }
+        Ok(pages)
+    }
+
+    /// Read the file change counter from the file itself, which another process bumps on
+    /// every write transaction
+    pub fn read_change_counter(&self) -> Result<u32, SqrliteError> {
+        // a WAL is read as it was when opened until refresh() is called, and SQLite leaves
+        // the counter alone for transactions committed to the WAL anyway
+        if self.wal.is_some() {
+            return Ok(self.header.change_counter);
+        }
+        let mut counter_buf = [0u8; CHANGE_COUNTER.1];
+        self.storage
+            .read(CHANGE_COUNTER.0 as u64, &mut counter_buf)
+            .map_err(|e| SqrliteError::io("error reading the header", e))?;
+        Ok(u32::from_be_bytes(counter_buf))
+    }
+
+    /// A table by name, matched as SQL does, ignoring ASCII case. A name that is not a
+    /// table gives [`SqrliteError::NoSuchTable`].
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// let table = db.table("READINGS")?;
+    /// assert_eq!(table.name, "readings");
+    /// assert!(matches!(
+    ///     db.table("missing"),
+    ///     Err(sqrlite::SqrliteError::NoSuchTable(_))
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn table(&self, name: &str) -> Result<TableHandle<'_>, SqrliteError> {
+        let schema = self.schema()?;
+        let known = schema
+            .find(name)
+            .is_some_and(|obj| matches!(obj.object_type, ObjectType::Table | ObjectType::View));
+        if !known {
+            return Err(NoSuchTable {
+                name: name.to_owned(),
+            }
+            .into());
+        }
+        // views get an error of their own
+        let rootpage = schema.find_table(name)?.rootpage;
+        Ok(TableHandle::new(self, rootpage, schema.table_def(name)?))
+    }
+
+    /// Every table in schema order, leaving out SQLite's own tables and virtual tables, which
+    /// have no b-tree to read.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// for table in db.tables()? {
+    ///     println!("{}: {} rows", table.name, table.count()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tables(&self) -> Result<Vec<TableHandle<'_>>, SqrliteError> {
+        let schema = self.schema()?;
+        schema
+            .objects_of_type(ObjectType::Table)
+            .filter(|obj| !obj.is_internal() && obj.rootpage != 0)
+            .map(|obj| {
+                let def = schema.table_def(&obj.name)?;
+                Ok(TableHandle::new(self, obj.rootpage, def))
+            })
+            .collect()
+    }
+
+    /// Every index in schema order, including those SQLite made for PRIMARY KEY and UNIQUE
+    /// constraints.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// let index = &db.indexes()?[0];
+    /// assert_eq!(index.name, "readings_sensor");
+    /// assert_eq!(index.table()?.name, "readings");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn indexes(&self) -> Result<Vec<IndexHandle<'_>>, SqrliteError> {
+        let schema = self.schema()?;
+        schema
+            .objects_of_type(ObjectType::Index)
+            .filter(|obj| obj.rootpage != 0)
+            .map(|obj| {
+                let def = schema.index_def(&obj.name)?;
+                Ok(IndexHandle::new(self, obj.rootpage, def))
+            })
+            .collect()
+    }
+
+    /// Read the AUTOINCREMENT high-water mark of each table from sqlite_sequence. Databases
+    /// without any AUTOINCREMENT column have no such table and yield an empty map.
+    pub fn sequence_values(&self) -> Result<BTreeMap<String, i64>, SqrliteError> {
+        let schema = self.schema()?;
+        let mut values = BTreeMap::new();
+        let rootpage = match schema.find(SEQUENCE_TABLE) {
+            Some(table) => table.rootpage,
+            None => return Ok(values),
+        };
+
+        for row in TableScan::new(self, rootpage).rows() {
+            let (_, row) = row?;
+            match row.as_slice() {
+                [FieldData::Text(name), FieldData::Integer(seq)] => {
+                    values.insert(name.clone(), *seq);
+                }
+                [FieldData::Text(name), FieldData::BooleanFalse(_)] => {
+                    values.insert(name.clone(), 0);
+                }
+                [FieldData::Text(name), FieldData::BooleanTrue(_)] => {
+                    values.insert(name.clone(), 1);
+                }
+                _ => {
+                    return Err(CorruptData::new(
+                        "sqlite_sequence has a row that is not a table name and a number",
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Read and parse the header at the start of the file, along with the number of pages
+fn read_header(storage: &dyn PageSource) -> Result<(DBHeader, u32), SqrliteError> {
+    let len = storage.len()?;
+    let mut bytes = [0; DB_HEADER_SIZE];
+    if len >= DB_HEADER_SIZE as u64 {
+        storage
+            .read(0, &mut bytes)
+            .map_err(|e| SqrliteError::io("error reading the database header", e))?;
+    }
+    // say what the file looks like instead, as an encrypted database is easily taken for a
+    // damaged one
+    if !bytes.starts_with(MAGIC) {
+        return Err(NotADatabaseError::new(sniff(storage)?).into());
+    }
+    let header = DBHeader::parse(&bytes)?;
+    let page_count = header.page_count(len);
+    Ok((header, page_count))
+}
+
+/// Read the header and page count, and the WAL if the database file is in WAL mode and the
+/// options ask for it. Page 1 and the size of the database are taken from the WAL when it has
+/// them, as they are newer than the copies in the database file.
+fn read_snapshot(
+    storage: &dyn PageSource,
+    path: Option<&Path>,
+    options: DatabaseOptions,
+) -> Result<(DBHeader, u32, Option<WalIndex>), SqrliteError> {
+    let (header, page_count) = read_header(storage)?;
+    let path = match path {
+        Some(path) => path,
+        None => return Ok((header, page_count, None)),
+    };
+    if header.journal_mode() == JournalMode::Rollback && options.check_journal {
+        if let Some(hot) = find_hot_journal(&with_suffix(path, "-journal"), header.page_size)? {
+            return Err(hot.into());
+        }
+    }
+    if !options.read_wal || header.journal_mode() != JournalMode::Wal {
+        return Ok((header, page_count, None));
+    }
+    let wal = match WalIndex::open(&with_suffix(path, "-wal"))? {
+        Some(wal) => wal,
+        None => return Ok((header, page_count, None)),
+    };
+    if wal.header.page_size != header.page_size {
+        return Err(WalError::new(&format!(
+            "WAL page size {} does not match the database page size {}",
+            wal.header.page_size, header.page_size
+        ))
+        .into());
+    }
+
+    let mut header = header;
+    let mut page = vec![0; header.page_size as usize];
+    if wal.read_page(1, &mut page)? {
+        let mut bytes = [0; DB_HEADER_SIZE];
+        bytes.copy_from_slice(&page[..DB_HEADER_SIZE]);
+        header = DBHeader::parse(&bytes)?;
+    }
+    let page_count = match wal.database_size {
+        0 => page_count,
+        size => size,
+    };
+    Ok((header, page_count, Some(wal)))
+}
+
+/// The path of a file SQLite keeps beside the database, named by appending a suffix
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = path.to_path_buf().into_os_string();
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+/// Number of pages of the given size that fit in a cache of `bytes`
+fn cache_capacity(bytes: u64, page_size: u32) -> usize {
+    (bytes / u64::from(page_size))
+        .try_into()
+        .unwrap_or(usize::MAX)
+}
+
+/// Take a lock even if a thread panicked while holding it. The caches behind these locks are
+/// only ever filled with whole entries, so whatever that thread left is still usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
@@ -1,17 +1,155 @@
-#![allow(dead_code)]
+use std::fmt;
 
-// use std::error::Error;
-
-// use crate::db::Database;
+use crate::btree::tree_stats;
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::header::{AutoVacuum, JournalMode};
+use crate::record::TextEncoding;
+use crate::schema::ObjectType;
 
+/// What the SQLite shell's `.dbinfo` command reports about a database, read from its header
+/// and sqlite_schema, along with how the file's length compares with the page count. Its
+/// Display prints the shell's lines in the shell's layout.
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+/// let info = sqrlite::DBInfo::read_info(&db)?;
+/// assert_eq!(info.db_page_size, 4096);
+/// assert_eq!(info.num_indexes, 1);
+/// println!("{}", info);
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DBInfo {
-    pub db_page_size: u16,
+    /// size of every page in bytes
+    pub db_page_size: u32,
+    /// number of pages in the database, as the reader works it out
     pub db_page_count: u32,
+    /// 1 for rollback journal mode, 2 for WAL
+    pub write_format: u8,
+    /// 1 for rollback journal mode, 2 for WAL
+    pub read_format: u8,
+    /// bytes at the end of each page set aside for extensions
+    pub reserved_bytes: u8,
+    /// bumped by every write transaction in rollback mode
+    pub change_counter: u32,
+    /// the page count recorded in the header
+    pub header_page_count: u32,
+    /// whether the header page count is stale, and so ignored in favour of the file size
+    pub header_page_count_stale: bool,
+    /// number of whole pages the file holds
+    pub file_page_count: u32,
+    /// pages on the freelist, as the header records
+    pub freelist_count: u32,
+    /// bumped whenever the schema changes
+    pub schema_cookie: u32,
+    /// 1 to 4, the newest schema format features the file may use
+    pub schema_format: u32,
+    /// the page cache size suggested by PRAGMA default_cache_size
+    pub default_cache_size: i32,
+    /// whether pointer-map pages are kept, and how the file is vacuumed
+    pub auto_vacuum: AutoVacuum,
+    /// largest root page, 0 without auto_vacuum
+    pub autovacuum_top_root: u32,
+    /// whether auto_vacuum is incremental rather than full
+    pub incremental_vacuum: bool,
+    /// encoding of every string in the database
+    pub text_encoding: TextEncoding,
+    /// as stored, 0 until the first table is created
+    pub text_encoding_value: u32,
+    /// of the SQLite library that last wrote the file
+    pub software_version: u32,
+    /// the change counter value at that write, which the header page count is only good for
+    pub version_valid_for: u32,
+    /// rollback or WAL, going by the read and write formats
+    pub journal_mode: JournalMode,
+    /// set with PRAGMA user_version
+    pub user_version: i32,
+    /// set with PRAGMA application_id to mark the file format of an application
+    pub application_id: i32,
+    /// every table in sqlite_schema, as the SQLite shell counts them, which takes in SQLite's
+    /// own tables such as sqlite_sequence but not sqlite_schema itself
     pub num_tables: u32,
+    /// tables whose names start with `sqlite_`
+    pub num_internal_tables: u32,
+    /// indexes, those SQLite made for constraints included
     pub num_indexes: u32,
+    /// triggers in sqlite_schema
     pub num_triggers: u32,
+    /// views in sqlite_schema
     pub num_views: u32,
+    /// characters of SQL in sqlite_schema
+    pub schema_size: u64,
+    /// bytes of SQL in sqlite_schema, as stored in the text encoding
+    pub schema_bytes: u64,
+    /// rows of sqlite_schema, of every type
+    pub schema_entries: u32,
+    /// length of the database file in bytes
+    pub file_size: u64,
+    /// how the file's length compares with the page count
+    pub file_size_check: FileSizeCheck,
+}
+
+/// How the length of the file compares with the pages the database has
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FileSizeCheck {
+    /// the file holds every page and nothing more
+    #[default]
+    Exact,
+    /// bytes after the last page, left by a crash before the file was truncated or added on
+    /// the end since, which SQLite ignores
+    Longer {
+        /// bytes past the last page
+        extra_bytes: u64,
+    },
+    /// pages of the database found neither in the file nor in the WAL: the file was cut short,
+    /// and reading those pages fails
+    Shorter {
+        /// pages past the end of the file
+        missing_pages: u32,
+    },
+}
+
+impl fmt::Display for FileSizeCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact => write!(f, "the file holds exactly the database's pages"),
+            Self::Longer { extra_bytes } => write!(
+                f,
+                "the file has {} bytes past the database's last page",
+                extra_bytes
+            ),
+            Self::Shorter { missing_pages } => write!(
+                f,
+                "the file is truncated: {} pages of the database are missing from it",
+                missing_pages
+            ),
+        }
+    }
+}
+
+/// The size of one table or index, as the whole of its b-tree
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ObjectStats {
+    /// name of the table or index
+    pub name: String,
+    /// table or index
+    pub object_type: ObjectType,
+    /// root page of the b-tree
+    pub rootpage: u32,
+    /// levels of the b-tree, 1 for a tree that is a single leaf
+    pub depth: usize,
+    /// b-tree pages and overflow pages
+    pub pages: u64,
+    /// rows of a table, entries of an index
+    pub entries: u64,
+    /// payload of every entry, including bytes on overflow pages
+    pub payload_bytes: u64,
 }
 
 impl Default for DBInfo {
@@ -19,43 +157,204 @@ impl Default for DBInfo {
         DBInfo {
             db_page_size: 512, // minimum page size allowed by SQLite
             db_page_count: 1,
+            write_format: 1,
+            read_format: 1,
+            reserved_bytes: 0,
+            change_counter: 0,
+            header_page_count: 1,
+            header_page_count_stale: false,
+            file_page_count: 1,
+            freelist_count: 0,
+            schema_cookie: 0,
+            schema_format: 4,
+            default_cache_size: 0,
+            auto_vacuum: AutoVacuum::None,
+            autovacuum_top_root: 0,
+            incremental_vacuum: false,
+            text_encoding: TextEncoding::Utf8,
+            text_encoding_value: 1,
+            software_version: 0,
+            version_valid_for: 0,
+            journal_mode: JournalMode::Rollback,
+            user_version: 0,
+            application_id: 0,
             num_tables: 0,
+            num_internal_tables: 0,
             num_indexes: 0,
             num_triggers: 0,
             num_views: 0,
+            schema_size: 0,
+            schema_bytes: 0,
+            schema_entries: 0,
+            file_size: 0,
+            file_size_check: FileSizeCheck::Exact,
         }
     }
 }
 
-// impl DBInfo {
-//     pub fn read_info(db: &Database) -> Result<Self, Box<dyn Error>> {
-//         let pg_size_arr = db.header[(PG_SIZE.0)..(PG_SIZE.0 + PG_SIZE.1)]
-//             .try_into()
-//             .map_err(|e: std::array::TryFromSliceError| {
-//                 "error reading header: ".to_owned() + &e.to_string()
-//             })?;
-//         let page_size = u16::from_be_bytes(pg_size_arr);
-
-//         let pg_count_arr = db.header[(PG_COUNT.0)..(PG_COUNT.0 + PG_COUNT.1)]
-//             .try_into()
-//             .map_err(|e: std::array::TryFromSliceError| {
-//                 "error reading header: ".to_owned() + &e.to_string()
-//             })?;
-//         let page_count = u32::from_be_bytes(pg_count_arr);
-
-//         Ok(Self {
-//             db_page_size: page_size,
-//             db_page_count: page_count,
-//             ..Default::default()
-//         })
-//     }
-
-//     // fn read_schema_info() {
-//     //     todo!();
-//     // }
-
-//     // fn parse_page_header(&self, pg_number: usize) {
-//     //     // input: page_number
-//     //     todo!();
-//     // }
-// }
+impl DBInfo {
+    /// Read what `.dbinfo` shows from the header and sqlite_schema
+    pub fn read_info(db: &Database) -> Result<Self, SqrliteError> {
+        let mut info = Self {
+            db_page_size: db.header.page_size,
+            db_page_count: db.page_count,
+            write_format: db.header.write_version,
+            read_format: db.header.read_version,
+            reserved_bytes: db.header.reserved_space,
+            change_counter: db.header.change_counter,
+            header_page_count: db.header.database_size,
+            header_page_count_stale: !db.header.database_size_valid(),
+            file_page_count: db.file_page_count()?,
+            file_size: db.file_size()?,
+            file_size_check: Self::consistency(db)?,
+            freelist_count: db.freelist_count(),
+            schema_cookie: db.header.schema_cookie,
+            schema_format: db.header.schema_format,
+            default_cache_size: db.header.default_cache_size,
+            auto_vacuum: db.auto_vacuum(),
+            autovacuum_top_root: db.header.largest_root_page,
+            incremental_vacuum: db.header.incremental_vacuum,
+            text_encoding: db.text_encoding,
+            text_encoding_value: db.header.text_encoding_value,
+            software_version: db.software_version(),
+            version_valid_for: db.header.version_valid_for,
+            journal_mode: db.journal_mode(),
+            user_version: db.user_version(),
+            application_id: db.application_id(),
+            ..Default::default()
+        };
+        info.read_schema_info(db)?;
+        Ok(info)
+    }
+
+    /// Whether the header and the file size disagree on the number of pages. In WAL mode the
+    /// file holds fewer pages than the database until a checkpoint, so that alone is expected.
+    pub fn page_count_mismatch(&self) -> bool {
+        self.header_page_count != self.file_page_count
+            && (self.header_page_count_stale || self.journal_mode == JournalMode::Rollback)
+    }
+
+    /// Compare the length of the file with the pages the database has, which only needs the
+    /// header, so a truncated file is found out before anything past its end is read. Pages
+    /// past the end of the file are not missing if the WAL has them.
+    pub fn consistency(db: &Database) -> Result<FileSizeCheck, SqrliteError> {
+        let missing_pages = db.missing_pages()?;
+        if missing_pages > 0 {
+            return Ok(FileSizeCheck::Shorter { missing_pages });
+        }
+        let pages_bytes = u64::from(db.page_count) * u64::from(db.page_size);
+        Ok(match db.file_size()?.checked_sub(pages_bytes) {
+            Some(extra_bytes) if extra_bytes > 0 => FileSizeCheck::Longer { extra_bytes },
+            _ => FileSizeCheck::Exact,
+        })
+    }
+
+    /// The size of every table and index, sqlite_schema first and the rest in schema order,
+    /// reading each b-tree once
+    pub fn read_extended(db: &Database) -> Result<Vec<ObjectStats>, SqrliteError> {
+        db.schema()?
+            .btrees()
+            .map(|(name, object_type, rootpage)| {
+                let stats = tree_stats(db, rootpage)?;
+                Ok(ObjectStats {
+                    name: name.to_owned(),
+                    object_type,
+                    rootpage,
+                    depth: stats.depth,
+                    pages: stats.pages(),
+                    entries: stats.entries,
+                    payload_bytes: stats.payload_bytes,
+                })
+            })
+            .collect()
+    }
+
+    /// Everything taken from sqlite_schema, in one pass over its rows
+    fn read_schema_info(&mut self, db: &Database) -> Result<(), SqrliteError> {
+        let schema = db.schema()?;
+        for obj in &schema.objects {
+            self.schema_entries += 1;
+            match obj.object_type {
+                ObjectType::Table => {
+                    self.num_tables += 1;
+                    if obj.is_internal() {
+                        self.num_internal_tables += 1;
+                    }
+                }
+                ObjectType::Index => self.num_indexes += 1,
+                ObjectType::Trigger => self.num_triggers += 1,
+                ObjectType::View => self.num_views += 1,
+            }
+            if let Some(sql) = &obj.sql {
+                // as length(sql) counts, in characters
+                self.schema_size += sql.chars().count() as u64;
+                self.schema_bytes += self.text_encoding.encoded_len(sql) as u64;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The lines the SQLite shell prints for .dbinfo, formatted as it formats them, which shows
+// header fields as unsigned numbers whatever their type. The shell's last line, the data
+// version, is left out: it counts changes seen by the shell's own connection and is not
+// stored in the file.
+impl fmt::Display for DBInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text_encoding = match self.text_encoding_value {
+            1 => "1 (utf8)".to_owned(),
+            2 => "2 (utf16le)".to_owned(),
+            3 => "3 (utf16be)".to_owned(),
+            value => value.to_string(),
+        };
+        let lines: [(&str, &dyn fmt::Display); 21] = [
+            ("database page size:", &self.db_page_size),
+            ("write format:", &self.write_format),
+            ("read format:", &self.read_format),
+            ("reserved bytes:", &self.reserved_bytes),
+            ("file change counter:", &self.change_counter),
+            ("database page count:", &self.header_page_count),
+            ("freelist page count:", &self.freelist_count),
+            ("schema cookie:", &self.schema_cookie),
+            ("schema format:", &self.schema_format),
+            ("default cache size:", &(self.default_cache_size as u32)),
+            ("autovacuum top root:", &self.autovacuum_top_root),
+            ("incremental vacuum:", &u8::from(self.incremental_vacuum)),
+            ("text encoding:", &text_encoding),
+            ("user version:", &(self.user_version as u32)),
+            ("application id:", &(self.application_id as u32)),
+            ("software version:", &self.software_version),
+            ("number of tables:", &self.num_tables),
+            ("number of indexes:", &self.num_indexes),
+            ("number of triggers:", &self.num_triggers),
+            ("number of views:", &self.num_views),
+            ("schema size:", &self.schema_size),
+        ];
+        for (label, value) in lines {
+            writeln!(f, "{:<20} {}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// An application id as the four ASCII characters it usually spells, if they are printable
+pub fn application_id_tag(application_id: i32) -> Option<String> {
+    let bytes = application_id.to_be_bytes();
+    bytes
+        .iter()
+        .all(|b| b.is_ascii_graphic() || *b == b' ')
+        .then(|| bytes.iter().map(|&b| char::from(b)).collect())
+}
+
+/// A SQLite version number as the release it stands for, such as 3045002 as "3.45.2". Files
+/// last written by versions before 3.7.0 have no number, which is 0.
+pub fn sqlite_version_text(version: u32) -> Option<String> {
+    (version != 0).then(|| {
+        format!(
+            "{}.{}.{}",
+            version / 1_000_000,
+            version / 1000 % 1000,
+            version % 1000
+        )
+    })
+}
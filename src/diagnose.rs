@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::index_btree::read_index_entries;
+use crate::query::{describe_row, StoredRow};
+use crate::record::FieldData;
+use crate::scan::TableScan;
+use crate::schema::TableDef;
+
+/// The kinds of mismatch between a row's record and its table's schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// Fewer fields than the table stores, all of whose missing columns could have been
+    /// added by ALTER TABLE ADD COLUMN after the row was written. SQLite reads them as their
+    /// defaults, so the row is valid, but holds values the application never wrote.
+    AddedColumns,
+    /// Fewer fields than the table stores, missing a column no ALTER TABLE could have added:
+    /// one in the primary key, or NOT NULL without a default
+    MissingFields,
+    /// More fields than the table stores
+    ExcessFields,
+    /// A value of a STRICT table that its column's declared type does not allow
+    StrictType,
+}
+
+/// A problem found in one row of a table. For field count mismatches `expected` and
+/// `actual` are the numbers of stored columns and fields, and `column` is the first missing
+/// column, or the first no ALTER TABLE could have added. For type mismatches they are the
+/// declared type and the value's storage class.
+#[derive(Debug, Clone)]
+pub struct RowDiagnostic {
+    /// None in a WITHOUT ROWID table
+    pub rowid: Option<i64>,
+    /// the rowid, or the primary key of a WITHOUT ROWID table
+    pub row: String,
+    /// what kind of mismatch it is
+    pub kind: DiagnosticKind,
+    /// the column concerned, if any
+    pub column: Option<String>,
+    /// what the schema calls for
+    pub expected: String,
+    /// what the record holds
+    pub actual: String,
+}
+
+impl fmt::Display for RowDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let column = self.column.as_deref().unwrap_or_default();
+        match self.kind {
+            DiagnosticKind::AddedColumns => write!(
+                f,
+                "{}: {} of {} fields, `{}` onwards read as defaults",
+                self.row, self.actual, self.expected, column
+            ),
+            DiagnosticKind::MissingFields => write!(
+                f,
+                "{}: {} of {} fields, missing `{}`",
+                self.row, self.actual, self.expected, column
+            ),
+            DiagnosticKind::ExcessFields => write!(
+                f,
+                "{}: {} fields where the table stores {}",
+                self.row, self.actual, self.expected
+            ),
+            DiagnosticKind::StrictType => write!(
+                f,
+                "{}: `{}` declared {} holds a {} value",
+                self.row, column, self.expected, self.actual
+            ),
+        }
+    }
+}
+
+/// Scan a table and report every row whose record does not match the schema: a field count
+/// other than the number of stored columns, or a value a STRICT column does not allow
+pub fn diagnose_table(db: &Database, name: &str) -> Result<Vec<RowDiagnostic>, SqrliteError> {
+    let schema = db.schema()?;
+    let rootpage = schema.find_table(name)?.rootpage;
+    let table = schema.table_def(name)?;
+
+    let rows: Box<dyn Iterator<Item = StoredRow>> = if table.without_rowid {
+        Box::new(
+            read_index_entries(db, rootpage)?
+                .into_iter()
+                .map(|values| Ok((None, values))),
+        )
+    } else {
+        Box::new(
+            TableScan::new(db, rootpage)
+                .rows()
+                .map(|row| row.map(|(rowid, values)| (Some(rowid), values))),
+        )
+    };
+
+    let mut diagnostics = vec![];
+    for row in rows {
+        let (rowid, values) = row?;
+        diagnostics.extend(diagnose_row(&table, rowid, &values));
+    }
+    Ok(diagnostics)
+}
+
+fn diagnose_row(table: &TableDef, rowid: Option<i64>, values: &[FieldData]) -> Vec<RowDiagnostic> {
+    let row = describe_row(table, rowid, values);
+    let diagnostic = |kind, column: Option<&str>, expected: String, actual: String| RowDiagnostic {
+        rowid,
+        row: row.clone(),
+        kind,
+        column: column.map(str::to_owned),
+        expected,
+        actual,
+    };
+
+    // the stored columns by their position in the record
+    let mut stored = (0..table.columns.len())
+        .filter_map(|idx| Some((table.storage_index(idx)?, idx)))
+        .collect::<Vec<_>>();
+    stored.sort_unstable();
+
+    let mut diagnostics = vec![];
+    if values.len() > stored.len() {
+        diagnostics.push(diagnostic(
+            DiagnosticKind::ExcessFields,
+            None,
+            stored.len().to_string(),
+            values.len().to_string(),
+        ));
+    } else if values.len() < stored.len() {
+        // ALTER TABLE ADD COLUMN cannot add key columns, or NOT NULL ones without a default
+        let missing = &stored[values.len()..];
+        let not_addable = missing.iter().find(|&&(_, idx)| {
+            table.primary_key_position(idx).is_some()
+                || (table.is_not_null(idx) && table.missing_value(idx).is_null())
+        });
+        let (kind, (_, idx)) = match not_addable {
+            Some(column) => (DiagnosticKind::MissingFields, column),
+            None => (DiagnosticKind::AddedColumns, &missing[0]),
+        };
+        diagnostics.push(diagnostic(
+            kind,
+            Some(&table.columns[*idx].name),
+            stored.len().to_string(),
+            values.len().to_string(),
+        ));
+    }
+
+    if table.strict {
+        for &(slot, idx) in &stored {
+            let column = &table.columns[idx];
+            match values.get(slot) {
+                Some(value) if !column.strict_accepts(value) => diagnostics.push(diagnostic(
+                    DiagnosticKind::StrictType,
+                    Some(&column.name),
+                    column.decl_type.clone().unwrap_or_default(),
+                    value.type_name().to_owned(),
+                )),
+                _ => {}
+            }
+        }
+    }
+    diagnostics
+}
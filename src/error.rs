@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::btree_page::{CorruptPage, InvalidPageNumber};
+use crate::cell::{CellParseError, InvalidFieldError, OverflowError};
+use crate::db::{
+    AllocationBudgetError, ConcurrentModification, CorruptData, FreelistError, TruncatedDatabase,
+};
+use crate::header::HeaderError;
+use crate::index_btree::IndexBtreeError;
+use crate::journal::HotJournalError;
+use crate::ptrmap::PtrMapError;
+use crate::query::QueryError;
+use crate::record::{FieldError, ParseError, RecordSizeMismatch, SerialTypeError};
+use crate::scan::ScanError;
+use crate::schema::SchemaError;
+use crate::sniff::NotADatabaseError;
+use crate::sql::SqlParseError;
+use crate::table::NoSuchTable;
+use crate::varint::TruncatedVarintError;
+use crate::wal::WalError;
+
+/// Every way reading a database can fail. Most variants hold the error of the module that
+/// found the problem, with its page numbers, rowids and so on as fields; Display and source()
+/// pass through to it, so a variant only adds the kind of problem to match on.
+///
+/// ```
+/// use sqrlite::{Database, SqrliteError};
+///
+/// let db = Database::new("tests/fixtures/scan.db").unwrap();
+/// match db.table("apples") {
+///     Err(SqrliteError::NoSuchTable(e)) => assert_eq!(e.name, "apples"),
+///     other => panic!("expected no such table, got {:?}", other.map(|t| t.name)),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum SqrliteError {
+    /// A file could not be opened or read, other than by being too short
+    Io {
+        /// what was being done, such as the path being opened
+        context: String,
+        /// the error from the operating system
+        source: io::Error,
+    },
+
+    // the database file and the journal or WAL beside it
+    /// The file does not start with the SQLite header string
+    NotADatabase(NotADatabaseError),
+    /// A header field holds a value SQLite never writes
+    InvalidHeader(HeaderError),
+    /// The file is shorter than the pages the header counts
+    Truncated(TruncatedDatabase),
+    /// A hot rollback journal shows a write was cut short, so the file may be half-written
+    HotJournal(HotJournalError),
+    /// The -wal file beside a WAL-mode database is malformed
+    Wal(WalError),
+    /// Another process wrote to the database while rows were being read
+    ConcurrentModification(ConcurrentModification),
+    /// A value would need more memory than the allocation budget allows
+    AllocationBudget(AllocationBudgetError),
+
+    // pages and the structures on them
+    /// A b-tree refers to a page past the end of the database, or to the lock-byte page or a
+    /// pointer-map page
+    InvalidPageNumber(InvalidPageNumber),
+    /// A b-tree page has a bad header, cell pointer or freeblock
+    CorruptPage(CorruptPage),
+    /// A cell runs off its page or holds impossible sizes
+    CellParse(CellParseError),
+    /// A cell lacks a field its page type has
+    InvalidField(InvalidFieldError),
+    /// An overflow chain ends early, loops, or leaves the database
+    Overflow(OverflowError),
+    /// The freelist is broken
+    Freelist(FreelistError),
+    /// A pointer-map entry could not be read
+    PtrMap(PtrMapError),
+    /// A size or count in the file is more than the file could hold
+    Corrupt(CorruptData),
+
+    // records and the values in them
+    /// A record header is malformed
+    RecordParse(ParseError),
+    /// A record header holds a serial type that does not exist
+    SerialType(SerialTypeError),
+    /// The values of a record do not fill its payload exactly
+    RecordSize(RecordSizeMismatch),
+    /// A value of a record could not be decoded
+    Field(FieldError),
+    /// A varint runs past the end of its input
+    Varint(TruncatedVarintError),
+    /// A b-tree scan met a page or cell it did not expect
+    Scan(ScanError),
+    /// An index b-tree is malformed
+    IndexBtree(IndexBtreeError),
+
+    // the schema and queries against it
+    /// The schema holds SQL this reader cannot make sense of, or contradicts itself
+    Schema(SchemaError),
+    /// A statement could not be parsed
+    SqlParse(SqlParseError),
+    /// A query asks for something it cannot be given
+    Query(QueryError),
+    /// No table has the name asked for
+    NoSuchTable(NoSuchTable),
+    /// A table has no column of the name asked for
+    NoSuchColumn {
+        /// the table searched
+        table: String,
+        /// the name asked for
+        column: String,
+    },
+    /// A table has no row with the rowid asked for
+    NoSuchRow {
+        /// the table searched
+        table: String,
+        /// the rowid asked for
+        rowid: i64,
+    },
+    /// A value of another type than the one asked for, such as a blob to stream
+    TypeMismatch(String),
+    /// Something asked of this reader that it does not do, such as a rowid lookup in a
+    /// WITHOUT ROWID table
+    Unsupported(String),
+}
+
+impl SqrliteError {
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// The error the variant holds, for the variants that hold one
+    fn inner(&self) -> Option<&(dyn Error + 'static)> {
+        Some(match self {
+            Self::NotADatabase(e) => e,
+            Self::InvalidHeader(e) => e,
+            Self::Truncated(e) => e,
+            Self::HotJournal(e) => e,
+            Self::Wal(e) => e,
+            Self::ConcurrentModification(e) => e,
+            Self::AllocationBudget(e) => e,
+            Self::InvalidPageNumber(e) => e,
+            Self::CorruptPage(e) => e,
+            Self::CellParse(e) => e,
+            Self::InvalidField(e) => e,
+            Self::Overflow(e) => e,
+            Self::Freelist(e) => e,
+            Self::PtrMap(e) => e,
+            Self::Corrupt(e) => e,
+            Self::RecordParse(e) => e,
+            Self::SerialType(e) => e,
+            Self::RecordSize(e) => e,
+            Self::Field(e) => e,
+            Self::Varint(e) => e,
+            Self::Scan(e) => e,
+            Self::IndexBtree(e) => e,
+            Self::Schema(e) => e,
+            Self::SqlParse(e) => e,
+            Self::Query(e) => e,
+            Self::NoSuchTable(e) => e,
+            Self::Io { .. }
+            | Self::NoSuchColumn { .. }
+            | Self::NoSuchRow { .. }
+            | Self::TypeMismatch(_)
+            | Self::Unsupported(_) => return None,
+        })
+    }
+}
+
+impl fmt::Display for SqrliteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { context, .. } => write!(f, "{}", context),
+            Self::NoSuchColumn { table, column } => {
+                write!(f, "no such column: {} (in table {})", column, table)
+            }
+            Self::NoSuchRow { table, rowid } => {
+                write!(f, "no row with rowid {} in {}", rowid, table)
+            }
+            Self::TypeMismatch(details) | Self::Unsupported(details) => write!(f, "{}", details),
+            _ => match self.inner() {
+                Some(inner) => write!(f, "{}", inner),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+impl Error for SqrliteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => self.inner().and_then(Error::source),
+        }
+    }
+}
+
+impl From<io::Error> for SqrliteError {
+    fn from(source: io::Error) -> Self {
+        Self::io("I/O error", source)
+    }
+}
+
+macro_rules! from_error {
+    ($($error:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$error> for SqrliteError {
+                fn from(e: $error) -> Self {
+                    Self::$variant(e)
+                }
+            }
+        )*
+    };
+}
+
+from_error! {
+    NotADatabaseError => NotADatabase,
+    HeaderError => InvalidHeader,
+    TruncatedDatabase => Truncated,
+    HotJournalError => HotJournal,
+    WalError => Wal,
+    ConcurrentModification => ConcurrentModification,
+    AllocationBudgetError => AllocationBudget,
+    InvalidPageNumber => InvalidPageNumber,
+    CorruptPage => CorruptPage,
+    CellParseError => CellParse,
+    InvalidFieldError => InvalidField,
+    OverflowError => Overflow,
+    FreelistError => Freelist,
+    PtrMapError => PtrMap,
+    CorruptData => Corrupt,
+    ParseError => RecordParse,
+    SerialTypeError => SerialType,
+    RecordSizeMismatch => RecordSize,
+    FieldError => Field,
+    TruncatedVarintError => Varint,
+    ScanError => Scan,
+    IndexBtreeError => IndexBtree,
+    SchemaError => Schema,
+    SqlParseError => SqlParse,
+    QueryError => Query,
+    NoSuchTable => NoSuchTable,
+}
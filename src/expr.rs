@@ -0,0 +1,672 @@
+use std::cmp::Ordering;
+use std::ops;
+
+use crate::record::FieldData;
+use crate::schema::{Affinity, TableDef};
+use crate::sql::{SqlParseError, Token, TokenKind, TokenStream};
+
+/// A comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `=` or `==`
+    Eq,
+    /// `!=` or `<>`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl CompareOp {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "=" | "==" => Some(Self::Eq),
+            "!=" | "<>" => Some(Self::Ne),
+            "<" => Some(Self::Lt),
+            "<=" => Some(Self::Le),
+            ">" => Some(Self::Gt),
+            ">=" => Some(Self::Ge),
+            _ => None,
+        }
+    }
+
+    /// The operator with its operands swapped, so `5 < x` becomes `x > 5`
+    pub fn flipped(&self) -> Self {
+        match self {
+            CompareOp::Lt => CompareOp::Gt,
+            CompareOp::Le => CompareOp::Ge,
+            CompareOp::Gt => CompareOp::Lt,
+            CompareOp::Ge => CompareOp::Le,
+            op => *op,
+        }
+    }
+
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// SQL's three-valued truth. A comparison involving NULL is Unknown, which NOT leaves
+/// Unknown; AND is False if either side is, and OR is True if either side is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriBool {
+    /// the condition holds
+    True,
+    /// the condition does not hold
+    False,
+    /// the condition involves NULL, so it is not known to hold
+    Unknown,
+}
+
+impl TriBool {
+    /// SQL's AND: False if either side is, True if both are, Unknown otherwise
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (TriBool::False, _) | (_, TriBool::False) => TriBool::False,
+            (TriBool::True, TriBool::True) => TriBool::True,
+            _ => TriBool::Unknown,
+        }
+    }
+
+    /// SQL's OR: True if either side is, False if both are, Unknown otherwise
+    pub fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (TriBool::True, _) | (_, TriBool::True) => TriBool::True,
+            (TriBool::False, TriBool::False) => TriBool::False,
+            _ => TriBool::Unknown,
+        }
+    }
+
+    /// Whether the value is True, as WHERE requires of the rows it keeps
+    pub fn is_true(self) -> bool {
+        self == TriBool::True
+    }
+}
+
+impl ops::Not for TriBool {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            TriBool::True => TriBool::False,
+            TriBool::False => TriBool::True,
+            TriBool::Unknown => TriBool::Unknown,
+        }
+    }
+}
+
+impl From<bool> for TriBool {
+    fn from(b: bool) -> Self {
+        if b {
+            TriBool::True
+        } else {
+            TriBool::False
+        }
+    }
+}
+
+// None is unknown
+impl From<Option<bool>> for TriBool {
+    fn from(b: Option<bool>) -> Self {
+        b.map_or(TriBool::Unknown, TriBool::from)
+    }
+}
+
+/// A column named in an expression
+#[derive(Debug, Clone)]
+pub struct ColumnRef {
+    /// the name as written, matched against the table's columns ignoring ASCII case
+    pub name: String,
+    // position in the table definition, declared collation and affinity, set by
+    // `Expr::resolve`
+    index: Option<usize>,
+    collation: Option<Collation>,
+    affinity: Option<Affinity>,
+}
+
+/// A WHERE-clause expression: comparisons between columns and literals combined with
+/// AND, OR, and NOT. `x BETWEEN a AND b` is stored as `x >= a AND x <= b`.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// the value of a column
+    Column(ColumnRef),
+    /// a constant value
+    Literal(FieldData),
+    /// a comparison of two operands
+    Compare {
+        /// the operator
+        op: CompareOp,
+        /// the left operand
+        left: Box<Expr>,
+        /// the right operand
+        right: Box<Expr>,
+    },
+    /// `expr IS NULL`, or `expr IS NOT NULL` when `negated`
+    IsNull {
+        /// the operand
+        expr: Box<Expr>,
+        /// whether the test is IS NOT NULL
+        negated: bool,
+    },
+    /// both conditions
+    And(Box<Expr>, Box<Expr>),
+    /// either condition
+    Or(Box<Expr>, Box<Expr>),
+    /// the opposite of the condition
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression from the tokens of a WHERE clause
+    pub fn parse(tokens: &mut TokenStream) -> Result<Self, SqlParseError> {
+        parse_or(tokens)
+    }
+
+    /// Bind every column reference to its position in the table
+    pub fn resolve(&mut self, table: &TableDef) -> Result<(), SqlParseError> {
+        match self {
+            Expr::Column(column) => {
+                let idx = table.column_index(&column.name).ok_or_else(|| {
+                    SqlParseError::new(&format!("no such column: {}", column.name))
+                })?;
+                let name = table.columns[idx].collation.as_deref();
+                let collation = Collation::from_name(name).ok_or_else(|| {
+                    SqlParseError::new(&format!(
+                        "no such collation sequence: {}",
+                        name.unwrap_or_default()
+                    ))
+                })?;
+                column.index = Some(idx);
+                column.collation = Some(collation);
+                column.affinity = Some(table.columns[idx].affinity());
+                Ok(())
+            }
+            Expr::Literal(_) => Ok(()),
+            Expr::Compare { left, right, .. } | Expr::And(left, right) | Expr::Or(left, right) => {
+                left.resolve(table)?;
+                right.resolve(table)
+            }
+            Expr::IsNull { expr, .. } | Expr::Not(expr) => expr.resolve(table),
+        }
+    }
+
+    /// The `column <op> literal` terms joined by top-level ANDs, with the column's table
+    /// position and the operator oriented so the column is on the left. Only meaningful once
+    /// the expression has been resolved.
+    pub fn column_comparisons(&self) -> Vec<(usize, CompareOp, &FieldData)> {
+        match self {
+            Expr::And(left, right) => {
+                let mut terms = left.column_comparisons();
+                terms.extend(right.column_comparisons());
+                terms
+            }
+            Expr::Compare { op, left, right } => match (left.as_ref(), right.as_ref()) {
+                (
+                    Expr::Column(ColumnRef {
+                        index: Some(idx), ..
+                    }),
+                    Expr::Literal(value),
+                ) => vec![(*idx, *op, value)],
+                (
+                    Expr::Literal(value),
+                    Expr::Column(ColumnRef {
+                        index: Some(idx), ..
+                    }),
+                ) => vec![(*idx, op.flipped(), value)],
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// The values that `column = literal` terms joined by top-level ANDs require columns to
+    /// have, by table position
+    pub fn required_values(&self) -> Vec<(usize, &FieldData)> {
+        self.column_comparisons()
+            .into_iter()
+            .filter(|(_, op, _)| *op == CompareOp::Eq)
+            .map(|(idx, _, value)| (idx, value))
+            .collect()
+    }
+
+    /// Table positions of the columns the expression refers to, once resolved
+    pub fn columns(&self) -> Vec<usize> {
+        let mut columns = match self {
+            Expr::Column(ColumnRef {
+                index: Some(idx), ..
+            }) => vec![*idx],
+            Expr::Column(_) | Expr::Literal(_) => vec![],
+            Expr::Compare { left, right, .. } | Expr::And(left, right) | Expr::Or(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expr::IsNull { expr, .. } | Expr::Not(expr) => expr.columns(),
+        };
+        columns.sort_unstable();
+        columns.dedup();
+        columns
+    }
+
+    /// Evaluate against a row holding every column of the table in schema order. Comparisons
+    /// involving NULL are unknown, and WHERE keeps only the rows for which this is true.
+    pub fn evaluate(&self, row: &[FieldData]) -> TriBool {
+        match self {
+            Expr::Compare { op, left, right } => {
+                let (a, b) = convert_operands(
+                    left.value(row),
+                    right.value(row),
+                    comparison_affinity(left.affinity(), right.affinity()),
+                );
+                // text compares with the collation of the left column, else the right one
+                let ordering = match (&a, &b, left.collation().or(right.collation())) {
+                    (FieldData::Text(x), FieldData::Text(y), Some(collation)) => {
+                        Some(collation.compare(x, y))
+                    }
+                    _ => a.sqlite_cmp(&b),
+                };
+                ordering.map(|ordering| op.holds(ordering)).into()
+            }
+            Expr::IsNull { expr, negated } => {
+                let is_null = matches!(expr.value(row), FieldData::Null(_));
+                (is_null != *negated).into()
+            }
+            Expr::And(left, right) => left.evaluate(row).and(right.evaluate(row)),
+            Expr::Or(left, right) => left.evaluate(row).or(right.evaluate(row)),
+            Expr::Not(expr) => !expr.evaluate(row),
+            Expr::Column(_) | Expr::Literal(_) => truthiness(&self.value(row)).into(),
+        }
+    }
+
+    // Only column references have an affinity; literals and nested conditions have none
+    fn affinity(&self) -> Option<Affinity> {
+        match self {
+            Expr::Column(column) => column.affinity,
+            _ => None,
+        }
+    }
+
+    fn collation(&self) -> Option<Collation> {
+        match self {
+            Expr::Column(column) => column.collation,
+            _ => None,
+        }
+    }
+
+    fn value(&self, row: &[FieldData]) -> FieldData {
+        match self {
+            Expr::Column(ColumnRef {
+                index: Some(idx), ..
+            }) => row.get(*idx).cloned().unwrap_or(FieldData::Null(())),
+            Expr::Literal(value) => value.clone(),
+            _ => match self.evaluate(row) {
+                TriBool::True => FieldData::Integer(1),
+                TriBool::False => FieldData::Integer(0),
+                TriBool::Unknown => FieldData::Null(()),
+            },
+        }
+    }
+}
+
+/// The built-in collating sequences used to order text in indexes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// compares the bytes of the text
+    Binary,
+    /// folds ASCII letters only, like SQLite
+    NoCase,
+    /// ignores trailing spaces
+    RTrim,
+}
+
+impl Collation {
+    /// Look up a collation by name; no name means the default BINARY collation
+    pub fn from_name(name: Option<&str>) -> Option<Self> {
+        match name {
+            None => Some(Self::Binary),
+            Some(name) if name.eq_ignore_ascii_case("BINARY") => Some(Self::Binary),
+            Some(name) if name.eq_ignore_ascii_case("NOCASE") => Some(Self::NoCase),
+            Some(name) if name.eq_ignore_ascii_case("RTRIM") => Some(Self::RTrim),
+            Some(_) => None,
+        }
+    }
+
+    /// Compare two strings under the collation
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.as_bytes().cmp(b.as_bytes()),
+            Collation::NoCase => a
+                .bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            Collation::RTrim => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+        }
+    }
+}
+
+// The affinity SQLite converts both operands of a comparison to: NUMERIC when either side is
+// a column with a numeric affinity, otherwise the affinity of the only side that is a
+// column. BLOB affinity converts nothing.
+fn comparison_affinity(left: Option<Affinity>, right: Option<Affinity>) -> Option<Affinity> {
+    match (left, right) {
+        (Some(a), Some(b)) if a.is_numeric() || b.is_numeric() => Some(Affinity::Numeric),
+        (Some(_), Some(_)) | (None, None) => None,
+        (Some(affinity), None) | (None, Some(affinity)) => Some(affinity),
+    }
+}
+
+// Convert the operands of a comparison as SQLite does at run time: under a numeric
+// affinity TEXT that reads as a number becomes that number, and under TEXT affinity numbers
+// become text, but only when the other operand is TEXT, so two numbers still compare as
+// numbers
+fn convert_operands(
+    a: FieldData,
+    b: FieldData,
+    affinity: Option<Affinity>,
+) -> (FieldData, FieldData) {
+    match affinity {
+        Some(affinity) if affinity.is_numeric() => (
+            a.apply_affinity(Affinity::Numeric),
+            b.apply_affinity(Affinity::Numeric),
+        ),
+        Some(Affinity::Text)
+            if matches!(a, FieldData::Text(_)) || matches!(b, FieldData::Text(_)) =>
+        {
+            (
+                a.apply_affinity(Affinity::Text),
+                b.apply_affinity(Affinity::Text),
+            )
+        }
+        _ => (a, b),
+    }
+}
+
+// Values used directly as conditions are true when numerically non-zero
+fn truthiness(value: &FieldData) -> Option<bool> {
+    match value {
+        FieldData::Text(text) => Some(text.trim().parse::<f64>().is_ok_and(|n| n != 0.0)),
+        FieldData::Blob(_) => Some(false),
+        _ => value.as_bool(),
+    }
+}
+
+/// Convert a literal token to a value, applying a leading minus sign to numbers
+pub fn literal_token_value(token: &Token, negative: bool) -> Option<FieldData> {
+    match token.kind {
+        TokenKind::Number => {
+            let sign = if negative { "-" } else { "" };
+            let digits = &token.value;
+            if let Some(hex) = digits
+                .strip_prefix("0x")
+                .or_else(|| digits.strip_prefix("0X"))
+            {
+                let value = u64::from_str_radix(hex, 16).ok()? as i64;
+                Some(FieldData::Integer(if negative {
+                    value.wrapping_neg()
+                } else {
+                    value
+                }))
+            } else if let Ok(value) = format!("{}{}", sign, digits).parse::<i64>() {
+                Some(FieldData::Integer(value))
+            } else {
+                let value = format!("{}{}", sign, digits).parse::<f64>().ok()?;
+                Some(FieldData::Real(value))
+            }
+        }
+        _ if negative => None,
+        TokenKind::String => Some(FieldData::Text(token.value.clone())),
+        TokenKind::Blob => {
+            if !token.value.len().is_multiple_of(2) || !token.value.is_ascii() {
+                return None;
+            }
+            let bytes = (0..token.value.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&token.value[i..i + 2], 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Some(FieldData::Blob(bytes))
+        }
+        TokenKind::Word if token.is_keyword("NULL") => Some(FieldData::Null(())),
+        TokenKind::Word if token.is_keyword("TRUE") => Some(FieldData::Integer(1)),
+        TokenKind::Word if token.is_keyword("FALSE") => Some(FieldData::Integer(0)),
+        _ => None,
+    }
+}
+
+fn parse_or(tokens: &mut TokenStream) -> Result<Expr, SqlParseError> {
+    let mut expr = parse_and(tokens)?;
+    while tokens.eat_keyword("OR") {
+        expr = Expr::Or(Box::new(expr), Box::new(parse_and(tokens)?));
+    }
+    Ok(expr)
+}
+
+fn parse_and(tokens: &mut TokenStream) -> Result<Expr, SqlParseError> {
+    let mut expr = parse_not(tokens)?;
+    while tokens.eat_keyword("AND") {
+        expr = Expr::And(Box::new(expr), Box::new(parse_not(tokens)?));
+    }
+    Ok(expr)
+}
+
+fn parse_not(tokens: &mut TokenStream) -> Result<Expr, SqlParseError> {
+    if tokens.eat_keyword("NOT") {
+        return Ok(Expr::Not(Box::new(parse_not(tokens)?)));
+    }
+    parse_comparison(tokens)
+}
+
+fn parse_comparison(tokens: &mut TokenStream) -> Result<Expr, SqlParseError> {
+    let left = parse_primary(tokens)?;
+
+    if tokens.eat_keyword("ISNULL") || tokens.eat_keywords(&["IS", "NULL"]) {
+        return Ok(Expr::IsNull {
+            expr: Box::new(left),
+            negated: false,
+        });
+    }
+    if tokens.eat_keyword("NOTNULL")
+        || tokens.eat_keywords(&["IS", "NOT", "NULL"])
+        || tokens.eat_keywords(&["NOT", "NULL"])
+    {
+        return Ok(Expr::IsNull {
+            expr: Box::new(left),
+            negated: true,
+        });
+    }
+
+    let negated = tokens.eat_keywords(&["NOT", "BETWEEN"]);
+    if negated || tokens.eat_keyword("BETWEEN") {
+        let low = parse_primary(tokens)?;
+        tokens.expect_keyword("AND")?;
+        let high = parse_primary(tokens)?;
+        let between = Expr::And(
+            Box::new(Expr::Compare {
+                op: CompareOp::Ge,
+                left: Box::new(left.clone()),
+                right: Box::new(low),
+            }),
+            Box::new(Expr::Compare {
+                op: CompareOp::Le,
+                left: Box::new(left),
+                right: Box::new(high),
+            }),
+        );
+        return Ok(if negated {
+            Expr::Not(Box::new(between))
+        } else {
+            between
+        });
+    }
+
+    let op = tokens
+        .peek()
+        .filter(|t| t.kind == TokenKind::Symbol)
+        .and_then(|t| CompareOp::from_symbol(&t.value));
+    match op {
+        Some(op) => {
+            tokens.next_token();
+            let right = parse_primary(tokens)?;
+            Ok(Expr::Compare {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            })
+        }
+        None => Ok(left),
+    }
+}
+
+fn parse_primary(tokens: &mut TokenStream) -> Result<Expr, SqlParseError> {
+    if tokens.eat_symbol("(") {
+        let expr = parse_or(tokens)?;
+        tokens.expect_symbol(")")?;
+        return Ok(expr);
+    }
+
+    let negative = tokens.eat_symbol("-");
+    if !negative {
+        tokens.eat_symbol("+");
+    }
+    let token = tokens
+        .peek()
+        .cloned()
+        .ok_or_else(|| SqlParseError::new("expected expression but reached end of input"))?;
+
+    if let Some(value) = literal_token_value(&token, negative) {
+        tokens.next_token();
+        return Ok(Expr::Literal(value));
+    }
+    if negative {
+        return Err(SqlParseError::new(&format!(
+            "cannot negate `{}`",
+            token.value
+        )));
+    }
+
+    // a column may be qualified with its table name, which is dropped here
+    let mut name = tokens.identifier()?;
+    if tokens.eat_symbol(".") {
+        name = tokens.identifier()?;
+    }
+    Ok(Expr::Column(ColumnRef {
+        name,
+        index: None,
+        collation: None,
+        affinity: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: Collation, words: &[&'static str]) -> Vec<&'static str> {
+        let mut words = words.to_vec();
+        words.sort_by(|a, b| collation.compare(a, b));
+        words
+    }
+
+    #[test]
+    fn collations_order_text() {
+        let words = ["b", "B", "a", "_", "A ", "a  "];
+        // uppercase letters sort before `_` in bytes, and after it once folded to lowercase
+        assert_eq!(
+            sorted(Collation::Binary, &words),
+            ["A ", "B", "_", "a", "a  ", "b"]
+        );
+        assert_eq!(
+            sorted(Collation::NoCase, &words),
+            ["_", "a", "A ", "a  ", "b", "B"]
+        );
+        assert_eq!(
+            sorted(Collation::RTrim, &words),
+            ["A ", "B", "_", "a", "a  ", "b"]
+        );
+
+        assert_eq!(Collation::NoCase.compare("ABC", "abc"), Ordering::Equal);
+        assert_eq!(Collation::Binary.compare("ABC", "abc"), Ordering::Less);
+        assert_eq!(Collation::RTrim.compare("abc  ", "abc"), Ordering::Equal);
+        assert_eq!(Collation::RTrim.compare("  abc", "abc"), Ordering::Less);
+        // NOCASE folds ASCII only
+        assert_ne!(Collation::NoCase.compare("É", "é"), Ordering::Equal);
+    }
+
+    #[test]
+    fn collation_names() {
+        assert_eq!(Collation::from_name(None), Some(Collation::Binary));
+        assert_eq!(
+            Collation::from_name(Some("nocase")),
+            Some(Collation::NoCase)
+        );
+        assert_eq!(Collation::from_name(Some("RTrim")), Some(Collation::RTrim));
+        assert_eq!(Collation::from_name(Some("unicode")), None);
+    }
+
+    #[test]
+    fn three_valued_truth_tables() {
+        use TriBool::{False as F, True as T, Unknown as U};
+        // (a, b, a AND b, a OR b)
+        let table = [
+            (T, T, T, T),
+            (T, F, F, T),
+            (T, U, U, T),
+            (F, T, F, T),
+            (F, F, F, F),
+            (F, U, F, U),
+            (U, T, U, T),
+            (U, F, F, U),
+            (U, U, U, U),
+        ];
+        for (a, b, and, or) in table {
+            assert_eq!(a.and(b), and, "{:?} AND {:?}", a, b);
+            assert_eq!(a.or(b), or, "{:?} OR {:?}", a, b);
+        }
+        assert_eq!([!T, !F, !U], [F, T, U]);
+        assert_eq!([T, F, U].map(TriBool::is_true), [true, false, false]);
+        assert_eq!(TriBool::from(None), U);
+        assert_eq!(TriBool::from(Some(false)), F);
+    }
+
+    // Evaluate a WHERE expression over a row of t(a, b)
+    fn evaluate(sql: &str, a: FieldData, b: FieldData) -> TriBool {
+        let table = TableDef::parse("CREATE TABLE t(a, b)").unwrap();
+        let mut expr = Expr::parse(&mut TokenStream::new(sql).unwrap()).unwrap();
+        expr.resolve(&table).unwrap();
+        expr.evaluate(&[a, b])
+    }
+
+    #[test]
+    fn null_operands_in_where_clauses() {
+        let null = || FieldData::Null(());
+        let one = || FieldData::Integer(1);
+        let cases = [
+            ("a = 1", null(), one(), TriBool::Unknown),
+            ("NOT a = 1", null(), one(), TriBool::Unknown),
+            ("a = NULL", null(), one(), TriBool::Unknown),
+            ("a IS NULL", null(), one(), TriBool::True),
+            ("a IS NOT NULL", null(), one(), TriBool::False),
+            ("a = 1 AND b = 1", null(), one(), TriBool::Unknown),
+            ("a = 1 AND b = 2", null(), one(), TriBool::False),
+            ("a = 1 OR b = 1", null(), one(), TriBool::True),
+            ("a = 1 OR b = 2", null(), one(), TriBool::Unknown),
+            ("NOT (a = 1 OR b = 2)", null(), one(), TriBool::Unknown),
+            ("NOT (a = 1 AND b = 2)", null(), one(), TriBool::True),
+            ("a = 1 OR b = 2", one(), null(), TriBool::True),
+        ];
+        for (sql, a, b, expected) in cases {
+            assert_eq!(evaluate(sql, a, b), expected, "{}", sql);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::record::TextEncoding;
+
+/// Size of the database header at the start of page 1
+pub const DB_HEADER_SIZE: usize = 100;
+/// The header string every database file starts with
+pub const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// A header field holding a value SQLite would refuse. The field is named as in DBHeader, and
+/// for the header string the value is its first four bytes.
+#[derive(Debug)]
+pub struct HeaderError {
+    /// the DBHeader field
+    pub field: &'static str,
+    /// the value found there
+    pub value: u32,
+    details: String,
+}
+
+impl HeaderError {
+    fn new(field: &'static str, value: u32, details: &str) -> Self {
+        Self {
+            field,
+            value,
+            details: format!("file is not a valid database: {}", details),
+        }
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for HeaderError {}
+
+/// How the database is set up to commit transactions, told by the file format version
+/// numbers: in WAL mode recent commits live in a separate -wal file until a checkpoint copies
+/// them into the database file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum JournalMode {
+    /// changes are made in place, with a rollback journal to undo them
+    Rollback,
+    /// changes are appended to a -wal file first
+    Wal,
+}
+
+impl JournalMode {
+    /// The mode as PRAGMA journal_mode names it
+    pub fn name(&self) -> &'static str {
+        match self {
+            JournalMode::Rollback => "rollback",
+            JournalMode::Wal => "wal",
+        }
+    }
+}
+
+/// Whether the database keeps pointer-map pages so that free pages can be moved to the end
+/// of the file and cut off, on every commit or only when PRAGMA incremental_vacuum asks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum AutoVacuum {
+    /// no pointer-map pages
+    None,
+    /// free pages are moved to the end and cut off on every commit
+    Full,
+    /// free pages are kept until PRAGMA incremental_vacuum
+    Incremental,
+}
+
+impl AutoVacuum {
+    /// The mode as PRAGMA auto_vacuum names it
+    pub fn name(&self) -> &'static str {
+        match self {
+            AutoVacuum::None => "none",
+            AutoVacuum::Full => "full",
+            AutoVacuum::Incremental => "incremental",
+        }
+    }
+}
+
+/// The 100-byte header at the start of the database file, as described in
+/// <https://www.sqlite.org/fileformat2.html#the_database_header>
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DBHeader {
+    /// in bytes; the stored value 1 means 65536
+    pub page_size: u32,
+    /// file format write version: 1 for rollback journal, 2 for WAL
+    pub write_version: u8,
+    /// file format read version: 1 for rollback journal, 2 for WAL
+    pub read_version: u8,
+    /// unused bytes at the end of each page
+    pub reserved_space: u8,
+    /// maximum embedded payload fraction, always 64
+    pub max_payload_fraction: u8,
+    /// minimum embedded payload fraction, always 32
+    pub min_payload_fraction: u8,
+    /// leaf payload fraction, always 32
+    pub leaf_payload_fraction: u8,
+    /// incremented whenever the file is changed
+    pub change_counter: u32,
+    /// in pages, only trusted when version_valid_for matches
+    pub database_size: u32,
+    /// first page of the freelist, 0 if it is empty
+    pub first_freelist_trunk: u32,
+    /// number of pages on the freelist
+    pub freelist_count: u32,
+    /// incremented whenever the schema changes
+    pub schema_cookie: u32,
+    /// schema format number, 1 to 4
+    pub schema_format: u32,
+    /// suggested page cache size, as set with PRAGMA default_cache_size
+    pub default_cache_size: i32,
+    /// non-zero in auto_vacuum and incremental_vacuum databases
+    pub largest_root_page: u32,
+    /// the encoding of TEXT values
+    pub text_encoding: TextEncoding,
+    /// as stored, 0 until the first table is created
+    pub text_encoding_value: u32,
+    /// set by PRAGMA user_version for the application's own use
+    pub user_version: i32,
+    /// whether auto_vacuum is incremental
+    pub incremental_vacuum: bool,
+    /// set by PRAGMA application_id to identify the application's file format
+    pub application_id: i32,
+    /// the change counter as of when the database size was last written
+    pub version_valid_for: u32,
+    /// of the library that last wrote the file, e.g. 3045001
+    pub sqlite_version: u32,
+}
+
+impl DBHeader {
+    /// Parse the header, refusing files SQLite itself would not open
+    pub fn parse(bytes: &[u8; DB_HEADER_SIZE]) -> Result<Self, HeaderError> {
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        if &bytes[..16] != MAGIC {
+            return Err(HeaderError::new(
+                "magic",
+                u32_at(0),
+                "missing the SQLite format 3 header string",
+            ));
+        }
+        let page_size = match u16::from_be_bytes([bytes[16], bytes[17]]) {
+            1 => 65536,
+            value @ 512..=32768 if value.is_power_of_two() => u32::from(value),
+            value => {
+                return Err(HeaderError::new(
+                    "page_size",
+                    u32::from(value),
+                    &format!("invalid page size {} in header", value),
+                ))
+            }
+        };
+        let read_version = bytes[19];
+        if !(1..=2).contains(&read_version) {
+            return Err(HeaderError::new(
+                "read_version",
+                u32::from(read_version),
+                &format!("unsupported file format read version {}", read_version),
+            ));
+        }
+        let reserved_space = bytes[20];
+        if page_size - u32::from(reserved_space) < 480 {
+            return Err(HeaderError::new(
+                "reserved_space",
+                u32::from(reserved_space),
+                &format!(
+                    "{} reserved bytes leave too little of a {}-byte page usable",
+                    reserved_space, page_size
+                ),
+            ));
+        }
+        // the payload fractions were meant to be tunable but must always be 64, 32 and 32
+        let fractions = [
+            ("max_payload_fraction", bytes[21], 64),
+            ("min_payload_fraction", bytes[22], 32),
+            ("leaf_payload_fraction", bytes[23], 32),
+        ];
+        for (field, value, expected) in fractions {
+            if value != expected {
+                return Err(HeaderError::new(
+                    field,
+                    u32::from(value),
+                    &format!("{} is {} instead of {}", field, value, expected),
+                ));
+            }
+        }
+        // like the encoding below, the schema format is only set once the first table is made
+        let schema_format = u32_at(44);
+        if schema_format > 4 || (schema_format == 0 && u32_at(28) > 1) {
+            return Err(HeaderError::new(
+                "schema_format",
+                schema_format,
+                &format!("unsupported schema format {}", schema_format),
+            ));
+        }
+        let text_encoding = TextEncoding::from_header_value(u32_at(56)).ok_or_else(|| {
+            HeaderError::new(
+                "text_encoding",
+                u32_at(56),
+                &format!("unsupported text encoding {}", u32_at(56)),
+            )
+        })?;
+        // the encoding is only left unset until the first table is created, which adds a page
+        if u32_at(56) == 0 && u32_at(28) > 1 {
+            return Err(HeaderError::new(
+                "text_encoding",
+                0,
+                "no text encoding recorded for a database holding tables",
+            ));
+        }
+
+        Ok(Self {
+            page_size,
+            write_version: bytes[18],
+            read_version,
+            reserved_space,
+            max_payload_fraction: bytes[21],
+            min_payload_fraction: bytes[22],
+            leaf_payload_fraction: bytes[23],
+            change_counter: u32_at(24),
+            database_size: u32_at(28),
+            first_freelist_trunk: u32_at(32),
+            freelist_count: u32_at(36),
+            schema_cookie: u32_at(40),
+            schema_format,
+            default_cache_size: u32_at(48) as i32,
+            largest_root_page: u32_at(52),
+            text_encoding,
+            text_encoding_value: u32_at(56),
+            user_version: u32_at(60) as i32,
+            incremental_vacuum: u32_at(64) != 0,
+            application_id: u32_at(68) as i32,
+            version_valid_for: u32_at(92),
+            sqlite_version: u32_at(96),
+        })
+    }
+
+    /// Versions of SQLite before 3.7.0 left the size in the header stale, which the
+    /// version-valid-for number reveals by not matching the change counter
+    pub fn database_size_valid(&self) -> bool {
+        self.database_size != 0 && self.version_valid_for == self.change_counter
+    }
+
+    /// Number of pages in a database file of the given size, from the header unless the size
+    /// there is stale
+    pub fn page_count(&self, file_size: u64) -> u32 {
+        if self.database_size_valid() {
+            self.database_size
+        } else {
+            // SQLite stops at 2^32 - 2 pages, so only a file too large to be a database fails this
+            (file_size / u64::from(self.page_size))
+                .try_into()
+                .unwrap_or(u32::MAX)
+        }
+    }
+
+    /// Whether the database is in WAL mode, from the file format version numbers
+    pub fn journal_mode(&self) -> JournalMode {
+        if self.read_version == 2 || self.write_version == 2 {
+            JournalMode::Wal
+        } else {
+            JournalMode::Rollback
+        }
+    }
+
+    /// The auto-vacuum mode. The incremental vacuum flag only means something when there is
+    /// a largest root page.
+    pub fn auto_vacuum(&self) -> AutoVacuum {
+        match (self.largest_root_page, self.incremental_vacuum) {
+            (0, _) => AutoVacuum::None,
+            (_, false) => AutoVacuum::Full,
+            (_, true) => AutoVacuum::Incremental,
+        }
+    }
+
+    /// Bytes of each page available to b-tree content, before the reserved space
+    pub fn usable_size(&self) -> u32 {
+        self.page_size - u32::from(self.reserved_space)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, SqrliteError};
+
+    // The header of a freshly created database holding one table, with the given value in the
+    // page size field
+    fn header(page_size: u16) -> [u8; DB_HEADER_SIZE] {
+        let mut bytes = [0; DB_HEADER_SIZE];
+        bytes[..16].copy_from_slice(MAGIC);
+        bytes[16..18].copy_from_slice(&page_size.to_be_bytes());
+        bytes[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+        bytes[28..32].copy_from_slice(&2u32.to_be_bytes());
+        bytes[44..48].copy_from_slice(&4u32.to_be_bytes());
+        bytes[56..60].copy_from_slice(&1u32.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn page_size_of_one_means_64k() {
+        let parsed = DBHeader::parse(&header(1)).unwrap();
+        assert_eq!(parsed.page_size, 65536);
+        assert_eq!(parsed.usable_size(), 65536);
+    }
+
+    #[test]
+    fn page_sizes_are_powers_of_two_from_512() {
+        for shift in 9..=15 {
+            let page_size = 1u16 << shift;
+            let parsed = DBHeader::parse(&header(page_size)).unwrap();
+            assert_eq!(parsed.page_size, u32::from(page_size));
+        }
+        for page_size in [0, 2, 256, 511, 1000, 3072, 32769, 65535] {
+            let err = DBHeader::parse(&header(page_size)).unwrap_err();
+            assert_eq!(err.field, "page_size");
+            assert_eq!(err.value, u32::from(page_size));
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "file is not a valid database: invalid page size {} in header",
+                    page_size
+                )
+            );
+        }
+    }
+
+    fn assert_invalid(bytes: &[u8; DB_HEADER_SIZE], field: &str, value: u32, details: &str) {
+        let err = DBHeader::parse(bytes).unwrap_err();
+        assert_eq!((err.field, err.value), (field, value));
+        assert_eq!(
+            err.to_string(),
+            format!("file is not a valid database: {}", details)
+        );
+    }
+
+    #[test]
+    fn header_string() {
+        let mut bytes = header(4096);
+        bytes[..16].copy_from_slice(b"SQLite format 2\0");
+        assert_invalid(
+            &bytes,
+            "magic",
+            u32::from_be_bytes(*b"SQLi"),
+            "missing the SQLite format 3 header string",
+        );
+    }
+
+    #[test]
+    fn read_version() {
+        for version in [1, 2] {
+            let mut bytes = header(4096);
+            bytes[19] = version;
+            assert!(DBHeader::parse(&bytes).is_ok());
+        }
+        for version in [0, 3, 255] {
+            let mut bytes = header(4096);
+            bytes[19] = version;
+            let details = format!("unsupported file format read version {}", version);
+            assert_invalid(&bytes, "read_version", u32::from(version), &details);
+        }
+    }
+
+    #[test]
+    fn reserved_space_leaves_480_usable_bytes() {
+        let mut bytes = header(512);
+        bytes[20] = 32;
+        assert_eq!(DBHeader::parse(&bytes).unwrap().usable_size(), 480);
+        bytes[20] = 33;
+        assert_invalid(
+            &bytes,
+            "reserved_space",
+            33,
+            "33 reserved bytes leave too little of a 512-byte page usable",
+        );
+        // the whole byte is fine on larger pages
+        let mut bytes = header(1024);
+        bytes[20] = 255;
+        assert_eq!(DBHeader::parse(&bytes).unwrap().usable_size(), 769);
+    }
+
+    #[test]
+    fn payload_fractions() {
+        let fields = [
+            (21, "max_payload_fraction", 64),
+            (22, "min_payload_fraction", 32),
+            (23, "leaf_payload_fraction", 32),
+        ];
+        for (offset, field, expected) in fields {
+            for value in [0, expected - 1, 255] {
+                let mut bytes = header(4096);
+                bytes[offset] = value;
+                let details = format!("{} is {} instead of {}", field, value, expected);
+                assert_invalid(&bytes, field, u32::from(value), &details);
+            }
+        }
+    }
+
+    #[test]
+    fn schema_format() {
+        for format in 1..=4u32 {
+            let mut bytes = header(4096);
+            bytes[44..48].copy_from_slice(&format.to_be_bytes());
+            assert_eq!(DBHeader::parse(&bytes).unwrap().schema_format, format);
+        }
+        for format in [5u32, 0x0100_0000] {
+            let mut bytes = header(4096);
+            bytes[44..48].copy_from_slice(&format.to_be_bytes());
+            let details = format!("unsupported schema format {}", format);
+            assert_invalid(&bytes, "schema_format", format, &details);
+        }
+        // 0 is left in the header of a database no table has been made in yet
+        let mut bytes = header(4096);
+        bytes[44..48].fill(0);
+        bytes[28..32].copy_from_slice(&1u32.to_be_bytes());
+        assert!(DBHeader::parse(&bytes).is_ok());
+        bytes[28..32].copy_from_slice(&2u32.to_be_bytes());
+        assert_invalid(&bytes, "schema_format", 0, "unsupported schema format 0");
+    }
+
+    #[test]
+    fn text_encoding() {
+        let mut bytes = header(4096);
+        bytes[56..60].copy_from_slice(&4u32.to_be_bytes());
+        assert_invalid(&bytes, "text_encoding", 4, "unsupported text encoding 4");
+    }
+
+    #[test]
+    fn opening_a_database_with_a_bad_header() {
+        let fixture = include_bytes!("../tests/fixtures/scan.db");
+        for (offset, value, field) in [(16, 0, "page_size"), (21, 0, "max_payload_fraction")] {
+            let mut bytes = fixture.to_vec();
+            bytes[offset..offset + 2].fill(value);
+            match Database::from_bytes(bytes) {
+                Err(SqrliteError::InvalidHeader(e)) => assert_eq!(e.field, field),
+                other => panic!("expected an invalid header, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use crate::btree_page::{BtreePage, PageType};
+use crate::cell::CellRef;
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::expr::Collation;
+use crate::record::{decode_row, FieldData};
+use crate::schema::IndexColumn;
+
+/// An index b-tree that cannot be read as its definition says, such as one with an unknown
+/// collation or an entry not ending in a rowid
+#[derive(Debug)]
+pub struct IndexBtreeError {
+    details: String,
+}
+
+impl IndexBtreeError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for IndexBtreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for IndexBtreeError {}
+
+/// How one column of an index key is ordered
+#[derive(Debug, Clone, Copy)]
+pub struct KeyOrder {
+    /// how text in the column compares
+    pub collation: Collation,
+    /// whether the column is ordered largest first
+    pub descending: bool,
+}
+
+impl KeyOrder {
+    /// The order of each column of an index, failing on a collation SQLite does not build in
+    pub fn for_columns<'a, I>(columns: I) -> Result<Vec<Self>, IndexBtreeError>
+    where
+        I: IntoIterator<Item = &'a IndexColumn>,
+    {
+        columns
+            .into_iter()
+            .map(|col| {
+                let name = col.collation.as_deref();
+                Ok(Self {
+                    collation: Collation::from_name(name).ok_or_else(|| {
+                        IndexBtreeError::new(&format!(
+                            "no such collation sequence: {}",
+                            name.unwrap_or_default()
+                        ))
+                    })?,
+                    descending: col.descending,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Compare two index records the way SQLite orders them in the b-tree: field by field, each
+/// with its column's collation and direction, a record that runs out of fields first sorting
+/// before the other. Fields beyond the columns in `order` (the rowid) are compared ascending
+/// with BINARY collation.
+pub fn compare_records(a: &[FieldData], b: &[FieldData], order: &[KeyOrder]) -> Ordering {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        let key_order = order.get(i).copied().unwrap_or(KeyOrder {
+            collation: Collation::Binary,
+            descending: false,
+        });
+        let ordering = compare_field(x, y, key_order.collation);
+        if ordering != Ordering::Equal {
+            return if key_order.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Compare the leading fields of an index record with a probe key that may cover only a
+/// prefix of the index columns, in the order the entries are stored in the b-tree
+pub fn compare_key(record: &[FieldData], probe: &[FieldData], order: &[KeyOrder]) -> Ordering {
+    compare_records(&record[..record.len().min(probe.len())], probe, order)
+}
+
+// Sort order of two values within an index: unlike in expressions, NULLs are equal to each
+// other and sort before everything else
+fn compare_field(a: &FieldData, b: &FieldData, collation: Collation) -> Ordering {
+    match (a, b) {
+        (FieldData::Null(_), FieldData::Null(_)) => Ordering::Equal,
+        (FieldData::Null(_), _) => Ordering::Less,
+        (_, FieldData::Null(_)) => Ordering::Greater,
+        (FieldData::Text(x), FieldData::Text(y)) => collation.compare(x, y),
+        _ => a.sqlite_cmp(b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Find the entry whose leading fields equal `probe` in the index b-tree rooted at
+/// `root_page`, binary-searching the cells of each page on the way down. Entries of an index
+/// b-tree live in interior pages as well as leaves, so the search can end above the leaves.
+pub fn seek_key(
+    db: &Database,
+    root_page: u32,
+    probe: &[FieldData],
+    order: &[KeyOrder],
+) -> Result<Option<Vec<FieldData>>, SqrliteError> {
+    let mut buffer = vec![];
+    let mut page_num = root_page;
+    loop {
+        let page = read_index_page(db, page_num, &mut buffer)?;
+        let start = KeyBound::inclusive(probe);
+        let low = partition_point(db, &page, &buffer, |entry| start.precedes(entry, order))?;
+
+        let mut left_child = None;
+        if low < page.cell_pointers.len() {
+            let (child, record) = read_entry(db, &page, &buffer, low)?;
+            if compare_key(&record, probe, order) == Ordering::Equal {
+                return Ok(Some(record));
+            }
+            left_child = child;
+        }
+        page_num = match page.page_type {
+            PageType::InteriorIndex => match left_child.or(page.rightmost_ptr) {
+                Some(child) => child,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+    }
+}
+
+/// Decode every entry of the index b-tree rooted at `root_page`, in key order
+pub fn read_index_entries(
+    db: &Database,
+    root_page: u32,
+) -> Result<Vec<Vec<FieldData>>, SqrliteError> {
+    let mut entries = vec![];
+    let mut buffer = vec![];
+    // pages still being visited, with the position of the next cell to read from each
+    let mut stack = vec![(root_page, 0)];
+
+    while let Some((page_num, position)) = stack.pop() {
+        let page = read_index_page(db, page_num, &mut buffer)?;
+        match page.page_type {
+            PageType::LeafIndex => {
+                for i in 0..page.cell_pointers.len() {
+                    entries.push(read_entry(db, &page, &buffer, i)?.1);
+                }
+            }
+            _ => {
+                // an interior cell's entry sorts after everything in its left child
+                if position > 0 {
+                    entries.push(read_entry(db, &page, &buffer, position - 1)?.1);
+                }
+                if position < page.cell_pointers.len() {
+                    let (child, _) = read_entry(db, &page, &buffer, position)?;
+                    stack.push((page_num, position + 1));
+                    stack.push((child.unwrap_or_default(), 0));
+                } else if let Some(ptr) = page.rightmost_ptr {
+                    stack.push((ptr, 0));
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// One end of a range of index keys. The key may cover only a prefix of the index columns,
+/// in which case it bounds every entry starting with that prefix.
+#[derive(Debug, Clone)]
+pub struct KeyBound {
+    /// the key, or a prefix of it
+    pub key: Vec<FieldData>,
+    /// whether entries equal to the key are in the range
+    pub inclusive: bool,
+}
+
+impl KeyBound {
+    /// A bound taking in entries equal to the key
+    pub fn inclusive(key: &[FieldData]) -> Self {
+        Self {
+            key: key.to_vec(),
+            inclusive: true,
+        }
+    }
+
+    /// A bound leaving out entries equal to the key
+    pub fn exclusive(key: &[FieldData]) -> Self {
+        Self {
+            key: key.to_vec(),
+            inclusive: false,
+        }
+    }
+
+    // Whether an entry sorts before the range this bound starts
+    fn precedes(&self, entry: &[FieldData], order: &[KeyOrder]) -> bool {
+        match compare_key(entry, &self.key, order) {
+            Ordering::Less => true,
+            Ordering::Equal => !self.inclusive,
+            Ordering::Greater => false,
+        }
+    }
+
+    // Whether an entry sorts after the range this bound ends
+    fn follows(&self, entry: &[FieldData], order: &[KeyOrder]) -> bool {
+        match compare_key(entry, &self.key, order) {
+            Ordering::Less => false,
+            Ordering::Equal => !self.inclusive,
+            Ordering::Greater => true,
+        }
+    }
+}
+
+/// Iterates over the rowids of the index entries within a range of keys, in index order.
+/// The search descends to the first entry in the range and then walks the tree in order,
+/// climbing back through parent pages to continue across leaf boundaries, until an entry
+/// passes the upper bound. Bounds are in index order, so for a DESC column the larger value
+/// is the lower bound.
+pub struct IndexSeek<'a> {
+    db: &'a Database,
+    lower: Option<KeyBound>,
+    upper: Option<KeyBound>,
+    order: Vec<KeyOrder>,
+    path: Vec<Frame>, // pages from the root to the current position
+
+    pages_read: usize,
+    done: bool,
+}
+
+// One page on the path from the root to the position of an `IndexSeek`. On interior pages
+// `index` is the child being visited, on leaves it is the next cell to read.
+struct Frame {
+    page: BtreePage,
+    buffer: Vec<u8>,
+    index: usize,
+}
+
+impl<'a> IndexSeek<'a> {
+    /// Entries whose leading columns equal the key
+    pub fn eq(
+        db: &'a Database,
+        index_root: u32,
+        key: &[FieldData],
+        order: &[KeyOrder],
+    ) -> Result<Self, SqrliteError> {
+        let bound = KeyBound::inclusive(key);
+        Self::range(db, index_root, Some(bound.clone()), Some(bound), order)
+    }
+
+    /// Entries between the bounds; a missing bound leaves that end of the index open
+    pub fn range(
+        db: &'a Database,
+        index_root: u32,
+        lower: Option<KeyBound>,
+        upper: Option<KeyBound>,
+        order: &[KeyOrder],
+    ) -> Result<Self, SqrliteError> {
+        let mut seek = Self {
+            db,
+            lower,
+            upper,
+            order: order.to_vec(),
+            path: vec![],
+            pages_read: 0,
+            done: false,
+        };
+
+        let mut page_num = index_root;
+        loop {
+            let mut buffer = vec![];
+            let page = read_index_page(seek.db, page_num, &mut buffer)?;
+            seek.pages_read += 1;
+            let low = match &seek.lower {
+                Some(lower) => partition_point(seek.db, &page, &buffer, |entry| {
+                    lower.precedes(entry, &seek.order)
+                })?,
+                None => 0,
+            };
+            let child = match page.page_type {
+                PageType::InteriorIndex => Some(child_page(&page, &buffer, low)?),
+                _ => None,
+            };
+            seek.path.push(Frame {
+                page,
+                buffer,
+                index: low,
+            });
+            match child {
+                Some(child) => page_num = child,
+                None => return Ok(seek),
+            }
+        }
+    }
+
+    /// Number of b-tree pages read so far
+    pub fn pages_read(&self) -> usize {
+        self.pages_read
+    }
+
+    // Read the entry at the current position and move past it
+    fn next_entry(&mut self) -> Result<Option<Vec<FieldData>>, SqrliteError> {
+        loop {
+            let Some(frame) = self.path.last_mut() else {
+                return Ok(None);
+            };
+            let position = frame.index;
+            let num_cells = frame.page.cell_pointers.len();
+            match frame.page.page_type {
+                PageType::LeafIndex if position < num_cells => {
+                    frame.index += 1;
+                    return Ok(Some(
+                        read_entry(self.db, &frame.page, &frame.buffer, position)?.1,
+                    ));
+                }
+                // an interior cell's entry comes after its left child's subtree, and is
+                // followed by the subtree of the next child
+                PageType::InteriorIndex if position < num_cells => {
+                    frame.index += 1;
+                    let (_, entry) = read_entry(self.db, &frame.page, &frame.buffer, position)?;
+                    let mut child = child_page(&frame.page, &frame.buffer, position + 1)?;
+                    loop {
+                        let mut buffer = vec![];
+                        let page = read_index_page(self.db, child, &mut buffer)?;
+                        self.pages_read += 1;
+                        let next = match page.page_type {
+                            PageType::InteriorIndex => Some(child_page(&page, &buffer, 0)?),
+                            _ => None,
+                        };
+                        self.path.push(Frame {
+                            page,
+                            buffer,
+                            index: 0,
+                        });
+                        match next {
+                            Some(next) => child = next,
+                            None => break,
+                        }
+                    }
+                    return Ok(Some(entry));
+                }
+                _ => {
+                    self.path.pop();
+                    // the parent resumes at the cell following the finished child
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for IndexSeek<'_> {
+    type Item = Result<i64, SqrliteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let entry = match self.next_entry() {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if self
+            .upper
+            .as_ref()
+            .is_some_and(|upper| upper.follows(&entry, &self.order))
+        {
+            self.done = true;
+            return None;
+        }
+        match entry.last() {
+            Some(FieldData::Integer(rowid)) => Some(Ok(*rowid)),
+            // rowids 0 and 1 are stored as the constant serial types
+            Some(FieldData::BooleanFalse(_)) => Some(Ok(0)),
+            Some(FieldData::BooleanTrue(_)) => Some(Ok(1)),
+            _ => {
+                self.done = true;
+                Some(Err(IndexBtreeError::new(
+                    "index entry does not end with a rowid",
+                )
+                .into()))
+            }
+        }
+    }
+}
+
+// Read a whole index b-tree page into `buffer` and parse its header
+fn read_index_page(
+    db: &Database,
+    page_num: u32,
+    buffer: &mut Vec<u8>,
+) -> Result<BtreePage, SqrliteError> {
+    let mut page = BtreePage::default();
+    page.read_page(db, page_num, buffer)?;
+    if !matches!(
+        page.page_type,
+        PageType::InteriorIndex | PageType::LeafIndex
+    ) {
+        return Err(
+            IndexBtreeError::new(&format!("page {} is not an index page", page_num)).into(),
+        );
+    }
+    Ok(page)
+}
+
+// Binary-search a page for the position of the first cell whose entry does not precede
+// the wanted range; cells are in key order, so `before` holds for a prefix of them
+fn partition_point<F>(
+    db: &Database,
+    page: &BtreePage,
+    buffer: &[u8],
+    before: F,
+) -> Result<usize, SqrliteError>
+where
+    F: Fn(&[FieldData]) -> bool,
+{
+    let (mut low, mut high) = (0, page.cell_pointers.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        let (_, record) = read_entry(db, page, buffer, mid)?;
+        if before(&record) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+// Page number of the child at a position of an interior page, with the rightmost child
+// after the last cell
+fn child_page(page: &BtreePage, buffer: &[u8], index: usize) -> Result<u32, SqrliteError> {
+    if index == page.cell_pointers.len() {
+        return page.rightmost_ptr.ok_or_else(|| {
+            IndexBtreeError::new(&format!(
+                "interior page {} has no rightmost pointer",
+                page.page_num
+            ))
+            .into()
+        });
+    }
+    let cell = CellRef::get(page, buffer, index)?;
+    cell.left_child_pointer().ok_or_else(|| {
+        IndexBtreeError::new(&format!(
+            "cell {} of page {} has no child",
+            index, page.page_num
+        ))
+        .into()
+    })
+}
+
+// Read the index-th cell of an index page, returning its left child pointer (for interior
+// pages) and its decoded record
+fn read_entry(
+    db: &Database,
+    page: &BtreePage,
+    buffer: &[u8],
+    index: usize,
+) -> Result<(Option<u32>, Vec<FieldData>), SqrliteError> {
+    let cell = CellRef::get(page, buffer, index)?;
+    Ok((cell.left_child_pointer(), decode_row(db, cell)?))
+}
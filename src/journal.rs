@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::SqrliteError;
+
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+/// Size of the header at the start of a rollback journal
+pub const JOURNAL_HEADER_SIZE: usize = 28;
+// bytes each page record adds to the original page image: its page number and a checksum
+const RECORD_OVERHEAD: u64 = 8;
+
+/// A rollback journal left by a transaction that never finished: the database file may hold
+/// some of its changes, which SQLite would undo from the journal before reading
+#[derive(Debug)]
+pub struct HotJournalError {
+    /// the journal file
+    pub journal_path: PathBuf,
+    /// in the first segment; a journal can be extended with more segments
+    pub records: u32,
+}
+
+impl fmt::Display for HotJournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is a hot journal of an unfinished transaction, so the database file may \
+             be half-written; open it with sqlite3 to roll the transaction back, or ignore \
+             the journal to read it as it is",
+            self.journal_path.display()
+        )
+    }
+}
+
+impl Error for HotJournalError {}
+
+/// The start of a rollback journal, as described in
+/// <https://www.sqlite.org/fileformat2.html#the_rollback_journal>
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JournalHeader {
+    /// number of page records, where 0xffffffff means as many as fit in the file
+    pub record_count: u32,
+    /// random value used in the checksum of each page record
+    pub nonce: u32,
+    /// of the database when the transaction began
+    pub initial_page_count: u32,
+    /// the journal's header is padded to a whole sector of this size
+    pub sector_size: u32,
+    /// page size of the database
+    pub page_size: u32,
+}
+
+impl JournalHeader {
+    /// Parse the header, or None if the magic number is missing. A journal whose header was
+    /// zeroed or never written has nothing to roll back.
+    pub fn parse(bytes: &[u8; JOURNAL_HEADER_SIZE]) -> Option<Self> {
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        if bytes[..8] != JOURNAL_MAGIC {
+            return None;
+        }
+        Some(Self {
+            record_count: u32_at(8),
+            nonce: u32_at(12),
+            initial_page_count: u32_at(16),
+            sector_size: u32_at(20),
+            page_size: u32_at(24),
+        })
+    }
+
+    /// Number of page records in a journal file of the given size
+    pub fn records(&self, file_size: u64) -> u32 {
+        if self.record_count != u32::MAX {
+            return self.record_count;
+        }
+        let record_size = u64::from(self.page_size) + RECORD_OVERHEAD;
+        (file_size.saturating_sub(u64::from(self.sector_size)) / record_size)
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+}
+
+/// Look for a hot journal beside a database using pages of `page_size` bytes: one with a
+/// valid header for that page size and at least one page record to roll back
+pub fn find_hot_journal(
+    journal_path: &Path,
+    page_size: u32,
+) -> Result<Option<HotJournalError>, SqrliteError> {
+    let mut file = match File::open(journal_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            let context = format!("cannot open {}", journal_path.display());
+            return Err(SqrliteError::io(context, e));
+        }
+    };
+    let mut bytes = [0; JOURNAL_HEADER_SIZE];
+    match file.read_exact(&mut bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(SqrliteError::io("error reading the journal header", e)),
+    }
+    let header = match JournalHeader::parse(&bytes) {
+        Some(header) if header.page_size == page_size => header,
+        _ => return Ok(None),
+    };
+    match header.records(file.metadata()?.len()) {
+        0 => Ok(None),
+        records => Ok(Some(HotJournalError {
+            journal_path: journal_path.to_path_buf(),
+            records,
+        })),
+    }
+}
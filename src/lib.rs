@@ -1,6 +1,106 @@
+//! A reader for SQLite database files, written from the file format documentation. It
+//! opens a database without linking SQLite, reads its schema, tables and indexes, runs
+//! simple SELECT statements, and reports on the file's structure and consistency the way the
+//! SQLite shell's `.dbinfo` and `PRAGMA integrity_check` do. Nothing is ever written to the
+//! file.
+//!
+//! ```
+//! # fn main() -> Result<(), sqrlite::SqrliteError> {
+//! use sqrlite::{Database, FieldData};
+//!
+//! let db = Database::new("tests/fixtures/scan.db")?;
+//! let readings = db.table("readings")?;
+//! assert_eq!(readings.count()?, 5000);
+//!
+//! let row = readings.get(17)?.expect("row 17 exists");
+//! assert_eq!(row.get("sensor").and_then(FieldData::as_str), Some("sensor-0"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The types most programs need are re-exported here. The modules stay public for reading
+//! pages, cells and b-trees directly.
+
+#![warn(missing_docs)]
+
+/// The shape of a b-tree: its depth, and the pages and cells at each level
+pub mod btree;
+/// Parsing b-tree pages: the page header, cell pointer array and freeblock chain
 pub mod btree_page;
+/// The cells of b-tree pages and the payloads they hold, overflow pages included
 pub mod cell;
+/// Opening a database and reading its pages
 pub mod db;
+/// The header fields and counts shown by the SQLite shell's `.dbinfo`
 pub mod dbinfo;
+/// Checking the rows of a table against its schema
+pub mod diagnose;
+/// The error type of every fallible call
+pub mod error;
+/// WHERE-clause expressions, evaluated with SQL's three-valued logic
+pub mod expr;
+/// The 100-byte database header
+pub mod header;
+/// Seeks and range scans over index b-trees
+pub mod index_btree;
+/// Detecting a hot rollback journal left beside a database
+pub mod journal;
+/// The cache of recently read pages
+pub mod page_cache;
+/// Where the bytes of a database come from
+pub mod page_source;
+/// Scanning a table on every thread of the rayon pool
+#[cfg(feature = "rayon")]
+pub mod parallel;
+/// Pointer-map pages of auto_vacuum databases
+pub mod ptrmap;
+/// Parsing and running SELECT statements
+pub mod query;
+/// Guarding reads made of many pages against concurrent writers
+pub mod read_guard;
+/// Records and the values in them
 pub mod record;
+/// Scans and cursors over table b-trees
+pub mod scan;
+/// The sqlite_schema table and the CREATE statements in it
+pub mod schema;
+/// serde serialization of values, rows and `.dbinfo`
+#[cfg(feature = "serde")]
+pub mod serialize;
+/// Guessing what a file that is not a SQLite database holds
+pub mod sniff;
+/// Tokenizing SQL, and the rules name lookups follow
+pub mod sql;
+/// Tables and indexes, read without dealing with b-trees
+pub mod table;
+/// The variable-length integers of record headers and cells
 pub mod varint;
+/// Reading the -wal file of a database in WAL mode
+pub mod wal;
+
+// The types most programs need, so they can be used as sqrlite::Database and so on. The
+// modules stay public for everything else, such as reading b-tree pages directly.
+pub use db::{Database, DatabaseOptions};
+pub use dbinfo::{DBInfo, FileSizeCheck, ObjectStats};
+pub use page_source::PageSource;
+pub use query::{execute, QueryResult, Select};
+pub use read_guard::ReadGuard;
+pub use record::{DataType, FieldData, NamedRecord, Record, TextEncoding};
+pub use scan::{ReverseTableScan, SeekResult, TableCursor, TableScan};
+pub use schema::{Affinity, ColumnDef, IndexDef, ObjectType, Schema, SchemaObject, TableDef};
+pub use table::{IndexHandle, TableHandle};
+
+// Every fallible call returns a SqrliteError, whose variants tell the kinds of failure apart
+// and hold the errors below, with the details of where the problem was found
+pub use btree_page::{CorruptPage, InvalidPageNumber};
+pub use cell::CellParseError;
+pub use db::{ConcurrentModification, CorruptData, TruncatedDatabase};
+pub use error::SqrliteError;
+pub use header::HeaderError;
+pub use journal::HotJournalError;
+pub use query::QueryError;
+pub use record::{ParseError, RecordSizeMismatch, SerialTypeError};
+pub use schema::SchemaError;
+pub use sniff::NotADatabaseError;
+pub use table::NoSuchTable;
+pub use wal::WalError;
@@ -1,8 +1,24 @@
+use std::cmp::Reverse;
 use std::env;
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
 
-use sqrlite::db::Database;
+use sqrlite::btree::tree_stats;
+use sqrlite::db::{Database, DatabaseOptions};
+use sqrlite::dbinfo::{application_id_tag, sqlite_version_text, DBInfo, FileSizeCheck};
+use sqrlite::diagnose::diagnose_table;
+use sqrlite::error::SqrliteError;
+use sqrlite::ptrmap::PtrMap;
+use sqrlite::query::{execute, lookup_primary_key, ResultColumn, Select};
+use sqrlite::read_guard::ReadGuard;
+use sqrlite::record::{DataType, FieldData, Record};
+use sqrlite::scan::{SeekResult, TableCursor};
+use sqrlite::schema::{Affinity, ObjectType};
+use sqrlite::sql::{identifiers_match, quote_identifier};
+use sqrlite::table::NoSuchTable;
 
 #[derive(Debug)]
 enum CMDError {
@@ -23,8 +39,79 @@ impl fmt::Display for CMDError {
 
 impl Error for CMDError {}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = env::args().collect::<Vec<_>>();
+// Open the database as the flags ask, warning when that means ignoring changes SQLite would
+// take into account
+fn open_database(path: &str, options: DatabaseOptions) -> Result<Database, SqrliteError> {
+    let db = Database::with_options(path, options)?;
+    if !options.read_wal && db.pending_wal_size().is_some() {
+        eprintln!(
+            "warning: ignoring {}-wal; results may be missing recent commits",
+            path
+        );
+    }
+    if !options.check_journal && db.hot_journal()?.is_some() {
+        eprintln!(
+            "warning: ignoring hot journal {}-journal; results may include part of an unfinished transaction",
+            path
+        );
+    }
+    Ok(db)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let mut message = e.to_string();
+            let mut source = e.source();
+            while let Some(cause) = source {
+                message += &format!(": {}", cause);
+                source = cause.source();
+            }
+            eprintln!("Error: {}", message);
+            ExitCode::from(exit_code(e.as_ref()))
+        }
+    }
+}
+
+// SQLite's primary result code for the kind of error, so scripts can tell a corrupt file
+// from a missing table the way they would with sqlite3's codes
+fn exit_code(e: &(dyn Error + 'static)) -> u8 {
+    match e.downcast_ref::<SqrliteError>() {
+        Some(SqrliteError::Io { .. }) => 10, // SQLITE_IOERR
+        Some(SqrliteError::NotADatabase(_) | SqrliteError::InvalidHeader(_)) => 26, // SQLITE_NOTADB
+        Some(SqrliteError::HotJournal(_) | SqrliteError::ConcurrentModification(_)) => 5, // SQLITE_BUSY
+        Some(SqrliteError::AllocationBudget(_)) => 18, // SQLITE_TOOBIG
+        Some(SqrliteError::TypeMismatch(_)) => 20,     // SQLITE_MISMATCH
+        Some(
+            SqrliteError::Schema(_)
+            | SqrliteError::SqlParse(_)
+            | SqrliteError::Query(_)
+            | SqrliteError::NoSuchTable(_)
+            | SqrliteError::NoSuchColumn { .. }
+            | SqrliteError::NoSuchRow { .. }
+            | SqrliteError::Unsupported(_),
+        )
+        | None => 1, // SQLITE_ERROR
+        Some(_) => 11,                                 // SQLITE_CORRUPT
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().collect::<Vec<_>>();
+    // --ignore-wal and --ignore-journal may appear anywhere; they read the database file as
+    // it is, without pending commits in a WAL or rolling back a hot journal. --verbose
+    // reports how well the page cache did once the command is done. --lock holds a shared
+    // lock on the database file while the command runs.
+    let options = DatabaseOptions {
+        read_wal: !args.iter().any(|arg| arg == "--ignore-wal"),
+        check_journal: !args.iter().any(|arg| arg == "--ignore-journal"),
+    };
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    let lock = args.iter().any(|arg| arg == "--lock");
+    args.retain(|arg| {
+        !["--ignore-wal", "--ignore-journal", "--verbose", "--lock"].contains(&arg.as_str())
+    });
     match args.len() {
         0 | 1 => {
             eprintln!("{}", CMDError::DBPathNotGiven);
@@ -37,16 +124,411 @@ fn main() -> Result<(), Box<dyn Error>> {
         _ => {}
     }
 
+    let db = open_database(&args[1], options)?;
+    // a command reading many pages, like .dump, fails at the end if the database changed
+    // while it ran, rather than leave output that mixes two versions unremarked
+    let guard = if lock {
+        ReadGuard::locked(&db)?
+    } else {
+        ReadGuard::new(&db)?
+    };
     let command = &args[2];
     match command.as_str() {
         ".dbinfo" => {
-            let db = Database::new(&args[1])?;
-            // let db_info = DBInfo::read_info(&db)?;
+            // -v adds the size of the schema, for keeping an eye on it growing, and --tables
+            // lists the size of each table and index instead, the largest first
+            let (extended, tables) = match args.get(3..).unwrap_or_default() {
+                [] => (false, false),
+                [flag] if flag == "-v" => (true, false),
+                [flag] if flag == "--tables" => (false, true),
+                _ => {
+                    return Err(
+                        CMDError::InvalidCommand(".dbinfo [-v | --tables]".to_owned()).into(),
+                    )
+                }
+            };
+            if tables {
+                print_table_sizes(&db)?;
+            } else {
+                let db_info = DBInfo::read_info(&db)?;
+                // the lines the SQLite shell prints, then what it leaves out
+                print!("{}", db_info);
+                println!("{:<20} {}", "journal mode:", db_info.journal_mode.name());
+                if let Some(release) = sqlite_version_text(db_info.software_version) {
+                    println!("{:<20} SQLite {}", "last written by:", release);
+                }
+                if db_info.num_internal_tables > 0 {
+                    println!("{:<20} {}", "internal tables:", db_info.num_internal_tables);
+                }
+                if db_info.page_count_mismatch() {
+                    let stale = if db_info.header_page_count_stale {
+                        " (the header's count is stale and ignored)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{:<20} {}{}",
+                        "file page count:", db_info.file_page_count, stale
+                    );
+                }
+                if db_info.file_size_check != FileSizeCheck::Exact {
+                    println!("{:<20} {}", "file size:", db_info.file_size_check);
+                }
+                if extended {
+                    println!("{:<20} {}", "schema entries:", db_info.schema_entries);
+                    println!("{:<20} {} bytes", "schema size:", db_info.schema_bytes);
+                }
+            }
+        }
+        ".user-version" => {
+            println!("{}", db.user_version());
+        }
+        ".application-id" => {
+            let id = db.application_id();
+            match application_id_tag(id) {
+                Some(tag) => println!("{} ({})", id, tag),
+                None => println!("{}", id),
+            }
+        }
+        ".wal-info" => {
+            match db.wal_info() {
+                Some(info) => println!(
+                    "{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}\n{:24}{:<1}",
+                    "WAL page size:",
+                    info.page_size,
+                    "checkpoint sequence:",
+                    info.checkpoint_seq,
+                    "salts:",
+                    format!("{:#010x} {:#010x}", info.salt1, info.salt2),
+                    "checksum byte order:",
+                    if info.big_endian_checksums { "big-endian" } else { "little-endian" },
+                    "frames present:",
+                    info.frame_count,
+                    "frames valid:",
+                    info.valid_frames,
+                    "frames committed:",
+                    info.committed_frames,
+                    "database page count:",
+                    info.database_size,
+                ),
+                None => println!("no WAL in use"),
+            }
+        }
+        ".tables" => {
+            let schema = db.schema()?;
+            let mut names = schema
+                .objects_of_type(ObjectType::Table)
+                .filter(|obj| !obj.is_internal())
+                .map(|obj| obj.name.as_str())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            println!("{}", names.join(" "));
+        }
+        ".views" => {
+            let schema = db.schema()?;
+            for view in schema.objects_of_type(ObjectType::View) {
+                println!("{}|{}", view.name, view.sql.as_deref().unwrap_or_default());
+            }
+        }
+        ".sequence" => {
+            for (name, seq) in db.sequence_values()? {
+                println!("{}|{}", name, seq);
+            }
+        }
+        ".triggers" => {
+            let schema = db.schema()?;
+            let table = args.get(3);
+            for trigger in schema
+                .objects_of_type(ObjectType::Trigger)
+                .filter(|obj| table.is_none_or(|t| identifiers_match(&obj.tbl_name, t)))
+            {
+                println!(
+                    "{}|{}",
+                    trigger.name,
+                    trigger.sql.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        ".columns" => {
+            let table_name = args
+                .get(3)
+                .ok_or_else(|| CMDError::InvalidCommand(".columns <table>".to_owned()))?;
+            let table = db.schema()?.table_def(table_name)?;
+
+            let headers = [
+                "cid",
+                "name",
+                "type",
+                "affinity",
+                "notnull",
+                "dflt_value",
+                "pk",
+                "hidden",
+            ];
+            let rows = table
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(cid, col)| {
+                    // same encoding as PRAGMA table_xinfo: 2 = VIRTUAL, 3 = STORED generated
+                    let hidden = match &col.generated {
+                        Some(generated) if generated.stored => 3,
+                        Some(_) => 2,
+                        None => 0,
+                    };
+                    vec![
+                        cid.to_string(),
+                        col.name.clone(),
+                        col.decl_type.clone().unwrap_or_default(),
+                        col.affinity().name().to_owned(),
+                        u8::from(table.is_not_null(cid)).to_string(),
+                        col.default
+                            .as_ref()
+                            .map(|d| d.text.clone())
+                            .unwrap_or_default(),
+                        table.primary_key_position(cid).unwrap_or(0).to_string(),
+                        hidden.to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            print_aligned(&headers, &rows);
+        }
+        ".pk-lookup" => {
+            let table_name = args.get(3).ok_or_else(|| {
+                CMDError::InvalidCommand(".pk-lookup <table> <key values...>".to_owned())
+            })?;
+            let table = db.schema()?.table_def(table_name)?;
+            let key = table
+                .primary_key_columns()
+                .iter()
+                .zip(&args[4..])
+                .map(|((idx, _), text)| key_value(text, table.columns[*idx].affinity()))
+                .collect::<Vec<_>>();
+            if let Some(row) = lookup_primary_key(&db, table_name, &key)? {
+                let values = row
+                    .values()
+                    .iter()
+                    .map(FieldData::to_string)
+                    .collect::<Vec<_>>();
+                println!("{}", values.join("|"));
+            }
+        }
+        ".btree" => {
+            let name = args
+                .get(3)
+                .ok_or_else(|| CMDError::InvalidCommand(".btree <table or index>".to_owned()))?;
+            let schema = db.schema()?;
+            let rootpage = match schema.find(name) {
+                Some(obj) if obj.rootpage != 0 => obj.rootpage,
+                _ => {
+                    return Err(SqrliteError::NoSuchTable(NoSuchTable {
+                        name: name.to_owned(),
+                    })
+                    .into())
+                }
+            };
+            let stats = tree_stats(&db, rootpage)?;
 
+            println!("{:24}{}", "depth:", stats.depth);
+            for (level, counts) in stats.levels.iter().enumerate() {
+                println!(
+                    "{:24}{} pages, {} cells",
+                    format!("level {}:", level),
+                    counts.pages,
+                    counts.cells
+                );
+            }
             println!(
-                "{:24}{:<1}\n{:24}{:<1}",
-                "database page size:", db.page_size, "database page count:", db.page_count
+                "{:24}{} min, {:.1} avg, {} max",
+                "cells per leaf:", stats.min_leaf_cells, stats.avg_leaf_cells, stats.max_leaf_cells
             );
+            println!("{:24}{}", "entries:", stats.entries);
+            println!("{:24}{}", "payload bytes:", stats.payload_bytes);
+            println!("{:24}{}", "overflow pages:", stats.overflow_pages);
+            for problem in &stats.corruption {
+                println!("{:24}{}", "corruption:", problem);
+            }
+        }
+        ".integrity-check" => {
+            // like PRAGMA integrity_check, "ok" or one line for each problem found, here with
+            // the file's size, the structure of its b-trees, the freelist, the pointer map and
+            // the column types of STRICT tables
+            let mut problems = vec![];
+            match DBInfo::consistency(&db)? {
+                FileSizeCheck::Exact => {}
+                check => problems.push(check.to_string()),
+            }
+            match db.schema() {
+                Ok(schema) => {
+                    for (name, _, rootpage) in schema.btrees() {
+                        match tree_stats(&db, rootpage) {
+                            Ok(stats) => problems.extend(
+                                stats
+                                    .corruption
+                                    .iter()
+                                    .map(|problem| format!("{}: {}", name, problem)),
+                            ),
+                            Err(e) => problems.push(format!("{}: {}", name, e)),
+                        }
+                    }
+                }
+                Err(e) => problems.push(format!("sqlite_schema: {}", e)),
+            }
+            if let Err(e) = db.verify_freelist() {
+                problems.push(e.to_string());
+            }
+            if let Some(ptrmap) = PtrMap::new(&db) {
+                match ptrmap.verify(&db) {
+                    Ok(mismatches) => problems.extend(mismatches),
+                    Err(e) => problems.push(format!("pointer map: {}", e)),
+                }
+            }
+            match db.tables() {
+                Ok(tables) => {
+                    for table in &tables {
+                        match table.check_strict_types() {
+                            Ok(mismatches) => {
+                                problems.extend(mismatches.iter().map(ToString::to_string))
+                            }
+                            Err(e) => problems.push(format!("{}: {}", table.name, e)),
+                        }
+                    }
+                }
+                Err(e) => problems.push(e.to_string()),
+            }
+            if problems.is_empty() {
+                println!("ok");
+            }
+            for problem in &problems {
+                println!("{}", problem);
+            }
+        }
+        ".diagnose" => {
+            let name = args
+                .get(3)
+                .ok_or_else(|| CMDError::InvalidCommand(".diagnose <table>".to_owned()))?;
+            let diagnostics = diagnose_table(&db, name)?;
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            if diagnostics.is_empty() {
+                println!("every row of {} matches the schema", name);
+            }
+        }
+        ".readblob" => {
+            let usage = || {
+                CMDError::InvalidCommand(".readblob <table> <rowid> <column> [outfile]".to_owned())
+            };
+            let (table_name, rowid, column, outfile) = match args.get(3..).unwrap_or_default() {
+                [table_name, rowid, column] => (table_name, rowid, column, None),
+                [table_name, rowid, column, outfile] => (table_name, rowid, column, Some(outfile)),
+                _ => return Err(usage().into()),
+            };
+            let rowid = rowid.parse::<i64>().map_err(|_| usage())?;
+            let schema = db.schema()?;
+            let table = schema.table_def(table_name)?;
+            if table.without_rowid {
+                return Err(SqrliteError::Unsupported(format!(
+                    "`{}` is a WITHOUT ROWID table",
+                    table.name
+                ))
+                .into());
+            }
+            let storage_index = table
+                .column_index(column)
+                .filter(|&idx| table.rowid_alias() != Some(idx))
+                .and_then(|idx| table.storage_index(idx))
+                .ok_or_else(|| SqrliteError::NoSuchColumn {
+                    table: table.name.clone(),
+                    column: column.to_owned(),
+                })?;
+
+            let mut cursor = TableCursor::new(&db, schema.find_table(table_name)?.rootpage);
+            if cursor.seek_rowid(rowid)? != SeekResult::Exact {
+                return Err(SqrliteError::NoSuchRow {
+                    table: table.name.clone(),
+                    rowid,
+                }
+                .into());
+            }
+            let payload = cursor.payload()?;
+            let record = Record::read_header(&mut payload.open_reader(&db))?;
+            let field = record
+                .fields
+                .as_ref()
+                .and_then(|fields| fields.get(storage_index))
+                .ok_or_else(|| {
+                    SqrliteError::TypeMismatch(format!(
+                        "{} of row {} has no stored value to read as a blob",
+                        column, rowid
+                    ))
+                })?;
+            if field.data_type() != DataType::Blob {
+                return Err(SqrliteError::TypeMismatch(format!(
+                    "{} of row {} is not a blob",
+                    column, rowid
+                ))
+                .into());
+            }
+
+            // the blob is streamed, so one spilling onto many overflow pages is never held
+            // in memory whole
+            let target = outfile.map_or("stdout", String::as_str);
+            let write_error = |source: io::Error| SqrliteError::Io {
+                context: format!("cannot write to {}", target),
+                source,
+            };
+            let mut out: Box<dyn Write> = match outfile {
+                Some(path) => Box::new(File::create(path).map_err(write_error)?),
+                None => Box::new(io::stdout().lock()),
+            };
+            let mut reader = field.open_blob_reader(&payload, &db)?;
+            let mut buffer = [0; 8192];
+            let mut written = 0;
+            loop {
+                let len = reader.read(&mut buffer)?;
+                if len == 0 {
+                    break;
+                }
+                out.write_all(&buffer[..len]).map_err(write_error)?;
+                written += len;
+            }
+            out.flush().map_err(write_error)?;
+            if let Some(outfile) = outfile {
+                println!("{} bytes written to {}", written, outfile);
+            }
+        }
+        ".schema" => {
+            for obj in &db.schema()?.objects {
+                if let Some(sql) = &obj.sql {
+                    println!("{};", sql);
+                }
+            }
+        }
+        ".dump" => {
+            dump(&db)?;
+        }
+        sql if !sql.starts_with('.') => {
+            // --types follows each value with its storage class, as typeof() names it
+            let show_types = match args.get(3..).unwrap_or_default() {
+                [] => false,
+                [flag] if flag == "--types" => true,
+                _ => return Err(CMDError::InvalidCommand(format!("{} [--types]", sql)).into()),
+            };
+            let select = Select::parse(sql)?;
+            for row in execute(&db, &select)?.rows {
+                let values = row
+                    .iter()
+                    .map(|value| {
+                        if show_types {
+                            format!("{} ({})", value, value.type_name())
+                        } else {
+                            value.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", values.join("|"));
+            }
         }
         _ => {
             eprintln!("{}", CMDError::InvalidCommand(command.clone()));
@@ -54,5 +536,142 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if verbose {
+        let stats = db.page_cache_stats();
+        eprintln!("page cache: {} hits, {} misses", stats.hits, stats.misses);
+    }
+    Ok(guard.finish()?)
+}
+
+// Print the size of every table and index, the largest first
+fn print_table_sizes(db: &Database) -> Result<(), SqrliteError> {
+    let mut objects = DBInfo::read_extended(db)?;
+    objects.sort_by_key(|obj| Reverse(obj.pages));
+    let headers = [
+        "name", "type", "rootpage", "depth", "pages", "entries", "payload",
+    ];
+    let rows = objects
+        .iter()
+        .map(|obj| {
+            vec![
+                obj.name.clone(),
+                obj.object_type.name().to_owned(),
+                obj.rootpage.to_string(),
+                obj.depth.to_string(),
+                obj.pages.to_string(),
+                obj.entries.to_string(),
+                obj.payload_bytes.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_aligned(&headers, &rows);
+    Ok(())
+}
+
+// Print rows as left-aligned columns under a header line
+fn print_aligned(headers: &[&str], rows: &[Vec<String>]) {
+    let widths = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let format_line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+    println!("{}", format_line(headers.to_vec()));
+    for row in rows {
+        println!("{}", format_line(row.iter().map(String::as_str).collect()));
+    }
+}
+
+// Interpret a command-line key value the way a column with the given affinity would store it
+fn key_value(text: &str, affinity: Affinity) -> FieldData {
+    if matches!(
+        affinity,
+        Affinity::Integer | Affinity::Real | Affinity::Numeric
+    ) {
+        if let Ok(n) = text.parse::<i64>() {
+            return FieldData::Integer(n);
+        }
+        if let Ok(r) = text.parse::<f64>() {
+            return FieldData::Real(r);
+        }
+    }
+    FieldData::Text(text.to_owned())
+}
+
+// Print the database as SQL text that recreates it, following the layout of sqlite3's .dump
+fn dump(db: &Database) -> Result<(), Box<dyn Error>> {
+    let schema = db.schema()?;
+    println!("PRAGMA foreign_keys=OFF;");
+    println!("BEGIN TRANSACTION;");
+
+    // sqlite_sequence is restored after the tables whose AUTOINCREMENT state it records
+    let (sequence, tables): (Vec<_>, Vec<_>) = schema
+        .objects_of_type(ObjectType::Table)
+        .filter(|table| table.sql.is_some())
+        .partition(|table| table.name == "sqlite_sequence");
+
+    for table in tables.iter().chain(&sequence) {
+        if table.name == "sqlite_sequence" {
+            println!("DELETE FROM sqlite_sequence;");
+        } else if table.is_internal() {
+            continue;
+        } else {
+            println!("{};", table.sql.as_deref().unwrap_or_default());
+        }
+
+        // generated columns are recomputed on insert, so their values are not dumped
+        let columns = schema
+            .table_def(&table.name)?
+            .columns
+            .into_iter()
+            .filter(|col| col.generated.is_none())
+            .map(|col| ResultColumn::Column(col.name))
+            .collect();
+        let select = Select {
+            columns,
+            table: table.name.clone(),
+            where_clause: None,
+            order_by: None,
+            limit: None,
+        };
+        for row in execute(db, &select)?.rows {
+            let values = row
+                .iter()
+                .map(FieldData::to_sql_literal)
+                .collect::<Vec<_>>();
+            println!(
+                "INSERT INTO {} VALUES({});",
+                quote_identifier(&table.name),
+                values.join(",")
+            );
+        }
+    }
+
+    for obj in &schema.objects {
+        if obj.object_type == ObjectType::Table || obj.is_internal() {
+            continue;
+        }
+        if let Some(sql) = &obj.sql {
+            println!("{};", sql);
+        }
+    }
+
+    println!("COMMIT;");
     Ok(())
 }
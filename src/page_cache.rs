@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// The bytes of one page, shared between the cache and its readers
+pub type PageBuf = [u8];
+
+/// Memory given to the page cache of a database unless its options say otherwise
+pub const DEFAULT_PAGE_CACHE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+/// How often the pages looked up in a cache were found there
+pub struct PageCacheStats {
+    /// lookups that found the page
+    pub hits: u64,
+    /// lookups that did not
+    pub misses: u64,
+}
+
+/// Recently read pages, dropping the least recently used once `capacity` pages are held
+#[derive(Debug, Default)]
+pub struct PageCache {
+    capacity: usize,
+    pages: HashMap<u32, (u64, Arc<PageBuf>)>, // page number to last use and contents
+    by_use: BTreeMap<u64, u32>,               // last use to page number, oldest first
+    clock: u64,
+    stats: PageCacheStats,
+}
+
+impl PageCache {
+    /// An empty cache holding up to `capacity` pages
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Number of pages the cache holds at most
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the number of pages held, dropping the least recently used ones if there are more
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.pages.len() > capacity {
+            self.evict();
+        }
+    }
+
+    /// Hits and misses so far
+    pub fn stats(&self) -> PageCacheStats {
+        self.stats
+    }
+
+    /// The page if it is cached, making it the most recently used
+    pub fn get(&mut self, page: u32) -> Option<Arc<PageBuf>> {
+        self.clock += 1;
+        match self.pages.get_mut(&page) {
+            Some((used, bytes)) => {
+                self.by_use.remove(used);
+                *used = self.clock;
+                self.by_use.insert(self.clock, page);
+                self.stats.hits += 1;
+                Some(Arc::clone(bytes))
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache a page, dropping the least recently used one if the cache is full
+    pub fn insert(&mut self, page: u32, bytes: Arc<PageBuf>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.pages.contains_key(&page) && self.pages.len() >= self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        if let Some((used, _)) = self.pages.insert(page, (self.clock, bytes)) {
+            self.by_use.remove(&used);
+        }
+        self.by_use.insert(self.clock, page);
+    }
+
+    /// Drop every page, as after the file was changed by another process
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.by_use.clear();
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, page)) = self.by_use.pop_first() {
+            self.pages.remove(&page);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+/// Where the bytes of a database come from. Reads name their offset rather than moving a
+/// cursor, so a source over remote storage can fetch only the ranges that are asked for, and
+/// threads sharing a database can read from it at the same time.
+pub trait PageSource: Send + Sync + fmt::Debug {
+    /// Fill `buf` with the bytes starting at `offset`, failing if the source ends first
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Total size in bytes
+    fn len(&self) -> io::Result<u64>;
+
+    /// Whether the source holds no bytes at all
+    fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl PageSource for File {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        read_file_at(self, offset, buf)
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl<T> PageSource for Cursor<T>
+where
+    T: AsRef<[u8]> + Send + Sync + fmt::Debug,
+{
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        read_slice(self.get_ref().as_ref(), offset, buf)
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.get_ref().as_ref().len() as u64)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl PageSource for memmap2::Mmap {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        read_slice(self, offset, buf)
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(<[u8]>::len(self) as u64)
+    }
+}
+
+/// A source over anything that can be read and seeked, such as a buffered file. The reader has
+/// a single position, so threads reading from it take turns.
+#[derive(Debug)]
+pub struct ReaderSource<R> {
+    reader: Mutex<R>,
+    len: u64,
+}
+
+impl<R: Read + Seek> ReaderSource<R> {
+    /// Wrap a reader, seeking to its end to learn its length
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            reader: Mutex::new(reader),
+            len,
+        })
+    }
+}
+
+impl<R> PageSource for ReaderSource<R>
+where
+    R: Read + Seek + Send + Sync + fmt::Debug,
+{
+    fn read(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        // a panic mid-read leaves nothing behind that the seek below does not reset
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(buf)
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.len)
+    }
+}
+
+/// Read from a file at `offset` without moving its cursor, so several threads can share it
+#[cfg(unix)]
+pub(crate) fn read_file_at(file: &File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_file_at(file: &File, offset: u64, mut buf: &mut [u8]) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    // seek_read may return fewer bytes than asked for, like read
+    let mut offset = offset;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn read_slice(bytes: &[u8], offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    let start = usize::try_from(offset).unwrap_or(usize::MAX);
+    match start
+        .checked_add(buf.len())
+        .and_then(|end| bytes.get(start..end))
+    {
+        Some(slice) => {
+            buf.copy_from_slice(slice);
+            Ok(())
+        }
+        None => Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("{} bytes at offset {} are past the end", buf.len(), offset),
+        )),
+    }
+}
@@ -0,0 +1,81 @@
+use rayon::prelude::*;
+
+use crate::btree_page::{BtreePage, PageType};
+use crate::cell::CellContent;
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::record::Record;
+use crate::scan::TableScan;
+
+/// Fold over every row of a table b-tree on the rayon thread pool. The subtrees under the
+/// root page are shared out among the threads, each of which opens its own handle on the
+/// database so they do not wait on each other's reads. A database that cannot be opened again,
+/// read from the caller's own source, is shared by all the threads instead. Each thread folds its rows
+/// starting from `identity()`, and the partial results are combined with `reduce`. Rows are
+/// visited in no particular order.
+pub fn par_fold<T, ID, F, R>(
+    db: &Database,
+    root_page: u32,
+    identity: ID,
+    fold: F,
+    reduce: R,
+) -> Result<T, SqrliteError>
+where
+    T: Send,
+    ID: Fn() -> T + Sync + Send,
+    F: Fn(T, i64, Record) -> T + Sync + Send,
+    R: Fn(T, T) -> T + Sync + Send,
+{
+    let subtrees = subtrees(db, root_page)?;
+
+    subtrees
+        .into_par_iter()
+        .map_init(
+            || db.can_reopen().then(|| db.reopen()),
+            |handle, subtree| {
+                // a thread that failed to open its handle reports it once, and shares `db`
+                // for the rest of its subtrees
+                if let Some(Err(_)) = handle {
+                    if let Some(Err(e)) = handle.take() {
+                        return Err(e);
+                    }
+                }
+                let db = match handle {
+                    Some(Ok(own)) => own,
+                    _ => db,
+                };
+                let mut acc = identity();
+                for row in TableScan::new(db, subtree) {
+                    let (rowid, record) = row?;
+                    acc = fold(acc, rowid, record);
+                }
+                Ok(acc)
+            },
+        )
+        .try_reduce(&identity, |a, b| Ok(reduce(a, b)))
+}
+
+/// Count the rows of a table b-tree using every thread of the pool
+pub fn par_count(db: &Database, root_page: u32) -> Result<i64, SqrliteError> {
+    par_fold(db, root_page, || 0, |count, _, _| count + 1, |a, b| a + b)
+}
+
+// The children of the root page, in key order, or just the root when it is a leaf
+fn subtrees(db: &Database, root_page: u32) -> Result<Vec<u32>, SqrliteError> {
+    let mut buffer = vec![];
+    let mut page = BtreePage::default();
+    page.read_page(db, root_page, &mut buffer)?;
+    match page.page_type {
+        PageType::InteriorTable => {
+            let mut children = vec![];
+            for index in 0..page.cell_pointers.len() {
+                children.push(
+                    CellContent::get_cell_data(&page, &buffer, index)?.get_left_child_pointer()?,
+                );
+            }
+            children.extend(page.rightmost_ptr);
+            Ok(children)
+        }
+        _ => Ok(vec![root_page]),
+    }
+}
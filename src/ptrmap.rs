@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::btree_page::{BtreePage, PageType};
+use crate::cell::{CellRef, Payload};
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::header::AutoVacuum;
+
+// each entry is a 1-byte type followed by a 4-byte parent page number
+const ENTRY_SIZE: u32 = 5;
+
+/// A pointer map that cannot be read, or whose entry for a page is invalid
+#[derive(Debug)]
+pub struct PtrMapError {
+    details: String,
+}
+
+impl PtrMapError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for PtrMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for PtrMapError {}
+
+/// What a page is used for, as recorded in its pointer-map entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtrMapType {
+    /// root of a b-tree; no parent
+    RootPage,
+    /// on the freelist; no parent
+    FreePage,
+    /// first page of an overflow chain; parent is the page holding the cell
+    Overflow1,
+    /// later page of an overflow chain; parent is the previous overflow page
+    Overflow2,
+    /// non-root b-tree page; parent is its parent b-tree page
+    Btree,
+}
+
+impl PtrMapType {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::RootPage),
+            2 => Some(Self::FreePage),
+            3 => Some(Self::Overflow1),
+            4 => Some(Self::Overflow2),
+            5 => Some(Self::Btree),
+            _ => None,
+        }
+    }
+
+    /// A description of the use, for messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RootPage => "a b-tree root page",
+            Self::FreePage => "a free page",
+            Self::Overflow1 => "the first page of an overflow chain",
+            Self::Overflow2 => "a later page of an overflow chain",
+            Self::Btree => "a b-tree page",
+        }
+    }
+}
+
+/// The pointer-map entry of one page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtrMapEntry {
+    /// what the page is used for
+    pub page_type: PtrMapType,
+    /// zero for root and free pages
+    pub parent: u32,
+}
+
+/// Layout of the pointer-map pages of an auto_vacuum or incremental_vacuum database. Each
+/// pointer-map page describes the pages that follow it, up to the next pointer-map page.
+#[derive(Debug, Clone, Copy)]
+pub struct PtrMap {
+    usable_size: u32,
+    page_count: u32,
+    lock_byte_page: u32,
+}
+
+impl PtrMap {
+    /// The pointer map of a database, or None if it has no pointer-map pages, which is the
+    /// case unless auto_vacuum is enabled
+    pub fn new(db: &Database) -> Option<Self> {
+        (db.auto_vacuum() != AutoVacuum::None).then_some(Self {
+            usable_size: db.page_size - u32::from(db.reserved_space),
+            page_count: db.page_count,
+            lock_byte_page: db.lock_byte_page(),
+        })
+    }
+
+    // Number of pages covered by one pointer-map page, counting the map page itself
+    fn pages_per_map(&self) -> u32 {
+        self.usable_size / ENTRY_SIZE + 1
+    }
+
+    /// The pointer-map page holding the entry for a page. The first map page is page 2, and
+    /// a map page that would fall on the lock-byte page moves to the page after it.
+    pub fn map_page(&self, page: u32) -> u32 {
+        let per_map = self.pages_per_map();
+        let map_page = (page.max(2) - 2) / per_map * per_map + 2;
+        if map_page == self.lock_byte_page {
+            map_page + 1
+        } else {
+            map_page
+        }
+    }
+
+    /// Whether a page is one of the pointer-map pages
+    pub fn is_ptrmap_page(&self, page: u32) -> bool {
+        page >= 2 && self.map_page(page) == page
+    }
+
+    /// Read the entry describing a page
+    pub fn entry(&self, db: &Database, page: u32) -> Result<PtrMapEntry, SqrliteError> {
+        if page < 3 || page > self.page_count || self.is_ptrmap_page(page) {
+            return Err(
+                PtrMapError::new(&format!("page {} has no pointer-map entry", page)).into(),
+            );
+        }
+        let map_page = self.map_page(page);
+        let offset = ENTRY_SIZE * (page - map_page - 1);
+        let bytes = db.page(map_page)?;
+        let entry = &bytes[offset as usize..(offset + ENTRY_SIZE) as usize];
+
+        let page_type = PtrMapType::from_byte(entry[0]).ok_or_else(|| {
+            PtrMapError::new(&format!(
+                "pointer-map entry for page {} has invalid type {}",
+                page, entry[0]
+            ))
+        })?;
+        Ok(PtrMapEntry {
+            page_type,
+            parent: u32::from_be_bytes([entry[1], entry[2], entry[3], entry[4]]),
+        })
+    }
+    /// Compare every pointer-map entry with the page it describes. Each b-tree is walked
+    /// from its root and each overflow chain from the cell that spills onto it, which with
+    /// the freelist gives the type and parent every page should have; pages whose entry
+    /// differs, or that nothing uses, are reported in the returned list.
+    pub fn verify(&self, db: &Database) -> Result<Vec<String>, SqrliteError> {
+        let mut expected = HashMap::new();
+        for (_, _, root) in db.schema()?.btrees() {
+            self.walk_btree(db, root, &mut expected)?;
+        }
+        // a broken freelist is reported by Database::verify_freelist, so only pages known
+        // to be free are checked here
+        let free_pages = db.freelist_pages().ok();
+        for &page in free_pages.iter().flatten() {
+            expected.insert(page, (PtrMapType::FreePage, 0));
+        }
+
+        let mut problems = vec![];
+        for page in 3..=self.page_count {
+            if self.is_ptrmap_page(page) || page == self.lock_byte_page {
+                continue;
+            }
+            let entry = match self.entry(db, page) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    problems.push(e.to_string());
+                    continue;
+                }
+            };
+            match expected.get(&page) {
+                Some(&(page_type, parent)) if (page_type, parent) != (entry.page_type, entry.parent) => {
+                    problems.push(format!(
+                        "pointer map says page {} is {} with parent {}, but it is {} with parent {}",
+                        page,
+                        entry.page_type.name(),
+                        entry.parent,
+                        page_type.name(),
+                        parent
+                    ))
+                }
+                Some(_) => {}
+                None if free_pages.is_some() => problems.push(format!(
+                    "page {} is not used by any b-tree, overflow chain or the freelist",
+                    page
+                )),
+                None => {}
+            }
+        }
+        Ok(problems)
+    }
+
+    // Record the type and parent of every page of a b-tree and of its overflow chains.
+    // Pages met a second time are skipped; tree_stats reports them.
+    fn walk_btree(
+        &self,
+        db: &Database,
+        root: u32,
+        expected: &mut HashMap<u32, (PtrMapType, u32)>,
+    ) -> Result<(), SqrliteError> {
+        let usable = u64::from(self.usable_size);
+        let mut buffer = vec![];
+        let mut overflow = vec![];
+        let mut pending = vec![(root, PtrMapType::RootPage, 0)];
+        while let Some((page_num, page_type, parent)) = pending.pop() {
+            if expected.insert(page_num, (page_type, parent)).is_some() {
+                continue;
+            }
+            let mut page = BtreePage::default();
+            page.read_page(db, page_num, &mut buffer)?;
+            for index in 0..page.cell_pointers.len() {
+                let cell = CellRef::get(&page, &buffer, index)?;
+                if let Some(child) = cell.left_child_pointer() {
+                    pending.push((child, PtrMapType::Btree, page_num));
+                }
+                let Some(payload) = cell.payload() else {
+                    continue;
+                };
+                let Some(first) = payload.overflow.map(u32::from_be_bytes) else {
+                    continue;
+                };
+                let spilled =
+                    payload.size - Payload::local_size(&page.page_type, payload.size, usable);
+                let chain_len = spilled.div_ceil(usable - 4);
+                let (mut next, mut page_type, mut parent) =
+                    (first, PtrMapType::Overflow1, page_num);
+                for _ in 0..chain_len {
+                    if next == 0 || next > self.page_count || expected.contains_key(&next) {
+                        break;
+                    }
+                    expected.insert(next, (page_type, parent));
+                    db.read_page_into(next, &mut overflow)?;
+                    (page_type, parent) = (PtrMapType::Overflow2, next);
+                    next = u32::from_be_bytes([overflow[0], overflow[1], overflow[2], overflow[3]]);
+                }
+            }
+            if matches!(
+                page.page_type,
+                PageType::InteriorTable | PageType::InteriorIndex
+            ) {
+                pending.extend(
+                    page.rightmost_ptr
+                        .map(|child| (child, PtrMapType::Btree, page_num)),
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,679 @@
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+use crate::cell::CellRef;
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::expr::{Collation, CompareOp, Expr};
+use crate::index_btree::{read_index_entries, seek_key, IndexSeek, KeyBound, KeyOrder};
+#[cfg(feature = "rayon")]
+use crate::parallel::par_count;
+use crate::record::{self, FieldData, NamedRecord, Record, RowLayout, TextEncoding};
+use crate::scan::{leaf_values, ReverseTableScan, SeekResult, TableCursor, TableScan};
+use crate::schema::{IndexDef, IndexKey, Schema, TableDef};
+use crate::sql::{identifiers_match, SqlParseError, TokenKind, TokenStream};
+
+/// A query that cannot be run against this database, such as ORDER BY a column the table
+/// does not have
+#[derive(Debug)]
+pub struct QueryError {
+    details: String,
+}
+
+impl QueryError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for QueryError {}
+
+/// One of the comma-separated result columns of a SELECT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResultColumn {
+    /// `*`, every column of the table
+    Star,
+    /// a column by name
+    Column(String),
+    /// `count(*)`
+    CountStar,
+    /// `typeof(<column>)`
+    TypeOf(String),
+}
+
+/// Where a result column's value comes from, by position in the table definition
+#[derive(Debug, Clone, Copy)]
+enum Projection {
+    Value(usize),
+    TypeOf(usize),
+}
+
+/// A WHERE clause, keeping its source text for matching against partial index predicates
+#[derive(Debug, Clone)]
+pub struct WhereClause {
+    /// the expression as written
+    pub text: String,
+    /// the expression parsed
+    pub expr: Expr,
+}
+
+/// `ORDER BY <column> [ASC | DESC]`
+#[derive(Debug, Clone)]
+pub struct OrderBy {
+    /// the column to sort by
+    pub column: String,
+    /// whether DESC was given
+    pub descending: bool,
+}
+
+/// `SELECT <result columns> FROM <table> [WHERE <expr>] [ORDER BY <column>] [LIMIT <n>]`
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// use sqrlite::{execute, Database, FieldData, Select};
+///
+/// let db = Database::new("tests/fixtures/scan.db")?;
+/// let select = Select::parse("SELECT id FROM readings WHERE sensor = 'sensor-3' LIMIT 2")?;
+/// let result = execute(&db, &select)?;
+/// assert_eq!(result.columns, ["id"]);
+/// assert_eq!(result.rows, [[FieldData::Integer(3)], [FieldData::Integer(20)]]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Select {
+    /// the result columns, in order
+    pub columns: Vec<ResultColumn>,
+    /// the table named after FROM
+    pub table: String,
+    /// the WHERE clause, if there is one
+    pub where_clause: Option<WhereClause>,
+    /// the ORDER BY clause, if there is one
+    pub order_by: Option<OrderBy>,
+    /// the LIMIT, if there is one
+    pub limit: Option<usize>,
+}
+
+impl Select {
+    /// Parse a SELECT statement, with or without a trailing semicolon
+    pub fn parse(sql: &str) -> Result<Self, SqlParseError> {
+        let mut tokens = TokenStream::new(sql)?;
+        tokens.expect_keyword("SELECT")?;
+
+        let mut columns = vec![];
+        loop {
+            if tokens.eat_symbol("*") {
+                columns.push(ResultColumn::Star);
+            } else if tokens.peek_keyword("COUNT")
+                && tokens.peek_nth(1).is_some_and(|t| t.is_symbol("("))
+            {
+                tokens.next_token();
+                tokens.expect_symbol("(")?;
+                tokens.expect_symbol("*")?;
+                tokens.expect_symbol(")")?;
+                columns.push(ResultColumn::CountStar);
+            } else if tokens.peek_keyword("TYPEOF")
+                && tokens.peek_nth(1).is_some_and(|t| t.is_symbol("("))
+            {
+                tokens.next_token();
+                tokens.expect_symbol("(")?;
+                let mut name = tokens.identifier()?;
+                if tokens.eat_symbol(".") {
+                    name = tokens.identifier()?;
+                }
+                tokens.expect_symbol(")")?;
+                columns.push(ResultColumn::TypeOf(name));
+            } else {
+                // a column may be qualified with its table name, which is dropped here
+                let mut name = tokens.identifier()?;
+                if tokens.eat_symbol(".") {
+                    name = tokens.identifier()?;
+                }
+                columns.push(ResultColumn::Column(name));
+            }
+            if !tokens.eat_symbol(",") {
+                break;
+            }
+        }
+
+        tokens.expect_keyword("FROM")?;
+        let table = tokens.qualified_name()?;
+
+        let where_clause = if tokens.eat_keyword("WHERE") {
+            let start = tokens.peek().map_or(0, |t| t.start);
+            let expr = Expr::parse(&mut tokens)?;
+            let end = tokens.peek().map_or(sql.len(), |t| t.start);
+            Some(WhereClause {
+                text: tokens.source(start, end).trim().to_owned(),
+                expr,
+            })
+        } else {
+            None
+        };
+
+        let order_by = if tokens.eat_keywords(&["ORDER", "BY"]) {
+            let mut column = tokens.identifier()?;
+            if tokens.eat_symbol(".") {
+                column = tokens.identifier()?;
+            }
+            let descending = tokens.eat_keyword("DESC");
+            if !descending {
+                tokens.eat_keyword("ASC");
+            }
+            Some(OrderBy { column, descending })
+        } else {
+            None
+        };
+
+        let limit = if tokens.eat_keyword("LIMIT") {
+            match tokens.next_token() {
+                Some(token) if matches!(token.kind, TokenKind::Number) => token.value.parse().ok(),
+                _ => None,
+            }
+            .map(Some)
+            .ok_or_else(|| SqlParseError::new("LIMIT must be followed by a non-negative integer"))?
+        } else {
+            None
+        };
+
+        if !tokens.is_at_end() {
+            return Err(SqlParseError::new(&format!(
+                "unsupported syntax after `FROM {}`",
+                table
+            )));
+        }
+
+        Ok(Self {
+            columns,
+            table,
+            where_clause,
+            order_by,
+            limit,
+        })
+    }
+}
+
+/// The rowid, if the table has one, and the stored values of a row read from a table b-tree
+pub(crate) type StoredRow = Result<(Option<i64>, Vec<FieldData>), SqrliteError>;
+
+/// The rows a SELECT returns, with its column names
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    /// column names as declared in the schema
+    pub columns: Vec<String>,
+    /// the values of each row, in the order of the columns
+    pub rows: Vec<Vec<FieldData>>,
+}
+
+/// Indexes on the queried table that could serve the query. Partial indexes are only
+/// included when the query's WHERE clause guarantees their predicate holds, since they have
+/// no entries for the other rows.
+pub fn candidate_indexes(schema: &Schema, select: &Select) -> Result<Vec<IndexDef>, SqrliteError> {
+    let query_where = select.where_clause.as_ref().map(|w| w.text.as_str());
+    let mut indexes = vec![];
+    for obj in schema.indexes_on(&select.table) {
+        let index = schema.index_def(&obj.name)?;
+        if index.is_usable() && index.usable_for(query_where) {
+            indexes.push(index);
+        }
+    }
+    Ok(indexes)
+}
+
+/// Run a SELECT against the database, reading every row it returns
+pub fn execute(db: &Database, select: &Select) -> Result<QueryResult, SqrliteError> {
+    // pages read while another process writes may come from either version of the file, so
+    // the change counter must be the same once all rows are read
+    let change_counter = db.read_change_counter()?;
+    let schema = db.schema()?;
+    let rootpage = schema.find_table(&select.table)?.rootpage;
+    let table = schema.table_def(&select.table)?;
+    let layout = RowLayout::new(&table);
+
+    let filter = match &select.where_clause {
+        Some(where_clause) => {
+            let mut expr = where_clause.expr.clone();
+            expr.resolve(&table)?;
+            Some(expr)
+        }
+        None => None,
+    };
+
+    // map each projected column to its position in the table definition
+    let count_only = select.columns == [ResultColumn::CountStar];
+    let mut projection = vec![];
+    let mut names = vec![];
+    for column in &select.columns {
+        match column {
+            ResultColumn::Star => {
+                projection.extend((0..table.columns.len()).map(Projection::Value));
+                names.extend(table.columns.iter().map(|col| col.name.clone()));
+            }
+            ResultColumn::Column(name) => {
+                let idx = table
+                    .column_index(name)
+                    .ok_or_else(|| SqrliteError::NoSuchColumn {
+                        table: table.name.clone(),
+                        column: name.clone(),
+                    })?;
+                projection.push(Projection::Value(idx));
+                names.push(table.columns[idx].name.clone());
+            }
+            ResultColumn::TypeOf(name) => {
+                let idx = table
+                    .column_index(name)
+                    .ok_or_else(|| SqrliteError::NoSuchColumn {
+                        table: table.name.clone(),
+                        column: name.clone(),
+                    })?;
+                projection.push(Projection::TypeOf(idx));
+                names.push(format!("typeof({})", name));
+            }
+            ResultColumn::CountStar if count_only => names.push("count(*)".to_owned()),
+            ResultColumn::CountStar => {
+                return Err(QueryError::new(
+                    "count(*) cannot be combined with other result columns",
+                )
+                .into())
+            }
+        }
+    }
+
+    // ORDER BY is served by reading rows in rowid order, so it has to name the rowid
+    let rowid_order = match &select.order_by {
+        Some(order_by) if !table.without_rowid && is_rowid_column(&table, &order_by.column) => {
+            Some(order_by.descending)
+        }
+        Some(order_by) => {
+            return Err(QueryError::new(&format!(
+                "cannot ORDER BY `{}`: only ordering by the rowid is supported",
+                order_by.column
+            ))
+            .into())
+        }
+        None => None,
+    };
+
+    // counting every row of a large table is split across threads when that is enabled
+    #[cfg(feature = "rayon")]
+    if count_only && filter.is_none() && !table.without_rowid {
+        let mut rows = vec![vec![FieldData::Integer(par_count(db, rootpage)?)]];
+        rows.truncate(select.limit.unwrap_or(1));
+        return Ok(QueryResult {
+            columns: names,
+            rows,
+        });
+    }
+
+    // rows are read lazily, so a LIMIT stops reading as soon as it is reached
+    let records: Box<dyn Iterator<Item = StoredRow>> = if table.without_rowid {
+        // equality on every primary key column pins down at most one row, which is found by
+        // searching the table's b-tree instead of scanning it
+        let required = filter
+            .as_ref()
+            .map(|f| f.required_values())
+            .unwrap_or_default();
+        let key = table
+            .primary_key_columns()
+            .iter()
+            .map(|(idx, _)| {
+                required
+                    .iter()
+                    .find(|(col, _)| col == idx)
+                    .map(|(_, value)| {
+                        (*value)
+                            .clone()
+                            .apply_affinity(table.columns[*idx].affinity())
+                    })
+            })
+            .collect::<Option<Vec<_>>>();
+        let entries = match key {
+            Some(key) => seek_primary_key(db, rootpage, &table, &key)?
+                .into_iter()
+                .collect::<Vec<_>>(),
+            None => read_index_entries(db, rootpage)?,
+        };
+        Box::new(entries.into_iter().map(|values| Ok((None, values))))
+    } else if let Some(lookup) = index_lookup(&schema, select, &table, filter.as_ref())? {
+        // fetch only the rows the index lists; the whole WHERE clause is still applied below
+        let mut rowids = IndexSeek::range(
+            db,
+            lookup.rootpage,
+            lookup.lower,
+            lookup.upper,
+            &lookup.order,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+        if let Some(descending) = rowid_order {
+            rowids.sort_unstable();
+            if descending {
+                rowids.reverse();
+            }
+        }
+        let mut cursor = TableCursor::new(db, rootpage);
+        Box::new(rowids.into_iter().filter_map(move |rowid| {
+            match cursor.seek_rowid(rowid) {
+                Ok(SeekResult::Exact) => Some(
+                    cursor
+                        .read_current(leaf_values)
+                        .map(|(_, values)| (Some(rowid), values)),
+                ),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    } else {
+        // count(*) without a filter never looks at the values. Otherwise the columns the
+        // WHERE clause needs are decoded first, straight from each page, and the rest of the
+        // record only for rows that pass. Every row of a STRICT table has its types checked,
+        // so those are always decoded in full.
+        let skip_values = count_only && filter.is_none();
+        let prefilter = filter.as_ref().filter(|_| !table.strict);
+        let filter_columns = prefilter.map(Expr::columns).unwrap_or_default();
+        let table = &table;
+        let mut read = move |db: &Database, cell: CellRef| {
+            let CellRef::LeafTable { row_id, payload } = cell else {
+                return Err(QueryError::new("expected a table leaf cell").into());
+            };
+            if skip_values {
+                return Ok(Some((row_id, vec![])));
+            }
+            let payload = payload.read(db)?;
+            let at_row = |err| record::at_row(err, row_id);
+            if let Some(filter) = prefilter {
+                let row = partial_row(table, row_id, &payload, db.text_encoding, &filter_columns)
+                    .map_err(at_row)?;
+                if !filter.evaluate(&row).is_true() {
+                    return Ok(None);
+                }
+            }
+            Ok(Some((
+                row_id,
+                Record::decode(&payload, db.text_encoding).map_err(at_row)?,
+            )))
+        };
+        let next_row: Box<dyn FnMut() -> Option<Result<_, _>>> = if rowid_order == Some(true) {
+            let mut scan = ReverseTableScan::new(db, rootpage);
+            Box::new(move || scan.next_with(&mut read))
+        } else {
+            let mut scan = TableScan::new(db, rootpage);
+            Box::new(move || scan.next_with(&mut read))
+        };
+        Box::new(
+            iter::from_fn(next_row)
+                .filter_map(Result::transpose)
+                .map(|row| row.map(|(rowid, values)| (Some(rowid), values))),
+        )
+    };
+
+    let mut rows = vec![];
+    let mut count = 0;
+    for record in records {
+        if !count_only && select.limit.is_some_and(|limit| rows.len() >= limit) {
+            break;
+        }
+        let (rowid, values) = record?;
+        if count_only && filter.is_none() {
+            count += 1;
+            continue;
+        }
+
+        if table.strict {
+            table.check_strict_types(&describe_row(&table, rowid, &values), &values)?;
+        }
+        let row = layout.row(rowid, values);
+        if filter
+            .as_ref()
+            .is_some_and(|f| !f.evaluate(row.values()).is_true())
+        {
+            continue;
+        }
+        if count_only {
+            count += 1;
+        } else {
+            rows.push(
+                projection
+                    .iter()
+                    .map(|&column| match column {
+                        Projection::Value(idx) => row.values()[idx].clone(),
+                        Projection::TypeOf(idx) => {
+                            FieldData::Text(row.values()[idx].type_name().to_owned())
+                        }
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    db.check_unchanged(change_counter)?;
+
+    if count_only {
+        rows.push(vec![FieldData::Integer(count)]);
+    }
+    if let Some(limit) = select.limit {
+        rows.truncate(limit);
+    }
+    Ok(QueryResult {
+        columns: names,
+        rows,
+    })
+}
+
+/// An index scan that finds the rows matching comparison terms of a WHERE clause
+struct IndexLookup {
+    rootpage: u32,
+    lower: Option<KeyBound>,
+    upper: Option<KeyBound>,
+    order: Vec<KeyOrder>,
+    score: usize,
+}
+
+/// Choose the index that best narrows the rows to read: its leading columns are pinned down
+/// by `column = literal` terms, optionally followed by one column constrained by `<`, `<=`,
+/// `>` or `>=` terms. A column only qualifies when the index orders it with the collation
+/// the comparison uses, which is the column's own.
+fn index_lookup(
+    schema: &Schema,
+    select: &Select,
+    table: &TableDef,
+    filter: Option<&Expr>,
+) -> Result<Option<IndexLookup>, SqrliteError> {
+    let terms = filter.map(|f| f.column_comparisons()).unwrap_or_default();
+    if terms.is_empty() {
+        return Ok(None);
+    }
+
+    let mut best: Option<IndexLookup> = None;
+    for index in candidate_indexes(schema, select)? {
+        let mut prefix = vec![];
+        let mut order = vec![];
+        let mut range = None;
+        for col in &index.columns {
+            let IndexKey::Column(name) = &col.key else {
+                break;
+            };
+            let Some(idx) = table.column_index(name) else {
+                break;
+            };
+            // index columns without their own COLLATE use the column's collation
+            let column_collation = table.columns[idx].collation.as_deref();
+            let index_collation = col.collation.as_deref().or(column_collation);
+            let collation = match Collation::from_name(index_collation) {
+                Some(collation) if Some(collation) == Collation::from_name(column_collation) => {
+                    collation
+                }
+                _ => break,
+            };
+            // like SQLite, the key looked up has the column's affinity applied
+            let affinity = table.columns[idx].affinity();
+            let column_terms = terms
+                .iter()
+                .filter(|(i, _, _)| *i == idx)
+                .map(|(_, op, value)| (op, (*value).clone().apply_affinity(affinity)));
+
+            let mut lower = None;
+            let mut upper = None;
+            let mut equal = None;
+            for (op, value) in column_terms {
+                match op {
+                    CompareOp::Eq => equal = Some(value),
+                    CompareOp::Gt => lower = Some((value, false)),
+                    CompareOp::Ge => lower = Some((value, true)),
+                    CompareOp::Lt => upper = Some((value, false)),
+                    CompareOp::Le => upper = Some((value, true)),
+                    CompareOp::Ne => {}
+                }
+            }
+            order.push(KeyOrder {
+                collation,
+                descending: col.descending,
+            });
+            if let Some(value) = equal {
+                prefix.push(value);
+                continue;
+            }
+            if lower.is_some() || upper.is_some() {
+                // NULL never satisfies a comparison, so the open end of the range still
+                // excludes the NULLs sorted at the start of the column
+                let lower = lower.unwrap_or((FieldData::Null(()), false));
+                range = Some(if col.descending {
+                    (upper, Some(lower))
+                } else {
+                    (Some(lower), upper)
+                });
+            } else {
+                order.pop();
+            }
+            break;
+        }
+
+        let score = prefix.len() * 2 + usize::from(range.is_some());
+        if score == 0 || best.as_ref().is_some_and(|b| b.score >= score) {
+            continue;
+        }
+        let Some(obj) = schema.find(&index.name) else {
+            continue;
+        };
+        let bound = |end: Option<(FieldData, bool)>| {
+            let mut key = prefix.clone();
+            match end {
+                Some((value, inclusive)) => {
+                    key.push(value);
+                    Some(KeyBound { key, inclusive })
+                }
+                None if key.is_empty() => None,
+                None => Some(KeyBound::inclusive(&key)),
+            }
+        };
+        let (lower, upper) = range.unwrap_or((None, None));
+        best = Some(IndexLookup {
+            rootpage: obj.rootpage,
+            lower: bound(lower),
+            upper: bound(upper),
+            order,
+            score,
+        });
+    }
+    Ok(best)
+}
+
+/// Find the row of a WITHOUT ROWID table with the given primary key values, listed in key
+/// order
+pub fn lookup_primary_key(
+    db: &Database,
+    table_name: &str,
+    key: &[FieldData],
+) -> Result<Option<NamedRecord>, SqrliteError> {
+    let schema = db.schema()?;
+    let rootpage = schema.find_table(table_name)?.rootpage;
+    let table = schema.table_def(table_name)?;
+    if !table.without_rowid {
+        return Err(
+            QueryError::new(&format!("`{}` is not a WITHOUT ROWID table", table.name)).into(),
+        );
+    }
+    match seek_primary_key(db, rootpage, &table, key)? {
+        Some(values) => {
+            table.check_strict_types(&describe_row(&table, None, &values), &values)?;
+            Ok(Some(RowLayout::new(&table).row(None, values)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn seek_primary_key(
+    db: &Database,
+    rootpage: u32,
+    table: &TableDef,
+    key: &[FieldData],
+) -> Result<Option<Vec<FieldData>>, SqrliteError> {
+    let key_columns = table.primary_key_columns();
+    if key.len() != key_columns.len() {
+        return Err(QueryError::new(&format!(
+            "primary key of `{}` has {} columns but {} values were given",
+            table.name,
+            key_columns.len(),
+            key.len()
+        ))
+        .into());
+    }
+    let order = KeyOrder::for_columns(key_columns.iter().map(|(_, col)| *col))?;
+    seek_key(db, rootpage, key, &order)
+}
+
+/// Whether a column name refers to the rowid, by one of its built-in names or through an
+/// INTEGER PRIMARY KEY column
+fn is_rowid_column(table: &TableDef, name: &str) -> bool {
+    match table.column_index(name) {
+        Some(idx) => table.rowid_alias() == Some(idx),
+        None => ["rowid", "_rowid_", "oid"]
+            .iter()
+            .any(|alias| identifiers_match(alias, name)),
+    }
+}
+
+/// Identify a row in error messages by its rowid, or by its primary key in a WITHOUT ROWID
+/// table, whose records begin with the key
+pub(crate) fn describe_row(table: &TableDef, rowid: Option<i64>, values: &[FieldData]) -> String {
+    match rowid {
+        Some(rowid) => format!("rowid {}", rowid),
+        None => {
+            let key = values
+                .iter()
+                .take(table.primary_key_columns().len())
+                .map(FieldData::to_sql_literal)
+                .collect::<Vec<_>>();
+            format!("row with primary key ({})", key.join(", "))
+        }
+    }
+}
+
+/// A row of a rowid table with only the given columns filled in and the others NULL,
+/// decoding just the fields of the record that those columns are stored in
+fn partial_row(
+    table: &TableDef,
+    rowid: i64,
+    payload: &[u8],
+    encoding: TextEncoding,
+    columns: &[usize],
+) -> Result<Vec<FieldData>, SqrliteError> {
+    let mut row = vec![FieldData::Null(()); table.columns.len()];
+    for &idx in columns {
+        row[idx] = match table.storage_index(idx) {
+            _ if table.rowid_alias() == Some(idx) => FieldData::Integer(rowid),
+            Some(slot) => Record::get_field(payload, slot, encoding)?
+                .unwrap_or_else(|| table.missing_value(idx)),
+            None => FieldData::Null(()),
+        };
+    }
+    Ok(row)
+}
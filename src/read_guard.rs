@@ -0,0 +1,58 @@
+use std::fs::File;
+
+use crate::db::Database;
+use crate::error::SqrliteError;
+
+/// Guards an operation made of many page reads, such as a dump, against passing off a mix of
+/// two versions of the database as one. The file change counter is noted when the guard is
+/// made, and finish() fails with ConcurrentModification if it has moved since.
+///
+/// With a lock, the guard also holds a shared advisory lock on the database file until it is
+/// dropped, keeping out writers that take the same kind of lock. On Windows that includes
+/// SQLite itself. On Unix it does not, as SQLite locks byte ranges with fcntl() while this is
+/// a flock() on the whole file, so there only the change counter check guards against SQLite.
+#[derive(Debug)]
+pub struct ReadGuard<'a> {
+    db: &'a Database,
+    change_counter: u32,
+    lock: Option<File>,
+}
+
+impl<'a> ReadGuard<'a> {
+    /// Start guarding a read, only checking the change counter at the end
+    pub fn new(db: &'a Database) -> Result<Self, SqrliteError> {
+        Ok(Self {
+            db,
+            change_counter: db.read_change_counter()?,
+            lock: None,
+        })
+    }
+
+    /// Start guarding a read while holding a shared lock on the database file, waiting for
+    /// any exclusive lock to be released first
+    pub fn locked(db: &'a Database) -> Result<Self, SqrliteError> {
+        let path = db.path().ok_or_else(|| {
+            SqrliteError::Unsupported("only a database read from a file can be locked".to_owned())
+        })?;
+        let file = File::open(path).map_err(|e| {
+            SqrliteError::io(format!("cannot open {} to lock it", path.display()), e)
+        })?;
+        file.lock_shared()
+            .map_err(|e| SqrliteError::io(format!("cannot lock {}", path.display()), e))?;
+        // the counter is read once the lock is held, so no locking writer can slip in between
+        let mut guard = Self::new(db)?;
+        guard.lock = Some(file);
+        Ok(guard)
+    }
+
+    /// Whether the guard holds a lock on the database file
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// End the read, failing if the database was modified while it was under way. The lock,
+    /// if any, is released when the file closes.
+    pub fn finish(self) -> Result<(), SqrliteError> {
+        self.db.check_unchanged(self.change_counter)
+    }
+}
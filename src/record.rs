@@ -1,9 +1,17 @@
+use std::cmp::{min, Ordering};
+use std::error::Error;
 use std::fmt;
-use std::{cmp::min, error::Error};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
 
-use crate::cell::CellContent;
-use crate::varint::{decode_be, MaxBytesExceededError};
+use crate::cell::{CellRef, Payload, PayloadReader};
+use crate::db::{CorruptData, Database};
+use crate::error::SqrliteError;
+use crate::schema::{Affinity, TableDef};
+use crate::sql::identifiers_match;
+use crate::varint::{decode_be, decode_from, push_varint, varint_len};
 
+/// A value whose bytes are not valid for its type, such as TEXT that is not valid UTF-8
 #[derive(Debug)]
 pub struct ParseError {
     details: String,
@@ -25,29 +33,517 @@ impl fmt::Display for ParseError {
 
 impl Error for ParseError {}
 
+/// A record header field with serial type 10 or 11. Those are reserved for SQLite's internal
+/// use and never appear in a database file, so meeting one means the record is corrupt.
 #[derive(Debug)]
+pub struct SerialTypeError {
+    /// position of the field in the record, from 0
+    pub field_index: usize,
+    /// the reserved serial type found
+    pub serial_type: u64,
+    /// the row the record belongs to, when it is known
+    pub rowid: Option<i64>,
+}
+
+impl fmt::Display for SerialTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "field {} of the record has reserved serial type {}",
+            self.field_index, self.serial_type
+        )?;
+        if let Some(rowid) = self.rowid {
+            write!(f, " (rowid {})", rowid)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SerialTypeError {}
+
+/// A record whose header describes fields taking more or fewer bytes than its payload holds.
+/// Records fill their payload exactly, so either way the record is truncated or corrupt.
+#[derive(Debug)]
+pub struct RecordSizeMismatch {
+    /// bytes the header and the fields it describes take up
+    pub expected: usize,
+    /// bytes the payload holds
+    pub actual: u64,
+    /// the row the record belongs to, when it is known
+    pub rowid: Option<i64>,
+}
+
+impl fmt::Display for RecordSizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "record fields take {} bytes but its payload has {}",
+            self.expected, self.actual
+        )?;
+        if let Some(rowid) = self.rowid {
+            write!(f, " (rowid {})", rowid)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RecordSizeMismatch {}
+
+/// Record which row a decoding error came from, if it is one that reports its row
+pub(crate) fn at_row(mut err: SqrliteError, rowid: i64) -> SqrliteError {
+    match &mut err {
+        SqrliteError::SerialType(e) => e.rowid = Some(rowid),
+        SqrliteError::RecordSize(e) => e.rowid = Some(rowid),
+        _ => {}
+    }
+    err
+}
+
+/// A field that cannot be read from a record: either the record has fewer fields than the
+/// index asked for, or the field's value runs past the end of the payload, which only a
+/// corrupt record does
+#[derive(Debug)]
+pub struct FieldError {
+    /// position of the field in the record, from 0
+    pub field_index: usize,
+    /// why it cannot be read
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "field {} of the record cannot be read: {}",
+            self.field_index, self.reason
+        )
+    }
+}
+
+impl Error for FieldError {}
+
+// Format a value the way the sqlite3 shell does in its default list mode. The one deviation
+// is BLOBs: sqlite3 writes their raw bytes, which need not be printable, so they are shown
+// in hex instead, as hex() would return them.
+impl fmt::Display for FieldData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldData::Null(_) => Ok(()),
+            FieldData::BooleanFalse(_) => write!(f, "0"),
+            FieldData::BooleanTrue(_) => write!(f, "1"),
+            FieldData::Integer(n) => write!(f, "{}", n),
+            FieldData::Real(r) => write!(f, "{}", format_real(*r)),
+            FieldData::Text(text) => write!(f, "{}", text),
+            FieldData::Blob(bytes) => bytes.iter().try_for_each(|b| write!(f, "{:02X}", b)),
+        }
+    }
+}
+
+/// Standard base64 with padding, as in RFC 4648
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &b)| group | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Compare an integer with a real exactly. Converting the integer to a real instead would
+/// lose precision beyond 2^53, making distinct values compare equal.
+fn compare_int_real(i: i64, r: f64) -> Ordering {
+    const TWO_POW_63: f64 = 9223372036854775808.0;
+    if r.is_nan() || r >= TWO_POW_63 {
+        return Ordering::Less;
+    }
+    if r < -TWO_POW_63 {
+        return Ordering::Greater;
+    }
+    // the integer part of a real in range is exactly representable as both types
+    let whole = r.trunc();
+    i.cmp(&(whole as i64))
+        .then_with(|| 0.0.partial_cmp(&(r - whole)).unwrap_or(Ordering::Equal))
+}
+
+/// The number spelled out by TEXT holding only an integer or real literal, ignoring any
+/// surrounding whitespace. Hexadecimal, and the words Rust would read as infinity or NaN,
+/// are not numbers to SQLite.
+fn numeric_text(text: &str) -> Option<FieldData> {
+    let text = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let unsigned = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if !unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()) {
+        if let Ok(n) = text.parse() {
+            return Some(FieldData::Integer(n));
+        }
+    }
+    let well_formed = unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && unsigned
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'));
+    if !well_formed {
+        return None;
+    }
+    text.parse().ok().map(FieldData::Real)
+}
+
+/// Format a REAL as SQLite's printf does with "%!.15g": 15 significant digits, switching to
+/// an exponent outside 1e-4..1e15, and always keeping a digit after the decimal point
+fn format_real(r: f64) -> String {
+    if r.is_nan() {
+        return String::new();
+    }
+    if r.is_infinite() {
+        return if r > 0.0 { "Inf" } else { "-Inf" }.to_owned();
+    }
+    if r == 0.0 {
+        return "0.0".to_owned();
+    }
+    // rounding to 15 digits first gives the exponent the choice of notation depends on
+    let scientific = format!("{:.14e}", r);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or_default();
+    if (-4..15).contains(&exponent) {
+        let fixed = format!("{:.*}", (14 - exponent) as usize, r);
+        trim_fraction(&fixed)
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}e{}{:02}",
+            trim_fraction(mantissa),
+            sign,
+            exponent.unsigned_abs()
+        )
+    }
+}
+
+/// Drop trailing zeros after the decimal point, keeping at least one digit there
+fn trim_fraction(number: &str) -> String {
+    match number.split_once('.') {
+        Some((whole, fraction)) => {
+            let fraction = fraction.trim_end_matches('0');
+            format!(
+                "{}.{}",
+                whole,
+                if fraction.is_empty() { "0" } else { fraction }
+            )
+        }
+        None => format!("{}.0", number),
+    }
+}
+
+/// How TEXT values are stored, as recorded in the database header
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TextEncoding {
+    /// UTF-8, the encoding of most databases
+    #[default]
+    Utf8,
+    /// UTF-16 little-endian
+    Utf16Le,
+    /// UTF-16 big-endian
+    Utf16Be,
+}
+
+impl TextEncoding {
+    /// The encoding for the value of the header's text encoding field. A new, empty database
+    /// has 0 there until its first table is created, and is read as UTF-8.
+    pub fn from_header_value(value: u32) -> Option<Self> {
+        match value {
+            0 | 1 => Some(Self::Utf8),
+            2 => Some(Self::Utf16Le),
+            3 => Some(Self::Utf16Be),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes the text takes up when stored in this encoding
+    pub fn encoded_len(&self, text: &str) -> usize {
+        match self {
+            Self::Utf8 => text.len(),
+            Self::Utf16Le | Self::Utf16Be => text.encode_utf16().count() * 2,
+        }
+    }
+
+    /// Decode the stored bytes of a TEXT value. Its size in the record header is in bytes,
+    /// so a UTF-16 value of odd size is corrupt.
+    pub fn decode(&self, data: &[u8]) -> Result<String, ParseError> {
+        let units = |from_bytes: fn([u8; 2]) -> u16| {
+            data.chunks_exact(2)
+                .map(move |pair| from_bytes([pair[0], pair[1]]))
+        };
+        match self {
+            Self::Utf8 => String::from_utf8(data.to_vec()).map_err(|_| ParseError::new("TEXT")),
+            _ if !data.len().is_multiple_of(2) => Err(ParseError::new("UTF-16 TEXT")),
+            Self::Utf16Le => String::from_utf16(&units(u16::from_le_bytes).collect::<Vec<_>>())
+                .map_err(|_| ParseError::new("UTF-16LE TEXT")),
+            Self::Utf16Be => String::from_utf16(&units(u16::from_be_bytes).collect::<Vec<_>>())
+                .map_err(|_| ParseError::new("UTF-16BE TEXT")),
+        }
+    }
+
+    /// The name SQLite's `.dbinfo` gives the encoding
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+        }
+    }
+
+    /// The bytes a TEXT value is stored as
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Self::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+}
+
+/// The type of a value as its serial type in a record header gives it, before it is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataType {
+    /// serial type 0
     Null,
+    /// serial type 8, the integer 0 stored in no bytes
     BooleanFalse,
+    /// serial type 9, the integer 1 stored in no bytes
     BooleanTrue,
+    /// serial types 1 to 6, a big-endian integer of 1 to 8 bytes
     Integer,
+    /// serial type 7, a big-endian IEEE 754 double
     Real,
+    /// odd serial types from 13
     Text,
+    /// even serial types from 12
     Blob,
 }
 
-#[derive(Debug)]
+/// A value read from a record. The constants 0 and 1, which records store in no bytes, keep
+/// variants of their own.
+///
+/// The derived equality is structural: Integer(1) and Real(1.0) differ, as do the serial
+/// type 9 constant and Integer(1). `sqlite_cmp` compares values the way SQL does.
+///
+/// ```
+/// use sqrlite::FieldData;
+///
+/// let value = FieldData::Integer(3);
+/// assert_eq!(value.type_name(), "integer");
+/// assert_eq!(value.as_f64(), Some(3.0));
+/// assert_eq!(FieldData::Text("x".into()).as_i64(), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
 pub enum FieldData {
+    /// NULL
     Null(()),
+    /// the integer 0, stored as serial type 8
     BooleanFalse(u8),
+    /// the integer 1, stored as serial type 9
     BooleanTrue(u8),
+    /// a 64-bit signed integer
     Integer(i64),
+    /// a 64-bit float
     Real(f64),
+    /// TEXT, decoded from the database's text encoding
     Text(String),
+    /// a BLOB, as stored
     Blob(Vec<u8>),
 }
 
 impl FieldData {
+    /// Name of the value's storage class, as returned by SQL's typeof()
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FieldData::Null(_) => "null",
+            FieldData::BooleanFalse(_) | FieldData::BooleanTrue(_) | FieldData::Integer(_) => {
+                "integer"
+            }
+            FieldData::Real(_) => "real",
+            FieldData::Text(_) => "text",
+            FieldData::Blob(_) => "blob",
+        }
+    }
+
+    /// Whether the value is NULL
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldData::Null(_))
+    }
+
+    /// Typed getters, converting between storage classes only where SQLite does so without
+    /// any parsing:
+    ///
+    /// ```text
+    ///              as_i64         as_f64     as_bool  as_str  as_blob
+    ///   NULL       None           None       None     None    None
+    ///   0/1 (8/9)  0 or 1         0.0/1.0    value    None    None
+    ///   INTEGER    value          as float   != 0     None    None
+    ///   REAL       truncated      value      != 0.0   None    None
+    ///   TEXT       None           None       None     value   UTF-8 bytes
+    ///   BLOB       None           None       None     None    value
+    /// ```
+    ///
+    /// A REAL is truncated towards zero and saturates at the bounds of i64, as CAST does; NaN
+    /// has no integer value. TEXT is never read as a number, since that depends on the
+    /// affinity of the column it came from.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FieldData::BooleanFalse(_) => Some(0),
+            FieldData::BooleanTrue(_) => Some(1),
+            FieldData::Integer(n) => Some(*n),
+            FieldData::Real(r) if r.is_nan() => None,
+            FieldData::Real(r) => Some(*r as i64),
+            _ => None,
+        }
+    }
+
+    /// The value as a float, for the numeric storage classes
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldData::BooleanFalse(_) => Some(0.0),
+            FieldData::BooleanTrue(_) => Some(1.0),
+            FieldData::Integer(n) => Some(*n as f64),
+            FieldData::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+
+    /// Whether a numeric value is non-zero
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldData::Real(r) => Some(*r != 0.0),
+            _ => self.as_i64().map(|n| n != 0),
+        }
+    }
+
+    /// The text of a TEXT value
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldData::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The bytes of a BLOB value, or of a TEXT value as UTF-8
+    pub fn as_blob(&self) -> Option<&[u8]> {
+        match self {
+            FieldData::Text(text) => Some(text.as_bytes()),
+            FieldData::Blob(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// A BLOB's bytes in uppercase hex, as SQL's hex() writes them. None for other values.
+    pub fn to_hex(&self) -> Option<String> {
+        match self {
+            FieldData::Blob(bytes) => Some(bytes.iter().map(|b| format!("{:02X}", b)).collect()),
+            _ => None,
+        }
+    }
+
+    /// A BLOB's bytes in standard padded base64. None for other values.
+    pub fn to_base64(&self) -> Option<String> {
+        match self {
+            FieldData::Blob(bytes) => Some(base64(bytes)),
+            _ => None,
+        }
+    }
+
+    /// Compare with another value the way SQL does, in storage class order: NULL < numbers <
+    /// TEXT < BLOB. Integers and reals compare by numeric value, so 1 equals 1.0, and TEXT
+    /// compares bytewise. Comparisons involving NULL are unknown.
+    pub fn sqlite_cmp(&self, other: &Self) -> Option<Ordering> {
+        fn class(value: &FieldData) -> u8 {
+            match value {
+                FieldData::Null(_) => 0,
+                FieldData::BooleanFalse(_)
+                | FieldData::BooleanTrue(_)
+                | FieldData::Integer(_)
+                | FieldData::Real(_) => 1,
+                FieldData::Text(_) => 2,
+                FieldData::Blob(_) => 3,
+            }
+        }
+
+        match (self, other) {
+            (FieldData::Null(_), _) | (_, FieldData::Null(_)) => None,
+            (FieldData::Integer(x), FieldData::Integer(y)) => Some(x.cmp(y)),
+            (FieldData::Text(x), FieldData::Text(y)) => Some(x.cmp(y)),
+            (FieldData::Blob(x), FieldData::Blob(y)) => Some(x.cmp(y)),
+            (FieldData::Real(x), FieldData::Real(y)) => x.partial_cmp(y),
+            (FieldData::Real(r), _) if class(other) == 1 => {
+                Some(compare_int_real(other.as_i64()?, *r).reverse())
+            }
+            (_, FieldData::Real(r)) if class(self) == 1 => {
+                Some(compare_int_real(self.as_i64()?, *r))
+            }
+            _ if class(self) == 1 && class(other) == 1 => {
+                Some(self.as_i64()?.cmp(&other.as_i64()?))
+            }
+            _ => Some(class(self).cmp(&class(other))),
+        }
+    }
+
+    /// The value converted to a column affinity the way SQLite converts values stored in, or
+    /// compared with, such a column: numeric affinities turn TEXT that is a well-formed number
+    /// into that number (INTEGER and NUMERIC preferring an integer when that loses nothing,
+    /// REAL always a real), TEXT affinity turns numbers into their text, and NULL and BLOB
+    /// values are never converted
+    pub fn apply_affinity(self, affinity: Affinity) -> Self {
+        let value = match (&self, affinity) {
+            (FieldData::Text(text), affinity) if affinity.is_numeric() => {
+                numeric_text(text).unwrap_or(self)
+            }
+            (FieldData::Null(_) | FieldData::Blob(_), _) | (_, Affinity::Blob) => return self,
+            (_, Affinity::Text) => return FieldData::Text(self.to_string()),
+            _ => self,
+        };
+        match (value, affinity) {
+            (value, Affinity::Real) => match value.as_f64() {
+                Some(r) => FieldData::Real(r),
+                None => value,
+            },
+            (FieldData::Real(r), _) => match r as i64 {
+                n if n as f64 == r && n > i64::MIN && n < i64::MAX => FieldData::Integer(n),
+                _ => FieldData::Real(r),
+            },
+            (value, _) => value,
+        }
+    }
+
+    /// Render the value as a SQL literal that reproduces it when read back, as used by `.dump`
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            FieldData::Null(_) => "NULL".to_owned(),
+            FieldData::BooleanFalse(_) => "0".to_owned(),
+            FieldData::BooleanTrue(_) => "1".to_owned(),
+            FieldData::Integer(n) => n.to_string(),
+            FieldData::Real(r) if r.is_nan() => "NULL".to_owned(),
+            FieldData::Real(r) if r.is_infinite() => {
+                if *r > 0.0 { "1e999" } else { "-1e999" }.to_owned()
+            }
+            FieldData::Real(r) => format!("{:?}", r),
+            FieldData::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            FieldData::Blob(bytes) => {
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<String>();
+                format!("X'{}'", hex)
+            }
+        }
+    }
+
     fn parse(data_type: DataType, data: &[u8]) -> Result<Self, ParseError> {
         match data_type {
             DataType::Null => {
@@ -111,20 +607,17 @@ impl FieldData {
                 ]);
                 Ok(FieldData::Real(value))
             }
-            DataType::Text => {
-                if let Ok(text) = String::from_utf8(data.to_vec()) {
-                    Ok(FieldData::Text(text))
-                } else {
-                    Err(ParseError::new("TEXT"))
-                }
-            }
+            DataType::Text => TextEncoding::Utf8.decode(data).map(FieldData::Text),
             DataType::Blob => Ok(FieldData::Blob(data.into())),
         }
     }
 }
 
+/// Where one value of a record lies in its payload, and its type, found from the record
+/// header without reading the value
 #[derive(Debug)]
 pub struct Field {
+    index: usize,
     size: usize,
     offset: usize,
     data_type: DataType,
@@ -133,6 +626,7 @@ pub struct Field {
 impl Default for Field {
     fn default() -> Self {
         Self {
+            index: 0,
             size: 0,
             offset: 0,
             data_type: DataType::Null,
@@ -141,126 +635,1132 @@ impl Default for Field {
 }
 
 impl Field {
-    pub fn read_data(&self, content: &CellContent) -> Result<FieldData, Box<dyn Error>> {
-        let payload = content.get_payload()?;
-        let data = &payload[self.offset..self.offset + self.size];
+    /// The index-th field of a record, described by its serial type in the record header,
+    /// with its value starting at `offset` into the payload
+    fn from_serial_type(
+        serial_type: u64,
+        index: usize,
+        offset: usize,
+    ) -> Result<Self, SerialTypeError> {
+        let (size, data_type) = match serial_type {
+            0 => (0, DataType::Null),
+            1..=4 => (serial_type as usize, DataType::Integer),
+            5 => (6, DataType::Integer),
+            6 => (8, DataType::Integer),
+            7 => (8, DataType::Real),
+            8 => (0, DataType::BooleanFalse),
+            9 => (0, DataType::BooleanTrue),
+            10 | 11 => {
+                return Err(SerialTypeError {
+                    field_index: index,
+                    serial_type,
+                    rowid: None,
+                })
+            }
+            _ if serial_type.is_multiple_of(2) => {
+                (((serial_type - 12) / 2) as usize, DataType::Blob)
+            }
+            _ => (((serial_type - 13) / 2) as usize, DataType::Text),
+        };
+        Ok(Self {
+            index,
+            size,
+            offset,
+            data_type,
+        })
+    }
+
+    /// The type the field's serial type gives it
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    /// Stream a TEXT or BLOB value straight from the payload of the record it belongs to,
+    /// without collecting the payload first
+    pub fn open_blob_reader<'a>(
+        &self,
+        payload: &'a Payload,
+        db: &'a Database,
+    ) -> Result<PayloadReader<'a>, SqrliteError> {
+        if !matches!(self.data_type, DataType::Text | DataType::Blob) {
+            return Err(ParseError::new("TEXT or BLOB").into());
+        }
+        payload
+            .open_reader(db)
+            .range(self.offset as u64, self.size as u64)
+    }
+
+    /// Read the field's value out of the payload of the record it belongs to, decoding TEXT
+    /// with the database's encoding
+    pub fn read(&self, payload: &[u8], encoding: TextEncoding) -> Result<FieldData, SqrliteError> {
+        let data = payload
+            .get(self.offset..self.offset.saturating_add(self.size))
+            .ok_or_else(|| FieldError {
+                field_index: self.index,
+                reason: format!(
+                    "its {} bytes at offset {} extend past the end of the {}-byte payload",
+                    self.size,
+                    self.offset,
+                    payload.len()
+                ),
+            })?;
 
         match self.data_type {
             DataType::Null => {
-                let field_value =
-                    FieldData::parse(DataType::Null, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::Null, data)?;
                 Ok(field_value)
             }
             DataType::BooleanFalse => {
-                let field_value =
-                    FieldData::parse(DataType::BooleanFalse, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::BooleanFalse, data)?;
                 Ok(field_value)
             }
             DataType::BooleanTrue => {
-                let field_value =
-                    FieldData::parse(DataType::BooleanTrue, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::BooleanTrue, data)?;
                 Ok(field_value)
             }
             DataType::Integer => {
-                let field_value =
-                    FieldData::parse(DataType::Integer, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::Integer, data)?;
                 Ok(field_value)
             }
             DataType::Real => {
-                let field_value =
-                    FieldData::parse(DataType::Real, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::Real, data)?;
                 Ok(field_value)
             }
             DataType::Text => {
-                let field_value =
-                    FieldData::parse(DataType::Text, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::Text(encoding.decode(data)?);
                 Ok(field_value)
             }
             DataType::Blob => {
-                let field_value =
-                    FieldData::parse(DataType::Blob, data).map_err(|e| e.to_string())?;
+                let field_value = FieldData::parse(DataType::Blob, data)?;
                 Ok(field_value)
             }
         }
     }
 }
 
+/// A record: a header of serial types followed by the values they describe, which is how
+/// SQLite stores each row of a table and each entry of an index.
+///
+/// ```
+/// use sqrlite::{FieldData, Record, TextEncoding};
+///
+/// let values = vec![FieldData::Integer(7), FieldData::Text("seven".into())];
+/// let payload = sqrlite::record::encode(&values);
+/// assert_eq!(Record::decode(&payload, TextEncoding::Utf8).unwrap(), values);
+/// ```
 #[derive(Debug, Default)]
 pub struct Record {
+    /// the fields of the header, once it has been parsed
     pub fields: Option<Vec<Field>>,
+    /// the whole payload, header included; empty when only the header was read
+    pub payload: Vec<u8>,
+    /// the database's text encoding, for reading TEXT values
+    pub encoding: TextEncoding,
 }
 
 impl Record {
+    /// An empty record with no header parsed
     pub fn new() -> Self {
         Self {
             ..Default::default()
         }
     }
 
-    pub fn load_fields(&mut self, payload: &[u8]) -> Result<(), MaxBytesExceededError> {
-        // read first varint from payload to determine size
-        let (header_size, mut idx) = decode_be(&payload[..9usize])?;
-        let mut fields = vec![];
-
-        let mut serial_type: u64;
-        let mut position = idx;
-        let mut field_start = header_size as usize;
-        while position < header_size as usize {
-            // let mut new_field = Field::default();
-            let mut new_field = Field {
-                offset: field_start,
-                ..Default::default()
-            };
-            // new_field.offset = field_start;
-            let end = min(position + 9usize, header_size as usize);
-            (serial_type, idx) = decode_be(&payload[position..end])?;
-            match serial_type {
-                0 => {
-                    new_field.size = 0;
-                    new_field.data_type = DataType::Null;
-                }
-                1..=4 => {
-                    new_field.size = serial_type as usize;
-                    new_field.data_type = DataType::Integer;
-                }
-                s @ 5 | s @ 6 => {
-                    new_field.size = if s == 5 { 6 } else { 8 };
-                    new_field.data_type = DataType::Integer;
-                }
-                7 => {
-                    new_field.size = 8;
-                    new_field.data_type = DataType::Real;
-                }
-                8 => {
-                    new_field.size = 0;
-                    new_field.data_type = DataType::BooleanFalse;
-                }
-                9 => {
-                    new_field.size = 0;
-                    new_field.data_type = DataType::BooleanTrue;
-                }
-                10 | 11 => {
-                    // Technically, 10 and 11 have variable sizes but are reserved for
-                    // internal SQLite use and should never appear in database files.
-                    todo!()
+    /// Take ownership of a cell's payload and parse the record header at its start
+    pub fn from_payload(payload: Vec<u8>, encoding: TextEncoding) -> Result<Self, SqrliteError> {
+        let mut record = Record::new();
+        record.load_fields(&payload)?;
+        record.payload = payload;
+        record.encoding = encoding;
+        Ok(record)
+    }
+
+    /// Parse the header of a record read from the start of a payload, leaving the payload
+    /// itself unread. The fields can then be streamed with `Field::open_blob_reader`.
+    pub fn read_header<R: Read + Seek>(reader: &mut R) -> Result<Self, SqrliteError> {
+        let (header_size, _) = decode_from(reader)?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut header = vec![];
+        reader.take(header_size).read_to_end(&mut header)?;
+        if header.len() as u64 != header_size {
+            return Err(
+                CorruptData::new("record header extends past the end of the payload").into(),
+            );
+        }
+
+        let (fields, record_size) = parse_header(&header)?;
+        check_record_size(record_size, reader.seek(SeekFrom::End(0))?)?;
+        Ok(Record {
+            fields: Some(fields),
+            ..Record::new()
+        })
+    }
+
+    /// Read every field of a record created with `from_payload`
+    pub fn read_values(&self) -> Result<Vec<FieldData>, SqrliteError> {
+        self.fields
+            .iter()
+            .flatten()
+            .map(|field| field.read(&self.payload, self.encoding))
+            .collect()
+    }
+
+    /// The number of fields in the record's header, 0 before it has been parsed
+    pub fn field_count(&self) -> usize {
+        self.fields.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Read the index-th field of a record created with `from_payload`
+    pub fn read_field(&self, index: usize) -> Result<FieldData, SqrliteError> {
+        let field = self
+            .fields
+            .as_ref()
+            .and_then(|fields| fields.get(index))
+            .ok_or_else(|| FieldError {
+                field_index: index,
+                reason: format!("the record has only {} fields", self.field_count()),
+            })?;
+        field.read(&self.payload, self.encoding)
+    }
+
+    /// Decode every field of the record held in `payload`, the standard way to read a row
+    pub fn decode(payload: &[u8], encoding: TextEncoding) -> Result<Vec<FieldData>, SqrliteError> {
+        let (fields, record_size) = parse_header(payload)?;
+        check_record_size(record_size, payload.len() as u64)?;
+        fields
+            .iter()
+            .map(|field| field.read(payload, encoding))
+            .collect()
+    }
+
+    /// Parse the header of the record held in `payload`, checking that the fields it
+    /// describes fill the payload exactly
+    pub fn load_fields(&mut self, payload: &[u8]) -> Result<(), SqrliteError> {
+        let (fields, record_size) = parse_header(payload)?;
+        check_record_size(record_size, payload.len() as u64)?;
+        self.fields = Some(fields);
+        Ok(())
+    }
+
+    /// Decode only the index-th field of the record in `payload`, reading the header just far
+    /// enough to find where that field starts. None if the record has fewer fields, as rows
+    /// written before an ALTER TABLE ADD COLUMN do.
+    pub fn get_field(
+        payload: &[u8],
+        index: usize,
+        encoding: TextEncoding,
+    ) -> Result<Option<FieldData>, SqrliteError> {
+        let (header_end, mut position) = header_size(payload)?;
+        let mut field_start = header_end;
+        let mut field_index = 0;
+        while position < header_end {
+            let (serial_type, idx) = decode_be(&payload[position..min(position + 9, header_end)])?;
+            let field = Field::from_serial_type(serial_type, field_index, field_start)?;
+            if field_index == index {
+                return field.read(payload, encoding).map(Some);
+            }
+            field_start = field_start.saturating_add(field.size);
+            field_index += 1;
+            position += idx;
+        }
+        Ok(None)
+    }
+}
+
+/// A row of a table with every column in schema order, addressable by column name. The
+/// column names are shared by all rows read with the same layout.
+#[derive(Debug, Clone)]
+pub struct NamedRecord {
+    /// None for WITHOUT ROWID tables
+    pub rowid: Option<i64>,
+    columns: Arc<[String]>,
+    values: Vec<FieldData>,
+}
+
+impl NamedRecord {
+    /// The value of a column, matching its name as SQL does, ignoring ASCII case
+    pub fn get(&self, name: &str) -> Option<&FieldData> {
+        let idx = self
+            .columns
+            .iter()
+            .position(|col| identifiers_match(col, name))?;
+        self.values.get(idx)
+    }
+
+    /// The column names, in schema order
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The values, in the order of the columns
+    pub fn values(&self) -> &[FieldData] {
+        &self.values
+    }
+
+    /// The values, in the order of the columns, without copying them
+    pub fn into_values(self) -> Vec<FieldData> {
+        self.values
+    }
+
+    /// Each column name with its value, in schema order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FieldData)> {
+        self.columns
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ColumnSource {
+    /// an INTEGER PRIMARY KEY, stored as the rowid
+    Rowid,
+    /// `missing` for records from before ADD COLUMN. SQLite stores a REAL with an integer
+    /// value as an integer to save space, so REAL columns turn integers back into reals.
+    Stored {
+        slot: usize,
+        missing: FieldData,
+        real: bool,
+    },
+    /// generated on read; NULL until expressions exist
+    Virtual,
+}
+
+/// Where each column of a table comes from when a stored record is turned into a row,
+/// worked out once so that reading many rows does not consult the table definition again
+#[derive(Debug, Clone)]
+pub struct RowLayout {
+    columns: Arc<[String]>,
+    sources: Vec<ColumnSource>,
+}
+
+impl RowLayout {
+    /// Work out the layout of a table's rows
+    pub fn new(table: &TableDef) -> Self {
+        let rowid_alias = table.rowid_alias();
+        let sources = (0..table.columns.len())
+            .map(|idx| match table.storage_index(idx) {
+                _ if Some(idx) == rowid_alias => ColumnSource::Rowid,
+                Some(slot) => ColumnSource::Stored {
+                    slot,
+                    missing: table.missing_value(idx),
+                    real: table.columns[idx].affinity() == Affinity::Real,
+                },
+                None => ColumnSource::Virtual,
+            })
+            .collect();
+        Self {
+            columns: table.columns.iter().map(|col| col.name.clone()).collect(),
+            sources,
+        }
+    }
+
+    /// Arrange the values stored in a record as a row holding every column of the table
+    pub fn row(&self, rowid: Option<i64>, mut values: Vec<FieldData>) -> NamedRecord {
+        let row = self
+            .sources
+            .iter()
+            .map(|source| match (rowid, source) {
+                (Some(rowid), ColumnSource::Rowid) => FieldData::Integer(rowid),
+                (_, ColumnSource::Stored { slot, real, .. }) if *slot < values.len() => {
+                    let value = std::mem::replace(&mut values[*slot], FieldData::Null(()));
+                    match value {
+                        FieldData::Integer(_)
+                        | FieldData::BooleanFalse(_)
+                        | FieldData::BooleanTrue(_)
+                            if *real =>
+                        {
+                            FieldData::Real(value.as_f64().unwrap_or_default())
+                        }
+                        value => value,
+                    }
                 }
-                _ => {
-                    if serial_type % 2 == 0 {
-                        new_field.size = ((serial_type - 12) / 2) as usize;
-                        new_field.data_type = DataType::Blob;
-                    } else {
-                        new_field.size = ((serial_type - 13) / 2) as usize;
-                        new_field.data_type = DataType::Text
+                (_, ColumnSource::Stored { missing, .. }) => missing.clone(),
+                _ => FieldData::Null(()),
+            })
+            .collect();
+        NamedRecord {
+            rowid,
+            columns: Arc::clone(&self.columns),
+            values: row,
+        }
+    }
+}
+
+/// Encode values as a UTF-8 record, giving each the smallest serial type that holds it. The
+/// constant serial types 8 and 9 are not used, so the record is readable whatever the
+/// database's schema format.
+pub fn encode(values: &[FieldData]) -> Vec<u8> {
+    encode_with(values, TextEncoding::Utf8, false)
+}
+
+/// Encode values as a record with TEXT in the given encoding. With `constants`, the integers
+/// 0 and 1 take serial types 8 and 9 and no body bytes, which needs schema format 4.
+pub fn encode_with(values: &[FieldData], encoding: TextEncoding, constants: bool) -> Vec<u8> {
+    let mut types = vec![];
+    let mut body = vec![];
+    for value in values {
+        let serial_type = match value {
+            FieldData::Null(_) => 0,
+            FieldData::Real(r) => {
+                body.extend(r.to_be_bytes());
+                7
+            }
+            FieldData::Text(text) => {
+                let bytes = encoding.encode(text);
+                let serial_type = 13 + 2 * bytes.len() as u64;
+                body.extend(bytes);
+                serial_type
+            }
+            FieldData::Blob(bytes) => {
+                body.extend(bytes);
+                12 + 2 * bytes.len() as u64
+            }
+            _ => {
+                let n = value.as_i64().unwrap_or_default();
+                match n {
+                    0 if constants => 8,
+                    1 if constants => 9,
+                    _ => {
+                        // the smallest of the 1, 2, 3, 4, 6 and 8 byte sizes holding n
+                        let (serial_type, size) = [(1, 1), (2, 2), (3, 3), (4, 4), (5, 6)]
+                            .into_iter()
+                            .find(|&(_, size)| n >> (8 * size - 1) == n >> 63)
+                            .unwrap_or((6, 8));
+                        body.extend(&n.to_be_bytes()[8 - size..]);
+                        serial_type
                     }
                 }
-            };
-            field_start += new_field.size;
-            fields.push(new_field);
-            position += idx;
+            }
+        };
+        push_varint(&mut types, serial_type);
+    }
+
+    // the header size counts the varint holding it, which may lengthen as the size grows
+    let mut header_size = types.len() + 1;
+    while types.len() + varint_len(header_size as u64) > header_size {
+        header_size = types.len() + varint_len(header_size as u64);
+    }
+    let mut record = Vec::with_capacity(header_size + body.len());
+    push_varint(&mut record, header_size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// The size of the header of the record at the start of `bytes`, which must hold the whole
+/// header, and the length of the varint giving it
+fn header_size(bytes: &[u8]) -> Result<(usize, usize), SqrliteError> {
+    let (size, len) = decode_be(&bytes[..min(bytes.len(), 9)])?;
+    match usize::try_from(size) {
+        Ok(size) if size >= len && size <= bytes.len() => Ok((size, len)),
+        _ => Err(CorruptData::new(&format!(
+            "record header size {} is invalid for a {}-byte payload",
+            size,
+            bytes.len()
+        ))
+        .into()),
+    }
+}
+
+/// Walk the header of the record at the start of `bytes`, returning its fields and the size
+/// of the whole record they describe
+fn parse_header(bytes: &[u8]) -> Result<(Vec<Field>, usize), SqrliteError> {
+    let (header_size, mut position) = header_size(bytes)?;
+    let mut fields = vec![];
+    let mut record_size = header_size;
+    while position < header_size {
+        let (serial_type, len) = decode_be(&bytes[position..min(position + 9, header_size)])?;
+        let field = Field::from_serial_type(serial_type, fields.len(), record_size)?;
+        record_size = record_size.saturating_add(field.size);
+        fields.push(field);
+        position += len;
+    }
+    Ok((fields, record_size))
+}
+
+fn check_record_size(record_size: usize, payload_size: u64) -> Result<(), RecordSizeMismatch> {
+    if record_size as u64 != payload_size {
+        return Err(RecordSizeMismatch {
+            expected: record_size,
+            actual: payload_size,
+            rowid: None,
+        });
+    }
+    Ok(())
+}
+
+/// Decode every field of a cell's record into its value, reading it from the page buffer
+/// unless it spills onto overflow pages
+pub(crate) fn decode_row(db: &Database, cell: CellRef) -> Result<Vec<FieldData>, SqrliteError> {
+    let payload = cell
+        .payload()
+        .ok_or_else(|| CorruptData::new("an interior table cell has no record"))?
+        .read(db)?;
+    Record::decode(&payload, db.text_encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn null() -> FieldData {
+        FieldData::Null(())
+    }
+
+    fn text(s: &str) -> FieldData {
+        FieldData::Text(s.to_owned())
+    }
+
+    #[test]
+    fn storage_classes_order_numbers_text_blobs() {
+        let ascending = [
+            FieldData::Integer(i64::MIN),
+            FieldData::Real(-0.5),
+            FieldData::BooleanFalse(0),
+            FieldData::Real(0.5),
+            FieldData::BooleanTrue(1),
+            FieldData::Integer(2),
+            FieldData::Real(f64::INFINITY),
+            text(""),
+            text("10"),
+            text("9"),
+            FieldData::Blob(vec![]),
+            FieldData::Blob(vec![0]),
+        ];
+        for (i, a) in ascending.iter().enumerate() {
+            for (j, b) in ascending.iter().enumerate() {
+                assert_eq!(a.sqlite_cmp(b), Some(i.cmp(&j)), "{:?} and {:?}", a, b);
+            }
         }
+    }
 
-        self.fields = Some(fields);
+    #[test]
+    fn null_compares_as_unknown() {
+        for value in [
+            null(),
+            FieldData::Integer(0),
+            text(""),
+            FieldData::Blob(vec![]),
+        ] {
+            assert_eq!(null().sqlite_cmp(&value), None);
+            assert_eq!(value.sqlite_cmp(&null()), None);
+        }
+    }
 
-        Ok(())
+    #[test]
+    fn integers_equal_reals_of_the_same_value() {
+        assert_eq!(
+            FieldData::Integer(1).sqlite_cmp(&FieldData::Real(1.0)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            FieldData::BooleanTrue(1).sqlite_cmp(&FieldData::Integer(1)),
+            Some(Ordering::Equal)
+        );
+        assert_ne!(FieldData::Integer(1), FieldData::Real(1.0));
+    }
+
+    // Casting the integer to f64 would round i64::MAX up to 2^63 and 2^53 + 1 down to 2^53
+    #[test]
+    fn integers_compare_exactly_with_reals() {
+        const TWO_POW_53: i64 = 1 << 53;
+        assert_eq!(
+            compare_int_real(i64::MAX, 9.223372036854776e18),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_int_real(TWO_POW_53 + 1, TWO_POW_53 as f64),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_int_real(TWO_POW_53, TWO_POW_53 as f64),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_int_real(i64::MIN, -9.223372036854776e18),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_int_real(i64::MIN, -9.223372036854778e18),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_int_real(i64::MIN + 1, -9.223372036854776e18),
+            Ordering::Greater
+        );
+        assert_eq!(compare_int_real(2, 2.5), Ordering::Less);
+        assert_eq!(compare_int_real(-2, -2.5), Ordering::Greater);
+        assert_eq!(compare_int_real(i64::MAX, f64::INFINITY), Ordering::Less);
+        assert_eq!(
+            compare_int_real(i64::MIN, f64::NEG_INFINITY),
+            Ordering::Greater
+        );
+
+        // and in either order through sqlite_cmp
+        let max = FieldData::Integer(i64::MAX);
+        let two_pow_63 = FieldData::Real(9.223372036854776e18);
+        assert_eq!(max.sqlite_cmp(&two_pow_63), Some(Ordering::Less));
+        assert_eq!(two_pow_63.sqlite_cmp(&max), Some(Ordering::Greater));
+    }
+
+    // A record holding one field of each serial type but the reserved ones, and the values
+    // it decodes to
+    fn every_serial_type() -> (Vec<u8>, Vec<FieldData>) {
+        let types = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 14, 13, 17];
+        let mut record = vec![1 + types.len() as u8];
+        record.extend(types);
+        record.push(0x80); // 1: 8-bit
+        record.extend([0x7f, 0xff]); // 2: 16-bit
+        record.extend([0xff, 0xff, 0xfe]); // 3: 24-bit
+        record.extend([0x80, 0, 0, 0]); // 4: 32-bit
+        record.extend([0x80, 0, 0, 0, 0, 1]); // 5: 48-bit
+        record.extend(i64::MAX.to_be_bytes()); // 6: 64-bit
+        record.extend(1.5f64.to_be_bytes()); // 7: float
+        record.push(0xab); // 14: 1-byte blob, after the empty one
+        record.extend(b"hi"); // 17: 2-byte text, after the empty one
+        let values = vec![
+            null(),
+            FieldData::Integer(-128),
+            FieldData::Integer(32767),
+            FieldData::Integer(-2),
+            FieldData::Integer(i32::MIN.into()),
+            FieldData::Integer(1 - (1 << 47)),
+            FieldData::Integer(i64::MAX),
+            FieldData::Real(1.5),
+            FieldData::BooleanFalse(0),
+            FieldData::BooleanTrue(1),
+            FieldData::Blob(vec![]),
+            FieldData::Blob(vec![0xab]),
+            text(""),
+            text("hi"),
+        ];
+        (record, values)
+    }
+
+    #[test]
+    fn decodes_every_serial_type() {
+        let (record, values) = every_serial_type();
+        assert_eq!(Record::decode(&record, TextEncoding::Utf8).unwrap(), values);
+        let parsed = Record::from_payload(record.clone(), TextEncoding::Utf8).unwrap();
+        assert_eq!(parsed.field_count(), values.len());
+        assert_eq!(parsed.read_values().unwrap(), values);
+        for (i, value) in values.iter().enumerate() {
+            let field = Record::get_field(&record, i, TextEncoding::Utf8).unwrap();
+            assert_eq!(field.as_ref(), Some(value), "field {}", i);
+            assert_eq!(&parsed.read_field(i).unwrap(), value);
+        }
+        assert_eq!(
+            Record::get_field(&record, values.len(), TextEncoding::Utf8).unwrap(),
+            None
+        );
+        assert!(parsed.read_field(values.len()).is_err());
+    }
+
+    #[test]
+    fn reserved_serial_types_are_errors() {
+        for serial_type in [10, 11] {
+            let record = [3, 1, serial_type, 5];
+            match Record::decode(&record, TextEncoding::Utf8) {
+                Err(SqrliteError::SerialType(e)) => {
+                    assert_eq!((e.field_index, e.serial_type), (1, u64::from(serial_type)));
+                }
+                other => panic!("expected a serial type error, got {:?}", other),
+            }
+            // fields before the reserved one can still be read on their own
+            assert_eq!(
+                Record::get_field(&record, 0, TextEncoding::Utf8).unwrap(),
+                Some(FieldData::Integer(5))
+            );
+            assert!(matches!(
+                Record::get_field(&record, 1, TextEncoding::Utf8),
+                Err(SqrliteError::SerialType(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn header_size_past_the_payload() {
+        // a header claiming 10 bytes of a 3-byte payload, and one whose size varint is cut off
+        for record in [&[10, 1, 5][..], &[0x81]] {
+            match Record::decode(record, TextEncoding::Utf8) {
+                Err(SqrliteError::Corrupt(_)) | Err(SqrliteError::Varint(_)) => {}
+                other => panic!("expected a corrupt header, got {:?}", other),
+            }
+            assert!(Record::get_field(record, 0, TextEncoding::Utf8).is_err());
+            assert!(Record::from_payload(record.to_vec(), TextEncoding::Utf8).is_err());
+        }
+        match Record::decode(&[10, 1, 5], TextEncoding::Utf8) {
+            Err(SqrliteError::Corrupt(e)) => assert_eq!(
+                e.to_string(),
+                "record header size 10 is invalid for a 3-byte payload"
+            ),
+            other => panic!("expected a corrupt header, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn one_null_column() {
+        let record = [0x02, 0x00];
+        assert_eq!(
+            Record::decode(&record, TextEncoding::Utf8).unwrap(),
+            [null()]
+        );
+        assert_eq!(
+            Record::get_field(&record, 0, TextEncoding::Utf8).unwrap(),
+            Some(null())
+        );
+        assert_eq!(
+            Record::get_field(&record, 1, TextEncoding::Utf8).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn two_byte_header_size() {
+        // 200 one-byte integers need a 201-byte header, whose size takes a 2-byte varint
+        let values = (0..200)
+            .map(|n| FieldData::Integer(n % 100 + 2))
+            .collect::<Vec<_>>();
+        let record = encode(&values);
+        assert_eq!(header_size(&record).unwrap(), (202, 2));
+        assert_eq!(&record[..2], [0x81, 0x4a]);
+
+        let (fields, record_size) = parse_header(&record).unwrap();
+        assert_eq!(record_size, record.len());
+        assert_eq!(record.len(), 202 + 200);
+        for (i, field) in fields.iter().enumerate() {
+            assert_eq!((field.index, field.offset, field.size), (i, 202 + i, 1));
+        }
+        assert_eq!(Record::decode(&record, TextEncoding::Utf8).unwrap(), values);
+        assert_eq!(
+            Record::get_field(&record, 199, TextEncoding::Utf8).unwrap(),
+            Some(values[199].clone())
+        );
+    }
+
+    #[test]
+    fn body_must_match_the_header() {
+        let (mut record, _) = every_serial_type();
+        record.push(0);
+        assert!(matches!(
+            Record::decode(&record, TextEncoding::Utf8),
+            Err(SqrliteError::RecordSize(_))
+        ));
+        record.truncate(record.len() - 2);
+        assert!(matches!(
+            Record::decode(&record, TextEncoding::Utf8),
+            Err(SqrliteError::RecordSize(_))
+        ));
+    }
+
+    #[test]
+    fn getter_conversion_matrix() {
+        type Row = (
+            FieldData,
+            Option<i64>,
+            Option<f64>,
+            Option<bool>,
+            Option<&'static str>,
+        );
+        let rows: [Row; 9] = [
+            (null(), None, None, None, None),
+            (
+                FieldData::BooleanFalse(0),
+                Some(0),
+                Some(0.0),
+                Some(false),
+                None,
+            ),
+            (
+                FieldData::BooleanTrue(1),
+                Some(1),
+                Some(1.0),
+                Some(true),
+                None,
+            ),
+            (
+                FieldData::Integer(-7),
+                Some(-7),
+                Some(-7.0),
+                Some(true),
+                None,
+            ),
+            (FieldData::Integer(0), Some(0), Some(0.0), Some(false), None),
+            (
+                FieldData::Real(-2.9),
+                Some(-2),
+                Some(-2.9),
+                Some(true),
+                None,
+            ),
+            (FieldData::Real(0.25), Some(0), Some(0.25), Some(true), None),
+            (text("42"), None, None, None, Some("42")),
+            (FieldData::Blob(b"42".to_vec()), None, None, None, None),
+        ];
+        for (value, i, f, b, s) in rows {
+            assert_eq!(value.as_i64(), i, "{:?}", value);
+            assert_eq!(value.as_f64(), f, "{:?}", value);
+            assert_eq!(value.as_bool(), b, "{:?}", value);
+            assert_eq!(value.as_str(), s, "{:?}", value);
+            assert_eq!(value.is_null(), value == null());
+        }
+        assert_eq!(text("42").as_blob(), Some(&b"42"[..]));
+        assert_eq!(FieldData::Blob(vec![1]).as_blob(), Some(&[1][..]));
+        assert_eq!(FieldData::Integer(1).as_blob(), None);
+        assert_eq!(null().as_blob(), None);
+    }
+
+    #[test]
+    fn real_to_integer_truncates_and_saturates() {
+        let cases = [
+            (1e300, i64::MAX),
+            (-1e300, i64::MIN),
+            (f64::INFINITY, i64::MAX),
+            (f64::NEG_INFINITY, i64::MIN),
+            (9.223372036854776e18, i64::MAX),
+            (-9.223372036854776e18, i64::MIN),
+            (-0.0, 0),
+            (-0.99, 0),
+            (1e15 + 0.5, 1_000_000_000_000_000),
+        ];
+        for (real, expected) in cases {
+            assert_eq!(FieldData::Real(real).as_i64(), Some(expected), "{}", real);
+        }
+        assert_eq!(FieldData::Real(f64::NAN).as_i64(), None);
+    }
+
+    #[test]
+    fn text_is_not_read_as_a_number() {
+        for s in ["1", "-3", "2.5", " 7", "0x10", ""] {
+            let value = text(s);
+            assert_eq!(value.as_i64(), None, "{:?}", s);
+            assert_eq!(value.as_f64(), None, "{:?}", s);
+            assert_eq!(value.as_bool(), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn reals_print_as_the_sqlite3_shell_does() {
+        // expected output from `sqlite3 :memory: "SELECT ..."`
+        let cases = [
+            (1e300, "1.0e+300"),
+            (1e-5, "1.0e-05"),
+            (9.999999999999999e14, "1.0e+15"),
+            (f64::INFINITY, "Inf"),
+            (f64::NEG_INFINITY, "-Inf"),
+            (3.0, "3.0"),
+            (0.1, "0.1"),
+            (-0.0, "0.0"),
+            (123456789012345.6, "123456789012346.0"),
+            (1e15, "1.0e+15"),
+            (1e14, "100000000000000.0"),
+            (0.0001, "0.0001"),
+            (0.00012345, "0.00012345"),
+            (1.0 / 3.0, "0.333333333333333"),
+            (2.5e-300, "2.5e-300"),
+            (-1.5e20, "-1.5e+20"),
+            (100.0, "100.0"),
+            (0.1 + 0.2, "0.3"),
+        ];
+        for (real, expected) in cases {
+            assert_eq!(format_real(real), expected, "{:e}", real);
+            assert_eq!(FieldData::Real(real).to_string(), expected);
+        }
+        assert_eq!(format_real(f64::NAN), "");
+    }
+
+    #[test]
+    fn trailing_fraction_zeros_are_trimmed() {
+        assert_eq!(trim_fraction("1.500"), "1.5");
+        assert_eq!(trim_fraction("2.000"), "2.0");
+        assert_eq!(trim_fraction("-7"), "-7.0");
+        assert_eq!(trim_fraction("0.0001000"), "0.0001");
+    }
+
+    #[test]
+    fn text_takes_numeric_affinities() {
+        use Affinity::{Integer, Numeric, Real};
+        let int = FieldData::Integer;
+        let real = FieldData::Real;
+        // what `sqlite3` stores for the text inserted into INTEGER, NUMERIC and REAL columns
+        let cases = [
+            ("1e5", int(100_000), int(100_000), real(1e5)),
+            ("1.", int(1), int(1), real(1.0)),
+            ("3.0", int(3), int(3), real(3.0)),
+            ("1.5", real(1.5), real(1.5), real(1.5)),
+            (".5", real(0.5), real(0.5), real(0.5)),
+            (" 12 ", int(12), int(12), real(12.0)),
+            ("+7", int(7), int(7), real(7.0)),
+            ("-0", int(0), int(0), real(0.0)),
+            ("00012", int(12), int(12), real(12.0)),
+            ("1e-400", int(0), int(0), real(0.0)),
+            (
+                "1e400",
+                real(f64::INFINITY),
+                real(f64::INFINITY),
+                real(f64::INFINITY),
+            ),
+            (
+                "9223372036854775807",
+                int(i64::MAX),
+                int(i64::MAX),
+                real(9.223372036854776e18),
+            ),
+            (
+                "9223372036854775808",
+                real(9.223372036854776e18),
+                real(9.223372036854776e18),
+                real(9.223372036854776e18),
+            ),
+            (
+                "-9223372036854775809",
+                real(-9.223372036854776e18),
+                real(-9.223372036854776e18),
+                real(-9.223372036854776e18),
+            ),
+        ];
+        for (input, integer, numeric, as_real) in cases {
+            assert_eq!(text(input).apply_affinity(Integer), integer, "{:?}", input);
+            assert_eq!(text(input).apply_affinity(Numeric), numeric, "{:?}", input);
+            assert_eq!(text(input).apply_affinity(Real), as_real, "{:?}", input);
+        }
+
+        // not numbers to SQLite, so left as text whatever the affinity
+        for input in [".", "", "0x10", "12abc", "inf", "NaN", "5e", "e5", "1e5x"] {
+            for affinity in [Integer, Numeric, Real] {
+                assert_eq!(
+                    text(input).apply_affinity(affinity),
+                    text(input),
+                    "{:?}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn numbers_take_affinities() {
+        let int = FieldData::Integer;
+        let real = FieldData::Real;
+        let cases = [
+            // reals holding an integer become one, short of the i64 bounds
+            (real(3.0), Affinity::Integer, int(3)),
+            (real(-0.0), Affinity::Integer, int(0)),
+            (real(3.5), Affinity::Integer, real(3.5)),
+            (real(1e20), Affinity::Numeric, real(1e20)),
+            (
+                real(-9.223372036854776e18),
+                Affinity::Integer,
+                real(-9.223372036854776e18),
+            ),
+            (
+                real(9.223372036854776e18),
+                Affinity::Numeric,
+                real(9.223372036854776e18),
+            ),
+            (int(3), Affinity::Real, real(3.0)),
+            (FieldData::BooleanTrue(1), Affinity::Real, real(1.0)),
+            (int(3), Affinity::Integer, int(3)),
+            (real(3.0), Affinity::Text, text("3.0")),
+            (real(1e20), Affinity::Text, text("1.0e+20")),
+            (int(-4), Affinity::Text, text("-4")),
+            (real(3.0), Affinity::Blob, real(3.0)),
+            (text("7"), Affinity::Blob, text("7")),
+            (text("7"), Affinity::Text, text("7")),
+        ];
+        for (value, affinity, expected) in cases {
+            assert_eq!(
+                value.clone().apply_affinity(affinity),
+                expected,
+                "{:?} {:?}",
+                value,
+                affinity
+            );
+        }
+        for affinity in [
+            Affinity::Integer,
+            Affinity::Text,
+            Affinity::Blob,
+            Affinity::Real,
+            Affinity::Numeric,
+        ] {
+            assert_eq!(null().apply_affinity(affinity), null());
+            let blob = FieldData::Blob(b"12".to_vec());
+            assert_eq!(blob.clone().apply_affinity(affinity), blob);
+        }
+    }
+
+    #[test]
+    fn sql_literals() {
+        let cases = [
+            (null(), "NULL"),
+            (FieldData::BooleanFalse(0), "0"),
+            (FieldData::BooleanTrue(1), "1"),
+            (FieldData::Integer(i64::MIN), "-9223372036854775808"),
+            (FieldData::Real(3.0), "3.0"),
+            (FieldData::Real(-0.1), "-0.1"),
+            (FieldData::Real(f64::INFINITY), "1e999"),
+            (FieldData::Real(f64::NEG_INFINITY), "-1e999"),
+            (FieldData::Real(f64::NAN), "NULL"),
+            (text(""), "''"),
+            (text("it's"), "'it''s'"),
+            (text("''"), "''''''"),
+            // only quotes are escaped in SQL strings
+            (text("a\nb\\"), "'a\nb\\'"),
+            (FieldData::Blob(vec![]), "X''"),
+            (FieldData::Blob(vec![0x00, 0xab, 0x7f]), "X'00AB7F'"),
+        ];
+        for (value, literal) in cases {
+            assert_eq!(value.to_sql_literal(), literal, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn real_literals_read_back_exactly() {
+        for r in [
+            0.1,
+            1.0 / 3.0,
+            1e20,
+            1e-300,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            -2.5e-7,
+        ] {
+            let literal = FieldData::Real(r).to_sql_literal();
+            assert_eq!(
+                numeric_text(&literal),
+                Some(FieldData::Real(r)),
+                "{}",
+                literal
+            );
+        }
+    }
+
+    fn row_layout(sql: &str) -> RowLayout {
+        RowLayout::new(&TableDef::parse(sql).unwrap())
+    }
+
+    #[test]
+    fn rowid_alias_takes_the_rowid() {
+        let layout = row_layout("CREATE TABLE t(a TEXT, id INTEGER PRIMARY KEY, b)");
+        // the alias is stored as NULL, in its place among the other columns
+        let row = layout.row(Some(42), vec![text("x"), null(), FieldData::Integer(7)]);
+        assert_eq!(row.rowid, Some(42));
+        assert_eq!(row.columns(), ["a", "id", "b"]);
+        assert_eq!(
+            row.values(),
+            [text("x"), FieldData::Integer(42), FieldData::Integer(7)]
+        );
+        assert_eq!(row.get("ID"), Some(&FieldData::Integer(42)));
+
+        // without a rowid, as for a row of a WITHOUT ROWID table, the stored NULL is kept
+        assert_eq!(
+            layout.row(None, vec![text("x"), null()]).get("id"),
+            Some(&null())
+        );
+
+        // INT PRIMARY KEY is an ordinary column holding its own value
+        let layout = row_layout("CREATE TABLE t(id INT PRIMARY KEY, b)");
+        let row = layout.row(Some(42), vec![FieldData::Integer(5), null()]);
+        assert_eq!(row.get("id"), Some(&FieldData::Integer(5)));
+    }
+
+    #[test]
+    fn short_records_take_the_defaults() {
+        // as though c, d and e had been added with ALTER TABLE ADD COLUMN after the row was
+        // written
+        let layout = row_layout(
+            "CREATE TABLE t(id INTEGER PRIMARY KEY, a, b REAL, \
+             c TEXT DEFAULT 'none', d INTEGER DEFAULT -5, e)",
+        );
+        let row = layout.row(Some(1), vec![null(), text("a"), FieldData::Integer(3)]);
+        assert_eq!(
+            row.iter().collect::<Vec<_>>(),
+            [
+                ("id", &FieldData::Integer(1)),
+                ("a", &text("a")),
+                ("b", &FieldData::Real(3.0)),
+                ("c", &text("none")),
+                ("d", &FieldData::Integer(-5)),
+                ("e", &null()),
+            ]
+        );
+
+        // a stored NULL is not replaced by the default
+        let row = layout.row(Some(2), vec![null(), null(), null(), null()]);
+        assert_eq!(row.get("c"), Some(&null()));
+        assert_eq!(row.get("d"), Some(&FieldData::Integer(-5)));
+    }
+
+    #[test]
+    fn virtual_columns_take_no_slot() {
+        let layout = row_layout("CREATE TABLE t(a, v AS (a * 2), b)");
+        let row = layout.row(Some(1), vec![FieldData::Integer(1), FieldData::Integer(2)]);
+        assert_eq!(
+            row.into_values(),
+            [FieldData::Integer(1), null(), FieldData::Integer(2)]
+        );
+    }
+
+    fn value() -> impl Strategy<Value = FieldData> {
+        prop_oneof![
+            Just(null()),
+            any::<i64>().prop_map(FieldData::Integer),
+            (-2i64..=2).prop_map(FieldData::Integer),
+            Just(FieldData::BooleanFalse(0)),
+            Just(FieldData::BooleanTrue(1)),
+            any::<f64>()
+                .prop_filter("NaN is stored as NULL", |r| !r.is_nan())
+                .prop_map(FieldData::Real),
+            any::<String>().prop_map(FieldData::Text),
+            prop::collection::vec(any::<u8>(), 0..100).prop_map(FieldData::Blob),
+        ]
+    }
+
+    // The value as decoding gives it back: 0 and 1 come back as integers unless they were
+    // stored as the constants of serial types 8 and 9
+    fn decoded(value: &FieldData, constants: bool) -> FieldData {
+        match (value.as_i64(), value) {
+            (Some(0), FieldData::Integer(_) | FieldData::BooleanFalse(_)) if constants => {
+                FieldData::BooleanFalse(0)
+            }
+            (Some(1), FieldData::Integer(_) | FieldData::BooleanTrue(_)) if constants => {
+                FieldData::BooleanTrue(1)
+            }
+            (
+                Some(n),
+                FieldData::Integer(_) | FieldData::BooleanFalse(_) | FieldData::BooleanTrue(_),
+            ) => FieldData::Integer(n),
+            _ => value.clone(),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn decode_inverts_encode(
+            values in prop::collection::vec(value(), 0..200),
+            encoding in prop::sample::select(&[TextEncoding::Utf8, TextEncoding::Utf16Le, TextEncoding::Utf16Be][..]),
+            constants: bool,
+        ) {
+            let record = encode_with(&values, encoding, constants);
+            let expected = values.iter().map(|v| decoded(v, constants)).collect::<Vec<_>>();
+            prop_assert_eq!(Record::decode(&record, encoding).unwrap(), expected);
+        }
+
+        // Integers take the smallest serial type that holds them
+        #[test]
+        fn integers_take_the_smallest_serial_type(n: i64) {
+            let record = encode(&[FieldData::Integer(n)]);
+            let size = [1, 2, 3, 4, 6, 8]
+                .into_iter()
+                .find(|&size| {
+                    let half = 1i128 << (8 * size - 1);
+                    (-half..half).contains(&i128::from(n))
+                })
+                .unwrap();
+            prop_assert_eq!(record.len(), 2 + size);
+        }
     }
 }
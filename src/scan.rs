@@ -0,0 +1,602 @@
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+use crate::btree_page::{BtreePage, PageType};
+use crate::cell::{CellContent, CellRef, Payload};
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::record::{self, FieldData, NamedRecord, Record, RowLayout};
+use crate::schema::TableDef;
+use crate::varint::{decode_be, decode_be_i64};
+
+/// A table b-tree holds a page or cell that a table scan cannot read, such as an index page
+/// or a cursor asked for a row when it is not on one
+#[derive(Debug)]
+pub struct ScanError {
+    details: String,
+}
+
+impl ScanError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for ScanError {}
+
+/// Iterates over every row of a table b-tree in rowid order, yielding each rowid with its
+/// record. Interior pages are descended depth-first using an explicit stack of pages still
+/// to visit, so the depth of the tree never grows the call stack. A cell that fails to parse
+/// is yielded as an error and the scan moves on to the next one.
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// use sqrlite::{Database, TableScan};
+///
+/// let db = Database::new("tests/fixtures/scan.db")?;
+/// let table = db.table("readings")?;
+/// let mut last = 0;
+/// for row in TableScan::new(&db, table.rootpage).rows() {
+///     let (rowid, values) = row?;
+///     assert!(rowid > last);
+///     assert_eq!(values.len(), 4);
+///     last = rowid;
+/// }
+/// assert_eq!(last, 5000);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TableScan<'a> {
+    db: &'a Database,
+    pending: Vec<u32>, // pages left to visit, the next one on top
+    page: BtreePage,   // the leaf page currently being read
+    buffer: Vec<u8>,   // contents of `page`, reused for every page visited
+    next_cell: usize,
+}
+
+impl<'a> TableScan<'a> {
+    /// A scan of the table b-tree rooted at `root_page`, starting before its first row
+    pub fn new(db: &'a Database, root_page: u32) -> Self {
+        Self {
+            db,
+            pending: vec![root_page],
+            page: BtreePage::default(),
+            buffer: vec![],
+            next_cell: 0,
+        }
+    }
+
+    /// The rows left in the scan, each rowid with its decoded values
+    pub fn rows(
+        mut self,
+    ) -> impl Iterator<Item = Result<(i64, Vec<FieldData>), SqrliteError>> + 'a {
+        iter::from_fn(move || self.next_with(leaf_values))
+    }
+
+    /// The rows left in the scan as rows of `table`, with every column in schema order
+    pub fn named_rows(
+        self,
+        table: &TableDef,
+    ) -> impl Iterator<Item = Result<NamedRecord, SqrliteError>> + 'a {
+        let layout = RowLayout::new(table);
+        self.rows().map(move |row| {
+            let (rowid, values) = row?;
+            Ok(layout.row(Some(rowid), values))
+        })
+    }
+
+    /// Read a page into the buffer. Children of interior pages are queued in key order,
+    /// followed by the rightmost child; only leaf pages remain current.
+    fn load_page(&mut self, page_num: u32) -> Result<(), SqrliteError> {
+        self.page = read_page(self.db, page_num, &mut self.buffer)?;
+        self.next_cell = 0;
+
+        if let PageType::InteriorTable = self.page.page_type {
+            let mut children = vec![];
+            for i in 0..self.page.cell_pointers.len() {
+                children.push(
+                    CellContent::get_cell_data(&self.page, &self.buffer, i)?
+                        .get_left_child_pointer()?,
+                );
+            }
+            self.pending.extend(self.page.rightmost_ptr);
+            self.pending.extend(children.into_iter().rev());
+            // nothing on an interior page is yielded
+            self.next_cell = self.page.cell_pointers.len();
+        }
+        Ok(())
+    }
+}
+
+impl TableScan<'_> {
+    /// Read the next row with `read`, which gets the row's cell still borrowed from its page
+    pub fn next_with<T, F>(&mut self, mut read: F) -> Option<Result<T, SqrliteError>>
+    where
+        F: FnMut(&Database, CellRef) -> Result<T, SqrliteError>,
+    {
+        loop {
+            if self.next_cell < self.page.cell_pointers.len() {
+                let index = self.next_cell;
+                self.next_cell += 1;
+                return Some(
+                    CellRef::get(&self.page, &self.buffer, index)
+                        .and_then(|cell| read(self.db, cell)),
+                );
+            }
+
+            let page_num = self.pending.pop()?;
+            if let Err(e) = self.load_page(page_num) {
+                // skip whatever part of the page was read
+                self.page = BtreePage::default();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl Iterator for TableScan<'_> {
+    type Item = Result<(i64, Record), SqrliteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(leaf_row)
+    }
+}
+
+/// Where [`TableCursor::seek_rowid`] left the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekResult {
+    /// positioned on the row with the requested rowid
+    Exact,
+    /// no such row; positioned on the first row after it
+    After,
+    /// every row comes before the requested rowid
+    PastEnd,
+}
+
+/// One page on the path from the root to the cursor position. On interior pages `index`
+/// is the child being visited, with the rightmost child at `cell_pointers.len()`.
+struct Frame {
+    page: BtreePage,
+    buffer: Vec<u8>,
+    index: usize,
+}
+
+/// Counters of the work done by a cursor, for checking that seeks stay logarithmic
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStats {
+    /// pages read from the database, cached or not
+    pub pages_read: usize,
+    /// cell keys compared while binary-searching pages
+    pub key_probes: usize,
+}
+
+/// A position within a table b-tree that can seek to a rowid and step to neighbouring rows
+/// in either direction. It keeps the path from the root to the current leaf cell, so moving
+/// to an adjacent leaf only revisits the pages between them.
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// use sqrlite::{Database, SeekResult, TableCursor};
+///
+/// let db = Database::new("tests/fixtures/scan.db")?;
+/// let mut cursor = TableCursor::new(&db, db.table("readings")?.rootpage);
+/// assert_eq!(cursor.seek_rowid(4999)?, SeekResult::Exact);
+/// assert!(cursor.next()?);
+/// assert_eq!(cursor.rowid()?, 5000);
+/// assert!(!cursor.next()?);
+/// assert_eq!(cursor.seek_rowid(6000)?, SeekResult::PastEnd);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TableCursor<'a> {
+    db: &'a Database,
+    root_page: u32,
+    path: Vec<Frame>, // empty when the cursor is not on a row
+    stats: CursorStats,
+}
+
+impl<'a> TableCursor<'a> {
+    /// A cursor on the table b-tree rooted at `root_page`, not yet on any row
+    pub fn new(db: &'a Database, root_page: u32) -> Self {
+        Self {
+            db,
+            root_page,
+            path: vec![],
+            stats: CursorStats::default(),
+        }
+    }
+
+    /// The work done since the cursor was made or its counters were reset
+    pub fn stats(&self) -> CursorStats {
+        self.stats
+    }
+
+    /// Set the counters back to zero
+    pub fn reset_stats(&mut self) {
+        self.stats = CursorStats::default();
+    }
+
+    /// Whether the cursor is positioned on a row
+    pub fn is_valid(&self) -> bool {
+        !self.path.is_empty()
+    }
+
+    /// Move to the first row, returning false if the table is empty
+    pub fn first(&mut self) -> Result<bool, SqrliteError> {
+        self.path.clear();
+        self.descend(self.root_page, false)
+    }
+
+    /// Move to the last row, returning false if the table is empty
+    pub fn last(&mut self) -> Result<bool, SqrliteError> {
+        self.path.clear();
+        self.descend(self.root_page, true)
+    }
+
+    /// Move to the row with the given rowid, or else the first row after it. Interior pages
+    /// are descended by their divider keys, so only one page per level is read.
+    pub fn seek_rowid(&mut self, rowid: i64) -> Result<SeekResult, SqrliteError> {
+        self.path.clear();
+        let mut page_num = self.root_page;
+        loop {
+            let mut buffer = vec![];
+            let page = read_page(self.db, page_num, &mut buffer)?;
+            self.stats.pages_read += 1;
+            let num_cells = page.cell_pointers.len();
+            let mut frame = Frame {
+                page,
+                buffer,
+                index: num_cells,
+            };
+
+            // Binary-search for the first cell whose key is >= rowid. Cell pointers are kept
+            // in key order, and every key in a child is <= the divider key of its cell.
+            let (mut low, mut high) = (0, num_cells);
+            while low < high {
+                let mid = (low + high) / 2;
+                self.stats.key_probes += 1;
+                if frame.key(mid)? < rowid {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            frame.index = low;
+
+            match frame.page.page_type {
+                PageType::InteriorTable => {
+                    page_num = frame.child(frame.index)?;
+                    self.path.push(frame);
+                }
+                _ => {
+                    let found = match frame.index {
+                        idx if idx == num_cells => None,
+                        idx => Some(frame.key(idx)?),
+                    };
+                    self.path.push(frame);
+                    let Some(found) = found else {
+                        // the following row, if any, begins the next leaf
+                        return Ok(if self.next_leaf(false)? {
+                            SeekResult::After
+                        } else {
+                            SeekResult::PastEnd
+                        });
+                    };
+                    return Ok(if found == rowid {
+                        SeekResult::Exact
+                    } else {
+                        SeekResult::After
+                    });
+                }
+            }
+        }
+    }
+
+    /// Step to the next row, returning false (and leaving the cursor unpositioned) after the
+    /// last one. Not an `Iterator`, since a cursor moves in both directions.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<bool, SqrliteError> {
+        let Some(leaf) = self.path.last_mut() else {
+            return Ok(false);
+        };
+        if leaf.index + 1 < leaf.page.cell_pointers.len() {
+            leaf.index += 1;
+            return Ok(true);
+        }
+        self.next_leaf(false)
+    }
+
+    /// Step to the previous row, returning false (and leaving the cursor unpositioned) before
+    /// the first one
+    pub fn prev(&mut self) -> Result<bool, SqrliteError> {
+        let Some(leaf) = self.path.last_mut() else {
+            return Ok(false);
+        };
+        if leaf.index > 0 {
+            leaf.index -= 1;
+            return Ok(true);
+        }
+        self.next_leaf(true)
+    }
+
+    /// The rowid of the current row, without reading the rest of its record
+    pub fn rowid(&mut self) -> Result<i64, SqrliteError> {
+        self.read_current(leaf_rowid)
+    }
+
+    /// The rowid and record of the current row
+    pub fn current(&mut self) -> Result<(i64, Record), SqrliteError> {
+        self.read_current(leaf_row)
+    }
+
+    /// The payload of the current row as stored in its cell, with any overflow pages unread
+    pub fn payload(&mut self) -> Result<Payload, SqrliteError> {
+        self.read_current(|_, cell| match cell {
+            CellRef::LeafTable { payload, .. } => Ok(payload.to_payload()),
+            _ => Err(ScanError::new("expected a table leaf cell").into()),
+        })
+    }
+
+    /// Read the current row with `read`, which gets the row's cell still borrowed from its page
+    pub fn read_current<T, F>(&mut self, read: F) -> Result<T, SqrliteError>
+    where
+        F: FnOnce(&Database, CellRef) -> Result<T, SqrliteError>,
+    {
+        let leaf = self
+            .path
+            .last()
+            .ok_or_else(|| ScanError::new("cursor is not positioned on a row"))?;
+        read(self.db, CellRef::get(&leaf.page, &leaf.buffer, leaf.index)?)
+    }
+
+    /// Leave the current leaf for the first row of the following leaf, or the last row of
+    /// the preceding one when `backward`: climb until an ancestor has a sibling subtree in
+    /// that direction, then descend to its nearest edge
+    fn next_leaf(&mut self, backward: bool) -> Result<bool, SqrliteError> {
+        self.path.pop();
+        while let Some(frame) = self.path.last_mut() {
+            let has_sibling = if backward {
+                frame.index > 0
+            } else {
+                frame.index < frame.page.cell_pointers.len()
+            };
+            if has_sibling {
+                if backward {
+                    frame.index -= 1;
+                } else {
+                    frame.index += 1;
+                }
+                let child = frame.child(frame.index)?;
+                return self.descend(child, backward);
+            }
+            self.path.pop();
+        }
+        Ok(false)
+    }
+
+    /// Descend from a page to the first row of its subtree, or the last one when
+    /// `rightmost`, pushing each page visited onto the path
+    fn descend(&mut self, page_num: u32, rightmost: bool) -> Result<bool, SqrliteError> {
+        let mut page_num = page_num;
+        loop {
+            let mut buffer = vec![];
+            let page = read_page(self.db, page_num, &mut buffer)?;
+            self.stats.pages_read += 1;
+            let num_cells = page.cell_pointers.len();
+            let interior = matches!(page.page_type, PageType::InteriorTable);
+            let index = match (rightmost, interior) {
+                (false, _) => 0,
+                (true, true) => num_cells,
+                (true, false) => num_cells.saturating_sub(1),
+            };
+            let frame = Frame {
+                page,
+                buffer,
+                index,
+            };
+
+            if interior {
+                page_num = frame.child(index)?;
+                self.path.push(frame);
+            } else if num_cells == 0 && self.path.is_empty() {
+                // the root of an empty table
+                return Ok(false);
+            } else if num_cells == 0 {
+                // a leaf emptied by deletions holds no rows; carry on past it
+                self.path.push(frame);
+                return self.next_leaf(rightmost);
+            } else {
+                self.path.push(frame);
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl Frame {
+    /// Page number of the child at the given position of an interior page
+    fn child(&self, index: usize) -> Result<u32, SqrliteError> {
+        if index == self.page.cell_pointers.len() {
+            return self.page.rightmost_ptr.ok_or_else(|| {
+                ScanError::new(&format!(
+                    "interior page {} has no rightmost pointer",
+                    self.page.page_num
+                ))
+                .into()
+            });
+        }
+        Ok(
+            CellContent::get_cell_data(&self.page, &self.buffer, index)?
+                .get_left_child_pointer()?,
+        )
+    }
+
+    /// The rowid of a leaf cell or the divider key of an interior cell, decoded straight from
+    /// the cell's leading varints without parsing its payload
+    fn key(&self, index: usize) -> Result<i64, SqrliteError> {
+        let offset = *self.page.cell_pointers.get(index).ok_or_else(|| {
+            ScanError::new(&format!(
+                "page {} has no cell {}",
+                self.page.page_num, index
+            ))
+        })? as usize;
+        let cell = self.buffer.get(offset..).unwrap_or_default();
+        let key_start = match self.page.page_type {
+            // skip the payload size
+            PageType::LeafTable => decode_be(cell)?.1,
+            // skip the left child pointer
+            _ => 4,
+        };
+        let bytes = cell.get(key_start..).unwrap_or_default();
+        if bytes.is_empty() {
+            return Err(ScanError::new(&format!(
+                "cell {} of page {} is truncated",
+                index, self.page.page_num
+            ))
+            .into());
+        }
+        Ok(decode_be_i64(&bytes[..bytes.len().min(9)])?.0)
+    }
+}
+
+/// Iterates over every row of a table b-tree in descending rowid order. It starts on the
+/// last row, found by following rightmost pointers, and steps backwards through cells and
+/// leaves using the cursor's path, so taking the first few rows reads only a few pages.
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// use sqrlite::{Database, ReverseTableScan};
+///
+/// let db = Database::new("tests/fixtures/scan.db")?;
+/// let rowids = ReverseTableScan::new(&db, db.table("readings")?.rootpage)
+///     .rows()
+///     .take(3)
+///     .map(|row| row.map(|(rowid, _)| rowid))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(rowids, [5000, 4999, 4998]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReverseTableScan<'a> {
+    cursor: TableCursor<'a>,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> ReverseTableScan<'a> {
+    /// A scan of the table b-tree rooted at `root_page`, starting after its last row
+    pub fn new(db: &'a Database, root_page: u32) -> Self {
+        Self {
+            cursor: TableCursor::new(db, root_page),
+            started: false,
+            done: false,
+        }
+    }
+
+    /// The rows left in the scan, each rowid with its decoded values
+    pub fn rows(
+        mut self,
+    ) -> impl Iterator<Item = Result<(i64, Vec<FieldData>), SqrliteError>> + 'a {
+        iter::from_fn(move || self.next_with(leaf_values))
+    }
+}
+
+impl ReverseTableScan<'_> {
+    /// Read the next row with `read`, which gets the row's cell still borrowed from its page
+    pub fn next_with<T, F>(&mut self, mut read: F) -> Option<Result<T, SqrliteError>>
+    where
+        F: FnMut(&Database, CellRef) -> Result<T, SqrliteError>,
+    {
+        if self.done {
+            return None;
+        }
+        let moved = if self.started {
+            self.cursor.prev()
+        } else {
+            self.started = true;
+            self.cursor.last()
+        };
+        match moved {
+            Ok(true) => Some(self.cursor.read_current(&mut read)),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // the cursor's position is unknown after a failed step
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Iterator for ReverseTableScan<'_> {
+    type Item = Result<(i64, Record), SqrliteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(leaf_row)
+    }
+}
+
+/// Read a whole table b-tree page into `buffer` and parse its header
+fn read_page(
+    db: &Database,
+    page_num: u32,
+    buffer: &mut Vec<u8>,
+) -> Result<BtreePage, SqrliteError> {
+    let mut page = BtreePage::default();
+    page.read_page(db, page_num, buffer)?;
+    if !matches!(
+        page.page_type,
+        PageType::InteriorTable | PageType::LeafTable
+    ) {
+        return Err(
+            ScanError::new(&format!("page {} is not a table b-tree page", page_num)).into(),
+        );
+    }
+    Ok(page)
+}
+
+/// The rowid and record of a table leaf cell, reading the overflow pages of a long record
+pub fn leaf_row(db: &Database, cell: CellRef) -> Result<(i64, Record), SqrliteError> {
+    match cell {
+        CellRef::LeafTable { row_id, payload } => {
+            let record =
+                Record::from_payload(payload.to_payload().reassemble(db)?, db.text_encoding)
+                    .map_err(|err| record::at_row(err, row_id))?;
+            Ok((row_id, record))
+        }
+        _ => Err(ScanError::new("expected a table leaf cell").into()),
+    }
+}
+
+/// The rowid and values of a table leaf cell, decoded without copying the record out of the
+/// page unless it spills onto overflow pages
+pub fn leaf_values(db: &Database, cell: CellRef) -> Result<(i64, Vec<FieldData>), SqrliteError> {
+    match cell {
+        CellRef::LeafTable { row_id, payload } => {
+            let values = Record::decode(&payload.read(db)?, db.text_encoding)
+                .map_err(|err| record::at_row(err, row_id))?;
+            Ok((row_id, values))
+        }
+        _ => Err(ScanError::new("expected a table leaf cell").into()),
+    }
+}
+
+/// The rowid of a table leaf cell, without reading its record
+pub fn leaf_rowid(_db: &Database, cell: CellRef) -> Result<i64, SqrliteError> {
+    match cell {
+        CellRef::LeafTable { row_id, .. } => Ok(row_id),
+        _ => Err(ScanError::new("expected a table leaf cell").into()),
+    }
+}
@@ -0,0 +1,1338 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::iter;
+use std::sync::Arc;
+
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::expr::literal_token_value;
+use crate::record::FieldData;
+use crate::scan::TableScan;
+use crate::sql::{
+    identifiers_match, normalize_identifier, split_conjuncts, tokenize, SqlParseError, TokenKind,
+    TokenStream,
+};
+
+const SCHEMA_ROOT_PAGE: u32 = 1;
+const INTERNAL_PREFIX: &str = "sqlite_";
+const AUTOINDEX_PREFIX: &str = "sqlite_autoindex_";
+
+// Keywords that begin a column constraint and therefore end a column's type name
+const COLUMN_CONSTRAINT_KEYWORDS: [&str; 11] = [
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "CHECK",
+    "DEFAULT",
+    "COLLATE",
+    "REFERENCES",
+    "GENERATED",
+    "AS",
+];
+
+/// The schema table or an object's SQL could not be understood
+#[derive(Debug)]
+pub struct SchemaError {
+    details: String,
+}
+
+impl SchemaError {
+    fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for SchemaError {}
+
+/// Kind of object recorded in sqlite_schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ObjectType {
+    /// An ordinary or virtual table
+    Table,
+    /// An index, explicit or created automatically for a constraint
+    Index,
+    /// A view, which has no b-tree
+    View,
+    /// A trigger, which has no b-tree
+    Trigger,
+}
+
+impl ObjectType {
+    fn from_name(name: &str) -> Result<Self, SchemaError> {
+        match name {
+            "table" => Ok(Self::Table),
+            "index" => Ok(Self::Index),
+            "view" => Ok(Self::View),
+            "trigger" => Ok(Self::Trigger),
+            _ => Err(SchemaError::new(&format!(
+                "unknown schema object type `{}`",
+                name
+            ))),
+        }
+    }
+
+    /// As written in the type column of sqlite_schema
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Table => "table",
+            Self::Index => "index",
+            Self::View => "view",
+            Self::Trigger => "trigger",
+        }
+    }
+}
+
+/// One row of the sqlite_schema table
+#[derive(Debug, Clone)]
+pub struct SchemaObject {
+    /// Type of the object
+    pub object_type: ObjectType,
+    /// Name of the object
+    pub name: String,
+    /// Table the object belongs to; for tables and views, their own name
+    pub tbl_name: String,
+    /// Root page of the object's b-tree, 0 for views, triggers and virtual tables
+    pub rootpage: u32,
+    /// The CREATE statement, or None for automatic indexes
+    pub sql: Option<String>,
+}
+
+/// Every object in sqlite_schema, looked up by case-insensitive name
+#[derive(Debug, Default)]
+pub struct Schema {
+    /// Objects in the order they appear in sqlite_schema
+    pub objects: Vec<SchemaObject>,
+    by_name: HashMap<String, usize>, // ASCII-lowercased name -> index into `objects`
+}
+
+impl Schema {
+    /// Read sqlite_schema from page 1 of the database
+    pub fn read(db: &Database) -> Result<Self, SqrliteError> {
+        let mut objects = vec![];
+        for row in TableScan::new(db, SCHEMA_ROOT_PAGE).rows() {
+            let (_, values) = row?;
+            objects.push(SchemaObject::from_values(&values)?);
+        }
+        Ok(Self::from_objects(objects))
+    }
+
+    /// Index a list of objects by name; the first object wins when two names collide
+    pub fn from_objects(objects: Vec<SchemaObject>) -> Self {
+        let mut by_name = HashMap::with_capacity(objects.len());
+        for (idx, obj) in objects.iter().enumerate() {
+            by_name
+                .entry(normalize_identifier(&obj.name))
+                .or_insert(idx);
+        }
+        Self { objects, by_name }
+    }
+
+    /// Find an object by name, ignoring ASCII case
+    pub fn find(&self, name: &str) -> Option<&SchemaObject> {
+        self.by_name
+            .get(&normalize_identifier(name))
+            .map(|&idx| &self.objects[idx])
+    }
+
+    /// All objects of one type, in schema order
+    pub fn objects_of_type(&self, object_type: ObjectType) -> impl Iterator<Item = &SchemaObject> {
+        self.objects
+            .iter()
+            .filter(move |obj| obj.object_type == object_type)
+    }
+
+    /// Number of objects of one type
+    pub fn count(&self, object_type: ObjectType) -> u32 {
+        self.objects_of_type(object_type).count() as u32
+    }
+
+    /// Name, type and root page of every b-tree in the file: sqlite_schema's own, then those
+    /// of the tables and indexes in schema order. Views, triggers and virtual tables have none.
+    pub fn btrees(&self) -> impl Iterator<Item = (&str, ObjectType, u32)> {
+        let objects = self
+            .objects
+            .iter()
+            .filter(|obj| obj.rootpage != 0)
+            .map(|obj| (obj.name.as_str(), obj.object_type, obj.rootpage));
+        iter::once(("sqlite_schema", ObjectType::Table, SCHEMA_ROOT_PAGE)).chain(objects)
+    }
+
+    /// All indexes declared on the given table, in schema order
+    pub fn indexes_on<'a>(&'a self, table: &'a str) -> impl Iterator<Item = &'a SchemaObject> {
+        self.objects_of_type(ObjectType::Index)
+            .filter(move |obj| identifiers_match(&obj.tbl_name, table))
+    }
+
+    /// Resolve the target of a query to a table with a b-tree. Views have rootpage 0 and
+    /// cannot be read until view expansion is supported, so they get their own error.
+    pub fn find_table(&self, name: &str) -> Result<&SchemaObject, SchemaError> {
+        match self.find(name) {
+            Some(obj) if obj.object_type == ObjectType::Table => Ok(obj),
+            Some(obj) if obj.object_type == ObjectType::View => Err(SchemaError::new(&format!(
+                "`{}` is a view; views are not yet queryable",
+                obj.name
+            ))),
+            _ => Err(SchemaError::new(&format!("no such table: {}", name))),
+        }
+    }
+
+    /// Parse the CREATE TABLE statement of the named table
+    pub fn table_def(&self, name: &str) -> Result<TableDef, SqrliteError> {
+        let obj = self.find_table(name)?;
+        match &obj.sql {
+            Some(sql) => Ok(TableDef::parse(sql)?),
+            None => Err(SchemaError::new(&format!("table `{}` has no SQL", name)).into()),
+        }
+    }
+
+    /// Build the definition of an index, synthesizing it from the table's constraints for
+    /// automatic indexes, whose `sql` column is NULL
+    pub fn index_def(&self, name: &str) -> Result<IndexDef, SqrliteError> {
+        let obj = match self.find(name) {
+            Some(obj) if obj.object_type == ObjectType::Index => obj,
+            _ => return Err(SchemaError::new(&format!("no such index: {}", name)).into()),
+        };
+        match &obj.sql {
+            Some(sql) => Ok(IndexDef::parse(sql)?),
+            None => {
+                let table = self.table_def(&obj.tbl_name)?;
+                Ok(IndexDef::from_autoindex(&obj.name, &table)?)
+            }
+        }
+    }
+}
+
+/// Holds the most recently read schema together with the file change counter it was read at,
+/// so a database rewritten by another process is never served stale rootpages
+#[derive(Debug, Default)]
+pub struct SchemaCache {
+    entry: Option<(u32, Arc<Schema>)>,
+}
+
+impl SchemaCache {
+    /// The cached schema, if it was read at this change counter
+    pub fn get(&self, change_counter: u32) -> Option<Arc<Schema>> {
+        match &self.entry {
+            Some((counter, schema)) if *counter == change_counter => Some(Arc::clone(schema)),
+            _ => None,
+        }
+    }
+
+    /// Remember a schema as read at the given change counter
+    pub fn store(&mut self, change_counter: u32, schema: Arc<Schema>) {
+        self.entry = Some((change_counter, schema));
+    }
+
+    /// Drop the cached schema so the next read goes to the file
+    pub fn invalidate(&mut self) {
+        self.entry = None;
+    }
+}
+
+impl SchemaObject {
+    /// Objects named `sqlite_*` are reserved for SQLite's own bookkeeping
+    pub fn is_internal(&self) -> bool {
+        self.name
+            .get(..INTERNAL_PREFIX.len())
+            .is_some_and(|prefix| identifiers_match(prefix, INTERNAL_PREFIX))
+    }
+
+    fn from_values(values: &[FieldData]) -> Result<Self, SchemaError> {
+        let text = |idx: usize| match values.get(idx) {
+            Some(FieldData::Text(s)) => Ok(s.clone()),
+            _ => Err(SchemaError::new(&format!(
+                "sqlite_schema column {} is not text",
+                idx
+            ))),
+        };
+        let rootpage = match values.get(3) {
+            Some(FieldData::Integer(n)) => u32::try_from(*n).map_err(|_| {
+                SchemaError::new(&format!(
+                    "sqlite_schema rootpage {} is not a page number",
+                    n
+                ))
+            })?,
+            Some(FieldData::Null(_)) | Some(FieldData::BooleanFalse(_)) => 0,
+            Some(FieldData::BooleanTrue(_)) => 1,
+            _ => return Err(SchemaError::new("sqlite_schema rootpage is not an integer")),
+        };
+        let sql = match values.get(4) {
+            Some(FieldData::Text(s)) => Some(s.clone()),
+            _ => None,
+        };
+
+        Ok(Self {
+            object_type: ObjectType::from_name(&text(0)?)?,
+            name: text(1)?,
+            tbl_name: text(2)?,
+            rootpage,
+            sql,
+        })
+    }
+}
+
+/// What an index column is keyed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexKey {
+    /// A column of the table, by name
+    Column(String),
+    /// expression indexes are captured as text and not yet usable
+    Expression(String),
+}
+
+/// One key column of an index or of a PRIMARY KEY/UNIQUE constraint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexColumn {
+    /// Column or expression the entries are keyed on
+    pub key: IndexKey,
+    /// Declared DESC
+    pub descending: bool,
+    /// Explicit COLLATE clause, overriding the column's collation
+    pub collation: Option<String>,
+}
+
+/// An index as declared by CREATE INDEX or implied by a constraint
+#[derive(Debug, Clone)]
+pub struct IndexDef {
+    /// Name of the index
+    pub name: String,
+    /// Table the index is on
+    pub table: String,
+    /// Key columns in order, not counting the trailing rowid
+    pub columns: Vec<IndexColumn>,
+    /// Declared UNIQUE
+    pub unique: bool,
+    /// predicate text of a partial index
+    pub where_clause: Option<String>,
+}
+
+impl IndexDef {
+    /// Parse a CREATE INDEX statement
+    pub fn parse(sql: &str) -> Result<Self, SqlParseError> {
+        let mut tokens = TokenStream::new(sql)?;
+        tokens.expect_keyword("CREATE")?;
+        let unique = tokens.eat_keyword("UNIQUE");
+        tokens.expect_keyword("INDEX")?;
+        tokens.eat_keywords(&["IF", "NOT", "EXISTS"]);
+        let name = tokens.qualified_name()?;
+        tokens.expect_keyword("ON")?;
+        let table = tokens.identifier()?;
+        let columns = parse_indexed_columns(&mut tokens)?;
+
+        let where_clause = if tokens.eat_keyword("WHERE") {
+            Some(tokens.capture_until(|_| false)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            name,
+            table,
+            columns,
+            unique,
+            where_clause,
+        })
+    }
+
+    /// Reconstruct an `sqlite_autoindex_<table>_<N>` index from the Nth PRIMARY KEY or UNIQUE
+    /// constraint of the table that actually required a separate index
+    pub fn from_autoindex(name: &str, table: &TableDef) -> Result<Self, SchemaError> {
+        let number = name
+            .strip_prefix(AUTOINDEX_PREFIX)
+            .and_then(|rest| rest.rsplit_once('_'))
+            .and_then(|(_, n)| n.parse::<usize>().ok())
+            .ok_or_else(|| SchemaError::new(&format!("`{}` is not an automatic index", name)))?;
+
+        let key = table
+            .indexed_keys()
+            .into_iter()
+            .nth(number.wrapping_sub(1))
+            .ok_or_else(|| {
+                SchemaError::new(&format!(
+                    "table `{}` has no constraint matching automatic index `{}`",
+                    table.name, name
+                ))
+            })?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            table: table.name.clone(),
+            columns: key.columns.clone(),
+            unique: true,
+            where_clause: None,
+        })
+    }
+
+    /// Whether every key is a plain column, i.e. the index can be used for lookups
+    pub fn is_usable(&self) -> bool {
+        self.columns
+            .iter()
+            .all(|col| matches!(col.key, IndexKey::Column(_)))
+    }
+
+    /// Whether the index covers every row a query with the given WHERE clause can return. A
+    /// partial index only qualifies when each term of its predicate is also a term of the
+    /// query's WHERE clause, or is a `col IS NOT NULL` implied by a comparison on `col`.
+    pub fn usable_for(&self, query_where: Option<&str>) -> bool {
+        let Some(predicate) = &self.where_clause else {
+            return true;
+        };
+        let (Ok(required), Ok(available)) = (
+            split_conjuncts(predicate),
+            split_conjuncts(query_where.unwrap_or_default()),
+        ) else {
+            return false;
+        };
+        required
+            .iter()
+            .all(|term| available.iter().any(|have| implies(have, term)))
+    }
+}
+
+/// Which kind of key a constraint declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    /// PRIMARY KEY
+    PrimaryKey,
+    /// UNIQUE
+    Unique,
+}
+
+/// A PRIMARY KEY or UNIQUE constraint, declared either on a column or on the table
+#[derive(Debug, Clone)]
+pub struct KeyConstraint {
+    /// PRIMARY KEY or UNIQUE
+    pub kind: KeyKind,
+    /// Columns of the key, in declaration order
+    pub columns: Vec<IndexColumn>,
+    /// declared as a column constraint rather than a table constraint
+    pub column_level: bool,
+}
+
+/// One column of a CREATE TABLE statement
+#[derive(Debug, Clone)]
+pub struct ColumnDef {
+    /// Name of the column
+    pub name: String,
+    /// Declared type as written, None when omitted
+    pub decl_type: Option<String>,
+    /// Collation from a COLLATE clause, None for the default BINARY
+    pub collation: Option<String>,
+    /// Set for GENERATED ALWAYS AS columns
+    pub generated: Option<GeneratedColumn>,
+    /// The DEFAULT clause, if any
+    pub default: Option<DefaultValue>,
+    /// Declared NOT NULL
+    pub not_null: bool,
+}
+
+impl ColumnDef {
+    /// Affinity implied by the declared type
+    pub fn affinity(&self) -> Affinity {
+        Affinity::from_decl_type(self.decl_type.as_deref())
+    }
+
+    /// Whether a STRICT table may store the value in this column, given its declared type
+    pub fn strict_accepts(&self, value: &FieldData) -> bool {
+        let decl_type = self.decl_type.as_deref().unwrap_or_default();
+        match value {
+            FieldData::Null(_) => true,
+            _ if decl_type.eq_ignore_ascii_case("ANY") => true,
+            FieldData::BooleanFalse(_) | FieldData::BooleanTrue(_) | FieldData::Integer(_) => {
+                // REAL values with no fractional part may be stored as integers
+                ["INT", "INTEGER", "REAL"]
+                    .iter()
+                    .any(|t| decl_type.eq_ignore_ascii_case(t))
+            }
+            FieldData::Real(_) => decl_type.eq_ignore_ascii_case("REAL"),
+            FieldData::Text(_) => decl_type.eq_ignore_ascii_case("TEXT"),
+            FieldData::Blob(_) => decl_type.eq_ignore_ascii_case("BLOB"),
+        }
+    }
+
+    /// VIRTUAL generated columns are computed on read and have no slot in the stored record
+    pub fn is_virtual(&self) -> bool {
+        self.generated.as_ref().is_some_and(|g| !g.stored)
+    }
+}
+
+/// Type affinity of a column, determined from its declared type by the rules in
+/// <https://www.sqlite.org/datatype3.html#determination_of_column_affinity>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    /// Declared type contains INT
+    Integer,
+    /// Declared type contains CHAR, CLOB or TEXT
+    Text,
+    /// Declared type contains BLOB or is missing
+    Blob,
+    /// Declared type contains REAL, FLOA or DOUB
+    Real,
+    /// Any other declared type
+    Numeric,
+}
+
+impl Affinity {
+    /// Affinity for a declared type, following the rules in order
+    pub fn from_decl_type(decl_type: Option<&str>) -> Self {
+        let decl_type = decl_type.unwrap_or_default().to_ascii_uppercase();
+        if decl_type.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| decl_type.contains(t))
+        {
+            Affinity::Text
+        } else if decl_type.contains("BLOB") || decl_type.is_empty() {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| decl_type.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    /// INTEGER, REAL and NUMERIC affinity convert text that looks like a number
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Affinity::Integer | Affinity::Real | Affinity::Numeric)
+    }
+
+    /// The affinity's name, as written in SQL
+    pub fn name(&self) -> &'static str {
+        match self {
+            Affinity::Integer => "INTEGER",
+            Affinity::Text => "TEXT",
+            Affinity::Blob => "BLOB",
+            Affinity::Real => "REAL",
+            Affinity::Numeric => "NUMERIC",
+        }
+    }
+}
+
+/// A REFERENCES clause or FOREIGN KEY table constraint
+#[derive(Debug, Clone)]
+pub struct ForeignKey {
+    /// Child columns in this table
+    pub columns: Vec<String>,
+    /// Table the key refers to
+    pub parent_table: String,
+    /// empty when the parent's primary key is implied
+    pub parent_columns: Vec<String>,
+    /// ON DELETE action as written, e.g. CASCADE
+    pub on_delete: Option<String>,
+    /// ON UPDATE action as written
+    pub on_update: Option<String>,
+}
+
+/// A column's DEFAULT clause
+#[derive(Debug, Clone)]
+pub struct DefaultValue {
+    /// the DEFAULT expression as written, without parentheses
+    pub text: String,
+    /// None when the expression is not a constant literal
+    pub value: Option<FieldData>,
+}
+
+/// The GENERATED ALWAYS AS clause of a column
+#[derive(Debug, Clone)]
+pub struct GeneratedColumn {
+    /// The generating expression as written
+    pub expression: String,
+    /// VIRTUAL unless declared STORED
+    pub stored: bool,
+}
+
+/// A table as declared by its CREATE TABLE statement
+#[derive(Debug, Clone)]
+pub struct TableDef {
+    /// Name of the table
+    pub name: String,
+    /// Columns in declaration order, including generated ones
+    pub columns: Vec<ColumnDef>,
+    /// in declaration order
+    pub keys: Vec<KeyConstraint>,
+    /// Foreign keys in declaration order
+    pub foreign_keys: Vec<ForeignKey>,
+    /// Declared WITHOUT ROWID
+    pub without_rowid: bool,
+    /// Declared STRICT
+    pub strict: bool,
+}
+
+impl TableDef {
+    /// Parse a CREATE TABLE statement
+    pub fn parse(sql: &str) -> Result<Self, SqlParseError> {
+        let mut tokens = TokenStream::new(sql)?;
+        tokens.expect_keyword("CREATE")?;
+        if !tokens.eat_keyword("TEMP") {
+            tokens.eat_keyword("TEMPORARY");
+        }
+        tokens.expect_keyword("TABLE")?;
+        tokens.eat_keywords(&["IF", "NOT", "EXISTS"]);
+        let name = tokens.qualified_name()?;
+
+        let mut table = TableDef {
+            name,
+            columns: vec![],
+            keys: vec![],
+            foreign_keys: vec![],
+            without_rowid: false,
+            strict: false,
+        };
+
+        tokens.expect_symbol("(")?;
+        loop {
+            if is_table_constraint_start(&tokens) {
+                table.parse_table_constraint(&mut tokens)?;
+            } else {
+                table.parse_column(&mut tokens)?;
+            }
+            if !tokens.eat_symbol(",") {
+                break;
+            }
+        }
+        tokens.expect_symbol(")")?;
+
+        while !tokens.is_at_end() {
+            if tokens.eat_keywords(&["WITHOUT", "ROWID"]) {
+                table.without_rowid = true;
+            } else if tokens.eat_keyword("STRICT") {
+                table.strict = true;
+            } else if !tokens.eat_symbol(",") {
+                tokens.next_token();
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Position of a column by name, ignoring ASCII case
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|col| identifiers_match(&col.name, name))
+    }
+
+    /// Position of a column's value within the stored record. VIRTUAL generated columns are
+    /// left out of the record entirely, shifting every later column down by one. Records of
+    /// a WITHOUT ROWID table start with the primary key columns, followed by the rest in
+    /// declaration order.
+    pub fn storage_index(&self, column: usize) -> Option<usize> {
+        if self.columns.get(column)?.is_virtual() {
+            return None;
+        }
+        let key_columns = if self.without_rowid {
+            self.primary_key_columns()
+                .into_iter()
+                .map(|(idx, _)| idx)
+                .collect()
+        } else {
+            vec![]
+        };
+        if let Some(position) = key_columns.iter().position(|&idx| idx == column) {
+            return Some(position);
+        }
+        Some(
+            key_columns.len()
+                + self.columns[..column]
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, col)| !col.is_virtual() && !key_columns.contains(idx))
+                    .count(),
+        )
+    }
+
+    /// Check that a record of a STRICT table only holds values of each column's declared
+    /// type. SQLite enforces this on write, so a mismatch means the file is corrupt. `row`
+    /// names the row in the error, e.g. `rowid 5`.
+    pub fn check_strict_types(&self, row: &str, record: &[FieldData]) -> Result<(), SchemaError> {
+        if !self.strict {
+            return Ok(());
+        }
+        for (idx, col) in self.columns.iter().enumerate() {
+            let Some(value) = self.storage_index(idx).and_then(|slot| record.get(slot)) else {
+                continue;
+            };
+            if !col.strict_accepts(value) {
+                return Err(SchemaError::new(&format!(
+                    "{} of STRICT table `{}` has a {} value in column `{}` declared {}",
+                    row,
+                    self.name,
+                    value.type_name(),
+                    col.name,
+                    col.decl_type.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The primary key columns in key order with their table positions, each listed once
+    pub fn primary_key_columns(&self) -> Vec<(usize, &IndexColumn)> {
+        let mut columns: Vec<(usize, &IndexColumn)> = vec![];
+        for col in self.primary_key().map_or(&[][..], |key| &key.columns) {
+            if let IndexKey::Column(name) = &col.key {
+                match self.column_index(name) {
+                    Some(idx) if !columns.iter().any(|(i, _)| *i == idx) => {
+                        columns.push((idx, col))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        columns
+    }
+
+    /// The PRIMARY KEY constraint, if one is declared
+    pub fn primary_key(&self) -> Option<&KeyConstraint> {
+        self.keys.iter().find(|key| key.kind == KeyKind::PrimaryKey)
+    }
+
+    /// The UNIQUE constraints, column- and table-level, in declaration order
+    pub fn unique_constraints(&self) -> impl Iterator<Item = &KeyConstraint> {
+        self.keys.iter().filter(|key| key.kind == KeyKind::Unique)
+    }
+
+    /// 1-based position of a column within the primary key, as reported by PRAGMA table_info
+    pub fn primary_key_position(&self, column: usize) -> Option<usize> {
+        let name = &self.columns.get(column)?.name;
+        self.primary_key()?
+            .columns
+            .iter()
+            .position(|col| matches!(&col.key, IndexKey::Column(n) if identifiers_match(n, name)))
+            .map(|pos| pos + 1)
+    }
+
+    /// A column is NOT NULL if declared so, or if it is part of a WITHOUT ROWID primary key
+    pub fn is_not_null(&self, column: usize) -> bool {
+        self.columns.get(column).is_some_and(|col| col.not_null)
+            || (self.without_rowid && self.primary_key_position(column).is_some())
+    }
+
+    /// The value of a column absent from a record. Rows written before an `ALTER TABLE ADD
+    /// COLUMN` have fewer fields than the schema, and SQLite reads the missing trailing
+    /// columns as their DEFAULT (NULL when there is none).
+    pub fn missing_value(&self, column: usize) -> FieldData {
+        self.columns
+            .get(column)
+            .and_then(|col| col.default.as_ref())
+            .and_then(|default| default.value.clone())
+            .unwrap_or(FieldData::Null(()))
+    }
+
+    /// Index of the INTEGER PRIMARY KEY column whose value is stored as the rowid rather
+    /// than in the record (where it appears as NULL)
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.kind == KeyKind::PrimaryKey)?;
+        match &key.columns[..] {
+            [IndexColumn {
+                key: IndexKey::Column(name),
+                ..
+            }] if self.is_rowid_alias(key) => self.column_index(name),
+            _ => None,
+        }
+    }
+
+    /// The key constraints that SQLite backs with an automatic index, in the order the
+    /// indexes are numbered. An INTEGER PRIMARY KEY aliases the rowid, the primary key of a
+    /// WITHOUT ROWID table is the table itself, and constraints repeating an earlier key's
+    /// columns reuse its index.
+    pub fn indexed_keys(&self) -> Vec<&KeyConstraint> {
+        let mut keys: Vec<&KeyConstraint> = vec![];
+        for key in &self.keys {
+            if key.kind == KeyKind::PrimaryKey && (self.without_rowid || self.is_rowid_alias(key)) {
+                continue;
+            }
+            let duplicate = keys.iter().any(|k| same_key_columns(k, key));
+            if !duplicate {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    // `INTEGER PRIMARY KEY DESC` on a column is a historical exception that does not alias
+    // the rowid, while `PRIMARY KEY (x DESC)` on the table does
+    fn is_rowid_alias(&self, key: &KeyConstraint) -> bool {
+        match key.columns.as_slice() {
+            [IndexColumn {
+                key: IndexKey::Column(name),
+                descending,
+                ..
+            }] if !(key.column_level && *descending) => {
+                self.column_index(name).is_some_and(|idx| {
+                    self.columns[idx]
+                        .decl_type
+                        .as_deref()
+                        .is_some_and(|t| t.eq_ignore_ascii_case("INTEGER"))
+                })
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_column(&mut self, tokens: &mut TokenStream) -> Result<(), SqlParseError> {
+        let name = tokens.identifier()?;
+        let decl_type = parse_type_name(tokens)?;
+        let mut column = ColumnDef {
+            name,
+            decl_type,
+            collation: None,
+            generated: None,
+            default: None,
+            not_null: false,
+        };
+
+        while !tokens.peek_symbol(",") && !tokens.peek_symbol(")") && !tokens.is_at_end() {
+            if tokens.eat_keyword("CONSTRAINT") {
+                tokens.identifier()?;
+            } else if tokens.eat_keywords(&["PRIMARY", "KEY"]) {
+                let descending = tokens.eat_keyword("DESC");
+                if !descending {
+                    tokens.eat_keyword("ASC");
+                }
+                skip_conflict_clause(tokens)?;
+                tokens.eat_keyword("AUTOINCREMENT");
+                self.push_column_key(KeyKind::PrimaryKey, &column, descending);
+            } else if tokens.eat_keyword("UNIQUE") {
+                skip_conflict_clause(tokens)?;
+                self.push_column_key(KeyKind::Unique, &column, false);
+            } else if tokens.eat_keyword("COLLATE") {
+                column.collation = Some(tokens.identifier()?);
+                for key in self.keys.iter_mut() {
+                    for col in key.columns.iter_mut() {
+                        if col.key == IndexKey::Column(column.name.clone()) {
+                            col.collation = column.collation.clone();
+                        }
+                    }
+                }
+            } else if tokens.eat_keywords(&["NOT", "NULL"]) {
+                skip_conflict_clause(tokens)?;
+                column.not_null = true;
+            } else if tokens.eat_keyword("REFERENCES") {
+                let fk = parse_foreign_key_clause(tokens, vec![column.name.clone()])?;
+                self.foreign_keys.push(fk);
+            } else if tokens.eat_keyword("DEFAULT") {
+                column.default = Some(parse_default(tokens)?);
+            } else if tokens.eat_keywords(&["GENERATED", "ALWAYS"]) || tokens.peek_keyword("AS") {
+                tokens.expect_keyword("AS")?;
+                tokens.expect_symbol("(")?;
+                let expression = tokens.capture_until(|_| false)?;
+                tokens.expect_symbol(")")?;
+                let stored = tokens.eat_keyword("STORED");
+                if !stored {
+                    tokens.eat_keyword("VIRTUAL");
+                }
+                column.generated = Some(GeneratedColumn { expression, stored });
+            } else {
+                skip_column_constraint(tokens)?;
+            }
+        }
+
+        self.columns.push(column);
+        Ok(())
+    }
+
+    fn push_column_key(&mut self, kind: KeyKind, column: &ColumnDef, descending: bool) {
+        let columns = vec![IndexColumn {
+            key: IndexKey::Column(column.name.clone()),
+            descending,
+            collation: column.collation.clone(),
+        }];
+        self.keys.push(KeyConstraint {
+            kind,
+            columns,
+            column_level: true,
+        });
+    }
+
+    fn parse_table_constraint(&mut self, tokens: &mut TokenStream) -> Result<(), SqlParseError> {
+        if tokens.eat_keyword("CONSTRAINT") {
+            tokens.identifier()?;
+        }
+        let kind = if tokens.eat_keywords(&["PRIMARY", "KEY"]) {
+            Some(KeyKind::PrimaryKey)
+        } else if tokens.eat_keyword("UNIQUE") {
+            Some(KeyKind::Unique)
+        } else {
+            None
+        };
+
+        match kind {
+            Some(kind) => {
+                let mut columns = parse_indexed_columns(tokens)?;
+                // Table-level keys inherit each column's declared collation unless overridden
+                for col in columns.iter_mut() {
+                    if let (None, IndexKey::Column(name)) = (&col.collation, &col.key) {
+                        col.collation = self
+                            .column_index(name)
+                            .and_then(|idx| self.columns[idx].collation.clone());
+                    }
+                }
+                skip_conflict_clause(tokens)?;
+                self.keys.push(KeyConstraint {
+                    kind,
+                    columns,
+                    column_level: false,
+                });
+            }
+            None if tokens.eat_keywords(&["FOREIGN", "KEY"]) => {
+                let columns = parse_name_list(tokens)?;
+                tokens.expect_keyword("REFERENCES")?;
+                let fk = parse_foreign_key_clause(tokens, columns)?;
+                self.foreign_keys.push(fk);
+            }
+            None => {
+                tokens.capture_until(|t| t.is_symbol(","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_table_constraint_start(tokens: &TokenStream) -> bool {
+    let Some(token) = tokens.peek() else {
+        return false;
+    };
+    if token.kind != TokenKind::Word {
+        return false;
+    }
+    ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+        .iter()
+        .any(|kw| token.is_keyword(kw))
+}
+
+fn same_key_columns(a: &KeyConstraint, b: &KeyConstraint) -> bool {
+    a.columns.len() == b.columns.len()
+        && a.columns
+            .iter()
+            .zip(&b.columns)
+            .all(|(x, y)| match (&x.key, &y.key) {
+                (IndexKey::Column(m), IndexKey::Column(n)) => identifiers_match(m, n),
+                _ => false,
+            })
+}
+
+// Parse `( indexed-column, ... )` as used by CREATE INDEX and PRIMARY KEY/UNIQUE clauses
+fn parse_indexed_columns(tokens: &mut TokenStream) -> Result<Vec<IndexColumn>, SqlParseError> {
+    tokens.expect_symbol("(")?;
+    let mut columns = vec![];
+    loop {
+        let is_plain_name = tokens.peek().is_some_and(|t| {
+            matches!(
+                t.kind,
+                TokenKind::Word | TokenKind::Quoted | TokenKind::String
+            )
+        }) && tokens.peek_nth(1).is_some_and(|t| {
+            t.is_symbol(",")
+                || t.is_symbol(")")
+                || t.is_keyword("COLLATE")
+                || t.is_keyword("ASC")
+                || t.is_keyword("DESC")
+        });
+
+        let key = if is_plain_name {
+            IndexKey::Column(tokens.identifier()?)
+        } else {
+            IndexKey::Expression(tokens.capture_until(|t| {
+                t.is_symbol(",")
+                    || t.is_keyword("COLLATE")
+                    || t.is_keyword("ASC")
+                    || t.is_keyword("DESC")
+            })?)
+        };
+
+        let collation = if tokens.eat_keyword("COLLATE") {
+            Some(tokens.identifier()?)
+        } else {
+            None
+        };
+        let descending = tokens.eat_keyword("DESC");
+        if !descending {
+            tokens.eat_keyword("ASC");
+        }
+
+        columns.push(IndexColumn {
+            key,
+            descending,
+            collation,
+        });
+        if !tokens.eat_symbol(",") {
+            break;
+        }
+    }
+    tokens.expect_symbol(")")?;
+    Ok(columns)
+}
+
+// A type name is a sequence of words optionally followed by one or two numeric arguments,
+// e.g. `UNSIGNED BIG INT` or `VARCHAR(255)`
+fn parse_type_name(tokens: &mut TokenStream) -> Result<Option<String>, SqlParseError> {
+    let mut words: Vec<String> = vec![];
+    while let Some(token) = tokens.peek() {
+        let is_type_word = matches!(token.kind, TokenKind::Word | TokenKind::Quoted)
+            && !COLUMN_CONSTRAINT_KEYWORDS
+                .iter()
+                .any(|kw| token.is_keyword(kw));
+        if !is_type_word {
+            break;
+        }
+        words.push(token.value.clone());
+        tokens.next_token();
+    }
+    if words.is_empty() {
+        return Ok(None);
+    }
+
+    let mut decl_type = words.join(" ");
+    if tokens.peek_symbol("(") {
+        let args = {
+            tokens.expect_symbol("(")?;
+            let args = tokens.capture_until(|_| false)?;
+            tokens.expect_symbol(")")?;
+            args
+        };
+        decl_type = format!("{}({})", decl_type, args);
+    }
+    Ok(Some(decl_type))
+}
+
+// Parse `DEFAULT (expr)`, `DEFAULT [+|-]number`, or `DEFAULT literal`, after the keyword
+fn parse_default(tokens: &mut TokenStream) -> Result<DefaultValue, SqlParseError> {
+    let text = if tokens.eat_symbol("(") {
+        let text = tokens.capture_until(|_| false)?;
+        tokens.expect_symbol(")")?;
+        text
+    } else {
+        let start = tokens.peek().map_or(0, |t| t.start);
+        if !tokens.eat_symbol("-") {
+            tokens.eat_symbol("+");
+        }
+        let end = tokens.next_token().map_or(start, |t| t.end);
+        tokens.source(start, end).to_owned()
+    };
+    let value = literal_value(&text);
+    Ok(DefaultValue { text, value })
+}
+
+// Evaluate an expression consisting of a single, optionally signed, literal
+fn literal_value(text: &str) -> Option<FieldData> {
+    let tokens = tokenize(text).ok()?;
+    let (negative, token) = match tokens.as_slice() {
+        [sign, token] if sign.is_symbol("-") => (true, token),
+        [sign, token] if sign.is_symbol("+") => (false, token),
+        [token] => (false, token),
+        _ => return None,
+    };
+
+    literal_token_value(token, negative)
+}
+
+// Whether a normalized WHERE term guarantees another one holds
+fn implies(term: &[String], implied: &[String]) -> bool {
+    if term == implied {
+        return true;
+    }
+    let column = match implied {
+        [col, is, not, null] if is == "is" && not == "not" && null == "null" => col,
+        [col, not, null] if not == "not" && null == "null" => col,
+        [col, notnull] if notnull == "notnull" => col,
+        _ => return false,
+    };
+    // a comparison against a non-NULL value is never true for a NULL column
+    match term {
+        [col, op, value] if col == column => {
+            matches!(op.as_str(), "=" | "!=" | "<" | "<=" | ">" | ">=") && value != "null"
+        }
+        _ => false,
+    }
+}
+
+fn skip_conflict_clause(tokens: &mut TokenStream) -> Result<(), SqlParseError> {
+    if tokens.eat_keywords(&["ON", "CONFLICT"]) {
+        tokens.identifier()?;
+    }
+    Ok(())
+}
+
+// Skip over a column constraint that does not affect the parsed definition
+fn skip_column_constraint(tokens: &mut TokenStream) -> Result<(), SqlParseError> {
+    if tokens.eat_keyword("NULL") {
+        Ok(())
+    } else if tokens.eat_keyword("CHECK") {
+        tokens.skip_parenthesized()
+    } else {
+        tokens.next_token();
+        Ok(())
+    }
+}
+
+// Parse `( name, ... )`
+fn parse_name_list(tokens: &mut TokenStream) -> Result<Vec<String>, SqlParseError> {
+    tokens.expect_symbol("(")?;
+    let mut names = vec![tokens.identifier()?];
+    while tokens.eat_symbol(",") {
+        names.push(tokens.identifier()?);
+    }
+    tokens.expect_symbol(")")?;
+    Ok(names)
+}
+
+// Parse `table [(columns)]` and any ON/MATCH/DEFERRABLE clauses following REFERENCES
+fn parse_foreign_key_clause(
+    tokens: &mut TokenStream,
+    columns: Vec<String>,
+) -> Result<ForeignKey, SqlParseError> {
+    let parent_table = tokens.identifier()?;
+    let parent_columns = if tokens.peek_symbol("(") {
+        parse_name_list(tokens)?
+    } else {
+        vec![]
+    };
+    let mut fk = ForeignKey {
+        columns,
+        parent_table,
+        parent_columns,
+        on_delete: None,
+        on_update: None,
+    };
+
+    loop {
+        if tokens.eat_keyword("ON") {
+            let on_delete = tokens.eat_keyword("DELETE");
+            if !on_delete {
+                tokens.expect_keyword("UPDATE")?;
+            }
+            let action = if tokens.eat_keywords(&["SET", "NULL"]) {
+                "SET NULL".to_owned()
+            } else if tokens.eat_keywords(&["SET", "DEFAULT"]) {
+                "SET DEFAULT".to_owned()
+            } else if tokens.eat_keywords(&["NO", "ACTION"]) {
+                "NO ACTION".to_owned()
+            } else {
+                tokens.identifier()?.to_ascii_uppercase() // CASCADE or RESTRICT
+            };
+            if on_delete {
+                fk.on_delete = Some(action);
+            } else {
+                fk.on_update = Some(action);
+            }
+        } else if tokens.eat_keyword("MATCH") {
+            tokens.identifier()?;
+        } else if tokens.eat_keywords(&["NOT", "DEFERRABLE"]) || tokens.eat_keyword("DEFERRABLE") {
+            if tokens.eat_keyword("INITIALLY") {
+                tokens.next_token();
+            }
+        } else {
+            return Ok(fk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usable(index_sql: &str, query_where: Option<&str>) -> bool {
+        IndexDef::parse(index_sql).unwrap().usable_for(query_where)
+    }
+
+    #[test]
+    fn partial_index_needs_every_predicate_term() {
+        let index = "CREATE INDEX i ON t(a) WHERE b = 1 AND c IS NOT NULL";
+        assert!(usable(index, Some("c > 5 AND (b = 1) AND a = 2")));
+        assert!(!usable(index, Some("b = 1")));
+        assert!(!usable(index, None));
+        assert!(usable("CREATE INDEX i ON t(a)", None));
+    }
+
+    #[test]
+    fn top_level_or_is_a_single_term() {
+        // `b = 1 AND c = 2 OR d = 3` holds for rows where b is not 1
+        let index = "CREATE INDEX i ON t(a) WHERE b = 1";
+        assert!(!usable(index, Some("b = 1 AND c = 2 OR d = 3")));
+        assert!(!usable(index, Some("d = 3 OR b = 1")));
+        assert!(usable(index, Some("(c = 2 OR d = 3) AND b = 1")));
+
+        let index = "CREATE INDEX i ON t(a) WHERE b = 1 OR c = 2";
+        assert!(usable(index, Some("b = 1 OR c = 2")));
+        assert!(usable(index, Some("a > 0 AND (b = 1 OR c = 2)")));
+        assert!(!usable(index, Some("b = 1 OR c = 2 OR d = 3")));
+    }
+
+    #[test]
+    fn between_and_does_not_split() {
+        let index = "CREATE INDEX i ON t(a) WHERE b BETWEEN 1 AND 5";
+        assert!(usable(index, Some("b BETWEEN 1 AND 5 AND c = 1")));
+        assert!(!usable(index, Some("c = 1 AND 5")));
+    }
+
+    fn column(name: &str, descending: bool, collation: Option<&str>) -> IndexColumn {
+        IndexColumn {
+            key: IndexKey::Column(name.to_owned()),
+            descending,
+            collation: collation.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn multi_column_index() {
+        let index =
+            IndexDef::parse("CREATE UNIQUE INDEX IF NOT EXISTS main.i ON t(a, b ASC, c)").unwrap();
+        assert_eq!(index.name, "i");
+        assert_eq!(index.table, "t");
+        assert!(index.unique);
+        assert_eq!(
+            index.columns,
+            [
+                column("a", false, None),
+                column("b", false, None),
+                column("c", false, None)
+            ]
+        );
+        assert_eq!(index.where_clause, None);
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        let index = IndexDef::parse(
+            r#"CREATE INDEX "my index" ON [my table]("first name", "last""name", `x`)"#,
+        )
+        .unwrap();
+        assert_eq!(index.name, "my index");
+        assert_eq!(index.table, "my table");
+        assert!(!index.unique);
+        assert_eq!(
+            index.columns,
+            [
+                column("first name", false, None),
+                column("last\"name", false, None),
+                column("x", false, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn desc_columns_and_collations() {
+        let index = IndexDef::parse(
+            "CREATE INDEX i ON t(a DESC, b COLLATE NOCASE, c COLLATE rtrim DESC, a + 1)",
+        )
+        .unwrap();
+        assert_eq!(
+            index.columns,
+            [
+                column("a", true, None),
+                column("b", false, Some("NOCASE")),
+                column("c", true, Some("rtrim")),
+                IndexColumn {
+                    key: IndexKey::Expression("a + 1".to_owned()),
+                    descending: false,
+                    collation: None,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn table_keys_keep_order_and_collation() {
+        let table = TableDef::parse(
+            r#"CREATE TABLE "t"("a" TEXT COLLATE NOCASE, b INTEGER UNIQUE, c, PRIMARY KEY (c DESC, "a"))"#,
+        )
+        .unwrap();
+        assert_eq!(table.name, "t");
+        let names = table
+            .columns
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(table.columns[0].collation.as_deref(), Some("NOCASE"));
+        // key columns without a COLLATE clause take the column's collation
+        assert_eq!(
+            table.primary_key().unwrap().columns,
+            [column("c", true, None), column("a", false, Some("NOCASE"))]
+        );
+        let unique = table.unique_constraints().collect::<Vec<_>>();
+        assert_eq!(unique.len(), 1);
+        assert!(unique[0].column_level);
+        assert_eq!(unique[0].columns, [column("b", false, None)]);
+    }
+
+    #[test]
+    fn rootpage_must_be_a_page_number() {
+        let row = |rootpage: i64| {
+            [
+                FieldData::Text("table".into()),
+                FieldData::Text("t".into()),
+                FieldData::Text("t".into()),
+                FieldData::Integer(rootpage),
+                FieldData::Text("CREATE TABLE t(a)".into()),
+            ]
+        };
+        let object = SchemaObject::from_values(&row(i64::from(u32::MAX))).unwrap();
+        assert_eq!(object.rootpage, u32::MAX);
+        for rootpage in [-1, 1 << 32] {
+            let err = SchemaObject::from_values(&row(rootpage)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("sqlite_schema rootpage {} is not a page number", rootpage)
+            );
+        }
+    }
+
+    #[test]
+    fn names_resolve_ignoring_ascii_case() {
+        let object = |object_type, name: &str, tbl_name: &str| SchemaObject {
+            object_type,
+            name: name.to_owned(),
+            tbl_name: tbl_name.to_owned(),
+            rootpage: 2,
+            sql: None,
+        };
+        let schema = Schema::from_objects(vec![
+            object(ObjectType::Table, "Users", "Users"),
+            object(ObjectType::Index, "users_name", "USERS"),
+            object(ObjectType::Table, "Été", "Été"),
+        ]);
+        assert_eq!(schema.find("USERS").unwrap().name, "Users");
+        assert_eq!(schema.find_table("users").unwrap().name, "Users");
+        assert_eq!(schema.find("Users_Name").unwrap().name, "users_name");
+        let indexes = schema.indexes_on("users").collect::<Vec<_>>();
+        assert_eq!(indexes.len(), 1);
+        assert!(schema.find("ÉTé").is_some());
+        assert!(schema.find("été").is_none());
+    }
+
+    #[test]
+    fn columns_differing_only_in_case_resolve_to_the_first() {
+        // SQLite refuses to create these, but a schema written by something else may
+        // hold them, and SQLite then resolves the name to the first
+        let table = TableDef::parse("CREATE TABLE t(Name TEXT, \"NAME\" TEXT, é, É)").unwrap();
+        assert_eq!(table.column_index("name"), Some(0));
+        assert_eq!(table.column_index("NAME"), Some(0));
+        assert_eq!(table.column_index("é"), Some(2));
+        assert_eq!(table.column_index("É"), Some(3));
+        assert_eq!(table.column_index("e"), None);
+    }
+}
@@ -0,0 +1,230 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::query::QueryResult;
+use crate::record::{base64, FieldData};
+
+/// How BLOB values are serialized. As bytes they become a byte string in binary formats
+/// such as CBOR but an array of numbers in JSON, where base64 text is usually wanted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlobEncoding {
+    /// as a byte string, or an array of numbers in formats without one
+    #[default]
+    Bytes,
+    /// as base64 text
+    Base64,
+}
+
+/// A value serialized with the given encoding for BLOBs. NULL becomes a unit/none value,
+/// integers (including the 0 and 1 stored in the header) an i64, REALs an f64 and TEXT a
+/// string.
+#[derive(Debug, Clone, Copy)]
+pub struct Value<'a> {
+    /// the value
+    pub value: &'a FieldData,
+    /// how a BLOB is written
+    pub blobs: BlobEncoding,
+}
+
+impl Serialize for Value<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            FieldData::Null(_) => serializer.serialize_none(),
+            FieldData::BooleanFalse(_) => serializer.serialize_i64(0),
+            FieldData::BooleanTrue(_) => serializer.serialize_i64(1),
+            FieldData::Integer(n) => serializer.serialize_i64(*n),
+            FieldData::Real(r) => serializer.serialize_f64(*r),
+            FieldData::Text(text) => serializer.serialize_str(text),
+            FieldData::Blob(bytes) => match self.blobs {
+                BlobEncoding::Bytes => serializer.serialize_bytes(bytes),
+                BlobEncoding::Base64 => serializer.serialize_str(&base64(bytes)),
+            },
+        }
+    }
+}
+
+impl Serialize for FieldData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Value {
+            value: self,
+            blobs: BlobEncoding::Bytes,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A row serialized as a map from column name to value, in column order
+#[derive(Debug, Clone, Copy)]
+pub struct NamedRow<'a> {
+    /// the column names, in schema order
+    pub columns: &'a [String],
+    /// the values, in the order of the columns
+    pub values: &'a [FieldData],
+    /// how BLOBs are written
+    pub blobs: BlobEncoding,
+}
+
+impl Serialize for NamedRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(self.values) {
+            let value = Value {
+                value,
+                blobs: self.blobs,
+            };
+            map.serialize_entry(column, &value)?;
+        }
+        map.end()
+    }
+}
+
+impl QueryResult {
+    /// The rows of the result, each ready to serialize as a map keyed by column name
+    pub fn named_rows(&self, blobs: BlobEncoding) -> impl Iterator<Item = NamedRow<'_>> {
+        self.rows.iter().map(move |values| NamedRow {
+            columns: &self.columns,
+            values,
+            blobs,
+        })
+    }
+}
+
+// A query result serializes as a sequence of rows keyed by column name
+impl Serialize for QueryResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.named_rows(BlobEncoding::Bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{execute, DBInfo, Database, Select};
+    use ciborium::Value as Cbor;
+    use serde_json::json;
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/scan.db");
+
+    fn fixture_rows() -> QueryResult {
+        let db = Database::from_bytes(FIXTURE.to_vec()).unwrap();
+        let select = Select::parse("SELECT * FROM readings WHERE id <= 2").unwrap();
+        execute(&db, &select).unwrap()
+    }
+
+    fn to_cbor<T: Serialize>(value: &T) -> Cbor {
+        let mut bytes = vec![];
+        ciborium::into_writer(value, &mut bytes).unwrap();
+        ciborium::from_reader(&bytes[..]).unwrap()
+    }
+
+    fn cbor_map(entries: &[(&str, Cbor)]) -> Cbor {
+        Cbor::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (Cbor::Text((*key).to_owned()), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn fixture_rows_to_json() {
+        let json = serde_json::to_string(&fixture_rows()).unwrap();
+        // columns stay in schema order
+        assert_eq!(
+            json,
+            r#"[{"id":1,"sensor":"sensor-1","value":0.25,"note":null},{"id":2,"sensor":"sensor-2","value":0.5,"note":null}]"#
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            parsed,
+            json!([
+                {"id": 1, "sensor": "sensor-1", "value": 0.25, "note": null},
+                {"id": 2, "sensor": "sensor-2", "value": 0.5, "note": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn fixture_rows_to_cbor() {
+        let row = |id: i64, sensor: &str, value: f64| {
+            cbor_map(&[
+                ("id", Cbor::Integer(id.into())),
+                ("sensor", Cbor::Text(sensor.to_owned())),
+                ("value", Cbor::Float(value)),
+                ("note", Cbor::Null),
+            ])
+        };
+        assert_eq!(
+            to_cbor(&fixture_rows()),
+            Cbor::Array(vec![row(1, "sensor-1", 0.25), row(2, "sensor-2", 0.5)])
+        );
+    }
+
+    #[test]
+    fn values_in_both_formats() {
+        let cases = [
+            (FieldData::Null(()), json!(null), Cbor::Null),
+            (FieldData::BooleanTrue(1), json!(1), Cbor::Integer(1.into())),
+            (
+                FieldData::Integer(i64::MIN),
+                json!(i64::MIN),
+                Cbor::Integer(i64::MIN.into()),
+            ),
+            (FieldData::Real(-1.5), json!(-1.5), Cbor::Float(-1.5)),
+            (
+                FieldData::Text("née".to_owned()),
+                json!("née"),
+                Cbor::Text("née".to_owned()),
+            ),
+            (
+                FieldData::Blob(vec![0, 255]),
+                json!([0, 255]),
+                Cbor::Bytes(vec![0, 255]),
+            ),
+        ];
+        for (value, expected_json, expected_cbor) in cases {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+                expected_json
+            );
+            assert_eq!(to_cbor(&value), expected_cbor);
+        }
+    }
+
+    #[test]
+    fn blobs_as_base64() {
+        let columns = ["data".to_owned()];
+        let values = [FieldData::Blob(b"sqrlite".to_vec())];
+        let row = NamedRow {
+            columns: &columns,
+            values: &values,
+            blobs: BlobEncoding::Base64,
+        };
+        assert_eq!(
+            serde_json::to_value(row).unwrap(),
+            json!({"data": "c3FybGl0ZQ=="})
+        );
+        assert_eq!(
+            to_cbor(&row),
+            cbor_map(&[("data", Cbor::Text("c3FybGl0ZQ==".to_owned()))])
+        );
+    }
+
+    #[test]
+    fn dbinfo_to_json_and_cbor() {
+        let db = Database::from_bytes(FIXTURE.to_vec()).unwrap();
+        let info = DBInfo::read_info(&db).unwrap();
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["db_page_size"], 4096);
+        assert_eq!(json["db_page_count"], 139);
+
+        let Cbor::Map(entries) = to_cbor(&info) else {
+            panic!("DBInfo should serialize as a map");
+        };
+        let page_size = entries
+            .iter()
+            .find(|(key, _)| key.as_text() == Some("db_page_size"))
+            .map(|(_, value)| value.clone());
+        assert_eq!(page_size, Some(Cbor::Integer(4096.into())));
+    }
+}
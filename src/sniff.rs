@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::header::{DB_HEADER_SIZE, MAGIC};
+use crate::page_source::PageSource;
+
+// bytes looked at to judge whether the content is random, about one page
+const SAMPLE_SIZE: usize = 4096;
+// Share of the most entropy a sample can have above which it is taken to be random. Encrypted
+// and compressed data come close to all of it; text and b-tree pages full of small integers
+// get nowhere near.
+const RANDOM_ENTROPY: f64 = 0.9;
+// how far into the file to look for a database joined onto the end of something else
+const SEARCH_LIMIT: u64 = 16 * 1024 * 1024;
+const SEARCH_CHUNK: usize = 64 * 1024;
+
+/// What a file that does not start with the SQLite header seems to be instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// a file with nothing in it
+    Empty,
+    /// a file shorter than the database header
+    TooShort {
+        /// size of the file in bytes
+        len: u64,
+    },
+    /// a SQLite header further into the file, as when a database is appended to another file
+    Embedded {
+        /// where the header starts
+        offset: u64,
+    },
+    /// random-looking content, such as a SQLCipher database or a compressed file
+    Encrypted,
+    /// anything else
+    Unknown,
+}
+
+/// A file that is not a SQLite database, with a guess at what it is instead
+#[derive(Debug)]
+pub struct NotADatabaseError {
+    /// what the file seems to be
+    pub kind: FileKind,
+    details: String,
+}
+
+impl NotADatabaseError {
+    /// The error for a file of the given kind, with a message suggesting what went wrong
+    pub fn new(kind: FileKind) -> Self {
+        let details = match kind {
+            FileKind::Empty => "file is empty, not a SQLite database".to_owned(),
+            FileKind::TooShort { len } => format!(
+                "file is only {} bytes, too short to be a SQLite database, which starts with a \
+                 {}-byte header",
+                len, DB_HEADER_SIZE
+            ),
+            FileKind::Embedded { offset } => format!(
+                "file does not start with a SQLite database, but has a 'SQLite format 3' header \
+                 at offset {}; it may be a database joined onto the end of another file",
+                offset
+            ),
+            FileKind::Encrypted => "file appears to be encrypted or is not a SQLite database \
+                                    (no 'SQLite format 3' header)"
+                .to_owned(),
+            FileKind::Unknown => {
+                "file is not a valid database: missing the SQLite format 3 header string".to_owned()
+            }
+        };
+        Self { kind, details }
+    }
+}
+
+impl fmt::Display for NotADatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for NotADatabaseError {}
+
+/// Guess what a source without the SQLite header at its start holds
+pub fn sniff(storage: &dyn PageSource) -> io::Result<FileKind> {
+    let len = storage.len()?;
+    if len == 0 {
+        return Ok(FileKind::Empty);
+    }
+    if len < DB_HEADER_SIZE as u64 {
+        return Ok(FileKind::TooShort { len });
+    }
+    if let Some(offset) = find_magic(storage, len)? {
+        return Ok(FileKind::Embedded { offset });
+    }
+
+    let mut sample = vec![0; len.min(SAMPLE_SIZE as u64) as usize];
+    storage.read(0, &mut sample)?;
+    // a sample of n bytes can have at most log2(n) bits per byte, as it holds n values at most
+    let most = (sample.len().min(256) as f64).log2();
+    if entropy(&sample) > RANDOM_ENTROPY * most {
+        Ok(FileKind::Encrypted)
+    } else {
+        Ok(FileKind::Unknown)
+    }
+}
+
+// Offset of the first SQLite header string after the start of the file, looking only as far
+// as SEARCH_LIMIT. Chunks overlap so a string split between two of them is still found.
+fn find_magic(storage: &dyn PageSource, len: u64) -> io::Result<Option<u64>> {
+    let end = len.min(SEARCH_LIMIT);
+    let mut chunk = vec![0; SEARCH_CHUNK];
+    let mut start = 1;
+    while start + MAGIC.len() as u64 <= end {
+        let size = (end - start).min(SEARCH_CHUNK as u64) as usize;
+        storage.read(start, &mut chunk[..size])?;
+        if let Some(at) = chunk[..size]
+            .windows(MAGIC.len())
+            .position(|window| window == MAGIC)
+        {
+            return Ok(Some(start + at as u64));
+        }
+        start += (size - (MAGIC.len() - 1)) as u64;
+    }
+    Ok(None)
+}
+
+// Shannon entropy of the bytes, from 0 when they are all the same to 8 when every value is
+// equally common
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+    let total = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, SqrliteError};
+    use std::io::Cursor;
+
+    fn kind(bytes: Vec<u8>) -> FileKind {
+        sniff(&Cursor::new(bytes)).unwrap()
+    }
+
+    // bytes from a xorshift generator, as uniform as ciphertext
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    fn database() -> Vec<u8> {
+        include_bytes!("../tests/fixtures/scan.db").to_vec()
+    }
+
+    #[test]
+    fn empty_and_short_files() {
+        assert_eq!(kind(vec![]), FileKind::Empty);
+        assert_eq!(kind(b"SQLite".to_vec()), FileKind::TooShort { len: 6 });
+        assert_eq!(kind(vec![0; 99]), FileKind::TooShort { len: 99 });
+        // a database cut inside its header is too short, not some other kind of file
+        let mut bytes = database();
+        bytes.truncate(50);
+        assert_eq!(kind(bytes), FileKind::TooShort { len: 50 });
+    }
+
+    #[test]
+    fn random_content_looks_encrypted() {
+        assert_eq!(kind(noise(100)), FileKind::Encrypted);
+        assert_eq!(kind(noise(64 * 1024)), FileKind::Encrypted);
+    }
+
+    #[test]
+    fn structured_content_is_unknown() {
+        let text = "id,name,price\n1,apple,0.5\n".repeat(200);
+        assert_eq!(kind(text.into_bytes()), FileKind::Unknown);
+        assert_eq!(kind(vec![0; 4096]), FileKind::Unknown);
+        // a JPEG header followed by a smooth gradient
+        let mut jpeg = vec![
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0,
+        ];
+        jpeg.extend((0..4096u32).map(|i| (i / 64) as u8));
+        assert_eq!(kind(jpeg), FileKind::Unknown);
+        // a database whose first bytes were overwritten still has low-entropy pages
+        let mut bytes = database();
+        bytes[..16].fill(0);
+        assert_eq!(kind(bytes), FileKind::Unknown);
+    }
+
+    #[test]
+    fn header_further_into_the_file() {
+        let mut bytes = b"#!/bin/sh\nexit 0\n".to_vec();
+        bytes.extend(database());
+        assert_eq!(kind(bytes), FileKind::Embedded { offset: 17 });
+
+        // found even behind random bytes and when split across two chunks of the search
+        let offset = SEARCH_CHUNK - 5;
+        let mut bytes = noise(offset);
+        bytes.extend(database());
+        assert_eq!(
+            kind(bytes),
+            FileKind::Embedded {
+                offset: offset as u64
+            }
+        );
+    }
+
+    #[test]
+    fn search_stops_at_the_limit() {
+        let mut bytes = vec![b'x'; SEARCH_LIMIT as usize];
+        bytes.extend(MAGIC);
+        assert_eq!(kind(bytes), FileKind::Unknown);
+
+        let mut bytes = vec![b'x'; SEARCH_LIMIT as usize - MAGIC.len()];
+        bytes.extend(MAGIC);
+        assert_eq!(
+            kind(bytes),
+            FileKind::Embedded {
+                offset: SEARCH_LIMIT - MAGIC.len() as u64
+            }
+        );
+    }
+
+    #[test]
+    fn opening_reports_the_kind() {
+        let cases = [
+            (vec![], "file is empty, not a SQLite database".to_owned()),
+            (
+                vec![0; 10],
+                "file is only 10 bytes, too short to be a SQLite database, which starts with \
+                 a 100-byte header"
+                    .to_owned(),
+            ),
+            (
+                noise(8192),
+                "file appears to be encrypted or is not a SQLite database (no 'SQLite format 3' \
+                 header)"
+                    .to_owned(),
+            ),
+            (
+                [vec![0; 512], database()].concat(),
+                "file does not start with a SQLite database, but has a 'SQLite format 3' header \
+                 at offset 512; it may be a database joined onto the end of another file"
+                    .to_owned(),
+            ),
+        ];
+        for (bytes, message) in cases {
+            match Database::from_bytes(bytes) {
+                Err(SqrliteError::NotADatabase(e)) => assert_eq!(e.to_string(), message),
+                other => panic!("expected a NotADatabase error, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
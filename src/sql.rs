@@ -0,0 +1,688 @@
+use std::error::Error;
+use std::fmt;
+
+/// A statement that cannot be parsed, or that names something the schema does not have
+#[derive(Debug)]
+pub struct SqlParseError {
+    details: String,
+}
+
+impl SqlParseError {
+    /// An error with the given description
+    pub fn new(details: &str) -> Self {
+        Self {
+            details: details.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for SqlParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SQL parse error: {}", self.details)
+    }
+}
+
+impl Error for SqlParseError {}
+
+/// SQLite resolves identifiers case-insensitively, folding ASCII letters only; other
+/// characters must match exactly. Quoting preserves the spelling of a name but does not make
+/// lookups case-sensitive. All name resolution goes through these two functions.
+pub fn identifiers_match(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// The spelling of a name that every spelling matching it shares, for use as a map key
+pub fn normalize_identifier(name: &str) -> String {
+    name.to_ascii_lowercase()
+}
+
+/// Quote an identifier for output as SQL, leaving plain non-keyword names bare like the
+/// sqlite3 shell does
+pub fn quote_identifier(name: &str) -> String {
+    let is_plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !is_keyword(name);
+    if is_plain {
+        name.to_owned()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Whether a word is one of SQLite's keywords, in any case
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.iter().any(|kw| kw.eq_ignore_ascii_case(word))
+}
+
+const KEYWORDS: [&str; 147] = [
+    "ABORT",
+    "ACTION",
+    "ADD",
+    "AFTER",
+    "ALL",
+    "ALTER",
+    "ALWAYS",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEFORE",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DEFAULT",
+    "DEFERRABLE",
+    "DEFERRED",
+    "DELETE",
+    "DESC",
+    "DETACH",
+    "DISTINCT",
+    "DO",
+    "DROP",
+    "EACH",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUDE",
+    "EXCLUSIVE",
+    "EXISTS",
+    "EXPLAIN",
+    "FAIL",
+    "FILTER",
+    "FIRST",
+    "FOLLOWING",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GENERATED",
+    "GLOB",
+    "GROUP",
+    "GROUPS",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IMMEDIATE",
+    "IN",
+    "INDEX",
+    "INDEXED",
+    "INITIALLY",
+    "INNER",
+    "INSERT",
+    "INSTEAD",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "ISNULL",
+    "JOIN",
+    "KEY",
+    "LAST",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "MATCH",
+    "MATERIALIZED",
+    "NATURAL",
+    "NO",
+    "NOT",
+    "NOTHING",
+    "NOTNULL",
+    "NULL",
+    "NULLS",
+    "OF",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OTHERS",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PLAN",
+    "PRAGMA",
+    "PRECEDING",
+    "PRIMARY",
+    "QUERY",
+    "RAISE",
+    "RANGE",
+    "RECURSIVE",
+    "REFERENCES",
+    "REGEXP",
+    "REINDEX",
+    "RELEASE",
+    "RENAME",
+    "REPLACE",
+    "RESTRICT",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "ROW",
+    "ROWS",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "TEMP",
+    "TEMPORARY",
+    "THEN",
+    "TIES",
+    "TO",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNBOUNDED",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VALUES",
+    "VIEW",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHOUT",
+];
+
+/// What a token is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// unquoted identifier or keyword
+    Word,
+    /// identifier quoted with "", [] or ``
+    Quoted,
+    /// '...' literal
+    String,
+    /// integer or real literal
+    Number,
+    /// x'...' literal
+    Blob,
+    /// operator or punctuation
+    Symbol,
+}
+
+/// One token of a SQL statement
+#[derive(Debug, Clone)]
+pub struct Token {
+    /// what the token is
+    pub kind: TokenKind,
+    /// unescaped text of the token
+    pub value: String,
+    /// byte offsets of the token in the source SQL
+    pub start: usize,
+    /// byte offset just past the token
+    pub end: usize,
+}
+
+impl Token {
+    /// Whether the token is the given keyword, in any case
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.value.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Whether the token is the given operator or punctuation
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.value == symbol
+    }
+}
+
+/// Split a SQL statement into tokens, skipping whitespace and comments
+pub fn tokenize(sql: &str) -> Result<Vec<Token>, SqlParseError> {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i];
+        match c {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = match sql[i + 2..].find("*/") {
+                    Some(end) => i + 2 + end + 2,
+                    None => bytes.len(),
+                };
+                continue;
+            }
+            b'\'' | b'"' | b'`' | b'[' => {
+                let close = if c == b'[' { b']' } else { c };
+                let (value, end) = read_quoted(sql, i, close)?;
+                let kind = if c == b'\'' {
+                    TokenKind::String
+                } else {
+                    TokenKind::Quoted
+                };
+                tokens.push(Token {
+                    kind,
+                    value,
+                    start,
+                    end,
+                });
+                i = end;
+            }
+            b'x' | b'X' if bytes.get(i + 1) == Some(&b'\'') => {
+                let (value, end) = read_quoted(sql, i + 1, b'\'')?;
+                tokens.push(Token {
+                    kind: TokenKind::Blob,
+                    value,
+                    start,
+                    end,
+                });
+                i = end;
+            }
+            c if c.is_ascii_digit()
+                || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit)) =>
+            {
+                i += 1;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'0'..=b'9' | b'.' | b'a'..=b'z' | b'A'..=b'Z' | b'_' => i += 1,
+                        b'+' | b'-' if matches!(bytes[i - 1], b'e' | b'E') => i += 1,
+                        _ => break,
+                    }
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number,
+                    value: sql[start..i].to_owned(),
+                    start,
+                    end: i,
+                });
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' || c >= 0x80 => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric()
+                        || bytes[i] == b'_'
+                        || bytes[i] == b'$'
+                        || bytes[i] >= 0x80)
+                {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Word,
+                    value: sql[start..i].to_owned(),
+                    start,
+                    end: i,
+                });
+            }
+            _ => {
+                let two = sql.get(i..i + 2).unwrap_or("");
+                let len = match two {
+                    "<=" | ">=" | "!=" | "<>" | "==" | "||" | "<<" | ">>" | "->" => {
+                        if sql.get(i..i + 3) == Some("->>") {
+                            3
+                        } else {
+                            2
+                        }
+                    }
+                    _ => sql[i..].chars().next().map_or(1, char::len_utf8),
+                };
+                i += len;
+                tokens.push(Token {
+                    kind: TokenKind::Symbol,
+                    value: sql[start..i].to_owned(),
+                    start,
+                    end: i,
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Read a quoted token starting at `open`, returning the unescaped contents and the end offset.
+// A doubled closing character inside the quotes stands for one literal character.
+fn read_quoted(sql: &str, open: usize, close: u8) -> Result<(String, usize), SqlParseError> {
+    let bytes = sql.as_bytes();
+    let mut value = String::new();
+    let mut i = open + 1;
+    let mut chunk_start = i;
+
+    while i < bytes.len() {
+        if bytes[i] == close {
+            value.push_str(&sql[chunk_start..i]);
+            if close != b']' && bytes.get(i + 1) == Some(&close) {
+                i += 2;
+                chunk_start = i - 1;
+                continue;
+            }
+            return Ok((value, i + 1));
+        }
+        i += 1;
+    }
+
+    Err(SqlParseError::new(&format!(
+        "unterminated quoted token starting at offset {}",
+        open
+    )))
+}
+
+/// A cursor over the tokens of a single statement with helpers for recursive-descent parsing
+#[derive(Debug)]
+pub struct TokenStream<'a> {
+    sql: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl<'a> TokenStream<'a> {
+    /// Tokenize a statement and start at its first token
+    pub fn new(sql: &'a str) -> Result<Self, SqlParseError> {
+        Ok(Self {
+            sql,
+            tokens: tokenize(sql)?,
+            position: 0,
+        })
+    }
+
+    /// Source text between two byte offsets, e.g. the `start` of one token and `end` of another
+    pub fn source(&self, start: usize, end: usize) -> &'a str {
+        &self.sql[start..end]
+    }
+
+    /// The next token, without moving past it
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// The token `n` places after the next one, without moving
+    pub fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.position + n)
+    }
+
+    /// The next token, moving past it
+    pub fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Whether the statement has ended, at its end or a `;`
+    pub fn is_at_end(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(token) => token.is_symbol(";"),
+        }
+    }
+
+    /// Whether the next token is the given keyword
+    pub fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    /// Whether the next token is the given symbol
+    pub fn peek_symbol(&self, symbol: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_symbol(symbol))
+    }
+
+    /// Move past the next token if it is the given keyword, returning whether it was
+    pub fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move past the next tokens if they are the given keywords in order, returning whether they
+    /// were
+    pub fn eat_keywords(&mut self, keywords: &[&str]) -> bool {
+        let matches = keywords
+            .iter()
+            .enumerate()
+            .all(|(i, kw)| self.peek_nth(i).is_some_and(|t| t.is_keyword(kw)));
+        if matches {
+            self.position += keywords.len();
+        }
+        matches
+    }
+
+    /// Move past the next token if it is the given symbol, returning whether it was
+    pub fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if self.peek_symbol(symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move past the given keyword, failing if the next token is anything else
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), SqlParseError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    /// Move past the given symbol, failing if the next token is anything else
+    pub fn expect_symbol(&mut self, symbol: &str) -> Result<(), SqlParseError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", symbol)))
+        }
+    }
+
+    /// Read an identifier in any of SQLite's quoting styles. SQLite also accepts a
+    /// single-quoted string where an identifier is expected, so that is allowed too.
+    pub fn identifier(&mut self) -> Result<String, SqlParseError> {
+        match self.peek() {
+            Some(token)
+                if matches!(
+                    token.kind,
+                    TokenKind::Word | TokenKind::Quoted | TokenKind::String
+                ) =>
+            {
+                let value = token.value.clone();
+                self.position += 1;
+                Ok(value)
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    /// Read a possibly schema-qualified name (`schema.name`), returning only the object name
+    pub fn qualified_name(&mut self) -> Result<String, SqlParseError> {
+        let name = self.identifier()?;
+        if self.eat_symbol(".") {
+            return self.identifier();
+        }
+        Ok(name)
+    }
+
+    /// Skip a balanced parenthesized group; the stream must be positioned on the `(`
+    pub fn skip_parenthesized(&mut self) -> Result<(), SqlParseError> {
+        self.expect_symbol("(")?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next_token() {
+                Some(token) if token.is_symbol("(") => depth += 1,
+                Some(token) if token.is_symbol(")") => depth -= 1,
+                Some(_) => {}
+                None => return Err(SqlParseError::new("unbalanced parentheses")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Advance until the predicate matches a token at parenthesis depth 0 (or the statement
+    /// ends), returning the source text that was skipped with surrounding whitespace trimmed.
+    pub fn capture_until<F>(&mut self, stop: F) -> Result<String, SqlParseError>
+    where
+        F: Fn(&Token) -> bool,
+    {
+        let start = self.peek().map_or(self.sql.len(), |t| t.start);
+        let mut end = start;
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            if depth == 0 && (stop(token) || token.is_symbol(";")) {
+                break;
+            }
+            if token.is_symbol("(") {
+                depth += 1;
+            } else if token.is_symbol(")") {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            end = token.end;
+            self.position += 1;
+        }
+        if depth != 0 {
+            return Err(SqlParseError::new("unbalanced parentheses"));
+        }
+        Ok(self.sql[start..end].trim().to_owned())
+    }
+
+    fn unexpected(&self, expected: &str) -> SqlParseError {
+        match self.peek() {
+            Some(token) => SqlParseError::new(&format!(
+                "expected {} but found `{}` at offset {}",
+                expected, token.value, token.start
+            )),
+            None => SqlParseError::new(&format!("expected {} but reached end of input", expected)),
+        }
+    }
+}
+
+/// Split an expression into its top-level AND terms. Each term is reduced to a sequence of
+/// normalized tokens so terms can be compared regardless of spacing, keyword case, identifier
+/// quoting, or redundant outer parentheses. OR binds more loosely than AND, so an expression
+/// with a top-level OR is a single term: `a AND b OR c` does not imply `a`.
+pub fn split_conjuncts(expr: &str) -> Result<Vec<Vec<String>>, SqlParseError> {
+    let mut terms = vec![];
+    let mut current: Vec<&Token> = vec![];
+    let mut depth = 0;
+    let mut in_between = false; // the AND of `x BETWEEN a AND b` does not separate terms
+    let mut top_level_or = false;
+    let tokens = tokenize(expr)?;
+    for token in &tokens {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+        } else if depth == 0 && token.is_keyword("BETWEEN") {
+            in_between = true;
+        } else if depth == 0 && token.is_keyword("OR") {
+            top_level_or = true;
+        }
+        if depth == 0 && token.is_keyword("AND") && !std::mem::take(&mut in_between) {
+            terms.push(std::mem::take(&mut current));
+        } else {
+            current.push(token);
+        }
+    }
+    terms.push(current);
+    if top_level_or {
+        terms = vec![tokens.iter().collect()];
+    }
+
+    Ok(terms
+        .into_iter()
+        .map(|mut term| {
+            while term.len() >= 2
+                && term[0].is_symbol("(")
+                && term[term.len() - 1].is_symbol(")")
+                && encloses(&term)
+            {
+                term = term[1..term.len() - 1].to_vec();
+            }
+            term.iter().map(|t| normalize_token(t)).collect()
+        })
+        .filter(|term: &Vec<String>| !term.is_empty())
+        .collect())
+}
+
+// Whether the opening parenthesis of the term is matched by its final token
+fn encloses(term: &[&Token]) -> bool {
+    let mut depth = 0;
+    for (i, token) in term.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+            if depth == 0 {
+                return i == term.len() - 1;
+            }
+        }
+    }
+    false
+}
+
+fn normalize_token(token: &Token) -> String {
+    match token.kind {
+        TokenKind::Word | TokenKind::Quoted => normalize_identifier(&token.value),
+        TokenKind::String => format!("'{}'", token.value.replace('\'', "''")),
+        TokenKind::Blob => format!("x'{}'", token.value.to_ascii_lowercase()),
+        TokenKind::Number => token.value.clone(),
+        TokenKind::Symbol => match token.value.as_str() {
+            "==" => "=".to_owned(),
+            "<>" => "!=".to_owned(),
+            other => other.to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_fold_ascii_case_only() {
+        assert!(identifiers_match("users", "USERS"));
+        assert!(identifiers_match("Last_Name", "last_NAME"));
+        // SQLite accepts a table with columns é and É, and tells them apart
+        assert!(!identifiers_match("é", "É"));
+        assert!(!identifiers_match("straße", "STRASSE"));
+        assert_eq!(normalize_identifier("ÉtAt"), "État");
+    }
+
+    #[test]
+    fn string_terms_keep_their_quotes_escaped() {
+        let terms = split_conjuncts("name = 'it''s' AND b = ''''").unwrap();
+        assert_eq!(terms, [["name", "=", "'it''s'"], ["b", "=", "''''"]]);
+        // so that a term can tell a quote inside a string from the end of it
+        assert_ne!(
+            split_conjuncts("a = 'x'' OR b = ''y'").unwrap(),
+            split_conjuncts("a = 'x' OR b = 'y'").unwrap()
+        );
+    }
+}
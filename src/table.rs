@@ -0,0 +1,212 @@
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+use crate::db::Database;
+use crate::error::SqrliteError;
+use crate::index_btree::read_index_entries;
+use crate::query::describe_row;
+use crate::record::{FieldData, NamedRecord, RowLayout};
+use crate::scan::{leaf_rowid, leaf_values, SeekResult, TableCursor, TableScan};
+use crate::schema::{IndexDef, SchemaError, TableDef};
+
+/// No table has the name asked for
+#[derive(Debug)]
+pub struct NoSuchTable {
+    /// the name asked for
+    pub name: String,
+}
+
+impl fmt::Display for NoSuchTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no such table: {}", self.name)
+    }
+}
+
+impl Error for NoSuchTable {}
+
+/// A table of the database with everything needed to read it, so that reading rows does not
+/// mean dealing with b-trees. Got from [`Database::table`] or [`Database::tables`].
+///
+/// ```
+/// # fn main() -> Result<(), sqrlite::SqrliteError> {
+/// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+/// let table = db.table("readings")?;
+/// let columns: Vec<&str> = table.def.columns.iter().map(|c| c.name.as_str()).collect();
+/// assert_eq!(columns, ["id", "sensor", "value", "note"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TableHandle<'a> {
+    db: &'a Database,
+    /// the table's name as the schema spells it
+    pub name: String,
+    /// root page of the table's b-tree
+    pub rootpage: u32,
+    /// the columns and constraints of the table, parsed from its CREATE TABLE statement
+    pub def: TableDef,
+}
+
+impl<'a> TableHandle<'a> {
+    pub(crate) fn new(db: &'a Database, rootpage: u32, def: TableDef) -> Self {
+        Self {
+            db,
+            name: def.name.clone(),
+            rootpage,
+            def,
+        }
+    }
+
+    /// Every row, in rowid order or in primary key order for a WITHOUT ROWID table. Rows of an
+    /// ordinary table are read as the iterator is advanced.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// use sqrlite::FieldData;
+    ///
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// let mut total = 0.0;
+    /// for row in db.table("readings")?.rows() {
+    ///     let row = row?;
+    ///     if let Some(value) = row.get("value").and_then(FieldData::as_f64) {
+    ///         total += value;
+    ///     }
+    /// }
+    /// assert_eq!(total, 0.25 * (5000.0 * 5001.0 / 2.0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rows(&self) -> Box<dyn Iterator<Item = Result<NamedRecord, SqrliteError>> + 'a> {
+        if !self.def.without_rowid {
+            return Box::new(TableScan::new(self.db, self.rootpage).named_rows(&self.def));
+        }
+        let layout = RowLayout::new(&self.def);
+        match read_index_entries(self.db, self.rootpage) {
+            Ok(entries) => Box::new(
+                entries
+                    .into_iter()
+                    .map(move |values| Ok(layout.row(None, values))),
+            ),
+            Err(e) => Box::new(iter::once(Err(e))),
+        }
+    }
+
+    /// Number of rows, counted without decoding them where the table has rowids.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// assert_eq!(db.table("readings")?.count()?, 5000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn count(&self) -> Result<u64, SqrliteError> {
+        if self.def.without_rowid {
+            return Ok(read_index_entries(self.db, self.rootpage)?.len() as u64);
+        }
+        let mut scan = TableScan::new(self.db, self.rootpage);
+        let mut count = 0;
+        while let Some(row) = scan.next_with(leaf_rowid) {
+            row?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The row with the given rowid, if there is one. WITHOUT ROWID tables have no rowids;
+    /// their rows are found by primary key with [`lookup_primary_key`].
+    ///
+    /// ```
+    /// # fn main() -> Result<(), sqrlite::SqrliteError> {
+    /// use sqrlite::FieldData;
+    ///
+    /// let db = sqrlite::Database::new("tests/fixtures/scan.db")?;
+    /// let table = db.table("readings")?;
+    /// let row = table.get(50)?.expect("row 50 exists");
+    /// assert_eq!(row.get("sensor").and_then(FieldData::as_str), Some("sensor-16"));
+    /// assert!(table.get(5001)?.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`lookup_primary_key`]: crate::query::lookup_primary_key
+    pub fn get(&self, rowid: i64) -> Result<Option<NamedRecord>, SqrliteError> {
+        if self.def.without_rowid {
+            return Err(SqrliteError::Unsupported(format!(
+                "`{}` is a WITHOUT ROWID table",
+                self.name
+            )));
+        }
+        let mut cursor = TableCursor::new(self.db, self.rootpage);
+        if cursor.seek_rowid(rowid)? != SeekResult::Exact {
+            return Ok(None);
+        }
+        let (_, values) = cursor.read_current(leaf_values)?;
+        Ok(Some(RowLayout::new(&self.def).row(Some(rowid), values)))
+    }
+
+    /// Every row of a STRICT table holding a value its column's declared type does not
+    /// allow, one error for each such row. SQLite enforces the types on write, so any found
+    /// mean the file is corrupt. Other tables have nothing to check.
+    pub fn check_strict_types(&self) -> Result<Vec<SchemaError>, SqrliteError> {
+        let mut mismatches = vec![];
+        if !self.def.strict {
+            return Ok(mismatches);
+        }
+        let mut check = |rowid, values: Vec<FieldData>| {
+            let row = describe_row(&self.def, rowid, &values);
+            if let Err(e) = self.def.check_strict_types(&row, &values) {
+                mismatches.push(e);
+            }
+        };
+        if self.def.without_rowid {
+            for values in read_index_entries(self.db, self.rootpage)? {
+                check(None, values);
+            }
+        } else {
+            let mut scan = TableScan::new(self.db, self.rootpage);
+            while let Some(row) = scan.next_with(leaf_values) {
+                let (rowid, values) = row?;
+                check(Some(rowid), values);
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+/// An index of the database, with its definition. Automatic indexes, made by SQLite for
+/// PRIMARY KEY and UNIQUE constraints, have a definition worked out from their table's.
+#[derive(Debug, Clone)]
+pub struct IndexHandle<'a> {
+    db: &'a Database,
+    /// the index's name as the schema spells it
+    pub name: String,
+    /// root page of the index's b-tree
+    pub rootpage: u32,
+    /// the table and columns indexed, parsed from the CREATE INDEX statement or worked out
+    /// from the table for an automatic index
+    pub def: IndexDef,
+}
+
+impl<'a> IndexHandle<'a> {
+    pub(crate) fn new(db: &'a Database, rootpage: u32, def: IndexDef) -> Self {
+        Self {
+            db,
+            name: def.name.clone(),
+            rootpage,
+            def,
+        }
+    }
+
+    /// The table the index is on
+    pub fn table(&self) -> Result<TableHandle<'a>, SqrliteError> {
+        self.db.table(&self.def.table)
+    }
+
+    /// Every entry of the index in index order: the indexed values followed by the rowid, or
+    /// by the primary key columns not already indexed for a WITHOUT ROWID table
+    pub fn entries(&self) -> Result<Vec<Vec<FieldData>>, SqrliteError> {
+        read_index_entries(self.db, self.rootpage)
+    }
+}
@@ -2,75 +2,357 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io::{self, Read};
 
+/// The input ended in the middle of a varint: every byte had its high bit set, asking for
+/// another. Nothing else makes a varint invalid, since a ninth byte always ends it.
 #[derive(Debug)]
-pub struct MaxBytesExceededError {
-    details: &'static str,
+pub struct TruncatedVarintError {
+    /// bytes available before the input ended
+    pub len: usize,
 }
 
-impl MaxBytesExceededError {
-    fn new() -> Self {
-        Self {
-            details: "Input is invalid for this varint:\n\
-                For valid u64 values, the maximum number of bytes must be 9 or less and the last \
-                byte must not have a continuation flag (its value must be < 0x80).",
-        }
+impl fmt::Display for TruncatedVarintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "varint is truncated: the input ends after {} continuation bytes",
+            self.len
+        )
     }
 }
 
-impl fmt::Display for MaxBytesExceededError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl Error for TruncatedVarintError {}
+
+/// The number of bytes in the varint of `value`: 7 bits per byte up to 56 bits, and nine
+/// bytes for anything larger
+pub fn varint_len(value: u64) -> usize {
+    if value >> 56 != 0 {
+        9
+    } else {
+        (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
     }
 }
 
-impl Error for MaxBytesExceededError {}
+/// Encode an unsigned integer to a big-endian varint at the start of `buf`, returning the
+/// number of bytes written. Values of up to 56 bits take 7 bits per byte; larger ones take
+/// nine bytes, the last holding 8 bits.
+pub fn encode_into(value: u64, buf: &mut [u8; 9]) -> usize {
+    let len = varint_len(value);
+    let mut rest = value;
+    if len == 9 {
+        buf[8] = rest as u8;
+        rest >>= 8;
+    }
+    for idx in (0..len.min(8)).rev() {
+        let continues = idx + 1 < len;
+        buf[idx] = (rest & 0x7f) as u8 | if continues { 0x80 } else { 0 };
+        rest >>= 7;
+    }
+    len
+}
 
-// Encode an unsigned integer up to 64 bits in size to a big-endian varint
+/// Encode an unsigned integer to a big-endian varint in a new vector
 pub fn encode_be<T>(value: T) -> (usize, Vec<u8>)
 where
     T: Into<u64>,
 {
-    let value_64bit: u64 = value.into();
-
-    let result: Vec<u8> = (0..64)
-        .step_by(7)
-        .rev()
-        .filter_map(|shift| {
-            let byte_value = ((value_64bit >> shift) & 0x7f) as u8;
-            if byte_value != 0 || shift == 0 {
-                Some(if shift == 0 {
-                    byte_value
-                } else {
-                    byte_value | 0x80
-                })
-            } else {
-                None
-            }
-        })
-        .collect();
+    let mut buf = [0; 9];
+    let len = encode_into(value.into(), &mut buf);
+    (len, buf[..len].to_vec())
+}
 
-    (result.len(), result)
+// Append the varint of `value` to `out` without allocating a vector for it
+pub(crate) fn push_varint(out: &mut Vec<u8>, value: u64) {
+    let mut buf = [0; 9];
+    let len = encode_into(value, &mut buf);
+    out.extend_from_slice(&buf[..len]);
 }
 
-// Read a big-endian varint from a slice of bytes
-pub fn decode_be(input: &[u8]) -> Result<(u64, usize), MaxBytesExceededError> {
-    let mut result = 0u64;
-    let mut position = 0;
+/// Encode a signed integer, such as a rowid, as the varint of its two's complement bits
+pub fn encode_be_i64(value: i64) -> (usize, Vec<u8>) {
+    encode_be(value as u64)
+}
+
+/// Read a big-endian varint from a slice of bytes. The first eight bytes each contribute
+/// their low 7 bits while their high bit is set; a ninth byte contributes all 8 of its bits.
+pub fn decode_be(input: &[u8]) -> Result<(u64, usize), TruncatedVarintError> {
+    // one and two byte varints, which hold nearly every serial type, header size and cell
+    // size, skip the loop
+    match *input {
+        [b0, ..] if b0 <= 0x7f => return Ok((u64::from(b0), 1)),
+        [b0, b1, ..] if b1 <= 0x7f => {
+            return Ok(((u64::from(b0 & 0x7f) << 7) | u64::from(b1), 2));
+        }
+        _ => {}
+    }
+    // with nine bytes available the varint must end within them, and indexing a fixed-size
+    // window needs no bounds checks
+    if let Some(window) = input.first_chunk::<9>() {
+        return Ok(decode_window(window));
+    }
 
+    let mut result = 0u64;
     for (idx, &byte) in input.iter().enumerate() {
-        // If MSB is set, keep accumulating up to max bytes
-        if byte > 0x7f {
-            if position > 7 {
-                return Err(MaxBytesExceededError::new());
+        result = (result << 7) | u64::from(byte & 0x7f);
+        if byte <= 0x7f {
+            return Ok((result, idx + 1));
+        }
+    }
+    // the input ended while the high bit still called for more bytes
+    Err(TruncatedVarintError { len: input.len() })
+}
+
+fn decode_window(window: &[u8; 9]) -> (u64, usize) {
+    let mut result = 0u64;
+    for (idx, &byte) in window[..8].iter().enumerate() {
+        result = (result << 7) | u64::from(byte & 0x7f);
+        if byte <= 0x7f {
+            return (result, idx + 1);
+        }
+    }
+    ((result << 8) | u64::from(window[8]), 9)
+}
+
+/// Read a big-endian varint from a reader one byte at a time, never reading past its end.
+/// Input ending mid-varint fails with UnexpectedEof carrying a TruncatedVarintError.
+pub fn decode_from<R: Read>(reader: &mut R) -> io::Result<(u64, usize)> {
+    // `read` is the number of bytes read before this one
+    let mut next_byte = |read: usize| {
+        let mut byte = [0];
+        match reader.read_exact(&mut byte) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                TruncatedVarintError { len: read },
+            )),
+            result => result.map(|_| byte[0]),
+        }
+    };
+    let mut result = 0u64;
+    for idx in 0..8 {
+        let byte = next_byte(idx)?;
+        result = (result << 7) | u64::from(byte & 0x7f);
+        if byte <= 0x7f {
+            return Ok((result, idx + 1));
+        }
+    }
+    Ok(((result << 8) | u64::from(next_byte(8)?), 9))
+}
+
+/// Read a varint holding a signed integer, such as a rowid, stored as the two's complement
+/// of its 64 bits
+pub fn decode_be_i64(input: &[u8]) -> Result<(i64, usize), TruncatedVarintError> {
+    decode_be(input).map(|(value, len)| (value as i64, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn unsigned_round_trip(value: u64) {
+            let (len, bytes) = encode_be(value);
+            prop_assert_eq!(len, bytes.len());
+            prop_assert_eq!(len, varint_len(value));
+            prop_assert_eq!(decode_be(&bytes).unwrap(), (value, len));
+            prop_assert_eq!(decode_from(&mut &bytes[..]).unwrap(), (value, len));
+        }
+
+        #[test]
+        fn signed_round_trip(value: i64) {
+            let (len, bytes) = encode_be_i64(value);
+            prop_assert_eq!(decode_be_i64(&bytes).unwrap(), (value, len));
+        }
+
+        // trailing bytes after a varint are left alone
+        #[test]
+        fn round_trip_with_trailing_bytes(value: u64, tail in prop::collection::vec(any::<u8>(), 0..16)) {
+            let (len, mut bytes) = encode_be(value);
+            bytes.extend_from_slice(&tail);
+            prop_assert_eq!(decode_be(&bytes).unwrap(), (value, len));
+        }
+
+        // Arbitrary bytes either decode from at most 9 of them, agreeing with the reader
+        // version and re-encoding to the bytes consumed, or fail only when every byte
+        // available asked for another
+        #[test]
+        fn decode_arbitrary_bytes(input in prop::collection::vec(any::<u8>(), 0..24)) {
+            match decode_be(&input) {
+                Ok((value, len)) => {
+                    prop_assert!((1..=9).contains(&len));
+                    prop_assert!(len <= input.len());
+                    prop_assert_eq!(decode_from(&mut &input[..]).unwrap(), (value, len));
+                    // a varint with leading 0x80 bytes is a longer spelling of a smaller one
+                    prop_assert!(varint_len(value) <= len);
+                    let padding = input[..len].iter().take_while(|&&b| b == 0x80).count();
+                    if padding == 0 {
+                        prop_assert_eq!(encode_be(value).1, &input[..len]);
+                    }
+                }
+                Err(TruncatedVarintError { len }) => {
+                    prop_assert!(input.len() < 9);
+                    prop_assert_eq!(len, input.len());
+                    prop_assert!(input.iter().all(|&b| b > 0x7f));
+                }
             }
-            result = (result << 7) | u64::from(byte & 0x7f);
-        } else {
-            result = (result << 7) | u64::from(byte);
-            position = idx;
-            break;
         }
     }
 
-    Ok((result, position + 1))
+    // The largest value of eight bytes, then nine-byte values: the smallest, the smallest with
+    // the top bit set, the largest, and two whose ninth byte uses its high bit. The bytes were
+    // read back out of the rowids of cells written by sqlite3.
+    #[test]
+    fn nine_byte_boundaries() {
+        let cases: [(u64, &[u8]); 6] = [
+            (
+                (1 << 56) - 1,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            ),
+            (
+                1 << 56,
+                &[0x80, 0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            ),
+            (
+                1 << 63,
+                &[0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            ),
+            (u64::MAX, &[0xff; 9]),
+            // the ninth byte carries all 8 of its bits, high bit included
+            (
+                (1 << 56) | 0xa5,
+                &[0x80, 0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xa5],
+            ),
+            (
+                (1 << 63) | 0xff,
+                &[0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xff],
+            ),
+        ];
+        for (value, bytes) in cases {
+            assert_eq!(
+                encode_be(value),
+                (bytes.len(), bytes.to_vec()),
+                "{value:#x}"
+            );
+            assert_eq!(
+                decode_be(bytes).unwrap(),
+                (value, bytes.len()),
+                "{value:#x}"
+            );
+            assert_eq!(decode_from(&mut &bytes[..]).unwrap(), (value, bytes.len()));
+        }
+    }
+
+    #[test]
+    fn lengths_at_each_seven_bit_boundary() {
+        for len in 1..=8 {
+            let largest = (1u64 << (7 * len)) - 1;
+            assert_eq!(varint_len(largest), len);
+            assert_eq!(decode_be(&encode_be(largest).1).unwrap(), (largest, len));
+            assert_eq!(varint_len(largest + 1), len + 1);
+            assert_eq!(
+                decode_be(&encode_be(largest + 1).1).unwrap(),
+                (largest + 1, len + 1)
+            );
+        }
+    }
+
+    // Negative keys set the top bit, so they always take all nine bytes
+    #[test]
+    fn signed_boundaries() {
+        let cases: [(i64, &[u8]); 4] = [
+            (-1, &[0xff; 9]),
+            (
+                i64::MIN,
+                &[0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            ),
+            (
+                i64::MAX,
+                &[0xbf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (0, &[0x00]),
+        ];
+        for (value, bytes) in cases {
+            assert_eq!(
+                encode_be_i64(value),
+                (bytes.len(), bytes.to_vec()),
+                "{value}"
+            );
+            assert_eq!(
+                decode_be_i64(bytes).unwrap(),
+                (value, bytes.len()),
+                "{value}"
+            );
+        }
+    }
+
+    // Keys read back as signed order below zero, where the unsigned bits would not
+    #[test]
+    fn signed_keys_order_as_signed() {
+        let mut keys = [i64::MAX, -1, 0, i64::MIN, 1, -300, 300];
+        let mut decoded: Vec<i64> = keys
+            .iter()
+            .map(|&key| decode_be_i64(&encode_be_i64(key).1).unwrap().0)
+            .collect();
+        decoded.sort();
+        keys.sort();
+        assert_eq!(decoded, keys);
+        assert_eq!(decoded[..3], [i64::MIN, -300, -1]);
+    }
+
+    #[test]
+    fn truncated_varints() {
+        for input in [&[][..], &[0x80], &[0xff; 8], &[0x81, 0x80, 0xff]] {
+            let err = decode_be(input).unwrap_err();
+            assert_eq!(err.len, input.len());
+            assert!(decode_be_i64(input).is_err());
+        }
+        assert_eq!(
+            decode_be(&[0xff; 8]).unwrap_err().to_string(),
+            "varint is truncated: the input ends after 8 continuation bytes"
+        );
+        // a ninth byte ends the varint whatever its high bit
+        assert_eq!(decode_be(&[0xff; 9]).unwrap(), (u64::MAX, 9));
+        assert_eq!(decode_be(&[0x80, 0x00]).unwrap(), (0, 2));
+    }
+
+    #[test]
+    fn reading_payloads_cut_at_every_byte() {
+        use std::io::Cursor;
+
+        let values = [0, 0x7f, 0x80, 0x3fff, 0x4000, 1 << 35, 1 << 56, u64::MAX];
+        let payload = values
+            .iter()
+            .flat_map(|&value| encode_be(value).1)
+            .collect::<Vec<_>>();
+        for cut in 0..=payload.len() {
+            let mut reader = Cursor::new(&payload[..cut]);
+            let mut read = vec![];
+            let err = loop {
+                let start = reader.position() as usize;
+                match decode_from(&mut reader) {
+                    Ok((value, len)) => {
+                        assert_eq!(reader.position() as usize, start + len);
+                        read.push(value);
+                    }
+                    Err(err) => break (err, start),
+                }
+            };
+            // every varint wholly before the cut is read, and the one it splits is truncated
+            assert_eq!(read, values[..read.len()]);
+            let (err, start) = err;
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            let truncated = err
+                .into_inner()
+                .unwrap()
+                .downcast::<TruncatedVarintError>()
+                .unwrap();
+            assert_eq!(truncated.len, cut - start);
+            assert_eq!(
+                decode_be(&payload[start..cut]).unwrap_err().len,
+                cut - start
+            );
+        }
+    }
 }
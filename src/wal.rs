@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::SqrliteError;
+use crate::page_source::read_file_at;
+
+/// Size of the header at the start of a -wal file
+pub const WAL_HEADER_SIZE: usize = 32;
+/// Size of the header before the page image in each frame
+pub const WAL_FRAME_HEADER_SIZE: usize = 24;
+// the low bit of the magic number tells whether checksums are computed on big-endian words
+const WAL_MAGIC: u32 = 0x377f0682;
+const WAL_FORMAT_VERSION: u32 = 3007000;
+
+/// A -wal file that cannot be read, or whose frames are inconsistent
+#[derive(Debug)]
+pub struct WalError {
+    details: String,
+}
+
+impl WalError {
+    pub(crate) fn new(details: &str) -> Self {
+        Self {
+            details: details.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for WalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for WalError {}
+
+/// The 32-byte header at the start of a -wal file, as described in
+/// <https://www.sqlite.org/fileformat2.html#wal_file_format>
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WalHeader {
+    /// 0x377f0682, or 0x377f0683 when checksums are computed on big-endian words
+    pub magic: u32,
+    /// WAL format version, 3007000
+    pub format_version: u32,
+    /// page size of the database
+    pub page_size: u32,
+    /// incremented with each checkpoint
+    pub checkpoint_seq: u32,
+    /// random values copied into every frame; a frame with other salts is left over from
+    /// before the WAL was last restarted
+    pub salt1: u32,
+    /// the second salt
+    pub salt2: u32,
+    /// first half of the header's checksum
+    pub checksum1: u32,
+    /// second half of the header's checksum
+    pub checksum2: u32,
+}
+
+impl WalHeader {
+    /// Parse the header, or None if it is not one SQLite would use. SQLite treats a WAL
+    /// without a valid header as empty rather than as an error.
+    pub fn parse(bytes: &[u8; WAL_HEADER_SIZE]) -> Option<Self> {
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        let magic = u32_at(0);
+        if magic & !1 != WAL_MAGIC || u32_at(4) != WAL_FORMAT_VERSION {
+            return None;
+        }
+        let page_size = u32_at(8);
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return None;
+        }
+        if checksum((0, 0), &bytes[..24], magic & 1 == 1) != (u32_at(24), u32_at(28)) {
+            return None;
+        }
+        Some(Self {
+            magic,
+            format_version: u32_at(4),
+            page_size,
+            checkpoint_seq: u32_at(12),
+            salt1: u32_at(16),
+            salt2: u32_at(20),
+            checksum1: u32_at(24),
+            checksum2: u32_at(28),
+        })
+    }
+
+    /// Whether frame checksums are computed on big-endian words, as the magic number's low bit says
+    pub fn big_endian_checksums(&self) -> bool {
+        self.magic & 1 == 1
+    }
+}
+
+/// The committed contents of a -wal file: for each page written by a committed transaction,
+/// where its latest image is. Frames after the last commit frame belong to a transaction that
+/// was still being written and are ignored, as SQLite does.
+#[derive(Debug)]
+pub struct WalIndex {
+    /// the WAL's header
+    pub header: WalHeader,
+    file: File,
+    frames: HashMap<u32, u64>, // page number to file offset of the page image
+    /// number of pages in the database as of the last commit, 0 if no frame was committed
+    pub database_size: u32,
+    /// frames up to and including the last commit frame
+    pub committed_frames: u32,
+    /// complete frames in the file
+    pub frame_count: u32,
+    /// frames leading up to the first invalid one
+    pub valid_frames: u32,
+}
+
+/// A summary of the WAL, for display
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WalInfo {
+    /// page size of the database
+    pub page_size: u32,
+    /// incremented with each checkpoint
+    pub checkpoint_seq: u32,
+    /// the first salt
+    pub salt1: u32,
+    /// the second salt
+    pub salt2: u32,
+    /// whether checksums are computed on big-endian words
+    pub big_endian_checksums: bool,
+    /// complete frames in the file
+    pub frame_count: u32,
+    /// frames leading up to the first invalid one
+    pub valid_frames: u32,
+    /// frames up to and including the last commit frame
+    pub committed_frames: u32,
+    /// number of pages in the database as of the last commit
+    pub database_size: u32,
+}
+
+impl WalIndex {
+    /// Read the -wal file at `path`, if there is one holding a valid header
+    pub fn open(path: &Path) -> Result<Option<Self>, SqrliteError> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                let context = format!("cannot open {}", path.display());
+                return Err(SqrliteError::io(context, e));
+            }
+        };
+        let mut bytes = [0; WAL_HEADER_SIZE];
+        match file.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(SqrliteError::io("error reading the WAL header", e)),
+        }
+        let header = match WalHeader::parse(&bytes) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let mut index = Self {
+            header,
+            file,
+            frames: HashMap::new(),
+            database_size: 0,
+            committed_frames: 0,
+            frame_count: 0,
+            valid_frames: 0,
+        };
+        index.read_frames()?;
+        Ok(Some(index))
+    }
+
+    // Index the frames in file order, stopping at the first that does not belong to the
+    // current WAL generation or fails its checksum. Each frame's checksum continues from the
+    // one before, so after a torn write none of the later frames can be trusted either.
+    fn read_frames(&mut self) -> Result<(), SqrliteError> {
+        let page_size = self.header.page_size as usize;
+        let frame_size = (WAL_FRAME_HEADER_SIZE + page_size) as u64;
+        let file_size = self.file.metadata()?.len();
+        // a frame cut short by the end of the file was never completely written
+        self.frame_count = ((file_size.saturating_sub(WAL_HEADER_SIZE as u64)) / frame_size)
+            .try_into()
+            .unwrap_or(u32::MAX);
+
+        let big_endian = self.header.big_endian_checksums();
+        let mut sums = (self.header.checksum1, self.header.checksum2);
+        let mut frame = vec![0; WAL_FRAME_HEADER_SIZE + page_size];
+        let mut offset = WAL_HEADER_SIZE as u64;
+        let mut pending = vec![];
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| SqrliteError::io("error reading the WAL", e))?;
+
+        for frame_num in 1..=self.frame_count {
+            self.file.read_exact(&mut frame).map_err(|e| {
+                SqrliteError::io(format!("error reading WAL frame {}", frame_num), e)
+            })?;
+            let u32_at = |at: usize| {
+                u32::from_be_bytes([frame[at], frame[at + 1], frame[at + 2], frame[at + 3]])
+            };
+            let page = u32_at(0);
+            let database_size = u32_at(4);
+            if page == 0 || u32_at(8) != self.header.salt1 || u32_at(12) != self.header.salt2 {
+                break;
+            }
+            sums = checksum(sums, &frame[..8], big_endian);
+            sums = checksum(sums, &frame[WAL_FRAME_HEADER_SIZE..], big_endian);
+            if sums != (u32_at(16), u32_at(20)) {
+                break;
+            }
+
+            self.valid_frames = frame_num;
+            pending.push((page, offset + WAL_FRAME_HEADER_SIZE as u64));
+            // a commit frame records the size of the database after its transaction
+            if database_size != 0 {
+                self.frames.extend(pending.drain(..));
+                self.database_size = database_size;
+                self.committed_frames = frame_num;
+            }
+            offset += frame_size;
+        }
+        Ok(())
+    }
+
+    /// A summary of the WAL, for display
+    pub fn info(&self) -> WalInfo {
+        WalInfo {
+            page_size: self.header.page_size,
+            checkpoint_seq: self.header.checkpoint_seq,
+            salt1: self.header.salt1,
+            salt2: self.header.salt2,
+            big_endian_checksums: self.header.big_endian_checksums(),
+            frame_count: self.frame_count,
+            valid_frames: self.valid_frames,
+            committed_frames: self.committed_frames,
+            database_size: self.database_size,
+        }
+    }
+
+    /// Whether the WAL holds a committed image of a page
+    pub fn contains(&self, page: u32) -> bool {
+        self.frames.contains_key(&page)
+    }
+
+    /// Numbers of the pages the WAL holds an image of, in no particular order
+    pub fn pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.frames.keys().copied()
+    }
+
+    /// Read the latest committed image of a page into `buffer`, returning false if the WAL
+    /// holds none and the page must be read from the database file
+    pub fn read_page(&self, page: u32, buffer: &mut [u8]) -> Result<bool, SqrliteError> {
+        let offset = match self.frames.get(&page) {
+            Some(&offset) => offset,
+            None => return Ok(false),
+        };
+        if buffer.len() != self.header.page_size as usize {
+            return Err(WalError::new(&format!(
+                "WAL page size {} does not match the database page size {}",
+                self.header.page_size,
+                buffer.len()
+            ))
+            .into());
+        }
+        read_file_at(&self.file, offset, buffer).map_err(|e| {
+            SqrliteError::io(format!("error reading page {} from the WAL", page), e)
+        })?;
+        Ok(true)
+    }
+}
+
+// The cumulative checksum stored in the WAL header and in every frame, continued from `sums`
+// over `bytes` taken as pairs of 32-bit words in the byte order the magic number chooses
+fn checksum(sums: (u32, u32), bytes: &[u8], big_endian: bool) -> (u32, u32) {
+    let word = |b: &[u8]| {
+        let b = [b[0], b[1], b[2], b[3]];
+        if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    };
+    let (mut s1, mut s2) = sums;
+    for pair in bytes.chunks_exact(8) {
+        s1 = s1.wrapping_add(word(&pair[..4])).wrapping_add(s2);
+        s2 = s2.wrapping_add(word(&pair[4..])).wrapping_add(s1);
+    }
+    (s1, s2)
+}
@@ -0,0 +1,62 @@
+// One column of each affinity holding the same values, so the storage classes in a column
+// differ from row to row, and the rows sqlite3 returns for WHERE comparisons against
+// numeric and text literals. Made with
+//
+//     CREATE TABLE mixed(id INTEGER PRIMARY KEY, i INTEGER, r REAL, t TEXT, n NUMERIC, b BLOB);
+//     INSERT INTO mixed(i, r, t, n, b) SELECT column1, column1, column1, column1, column1
+//     FROM (VALUES (42), ('42'), (42.0), ('42.0'), (' 42 '), ('042'), ('4.2e1'), ('abc'),
+//         (x'3432'), (NULL), (4.5), ('4.5'), ('1e2'), (100), ('100abc'), (-7), ('-7'), (''),
+//         ('0x2a'));
+//
+// affinity.txt holds a query and the ids sqlite3 3.51.2 returned for it on each line,
+// separated by a tab, for each column, each of =, < and > and the literals 42, '42', 42.0,
+// 4.5, '4.5', 'abc' and 100.
+
+use std::fs;
+
+use sqrlite::{execute, Database, FieldData, Select};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn open() -> Database {
+    Database::new(format!("{}/affinity.db", FIXTURES)).unwrap()
+}
+
+fn ids(db: &Database, sql: &str) -> Vec<i64> {
+    let result = execute(db, &Select::parse(sql).unwrap()).unwrap();
+    result
+        .rows
+        .iter()
+        .map(|row| row[0].as_i64().unwrap())
+        .collect()
+}
+
+#[test]
+fn storage_classes_are_mixed() {
+    let db = open();
+    let table = db.table("mixed").unwrap();
+    let row = |id| table.get(id).unwrap().unwrap();
+    // '42' was converted on insert by the numeric affinities and kept as text by the others
+    assert_eq!(row(2).get("i"), Some(&FieldData::Integer(42)));
+    assert_eq!(row(2).get("r"), Some(&FieldData::Real(42.0)));
+    assert_eq!(row(2).get("t"), Some(&FieldData::Text("42".into())));
+    assert_eq!(row(2).get("b"), Some(&FieldData::Text("42".into())));
+    // 'abc' cannot be converted, so every column holds it as text
+    assert_eq!(row(8).get("n"), Some(&FieldData::Text("abc".into())));
+    assert_eq!(row(9).get("i"), Some(&FieldData::Blob(b"42".to_vec())));
+}
+
+#[test]
+fn comparisons_match_sqlite() {
+    let db = open();
+    let transcript = fs::read_to_string(format!("{}/affinity.txt", FIXTURES)).unwrap();
+    for line in transcript.lines() {
+        let (sql, expected) = line.split_once('\t').unwrap();
+        let expected = expected
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| id.parse::<i64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids(&db, sql), expected, "{}", sql);
+    }
+}
@@ -0,0 +1,104 @@
+// A database of 64 KiB pages, whose page size the header stores as 1, with a row spilling
+// onto overflow pages. The `.dbinfo` transcript was recorded with SQLite 3.51.2. Made with
+//
+//     PRAGMA page_size = 65536;
+//     CREATE TABLE docs(id INTEGER PRIMARY KEY, title TEXT, body BLOB);
+//     CREATE TABLE tags(doc INTEGER, tag TEXT);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+//     INSERT INTO docs SELECT i, printf('doc %d', i), zeroblob(i * 4) FROM n;
+//     INSERT INTO docs VALUES (501, 'huge', zeroblob(200000));
+//     INSERT INTO tags SELECT id, 'even' FROM docs WHERE id % 2 = 0;
+//
+//     sqlite3 tests/fixtures/big_pages.db .dbinfo > tests/fixtures/big_pages.txt
+
+use std::fs;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::btree_page::BtreePage;
+use sqrlite::{DBInfo, Database, FieldData, ObjectType};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn open() -> Database {
+    Database::new(format!("{}/big_pages.db", FIXTURES)).unwrap()
+}
+
+#[test]
+fn header_page_size_of_one_is_64k() {
+    let db = open();
+    assert_eq!(db.page_size, 65536);
+    assert_eq!(db.page_count, 15);
+    let len = fs::metadata(format!("{}/big_pages.db", FIXTURES))
+        .unwrap()
+        .len();
+    assert_eq!(len, 15 * 65536);
+}
+
+#[test]
+fn dbinfo_matches_the_sqlite_shell() {
+    let db = open();
+    let info = DBInfo::read_info(&db).unwrap();
+    let transcript = fs::read_to_string(format!("{}/big_pages.txt", FIXTURES)).unwrap();
+    let expected = transcript
+        .lines()
+        .filter(|line| !line.starts_with("data version"))
+        .collect::<Vec<_>>();
+    assert_eq!(info.to_string().lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn tables_are_listed() {
+    let db = open();
+    let schema = db.schema().unwrap();
+    let names = schema
+        .objects_of_type(ObjectType::Table)
+        .map(|obj| obj.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["docs", "tags"]);
+}
+
+#[test]
+fn tables_scan_to_the_last_row() {
+    let db = open();
+    let docs = db.table("docs").unwrap();
+    let rows = docs.rows().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 501);
+    for row in &rows {
+        let id = row.rowid.unwrap();
+        let (title, size) = match id {
+            501 => ("huge".to_owned(), 200_000),
+            _ => (format!("doc {}", id), id as usize * 4),
+        };
+        assert_eq!(row.get("title"), Some(&FieldData::Text(title)));
+        assert_eq!(row.get("body"), Some(&FieldData::Blob(vec![0; size])));
+    }
+    // 200000 bytes spill over three 64 KiB pages past the part kept in the cell
+    let stats = tree_stats(&db, docs.rootpage).unwrap();
+    assert_eq!(stats.overflow_pages, 3);
+
+    assert_eq!(db.table("tags").unwrap().count().unwrap(), 250);
+}
+
+// On an empty 64 KiB page the cell content area starts at 65536, which the page header
+// stores as 0. Made with
+//
+//     PRAGMA page_size = 65536;
+//     CREATE TABLE empty(a);
+//     CREATE INDEX empty_a ON empty(a);
+#[test]
+fn empty_pages_start_their_content_at_64k() {
+    let db = Database::new(format!("{}/empty_64k.db", FIXTURES)).unwrap();
+    let mut buffer = vec![];
+    for name in ["empty", "empty_a"] {
+        let root = db.schema().unwrap().find(name).unwrap().rootpage;
+        let mut page = BtreePage::default();
+        page.read_page(&db, root, &mut buffer).unwrap();
+        assert_eq!(&buffer[5..7], [0, 0]);
+        assert_eq!(page.first_cell_start, 65536);
+        assert_eq!(page.free_space(), (0, 0, 65536 - 8));
+        let stats = tree_stats(&db, root).unwrap();
+        assert_eq!(stats.entries, 0);
+        assert!(stats.corruption.is_empty(), "{:?}", stats.corruption);
+    }
+    assert_eq!(db.table("empty").unwrap().count().unwrap(), 0);
+}
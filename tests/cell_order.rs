@@ -0,0 +1,99 @@
+// A leaf whose cells are laid out out of key order, with a freeblock between them, left by
+// deleting rows and inserting shorter ones in their place. Made with
+//
+//     PRAGMA page_size = 1024;
+//     CREATE TABLE t(id INTEGER PRIMARY KEY, body TEXT);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 12)
+//     INSERT INTO t SELECT i, printf('%.*c', 20 + i * 5, char(96 + i)) FROM n;
+//     DELETE FROM t WHERE id IN (3, 6, 9);
+//     INSERT INTO t VALUES (3, 'short'), (9, 'a little longer');
+
+use sqrlite::btree_page::BtreePage;
+use sqrlite::cell::CellRef;
+use sqrlite::varint::varint_len;
+use sqrlite::{Database, FieldData};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/shuffled.db");
+const ROWIDS: [i64; 11] = [1, 2, 3, 4, 5, 7, 8, 9, 10, 11, 12];
+
+fn body(rowid: i64) -> String {
+    match rowid {
+        3 => "short".to_owned(),
+        9 => "a little longer".to_owned(),
+        _ => {
+            let letter = char::from(b'a' + rowid as u8 - 1);
+            letter.to_string().repeat(20 + rowid as usize * 5)
+        }
+    }
+}
+
+fn leaf(db: &Database) -> (BtreePage, Vec<u8>) {
+    let mut page = BtreePage::default();
+    let mut buffer = vec![];
+    page.read_page(db, 2, &mut buffer).unwrap();
+    (page, buffer)
+}
+
+#[test]
+fn cells_are_not_stored_in_key_order() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (page, _) = leaf(&db);
+    assert!(!page.cell_pointers.is_sorted());
+    assert!(!page.cell_pointers.iter().rev().is_sorted());
+    assert!(!page.freeblocks.is_empty());
+}
+
+#[test]
+fn cells_come_in_pointer_order() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (page, buffer) = leaf(&db);
+    let rowids = (0..page.cell_pointers.len())
+        .map(|i| match CellRef::get(&page, &buffer, i).unwrap() {
+            CellRef::LeafTable { row_id, .. } => row_id,
+            other => panic!("unexpected cell {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(rowids, ROWIDS);
+
+    let rows = db.table("t").unwrap().rows();
+    let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        rows.iter()
+            .map(|row| row.rowid.unwrap())
+            .collect::<Vec<_>>(),
+        ROWIDS
+    );
+    for row in rows {
+        let rowid = row.rowid.unwrap();
+        assert_eq!(row.get("body"), Some(&FieldData::Text(body(rowid))));
+    }
+}
+
+#[test]
+fn cell_sizes_leave_out_the_free_space_after_them() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (page, buffer) = leaf(&db);
+    let cells = page.cells(&buffer).collect::<Result<Vec<_>, _>>().unwrap();
+    for (cell, rowid) in cells.iter().zip(ROWIDS) {
+        assert_eq!(cell.offset, u64::from(page.cell_pointers[cell.index]));
+        // the payload length and rowid varints, then a record of the header size, the NULL
+        // standing in for id, the body's serial type and the body
+        let len = body(rowid).len();
+        let payload = (2 + varint_len(13 + 2 * len as u64) + len) as u64;
+        let expected = varint_len(payload) + varint_len(rowid as u64) + payload as usize;
+        assert_eq!(cell.size, expected, "rowid {}", rowid);
+    }
+
+    // cells and free space account for every byte of the content area
+    let used = cells.iter().map(|cell| cell.size).sum::<usize>();
+    let free = page
+        .freeblocks
+        .iter()
+        .map(|&(_, size)| usize::from(size))
+        .sum::<usize>();
+    let content_area = page.usable_size() as usize - page.first_cell_start as usize;
+    assert_eq!(
+        used + free + usize::from(page.fragmented_bytes),
+        content_area
+    );
+}
@@ -0,0 +1,107 @@
+// Point lookups in a WITHOUT ROWID table keyed on a NOCASE text column and a descending
+// integer column, spread over several levels of 512-byte pages. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE stock(
+//         store TEXT COLLATE NOCASE,
+//         item INTEGER,
+//         qty INTEGER,
+//         note TEXT,
+//         PRIMARY KEY (store, item DESC)
+//     ) WITHOUT ROWID;
+//     WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 1199)
+//     INSERT INTO stock SELECT printf('store-%02d', i % 40), i / 40, i, printf('note %d', i) FROM n;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::query::lookup_primary_key;
+use sqrlite::{execute, Database, FieldData, Select, SqrliteError};
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/composite_pk.db"
+);
+
+fn key(store: &str, item: i64) -> Vec<FieldData> {
+    vec![FieldData::Text(store.to_owned()), FieldData::Integer(item)]
+}
+
+// the quantity is the row's number, from which the key was made
+fn qty(store: u32, item: u32) -> i64 {
+    i64::from(item * 40 + store)
+}
+
+#[test]
+fn every_row_is_found_by_its_key() {
+    let db = Database::new(FIXTURE).unwrap();
+    let root = db.table("stock").unwrap().rootpage;
+    assert!(tree_stats(&db, root).unwrap().depth >= 2);
+
+    for store in 0..40 {
+        for item in 0..30 {
+            let name = format!("store-{:02}", store);
+            let row = lookup_primary_key(&db, "stock", &key(&name, item.into()))
+                .unwrap()
+                .unwrap_or_else(|| panic!("{} {} not found", name, item));
+            assert_eq!(row.columns(), ["store", "item", "qty", "note"]);
+            let number = qty(store, item);
+            assert_eq!(row.get("store"), Some(&FieldData::Text(name)));
+            assert_eq!(row.get("qty").and_then(FieldData::as_i64), Some(number));
+            assert_eq!(
+                row.get("note"),
+                Some(&FieldData::Text(format!("note {}", number)))
+            );
+        }
+    }
+}
+
+#[test]
+fn text_key_compares_with_its_collation() {
+    let db = Database::new(FIXTURE).unwrap();
+    let row = lookup_primary_key(&db, "stock", &key("STORE-07", 3))
+        .unwrap()
+        .unwrap();
+    // the stored spelling comes back
+    assert_eq!(row.get("store"), Some(&FieldData::Text("store-07".into())));
+    assert_eq!(row.get("qty").and_then(FieldData::as_i64), Some(qty(7, 3)));
+}
+
+#[test]
+fn missing_keys_find_nothing() {
+    let db = Database::new(FIXTURE).unwrap();
+    for (store, item) in [("store-07", 30), ("store-07", -1), ("store-40", 0), ("", 0)] {
+        let row = lookup_primary_key(&db, "stock", &key(store, item)).unwrap();
+        assert!(row.is_none(), "{} {}", store, item);
+    }
+}
+
+#[test]
+fn key_must_have_a_value_per_column() {
+    let db = Database::new(FIXTURE).unwrap();
+    let result = lookup_primary_key(&db, "stock", &[FieldData::Text("store-07".into())]);
+    assert!(
+        matches!(result, Err(SqrliteError::Query(_))),
+        "{:?}",
+        result
+    );
+    let result = lookup_primary_key(&db, "sqlite_schema", &key("a", 1));
+    assert!(result.is_err());
+}
+
+#[test]
+fn where_clause_on_the_whole_key() {
+    let db = Database::new(FIXTURE).unwrap();
+    let select =
+        Select::parse("SELECT qty FROM stock WHERE item = 3 AND store = 'Store-07'").unwrap();
+    let result = execute(&db, &select).unwrap();
+    assert_eq!(result.rows, [[FieldData::Integer(qty(7, 3))]]);
+
+    // rows come out in key order: by store, then by item descending
+    let select = Select::parse("SELECT item FROM stock WHERE store = 'store-12'").unwrap();
+    let items = execute(&db, &select)
+        .unwrap()
+        .rows
+        .iter()
+        .map(|row| row[0].as_i64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(items, (0..30).rev().collect::<Vec<_>>());
+}
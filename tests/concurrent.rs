@@ -0,0 +1,97 @@
+// Many threads reading one Database at once must see exactly what a single thread sees: the
+// page cache and the cached schema are shared behind locks, and every read names its own
+// offset, so nothing one thread does may move another's position.
+
+use std::sync::Arc;
+use std::thread;
+
+use sqrlite::scan::leaf_values;
+use sqrlite::{Database, FieldData, SeekResult, TableCursor, TableScan};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scan.db");
+const THREADS: usize = 12;
+const ROUNDS: usize = 3;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn database_is_send_and_sync() {
+    assert_send_sync::<Database>();
+    assert_send_sync::<Arc<Database>>();
+}
+
+// Every row of the table, read by a full scan
+fn scan_all(db: &Database) -> Vec<(i64, Vec<FieldData>)> {
+    let rootpage = db.table("readings").unwrap().rootpage;
+    TableScan::new(db, rootpage)
+        .rows()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+// Rows looked up one at a time, by seeking a cursor and by rowid through a table handle, in
+// an order that jumps around the b-tree
+fn lookups(db: &Database, seed: i64) -> Vec<(i64, Vec<FieldData>)> {
+    let table = db.table("readings").unwrap();
+    let mut cursor = TableCursor::new(db, table.rootpage);
+    let mut rows = vec![];
+    for step in 0..400 {
+        let rowid = (seed * 7919 + step * 104_729) % 5_200 + 1;
+        let by_handle = table.get(rowid).unwrap();
+        if cursor.seek_rowid(rowid).unwrap() != SeekResult::Exact {
+            assert!(by_handle.is_none(), "rowid {}", rowid);
+            continue;
+        }
+        let (found, values) = cursor.read_current(leaf_values).unwrap();
+        assert_eq!(found, rowid);
+        // the handle fills in the INTEGER PRIMARY KEY, which the record stores as NULL, and
+        // turns REAL values stored as integers back into reals
+        let by_handle = by_handle.unwrap().into_values();
+        assert_eq!(by_handle[0], FieldData::Integer(rowid));
+        assert_eq!(by_handle[1], values[1]);
+        assert_eq!(by_handle[2].as_f64(), values[2].as_f64());
+        assert_eq!(by_handle[3], values[3]);
+        rows.push((rowid, values));
+    }
+    rows
+}
+
+#[test]
+fn concurrent_reads_match_a_single_thread() {
+    // a cache far smaller than the table keeps threads evicting each other's pages
+    let mut db = Database::new(FIXTURE).unwrap();
+    db.set_page_cache_size(16 * 4096);
+    let db = Arc::new(db);
+    let expected_scan = scan_all(&db);
+    assert_eq!(expected_scan.len(), 5_000);
+    let expected_lookups = (0..THREADS as i64)
+        .map(|seed| lookups(&db, seed))
+        .collect::<Vec<_>>();
+    let expected_lookups = Arc::new(expected_lookups);
+    let expected_scan = Arc::new(expected_scan);
+
+    let handles = (0..THREADS)
+        .map(|idx| {
+            let db = Arc::clone(&db);
+            let expected_scan = Arc::clone(&expected_scan);
+            let expected_lookups = Arc::clone(&expected_lookups);
+            thread::spawn(move || {
+                for round in 0..ROUNDS {
+                    // half the threads start with a scan and half with lookups, so both kinds
+                    // of read overlap
+                    if (idx + round) % 2 == 0 {
+                        assert_eq!(scan_all(&db), *expected_scan);
+                        assert_eq!(lookups(&db, idx as i64), expected_lookups[idx]);
+                    } else {
+                        assert_eq!(lookups(&db, idx as i64), expected_lookups[idx]);
+                        assert_eq!(scan_all(&db), *expected_scan);
+                    }
+                    assert_eq!(db.table("readings").unwrap().count().unwrap(), 5_000);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+// Seeking and stepping a cursor through a table b-tree three levels deep, whose rowids are
+// the even numbers 2 to 10000 so that seeks can land between rows. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE t(id INTEGER PRIMARY KEY, v TEXT);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+//     INSERT INTO t SELECT 2 * i, printf('row %d', 2 * i) FROM n;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::btree_page::{BtreePage, PageType};
+use sqrlite::cell::CellRef;
+use sqrlite::{Database, SeekResult, TableCursor};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cursor.db");
+const LAST_ROWID: i64 = 10_000;
+
+// (page number, rowids) of every leaf, left to right
+fn leaves(db: &Database, root_page: u32) -> Vec<(u32, Vec<i64>)> {
+    let mut leaves = vec![];
+    let mut pending = vec![root_page];
+    let mut buffer = vec![];
+    while let Some(page_num) = pending.pop() {
+        let mut page = BtreePage::default();
+        page.read_page(db, page_num, &mut buffer).unwrap();
+        let cells = (0..page.cell_pointers.len())
+            .map(|i| CellRef::get(&page, &buffer, i).unwrap())
+            .collect::<Vec<_>>();
+        if let PageType::LeafTable = page.page_type {
+            let rowids = cells.iter().map(|cell| match cell {
+                CellRef::LeafTable { row_id, .. } => *row_id,
+                _ => panic!("unexpected cell on leaf {}", page_num),
+            });
+            leaves.push((page_num, rowids.collect()));
+            continue;
+        }
+        // children are pushed right to left so that the leftmost is visited first
+        pending.extend(page.rightmost_ptr);
+        pending.extend(
+            cells
+                .iter()
+                .rev()
+                .map(|cell| cell.left_child_pointer().unwrap()),
+        );
+    }
+    leaves
+}
+
+fn open() -> (Database, u32) {
+    let db = Database::new(FIXTURE).unwrap();
+    let root = db.table("t").unwrap().rootpage;
+    (db, root)
+}
+
+#[test]
+fn fixture_is_three_levels_deep() {
+    let (db, root) = open();
+    assert!(tree_stats(&db, root).unwrap().depth >= 3);
+}
+
+#[test]
+fn next_and_prev_visit_every_row() {
+    let (db, root) = open();
+    let expected = (1..=LAST_ROWID / 2).map(|i| 2 * i).collect::<Vec<_>>();
+    let mut cursor = TableCursor::new(&db, root);
+
+    let mut forward = vec![];
+    let mut more = cursor.first().unwrap();
+    while more {
+        forward.push(cursor.rowid().unwrap());
+        more = cursor.next().unwrap();
+    }
+    assert_eq!(forward, expected);
+    assert!(!cursor.is_valid());
+
+    let mut backward = vec![];
+    let mut more = cursor.last().unwrap();
+    while more {
+        backward.push(cursor.rowid().unwrap());
+        more = cursor.prev().unwrap();
+    }
+    backward.reverse();
+    assert_eq!(backward, expected);
+    assert!(!cursor.is_valid());
+}
+
+#[test]
+fn stepping_across_leaf_boundaries() {
+    let (db, root) = open();
+    let leaves = leaves(&db, root);
+    assert!(leaves.len() > 2);
+    let mut cursor = TableCursor::new(&db, root);
+    for pair in leaves.windows(2) {
+        let last = *pair[0].1.last().unwrap();
+        let first = pair[1].1[0];
+
+        assert_eq!(cursor.seek_rowid(last).unwrap(), SeekResult::Exact);
+        assert!(cursor.next().unwrap());
+        assert_eq!(cursor.rowid().unwrap(), first);
+
+        assert_eq!(cursor.seek_rowid(first).unwrap(), SeekResult::Exact);
+        assert!(cursor.prev().unwrap());
+        assert_eq!(cursor.rowid().unwrap(), last);
+
+        // between the two leaves: past the end of the left one, so onto the right one
+        assert_eq!(cursor.seek_rowid(last + 1).unwrap(), SeekResult::After);
+        assert_eq!(cursor.rowid().unwrap(), first);
+    }
+}
+
+#[test]
+fn seeking_before_between_and_past_the_rows() {
+    let (db, root) = open();
+    let mut cursor = TableCursor::new(&db, root);
+
+    assert_eq!(cursor.seek_rowid(i64::MIN).unwrap(), SeekResult::After);
+    assert_eq!(cursor.rowid().unwrap(), 2);
+    assert!(!cursor.prev().unwrap());
+
+    for rowid in (1..=LAST_ROWID).step_by(97) {
+        let result = cursor.seek_rowid(rowid).unwrap();
+        let expected = if rowid % 2 == 0 {
+            SeekResult::Exact
+        } else {
+            SeekResult::After
+        };
+        assert_eq!(result, expected, "{}", rowid);
+        assert_eq!(cursor.rowid().unwrap(), rowid + rowid % 2);
+    }
+
+    assert_eq!(cursor.seek_rowid(LAST_ROWID).unwrap(), SeekResult::Exact);
+    assert!(!cursor.next().unwrap());
+    assert_eq!(
+        cursor.seek_rowid(LAST_ROWID + 1).unwrap(),
+        SeekResult::PastEnd
+    );
+    assert!(!cursor.is_valid());
+    assert_eq!(cursor.seek_rowid(i64::MAX).unwrap(), SeekResult::PastEnd);
+}
+
+#[test]
+fn empty_leaf_is_stepped_over() {
+    // SQLite merges away emptied leaves, so make one by zeroing the cell count of a leaf in
+    // the middle of the tree, as if every row on it had been deleted
+    let (db, root) = open();
+    let leaves = leaves(&db, root);
+    let middle = leaves.len() / 2;
+    let (emptied, ref lost) = leaves[middle];
+    let before = *leaves[middle - 1].1.last().unwrap();
+    let after = leaves[middle + 1].1[0];
+
+    let mut bytes = std::fs::read(FIXTURE).unwrap();
+    let offset = (emptied - 1) as usize * db.page_size as usize;
+    bytes[offset + 3..offset + 5].copy_from_slice(&[0, 0]);
+    let db = Database::from_bytes(bytes).unwrap();
+    let mut cursor = TableCursor::new(&db, root);
+
+    assert_eq!(cursor.seek_rowid(before).unwrap(), SeekResult::Exact);
+    assert!(cursor.next().unwrap());
+    assert_eq!(cursor.rowid().unwrap(), after);
+    assert!(cursor.prev().unwrap());
+    assert_eq!(cursor.rowid().unwrap(), before);
+
+    for &rowid in lost {
+        assert_eq!(cursor.seek_rowid(rowid).unwrap(), SeekResult::After);
+        assert_eq!(cursor.rowid().unwrap(), after);
+    }
+
+    let mut count = 0_usize;
+    let mut more = cursor.first().unwrap();
+    while more {
+        count += 1;
+        more = cursor.next().unwrap();
+    }
+    assert_eq!(count, LAST_ROWID as usize / 2 - lost.len());
+}
+
+#[test]
+fn interior_page_with_only_a_rightmost_pointer() {
+    // deleting nearly every row leaves SQLite rebalancing instead, so empty an interior page
+    // below the root by zeroing its cell count: its subtree is then just the rightmost child
+    let (db, root) = open();
+    let mut page = BtreePage::default();
+    let mut buffer = vec![];
+    page.read_page(&db, root, &mut buffer).unwrap();
+    let middle = page.cell_pointers.len() / 2;
+    let emptied = CellRef::get(&page, &buffer, middle)
+        .unwrap()
+        .left_child_pointer()
+        .unwrap();
+    let subtree = leaves(&db, emptied);
+    assert!(subtree.len() > 1);
+    let (kept, lost) = subtree.split_last().unwrap();
+    let lost = lost
+        .iter()
+        .flat_map(|(_, rowids)| rowids.iter().copied())
+        .collect::<Vec<_>>();
+    let expected = (1..=LAST_ROWID / 2)
+        .map(|i| 2 * i)
+        .filter(|rowid| !lost.contains(rowid))
+        .collect::<Vec<_>>();
+
+    let mut bytes = std::fs::read(FIXTURE).unwrap();
+    let offset = (emptied - 1) as usize * db.page_size as usize;
+    bytes[offset + 3..offset + 5].copy_from_slice(&[0, 0]);
+    let db = Database::from_bytes(bytes).unwrap();
+    let mut cursor = TableCursor::new(&db, root);
+
+    let mut forward = vec![];
+    let mut more = cursor.first().unwrap();
+    while more {
+        forward.push(cursor.rowid().unwrap());
+        more = cursor.next().unwrap();
+    }
+    assert_eq!(forward, expected);
+
+    let mut backward = vec![];
+    let mut more = cursor.last().unwrap();
+    while more {
+        backward.push(cursor.rowid().unwrap());
+        more = cursor.prev().unwrap();
+    }
+    backward.reverse();
+    assert_eq!(backward, expected);
+
+    // every key below the emptied page's rightmost child now leads into that child
+    let first_kept = kept.1[0];
+    for &rowid in lost.iter().step_by(7) {
+        assert_eq!(cursor.seek_rowid(rowid).unwrap(), SeekResult::After);
+        assert_eq!(cursor.rowid().unwrap(), first_kept);
+    }
+    for &rowid in &kept.1 {
+        assert_eq!(cursor.seek_rowid(rowid).unwrap(), SeekResult::Exact);
+    }
+
+    // full scans take the same path down
+    let table = db.table("t").unwrap();
+    assert_eq!(table.count().unwrap(), expected.len() as u64);
+}
@@ -0,0 +1,29 @@
+// DBInfo's Display against the SQLite shell's own `.dbinfo` for the same file. The transcript
+// was recorded with SQLite 3.51.2:
+//
+//     sqlite3 tests/fixtures/dbinfo.db .dbinfo > tests/fixtures/dbinfo.txt
+//
+// The fixture has a 1 KiB page size, a user version and application id, an AUTOINCREMENT
+// table with an index, a table with a UNIQUE constraint, a view, a trigger, and free pages
+// left by deleting rows.
+
+use std::fs;
+
+use sqrlite::{DBInfo, Database};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[test]
+fn display_matches_the_sqlite_shell() {
+    let db = Database::new(format!("{}/dbinfo.db", FIXTURES)).unwrap();
+    let info = DBInfo::read_info(&db).unwrap();
+
+    let transcript = fs::read_to_string(format!("{}/dbinfo.txt", FIXTURES)).unwrap();
+    // the data version comes from the connection rather than the file, so it is not shown
+    let expected = transcript
+        .lines()
+        .filter(|line| !line.starts_with("data version"))
+        .collect::<Vec<_>>();
+    let shown = info.to_string();
+    assert_eq!(shown.lines().collect::<Vec<_>>(), expected);
+}
@@ -0,0 +1,44 @@
+// The freelist of a database that had most of its rows deleted: 344 free pages of 512 bytes,
+// listed on a chain of three trunk pages. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT);
+//     -- 400 rows with bodies of 300 to 999 bytes, many spilling onto overflow pages
+//     DELETE FROM notes WHERE id % 4 != 0;
+
+use std::fs;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::{Database, SqrliteError};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/freelist.db");
+
+#[test]
+fn freelist_pages_match_the_header_count() {
+    let db = Database::new(FIXTURE).unwrap();
+    assert_eq!(db.freelist_count(), 344);
+    let free = db.freelist_pages().unwrap();
+    assert_eq!(free.len(), db.freelist_count() as usize);
+    db.verify_freelist().unwrap();
+
+    // every page is either free or in a b-tree, and none is both
+    let mut used = 0;
+    for (_, _, rootpage) in db.schema().unwrap().btrees() {
+        used += tree_stats(&db, rootpage).unwrap().pages();
+    }
+    assert_eq!(used + free.len() as u64, u64::from(db.page_count));
+    assert!(!free.contains(&1));
+    assert!(!free.contains(&db.table("notes").unwrap().rootpage));
+}
+
+#[test]
+fn freelist_count_mismatch_is_reported() {
+    let mut bytes = fs::read(FIXTURE).unwrap();
+    // the header's count of free pages, at offset 36
+    bytes[36..40].copy_from_slice(&345u32.to_be_bytes());
+    let db = Database::from_bytes(bytes).unwrap();
+    assert!(matches!(
+        db.verify_freelist(),
+        Err(SqrliteError::Freelist(_))
+    ));
+}
@@ -0,0 +1,120 @@
+// A table interleaving ordinary columns with VIRTUAL generated columns, which are not stored
+// in the record, and a STORED one, which is. Made with
+//
+//     CREATE TABLE items(
+//         id INTEGER PRIMARY KEY,
+//         price REAL,
+//         doubled REAL GENERATED ALWAYS AS (price * 2) VIRTUAL,
+//         name TEXT,
+//         label TEXT GENERATED ALWAYS AS (upper(name)) STORED,
+//         tax REAL AS (price / 10),
+//         qty INTEGER
+//     );
+//     INSERT INTO items(price, name, qty) VALUES (1.5, 'pen', 10), (20, 'book', 2), (NULL, 'gift', 1);
+
+use sqrlite::{execute, Database, FieldData, Select};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/generated.db");
+
+fn text(s: &str) -> FieldData {
+    FieldData::Text(s.to_owned())
+}
+
+#[test]
+fn generated_clauses_are_parsed() {
+    let db = Database::new(FIXTURE).unwrap();
+    let table = db.schema().unwrap().table_def("items").unwrap();
+    let generated = table
+        .columns
+        .iter()
+        .map(|col| {
+            col.generated
+                .as_ref()
+                .map(|g| (g.expression.as_str(), g.stored))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        generated,
+        [
+            None,
+            None,
+            Some(("price * 2", false)),
+            None,
+            Some(("upper(name)", true)),
+            Some(("price / 10", false)),
+            None,
+        ]
+    );
+    let slots = (0..table.columns.len())
+        .map(|idx| table.storage_index(idx))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        slots,
+        [Some(0), Some(1), None, Some(2), Some(3), None, Some(4)]
+    );
+}
+
+#[test]
+fn stored_columns_come_out_under_their_own_names() {
+    let db = Database::new(FIXTURE).unwrap();
+    let row = db.table("items").unwrap().get(2).unwrap().unwrap();
+    assert_eq!(row.get("id"), Some(&FieldData::Integer(2)));
+    assert_eq!(row.get("price"), Some(&FieldData::Real(20.0)));
+    assert_eq!(row.get("name"), Some(&text("book")));
+    assert_eq!(row.get("label"), Some(&text("BOOK")));
+    assert_eq!(row.get("qty"), Some(&FieldData::Integer(2)));
+    // virtual columns are not computed, and read as NULL
+    assert_eq!(row.get("doubled"), Some(&FieldData::Null(())));
+    assert_eq!(row.get("tax"), Some(&FieldData::Null(())));
+}
+
+#[test]
+fn select_star_places_virtual_columns_as_null() {
+    let db = Database::new(FIXTURE).unwrap();
+    let result = execute(&db, &Select::parse("SELECT * FROM items").unwrap()).unwrap();
+    assert_eq!(
+        result.columns,
+        ["id", "price", "doubled", "name", "label", "tax", "qty"]
+    );
+    let null = FieldData::Null(());
+    assert_eq!(
+        result.rows,
+        [
+            [
+                FieldData::Integer(1),
+                FieldData::Real(1.5),
+                null.clone(),
+                text("pen"),
+                text("PEN"),
+                null.clone(),
+                FieldData::Integer(10),
+            ],
+            [
+                FieldData::Integer(2),
+                FieldData::Real(20.0),
+                null.clone(),
+                text("book"),
+                text("BOOK"),
+                null.clone(),
+                FieldData::Integer(2),
+            ],
+            [
+                FieldData::Integer(3),
+                null.clone(),
+                null.clone(),
+                text("gift"),
+                text("GIFT"),
+                null.clone(),
+                // stored as serial type 9, the constant 1
+                FieldData::BooleanTrue(1),
+            ],
+        ]
+    );
+
+    let result = execute(
+        &db,
+        &Select::parse("SELECT qty FROM items WHERE name = 'pen'").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(result.rows, [[FieldData::Integer(10)]]);
+}
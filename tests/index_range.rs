@@ -0,0 +1,147 @@
+// Range scans over an index on a numeric column, in ascending and descending order. Every
+// price from 0 to 999 appears three times. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE items(id INTEGER PRIMARY KEY, price INTEGER);
+//     CREATE INDEX items_price ON items(price);
+//     CREATE INDEX items_price_desc ON items(price DESC);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000)
+//     INSERT INTO items SELECT i, i * 37 % 1000 FROM n;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::index_btree::{IndexSeek, KeyBound, KeyOrder};
+use sqrlite::table::IndexHandle;
+use sqrlite::{Database, FieldData};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/prices.db");
+
+fn index<'a>(db: &'a Database, name: &str) -> IndexHandle<'a> {
+    db.indexes()
+        .unwrap()
+        .into_iter()
+        .find(|index| index.name == name)
+        .unwrap()
+}
+
+fn price(n: i64) -> Vec<FieldData> {
+    vec![FieldData::Integer(n)]
+}
+
+// rowids of the items priced within `low..high`, sorted
+fn priced(low: i64, high: i64) -> Vec<i64> {
+    (1..=3000)
+        .filter(|i| (low..high).contains(&(i * 37 % 1000)))
+        .collect()
+}
+
+fn scan(
+    db: &Database,
+    index: &IndexHandle,
+    lower: Option<KeyBound>,
+    upper: Option<KeyBound>,
+) -> (Vec<i64>, usize) {
+    let order = KeyOrder::for_columns(&index.def.columns).unwrap();
+    let mut seek = IndexSeek::range(db, index.rootpage, lower, upper, &order).unwrap();
+    let mut rowids = seek.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+    rowids.sort();
+    (rowids, seek.pages_read())
+}
+
+#[test]
+fn half_open_and_closed_ranges() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = index(&db, "items_price");
+
+    // price >= 10 AND price < 20
+    let lower = KeyBound::inclusive(&price(10));
+    let upper = KeyBound::exclusive(&price(20));
+    assert_eq!(
+        scan(&db, &index, Some(lower), Some(upper)).0,
+        priced(10, 20)
+    );
+
+    // price > 10 AND price <= 20
+    let lower = KeyBound::exclusive(&price(10));
+    let upper = KeyBound::inclusive(&price(20));
+    assert_eq!(
+        scan(&db, &index, Some(lower), Some(upper)).0,
+        priced(11, 21)
+    );
+
+    assert_eq!(
+        scan(&db, &index, None, Some(KeyBound::exclusive(&price(3)))).0,
+        priced(0, 3)
+    );
+    assert_eq!(
+        scan(&db, &index, Some(KeyBound::inclusive(&price(998))), None).0,
+        priced(998, 1000)
+    );
+    let empty = (
+        Some(KeyBound::exclusive(&price(5))),
+        Some(KeyBound::exclusive(&price(6))),
+    );
+    assert_eq!(scan(&db, &index, empty.0, empty.1).0, []);
+}
+
+#[test]
+fn desc_columns_take_the_larger_value_as_lower_bound() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = index(&db, "items_price_desc");
+    let lower = KeyBound::exclusive(&price(20));
+    let upper = KeyBound::inclusive(&price(10));
+    assert_eq!(
+        scan(&db, &index, Some(lower), Some(upper)).0,
+        priced(10, 20)
+    );
+}
+
+#[test]
+fn scan_stops_reading_past_the_upper_bound() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = index(&db, "items_price");
+    let stats = tree_stats(&db, index.rootpage).unwrap();
+    assert!(stats.depth >= 3);
+
+    let lower = KeyBound::inclusive(&price(10));
+    let upper = KeyBound::exclusive(&price(20));
+    let (rowids, pages_read) = scan(&db, &index, Some(lower), Some(upper));
+    // the path down to the first entry, the leaves the 30 entries span, and at most a
+    // parent page and a leaf beyond them
+    let leaves_spanned = rowids.len().div_ceil(stats.min_leaf_cells) + 1;
+    assert!(
+        pages_read <= stats.depth + leaves_spanned + 2,
+        "{} pages read",
+        pages_read
+    );
+    assert!(pages_read < stats.levels.iter().map(|level| level.pages).sum());
+}
+
+#[test]
+fn cached_pages_are_not_read_again() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = index(&db, "items_price");
+    let bounds = || {
+        (
+            Some(KeyBound::inclusive(&price(500))),
+            Some(KeyBound::inclusive(&price(600))),
+        )
+    };
+
+    let before = db.page_cache_stats();
+    let (lower, upper) = bounds();
+    let (first, pages_read) = scan(&db, &index, lower, upper);
+    let after_first = db.page_cache_stats();
+    assert!(after_first.misses > before.misses);
+
+    let (lower, upper) = bounds();
+    let (second, pages_read_again) = scan(&db, &index, lower, upper);
+    let after_second = db.page_cache_stats();
+    assert_eq!(first, second);
+    assert_eq!(pages_read, pages_read_again);
+    // every page came from the cache, with no read from the file
+    assert_eq!(after_second.misses, after_first.misses);
+    assert_eq!(
+        after_second.hits - after_first.hits,
+        pages_read_again as u64
+    );
+}
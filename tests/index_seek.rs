@@ -0,0 +1,103 @@
+// Equality seeks on an index whose keys repeat across many leaves: 'misc' tags 2699 rows,
+// 'rust' 300 and 'rare' one. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE posts(id INTEGER PRIMARY KEY, tag TEXT, year INTEGER);
+//     CREATE INDEX posts_tag_year ON posts(tag, year);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000)
+//     INSERT INTO posts SELECT i, CASE WHEN i % 10 = 0 THEN 'rust' WHEN i = 1234 THEN 'rare'
+//         ELSE 'misc' END, 2000 + i % 7 FROM n;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::index_btree::{IndexSeek, KeyOrder};
+use sqrlite::{execute, Database, FieldData, Select};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tags.db");
+
+fn tag(i: i64) -> &'static str {
+    match i {
+        _ if i % 10 == 0 => "rust",
+        1234 => "rare",
+        _ => "misc",
+    }
+}
+
+fn text(s: &str) -> FieldData {
+    FieldData::Text(s.to_owned())
+}
+
+// rowids of the entries matching the key, sorted, and the pages read finding them
+fn seek(db: &Database, key: &[FieldData]) -> (Vec<i64>, usize) {
+    let schema = db.schema().unwrap();
+    let index = schema.index_def("posts_tag_year").unwrap();
+    let root = schema.find("posts_tag_year").unwrap().rootpage;
+    let order = KeyOrder::for_columns(&index.columns).unwrap();
+    let mut seek = IndexSeek::eq(db, root, key, &order).unwrap();
+    let mut rowids = seek.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+    rowids.sort();
+    (rowids, seek.pages_read())
+}
+
+fn tagged(name: &str) -> Vec<i64> {
+    (1..=3000).filter(|&i| tag(i) == name).collect()
+}
+
+#[test]
+fn duplicates_spanning_many_leaves_are_all_found() {
+    let db = Database::new(FIXTURE).unwrap();
+    let root = db
+        .schema()
+        .unwrap()
+        .find("posts_tag_year")
+        .unwrap()
+        .rootpage;
+    let stats = tree_stats(&db, root).unwrap();
+    assert!(stats.depth >= 2);
+
+    let (rowids, pages_read) = seek(&db, &[text("misc")]);
+    assert_eq!(rowids, tagged("misc"));
+    // more entries than any one leaf holds
+    assert!(rowids.len() > stats.max_leaf_cells);
+    assert!(pages_read > stats.depth);
+
+    assert_eq!(seek(&db, &[text("rust")]).0, tagged("rust"));
+    assert_eq!(seek(&db, &[text("rare")]).0, [1234]);
+}
+
+#[test]
+fn every_key_column_must_match() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (rowids, _) = seek(&db, &[text("rust"), FieldData::Integer(2003)]);
+    let expected = tagged("rust")
+        .into_iter()
+        .filter(|i| 2000 + i % 7 == 2003)
+        .collect::<Vec<_>>();
+    assert!(!expected.is_empty());
+    assert_eq!(rowids, expected);
+}
+
+#[test]
+fn absent_keys_find_nothing() {
+    let db = Database::new(FIXTURE).unwrap();
+    for key in ["", "a", "nope", "rusty", "zzz"] {
+        let (rowids, pages_read) = seek(&db, &[text(key)]);
+        assert_eq!(rowids, [], "{}", key);
+        assert!(pages_read <= 4, "{} pages read seeking {}", pages_read, key);
+    }
+    // numbers sort before text
+    assert_eq!(seek(&db, &[FieldData::Integer(7)]).0, []);
+}
+
+#[test]
+fn indexed_where_matches_a_scan() {
+    let db = Database::new(FIXTURE).unwrap();
+    let select = Select::parse("SELECT id FROM posts WHERE tag = 'rust'").unwrap();
+    let mut ids = execute(&db, &select)
+        .unwrap()
+        .rows
+        .iter()
+        .map(|row| row[0].as_i64().unwrap())
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, tagged("rust"));
+}
@@ -0,0 +1,218 @@
+// A 10 MiB blob spilling onto a chain of some 2,500 overflow pages, in a database built in
+// memory: sqlite_schema on page 1, the table's single leaf on page 2 and the chain after it.
+// Streaming the blob must hold no more than a page or two at a time, which a counting
+// allocator checks.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use sqrlite::btree_page::PageType;
+use sqrlite::cell::Payload;
+use sqrlite::record::{encode, Record};
+use sqrlite::varint::encode_be;
+use sqrlite::{DataType, Database, FieldData, SeekResult, TableCursor};
+
+const PAGE_SIZE: usize = 4096;
+const BLOB_SIZE: usize = 10 * 1024 * 1024;
+const ROOT: u32 = 2;
+
+// Counts the bytes allocated and not yet freed by the current thread, and the most there
+// have been at once, so that tests running alongside don't disturb the figures
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+fn track(allocated: usize, freed: usize) {
+    let _ = LIVE.try_with(|live| {
+        let now = (live.get() + allocated).saturating_sub(freed);
+        live.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            track(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        track(0, layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            track(new_size, layout.size());
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Peak bytes allocated by this thread while running `f`, over what was live before it
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let start = LIVE.with(Cell::get);
+    PEAK.with(|peak| peak.set(start));
+    let result = f();
+    (result, PEAK.with(Cell::get) - start)
+}
+
+// Byte `i` of the blob; the pattern does not repeat with the size of a page
+fn blob_byte(i: usize) -> u8 {
+    (i * 7 + i / 4093) as u8
+}
+
+fn blob() -> Vec<u8> {
+    (0..BLOB_SIZE).map(blob_byte).collect()
+}
+
+fn leaf_page(header_offset: usize, cell: &[u8]) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let start = PAGE_SIZE - cell.len();
+    page[start..].copy_from_slice(cell);
+    let header = &mut page[header_offset..header_offset + 10];
+    header[0] = 0x0d;
+    header[3..5].copy_from_slice(&1u16.to_be_bytes());
+    header[5..7].copy_from_slice(&(start as u16).to_be_bytes());
+    header[8..10].copy_from_slice(&(start as u16).to_be_bytes());
+    page
+}
+
+// The cell of a row whose payload may spill onto pages from `first_overflow` on, and the
+// part of the payload that spills
+fn leaf_cell(rowid: i64, payload: &[u8], first_overflow: u32) -> (Vec<u8>, &[u8]) {
+    let size = payload.len() as u64;
+    let local = Payload::local_size(&PageType::LeafTable, size, PAGE_SIZE as u64) as usize;
+    let mut cell = encode_be(size).1;
+    cell.extend(encode_be(rowid as u64).1);
+    cell.extend(&payload[..local]);
+    if local < payload.len() {
+        cell.extend(first_overflow.to_be_bytes());
+    }
+    (cell, &payload[local..])
+}
+
+fn database() -> Database {
+    let schema_row = encode(&[
+        FieldData::Text("table".into()),
+        FieldData::Text("blobs".into()),
+        FieldData::Text("blobs".into()),
+        FieldData::Integer(i64::from(ROOT)),
+        FieldData::Text("CREATE TABLE blobs(id INTEGER PRIMARY KEY, data BLOB)".into()),
+    ]);
+    let (schema_cell, _) = leaf_cell(1, &schema_row, 0);
+    let row = encode(&[FieldData::Null(()), FieldData::Blob(blob())]);
+    let (row_cell, spilled) = leaf_cell(1, &row, ROOT + 1);
+
+    let chain = spilled.chunks(PAGE_SIZE - 4).collect::<Vec<_>>();
+    let page_count = ROOT + chain.len() as u32;
+    let mut bytes = leaf_page(100, &schema_cell);
+    bytes[..16].copy_from_slice(b"SQLite format 3\0");
+    bytes[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    bytes[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    bytes[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    bytes[28..32].copy_from_slice(&page_count.to_be_bytes());
+    bytes[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    bytes[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    bytes[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for
+    bytes.extend(leaf_page(0, &row_cell));
+    for (i, chunk) in chain.iter().enumerate() {
+        let page = ROOT + 1 + i as u32;
+        let next = if page == page_count { 0 } else { page + 1 };
+        let mut overflow = next.to_be_bytes().to_vec();
+        overflow.extend(*chunk);
+        overflow.resize(PAGE_SIZE, 0);
+        bytes.extend(overflow);
+    }
+    Database::from_bytes(bytes).unwrap()
+}
+
+// Checks each byte written against the blob, without keeping any of them
+struct Verify {
+    offset: usize,
+}
+
+impl Write for Verify {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (i, &byte) in buf.iter().enumerate() {
+            assert_eq!(byte, blob_byte(self.offset + i), "byte {}", self.offset + i);
+        }
+        self.offset += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn row_payload(db: &Database) -> Payload {
+    let mut cursor = TableCursor::new(db, ROOT);
+    assert_eq!(cursor.seek_rowid(1).unwrap(), SeekResult::Exact);
+    cursor.payload().unwrap()
+}
+
+#[test]
+fn reassembles_the_whole_chain() {
+    let db = database();
+    assert!(db.page_count > 2500);
+    let row = db.table("blobs").unwrap().get(1).unwrap().unwrap();
+    match row.get("data") {
+        Some(FieldData::Blob(data)) => {
+            assert_eq!(data.len(), BLOB_SIZE);
+            assert!(*data == blob());
+        }
+        other => panic!("unexpected value {:?}", other),
+    }
+}
+
+#[test]
+fn streams_the_blob_a_page_at_a_time() {
+    let mut db = database();
+    // cached pages would count against the reader
+    db.set_page_cache_size(0);
+    let payload = row_payload(&db);
+    let record = Record::read_header(&mut payload.open_reader(&db)).unwrap();
+    let field = &record.fields.unwrap()[1];
+    assert_eq!(field.data_type(), DataType::Blob);
+
+    let (copied, peak) = peak_allocation(|| {
+        let mut reader = field.open_blob_reader(&payload, &db).unwrap();
+        io::copy(&mut reader, &mut Verify { offset: 0 }).unwrap()
+    });
+    assert_eq!(copied, BLOB_SIZE as u64);
+    // a page being read and the page held by the reader, with room for the list of the
+    // chain's pages the reader keeps for seeking
+    assert!(peak < 16 * PAGE_SIZE, "{} bytes allocated at once", peak);
+}
+
+#[test]
+fn seeks_within_the_chain() {
+    let db = database();
+    let payload = row_payload(&db);
+    let record = Record::read_header(&mut payload.open_reader(&db)).unwrap();
+    let mut reader = record.fields.unwrap()[1]
+        .open_blob_reader(&payload, &db)
+        .unwrap();
+
+    let mut buf = [0; 10_000];
+    for offset in [BLOB_SIZE - buf.len(), 4_000_000, 0, 8191, 9_999_999] {
+        reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+        let len = buf.len().min(BLOB_SIZE - offset);
+        reader.read_exact(&mut buf[..len]).unwrap();
+        Verify { offset }.write_all(&buf[..len]).unwrap();
+    }
+    assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), BLOB_SIZE as u64);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
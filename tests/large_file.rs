@@ -0,0 +1,229 @@
+// A database of more than 2^22 pages of 4 KiB, over 16 GiB, written as a sparse file holding
+// only the header page and a table b-tree of three pages far apart. Page numbers this large
+// overflow 32-bit byte offsets, so every read must work out offsets in 64 bits.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use sqrlite::btree_page::BtreePage;
+use sqrlite::record::encode;
+use sqrlite::varint::encode_be;
+use sqrlite::{execute, DBInfo, Database, FieldData, FileSizeCheck, Select, SqrliteError};
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_COUNT: u32 = (1 << 22) + 1001;
+const ROOT: u32 = (1 << 22) + 3;
+const LEFT_LEAF: u32 = 1_000_003;
+const RIGHT_LEAF: u32 = (1 << 22) + 1000;
+// the page spanning file offset 2^30
+const LOCK_BYTE_PAGE: u32 = (1 << 30) / PAGE_SIZE as u32 + 1;
+
+// A b-tree page with the given header bytes and cells, the cells packed at the end of the
+// page. Page 1 starts after the 100-byte database header.
+fn btree_page(header_offset: usize, header: &[u8], cells: &[Vec<u8>]) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let mut content_start = PAGE_SIZE;
+    let mut pointers = vec![];
+    for cell in cells {
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(cell);
+        pointers.extend((content_start as u16).to_be_bytes());
+    }
+    let mut page_header = header.to_vec();
+    page_header[3..5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page_header[5..7].copy_from_slice(&(content_start as u16).to_be_bytes());
+    page_header.extend(pointers);
+    page[header_offset..header_offset + page_header.len()].copy_from_slice(&page_header);
+    page
+}
+
+fn leaf_cell(rowid: i64, payload: &[u8]) -> Vec<u8> {
+    let mut cell = encode_be(payload.len() as u64).1;
+    cell.extend(encode_be(rowid as u64).1);
+    cell.extend(payload);
+    cell
+}
+
+fn leaf_page(header_offset: usize, rows: impl Iterator<Item = (i64, Vec<u8>)>) -> Vec<u8> {
+    let cells = rows
+        .map(|(rowid, payload)| leaf_cell(rowid, &payload))
+        .collect::<Vec<_>>();
+    btree_page(header_offset, &[0x0d, 0, 0, 0, 0, 0, 0, 0], &cells)
+}
+
+fn row(id: i64) -> (i64, Vec<u8>) {
+    let values = [
+        FieldData::Null(()),
+        FieldData::Text(format!("value {}", id)),
+    ];
+    (id, encode(&values))
+}
+
+fn database_header() -> [u8; 100] {
+    let mut header = [0; 100];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    header[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    header[28..32].copy_from_slice(&PAGE_COUNT.to_be_bytes());
+    header[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    header[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    header[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    header[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for
+    header[96..100].copy_from_slice(&3_045_000u32.to_be_bytes());
+    header
+}
+
+fn write_page(file: &mut File, page: u32, bytes: &[u8]) {
+    file.seek(SeekFrom::Start(u64::from(page - 1) * PAGE_SIZE as u64))
+        .unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+// Removes the file however the test ends
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// Each test writes its own file, named after it, since tests run in parallel
+fn write_database(test: &str) -> TempFile {
+    let name = format!("sqrlite-large-{}-{}.db", std::process::id(), test);
+    let path = std::env::temp_dir().join(name);
+    let temp = TempFile(path);
+    let mut file = File::create(&temp.0).unwrap();
+    file.set_len(u64::from(PAGE_COUNT) * PAGE_SIZE as u64)
+        .unwrap();
+
+    let schema_row = encode(&[
+        FieldData::Text("table".into()),
+        FieldData::Text("big".into()),
+        FieldData::Text("big".into()),
+        FieldData::Integer(i64::from(ROOT)),
+        FieldData::Text("CREATE TABLE big(id INTEGER PRIMARY KEY, v TEXT)".into()),
+    ]);
+    let mut page1 = leaf_page(100, [(1, schema_row)].into_iter());
+    page1[..100].copy_from_slice(&database_header());
+    write_page(&mut file, 1, &page1);
+
+    // the root holds one cell, keyed by the last rowid of the left leaf
+    let mut divider = LEFT_LEAF.to_be_bytes().to_vec();
+    divider.extend(encode_be(10u64).1);
+    let mut interior_header = vec![0x05, 0, 0, 0, 0, 0, 0, 0];
+    interior_header.extend(RIGHT_LEAF.to_be_bytes());
+    write_page(
+        &mut file,
+        ROOT,
+        &btree_page(0, &interior_header, &[divider]),
+    );
+    write_page(&mut file, LEFT_LEAF, &leaf_page(0, (1..=10).map(row)));
+    write_page(&mut file, RIGHT_LEAF, &leaf_page(0, (11..=20).map(row)));
+    temp
+}
+
+#[test]
+fn reads_a_database_past_2_pow_22_pages() {
+    let temp = write_database("read");
+    let db = Database::new(&temp.0).unwrap();
+    assert_eq!(db.page_count, PAGE_COUNT);
+
+    let info = DBInfo::read_info(&db).unwrap();
+    assert_eq!(info.db_page_count, PAGE_COUNT);
+    assert!(!info.header_page_count_stale);
+    assert_eq!(info.file_page_count, PAGE_COUNT);
+    assert_eq!(info.num_tables, 1);
+    assert_eq!(DBInfo::consistency(&db).unwrap(), FileSizeCheck::Exact);
+
+    let table = db.table("big").unwrap();
+    assert_eq!(table.rootpage, ROOT);
+    assert_eq!(table.count().unwrap(), 20);
+    for id in [1, 10, 11, 20] {
+        let row = table.get(id).unwrap().unwrap();
+        assert_eq!(row.get("id"), Some(&FieldData::Integer(id)));
+        assert_eq!(
+            row.get("v"),
+            Some(&FieldData::Text(format!("value {}", id)))
+        );
+    }
+    assert!(table.get(21).unwrap().is_none());
+
+    let select = Select::parse("SELECT v FROM big WHERE id = 17").unwrap();
+    let result = execute(&db, &select).unwrap();
+    assert_eq!(result.rows, [[FieldData::Text("value 17".into())]]);
+}
+
+// Point the header at a freelist of one trunk page, on page 2, listing the given leaves
+fn write_freelist(path: &PathBuf, leaves: &[u32]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    let mut trunk = vec![0; 4];
+    trunk.extend((leaves.len() as u32).to_be_bytes());
+    for leaf in leaves {
+        trunk.extend(leaf.to_be_bytes());
+    }
+    write_page(&mut file, 2, &trunk);
+    let mut header = 2u32.to_be_bytes().to_vec();
+    header.extend((leaves.len() as u32 + 1).to_be_bytes());
+    file.seek(SeekFrom::Start(32)).unwrap();
+    file.write_all(&header).unwrap();
+}
+
+#[test]
+fn lock_byte_page_is_never_a_btree_page() {
+    let temp = write_database("lock-byte");
+    let db = Database::new(&temp.0).unwrap();
+    assert_eq!(db.lock_byte_page(), LOCK_BYTE_PAGE);
+    let mut page = BtreePage::default();
+    match page.read_page(&db, LOCK_BYTE_PAGE, &mut vec![]) {
+        Err(SqrliteError::InvalidPageNumber(e)) => {
+            assert_eq!(e.page, LOCK_BYTE_PAGE);
+            assert_eq!(
+                e.to_string(),
+                format!(
+                    "page {} is the lock-byte page and does not hold b-tree data",
+                    LOCK_BYTE_PAGE
+                )
+            );
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // a child pointer onto it is reported rather than followed
+    let mut file = OpenOptions::new().write(true).open(&temp.0).unwrap();
+    let offset = u64::from(ROOT - 1) * PAGE_SIZE as u64 + 8;
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(&LOCK_BYTE_PAGE.to_be_bytes()).unwrap();
+    let db = Database::new(&temp.0).unwrap();
+    let result = db.table("big").unwrap().count();
+    assert!(
+        matches!(result, Err(SqrliteError::InvalidPageNumber(_))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn freelist_must_not_hold_the_lock_byte_page() {
+    let temp = write_database("freelist");
+    write_freelist(&temp.0, &[LOCK_BYTE_PAGE - 1, LOCK_BYTE_PAGE + 1]);
+    let db = Database::new(&temp.0).unwrap();
+    let free = db.freelist_pages().unwrap();
+    assert_eq!(
+        free.into_iter().collect::<Vec<_>>(),
+        [2, LOCK_BYTE_PAGE - 1, LOCK_BYTE_PAGE + 1]
+    );
+
+    write_freelist(&temp.0, &[3, LOCK_BYTE_PAGE]);
+    let db = Database::new(&temp.0).unwrap();
+    let err = db.freelist_pages().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "freelist trunk #1 (page 2) lists the lock-byte page {}",
+            LOCK_BYTE_PAGE
+        )
+    );
+}
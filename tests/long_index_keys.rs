@@ -0,0 +1,99 @@
+// An index over TEXT keys long enough that many of its entries spill onto overflow pages,
+// on interior pages as well as leaves. The keys are 64 to 403 bytes, on both sides of the
+// 102 bytes an index cell keeps on a 512-byte page. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE words(id INTEGER PRIMARY KEY, word TEXT);
+//     CREATE INDEX words_word ON words(word);
+//     -- 600 rows, word being a 4-digit prefix followed by 60 to 399 bytes of 'word word ...'
+
+use std::collections::VecDeque;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::btree_page::{BtreePage, PageType};
+use sqrlite::cell::CellRef;
+use sqrlite::index_btree::{IndexSeek, KeyOrder};
+use sqrlite::{Database, FieldData};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/long_keys.db");
+
+// (word, rowid) of every row, in index order
+fn rows_by_word(db: &Database) -> Vec<(String, i64)> {
+    let mut rows = db
+        .table("words")
+        .unwrap()
+        .rows()
+        .map(|row| {
+            let row = row.unwrap();
+            let word = row.get("word").and_then(FieldData::as_str).unwrap();
+            (word.to_owned(), row.rowid.unwrap())
+        })
+        .collect::<Vec<_>>();
+    rows.sort();
+    rows
+}
+
+#[test]
+fn interior_cells_spill_onto_overflow_pages() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = db.indexes().unwrap().pop().unwrap();
+    assert!(tree_stats(&db, index.rootpage).unwrap().depth >= 3);
+
+    // count the interior cells whose key straddles the spill threshold
+    let (mut local, mut spilled) = (0, 0);
+    let mut pending = VecDeque::from([index.rootpage]);
+    let mut buffer = vec![];
+    while let Some(page_num) = pending.pop_front() {
+        let mut page = BtreePage::default();
+        page.read_page(&db, page_num, &mut buffer).unwrap();
+        if let PageType::LeafIndex = page.page_type {
+            continue;
+        }
+        for i in 0..page.cell_pointers.len() {
+            let cell = CellRef::get(&page, &buffer, i).unwrap();
+            pending.push_back(cell.left_child_pointer().unwrap());
+            match cell.payload().unwrap().overflow {
+                Some(_) => spilled += 1,
+                None => local += 1,
+            }
+        }
+        pending.extend(page.rightmost_ptr);
+    }
+    assert!(
+        local > 0 && spilled > 0,
+        "{} local, {} spilled",
+        local,
+        spilled
+    );
+}
+
+#[test]
+fn entries_come_back_whole_and_in_order() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = db.indexes().unwrap().pop().unwrap();
+    let entries = index
+        .entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| match &entry[..] {
+            [FieldData::Text(word), rowid] => (word.clone(), rowid.as_i64().unwrap()),
+            _ => panic!("unexpected entry {:?}", entry),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(entries, rows_by_word(&db));
+}
+
+#[test]
+fn every_long_key_is_found_by_seeking() {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = db.indexes().unwrap().pop().unwrap();
+    let order = KeyOrder::for_columns(&index.def.columns).unwrap();
+    for (word, rowid) in rows_by_word(&db) {
+        let key = [FieldData::Text(word.clone())];
+        let found = IndexSeek::eq(&db, index.rootpage, &key, &order)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(found, [rowid], "{}", word);
+    }
+}
@@ -0,0 +1,115 @@
+// Rows whose TEXT spills onto chains of overflow pages, the longest some 300 pages long.
+// Each body repeats "abcdefg", whose length does not divide the 1020 bytes an overflow page
+// holds, so a page read out of order shows. Made with
+//
+//     PRAGMA page_size = 1024;
+//     CREATE TABLE docs(id INTEGER PRIMARY KEY, body TEXT);
+//     INSERT INTO docs SELECT column1, substr(replace(hex(zeroblob(column2)), '00', 'abcdefg'), 1, column2)
+//     FROM (VALUES (1, 300000), (2, 5000), (3, 100));
+
+use sqrlite::{Database, FieldData, SeekResult, SqrliteError, TableCursor};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/long_text.db");
+const PAGE_SIZE: usize = 1024;
+
+fn body(len: usize) -> String {
+    "abcdefg".chars().cycle().take(len).collect()
+}
+
+// The size of row 1's payload and the pages of its overflow chain, in order
+fn chain(db: &Database) -> (u64, Vec<u32>) {
+    let mut cursor = TableCursor::new(db, db.table("docs").unwrap().rootpage);
+    assert_eq!(cursor.seek_rowid(1).unwrap(), SeekResult::Exact);
+    let payload = cursor.payload().unwrap();
+    let mut pages = vec![];
+    let mut next = u32::from_be_bytes(payload.overflow.unwrap());
+    while next != 0 {
+        pages.push(next);
+        let page = db.page(next).unwrap();
+        next = u32::from_be_bytes([page[0], page[1], page[2], page[3]]);
+    }
+    (payload.size, pages)
+}
+
+// Row 1 read from a copy of the fixture whose chain has the `index`-th page point at `next`
+fn read_with_next(index: usize, next: u32) -> Result<(), SqrliteError> {
+    let db = Database::new(FIXTURE).unwrap();
+    let (_, pages) = chain(&db);
+    let mut bytes = std::fs::read(FIXTURE).unwrap();
+    let offset = (pages[index] - 1) as usize * PAGE_SIZE;
+    bytes[offset..offset + 4].copy_from_slice(&next.to_be_bytes());
+    let db = Database::from_bytes(bytes).unwrap();
+    db.table("docs").unwrap().get(1).map(|_| ())
+}
+
+fn overflow_error(result: Result<(), SqrliteError>) -> String {
+    match result {
+        Err(SqrliteError::Overflow(e)) => e.to_string(),
+        other => panic!("expected an overflow error, got {:?}", other),
+    }
+}
+
+#[test]
+fn long_text_is_reassembled() {
+    let db = Database::new(FIXTURE).unwrap();
+    let table = db.table("docs").unwrap();
+    for (id, len) in [(1, 300_000), (2, 5000), (3, 100)] {
+        let row = table.get(id).unwrap().unwrap();
+        assert_eq!(row.get("body"), Some(&FieldData::Text(body(len))), "{}", id);
+    }
+
+    let (size, pages) = chain(&db);
+    let local = size as usize - pages.len() * (PAGE_SIZE - 4);
+    assert!(local < PAGE_SIZE, "{} bytes in the cell", local);
+    assert!(pages.len() > 290);
+}
+
+#[test]
+fn chain_ending_early() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (size, pages) = chain(&db);
+    let local = size as usize - pages.len() * (PAGE_SIZE - 4);
+    assert_eq!(
+        overflow_error(read_with_next(4, 0)),
+        format!(
+            "overflow chain of a {}-byte payload ends after {} of its bytes",
+            size,
+            local + 5 * (PAGE_SIZE - 4)
+        )
+    );
+}
+
+#[test]
+fn chain_looping_back() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (size, pages) = chain(&db);
+    assert_eq!(
+        overflow_error(read_with_next(4, pages[2])),
+        format!(
+            "overflow chain of a {}-byte payload loops back to page {}",
+            size, pages[2]
+        )
+    );
+}
+
+#[test]
+fn chain_leading_off_the_database() {
+    let db = Database::new(FIXTURE).unwrap();
+    let (size, _) = chain(&db);
+    assert_eq!(
+        overflow_error(read_with_next(4, 1)),
+        format!(
+            "overflow chain of a {}-byte payload references page 1",
+            size
+        )
+    );
+    let past_end = db.page_count + 5;
+    assert_eq!(
+        overflow_error(read_with_next(4, past_end)),
+        format!(
+            "overflow chain of a {}-byte payload references an invalid page: \
+             page {} is past the last page of the database, {}",
+            size, past_end, db.page_count
+        )
+    );
+}
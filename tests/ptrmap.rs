@@ -0,0 +1,125 @@
+// An incremental_vacuum database of 512-byte pages, whose pointer-map pages are pages 2, 105,
+// 208 and 311. Deleting rows left free pages, which incremental_vacuum keeps until it is
+// run. Made with
+//
+//     PRAGMA page_size = 512;
+//     PRAGMA auto_vacuum = INCREMENTAL;
+//     CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT);
+//     CREATE INDEX notes_start ON notes(substr(body, 1, 20));
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300)
+//     INSERT INTO notes SELECT i, printf('note %d %.*c', i, 200 + i % 5 * 150, 'x') FROM n;
+//     DELETE FROM notes WHERE id % 3 = 0;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::btree_page::BtreePage;
+use sqrlite::header::AutoVacuum;
+use sqrlite::ptrmap::{PtrMap, PtrMapEntry, PtrMapType};
+use sqrlite::{Database, SqrliteError};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/autovacuum.db");
+const MAP_PAGES: [u32; 4] = [2, 105, 208, 311];
+
+fn open() -> (Database, PtrMap) {
+    let db = Database::new(FIXTURE).unwrap();
+    let map = PtrMap::new(&db).unwrap();
+    (db, map)
+}
+
+#[test]
+fn map_pages_are_where_sqlite_puts_them() {
+    let (db, map) = open();
+    assert_eq!(db.auto_vacuum(), AutoVacuum::Incremental);
+    assert_eq!(db.page_count, 390);
+    let found = (1..=db.page_count)
+        .filter(|&page| map.is_ptrmap_page(page))
+        .collect::<Vec<_>>();
+    assert_eq!(found, MAP_PAGES);
+    assert_eq!(map.map_page(3), 2);
+    assert_eq!(map.map_page(104), 2);
+    assert_eq!(map.map_page(106), 105);
+    assert_eq!(map.map_page(390), 311);
+}
+
+#[test]
+fn entries_describe_roots_and_free_pages() {
+    let (db, map) = open();
+    for root in [3, 4] {
+        assert_eq!(
+            map.entry(&db, root).unwrap(),
+            PtrMapEntry {
+                page_type: PtrMapType::RootPage,
+                parent: 0
+            }
+        );
+    }
+    let free = db.freelist_pages().unwrap();
+    assert_eq!(free.len(), 120);
+    for &page in &free {
+        let entry = map.entry(&db, page).unwrap();
+        assert_eq!(entry.page_type, PtrMapType::FreePage, "page {}", page);
+    }
+
+    // pages 1 and 2 and the map pages themselves have no entry
+    for page in [1, 2, 105, 391] {
+        assert!(map.entry(&db, page).is_err(), "page {}", page);
+    }
+}
+
+#[test]
+fn entries_match_the_btrees() {
+    let (db, map) = open();
+    assert_eq!(map.verify(&db).unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn walks_skip_the_map_pages() {
+    let (db, _) = open();
+    assert_eq!(db.table("notes").unwrap().count().unwrap(), 200);
+
+    // every page is page 1, a map page, free, or in one of the two b-trees
+    let mut pages = 1 + MAP_PAGES.len() as u64 + db.freelist_count() as u64;
+    for root in [3, 4] {
+        let stats = tree_stats(&db, root).unwrap();
+        assert!(stats.corruption.is_empty(), "{:?}", stats.corruption);
+        pages += stats.pages();
+    }
+    assert_eq!(pages, u64::from(db.page_count));
+
+    let mut page = BtreePage::default();
+    for map_page in MAP_PAGES {
+        let result = page.read_page(&db, map_page, &mut vec![]);
+        assert!(
+            matches!(result, Err(SqrliteError::InvalidPageNumber(_))),
+            "{:?}",
+            result
+        );
+    }
+}
+
+#[test]
+fn mismatched_entries_are_reported() {
+    let (db, map) = open();
+    let child = (3..=db.page_count)
+        .find(|&page| {
+            map.entry(&db, page)
+                .is_ok_and(|entry| entry.page_type == PtrMapType::Btree)
+        })
+        .unwrap();
+    let parent = map.entry(&db, child).unwrap().parent;
+
+    // point the entry at the wrong parent
+    let mut bytes = std::fs::read(FIXTURE).unwrap();
+    let offset = db.page_size as usize + 5 * (child - 3) as usize;
+    bytes[offset + 1..offset + 5].copy_from_slice(&(parent + 1).to_be_bytes());
+    let db = Database::from_bytes(bytes).unwrap();
+    assert_eq!(
+        map.verify(&db).unwrap(),
+        [format!(
+            "pointer map says page {} is a b-tree page with parent {}, \
+             but it is a b-tree page with parent {}",
+            child,
+            parent + 1,
+            parent
+        )]
+    );
+}
@@ -0,0 +1,132 @@
+// Index entries as SQLite sorted them, mixing every storage class, text in several
+// collations, integers and reals too close for a double to tell apart, and a descending
+// column, for checking compare_records against the order SQLite stored. Made with
+//
+//     PRAGMA page_size = 512;
+//     CREATE TABLE mixed(k INTEGER, v);
+//     CREATE INDEX mixed_v ON mixed(v);
+//     CREATE INDEX mixed_v_nocase ON mixed(v COLLATE NOCASE);
+//     CREATE INDEX mixed_v_rtrim_desc ON mixed(v COLLATE RTRIM DESC);
+//     CREATE INDEX mixed_k_v ON mixed(k, v DESC);
+//     INSERT INTO mixed(k, v) VALUES
+//         (1, NULL), (2, NULL), (1, 0), (2, -1), (3, 1), (1, 0.5), (2, -0.5), (3, 1.0),
+//         (1, 9007199254740992), (2, 9007199254740993), (3, 9007199254740992.0), (1, 9.3e18),
+//         (2, 9223372036854775807), (3, -9223372036854775808), (1, -9.3e18), (2, 1e308),
+//         (3, -1e308), (1, ''), (2, ' '), (3, 'a'), (1, 'A'), (2, 'a '), (3, 'A  '), (1, 'b'),
+//         (2, 'B'), (3, 'abc'), (1, 'ABC'), (2, 'ab'), (3, 'é'), (1, 'É'), (2, 'z'), (3, 'Z'),
+//         (1, '10'), (2, '9'), (3, x''), (1, x'00'), (2, x'0000'), (3, x'01'), (1, x'ff'),
+//         (2, x'41'), (3, 'a' || char(0) || 'b');
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 400)
+//     INSERT INTO mixed(k, v) SELECT i % 5, CASE i % 6
+//         WHEN 0 THEN i * 7919 % 1000 - 500
+//         WHEN 1 THEN (i * 7919 % 1000) / 7.0
+//         WHEN 2 THEN printf('%s%d', substr('aAbBcC', i % 6 + 1, 1), i * 31 % 97)
+//         WHEN 3 THEN printf('%d  ', i % 13)
+//         WHEN 4 THEN zeroblob(i % 4) || x'7f'
+//         END FROM n;
+
+use std::cmp::Ordering;
+
+use proptest::prelude::*;
+use sqrlite::index_btree::{compare_records, KeyOrder};
+use sqrlite::{Database, FieldData};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ordering.db");
+const INDEXES: [&str; 4] = [
+    "mixed_v",
+    "mixed_v_nocase",
+    "mixed_v_rtrim_desc",
+    "mixed_k_v",
+];
+
+// Every entry of an index in stored order, with the order of its columns; the rowid
+// following them compares in ascending binary order
+fn stored_entries(name: &str) -> (Vec<Vec<FieldData>>, Vec<KeyOrder>) {
+    let db = Database::new(FIXTURE).unwrap();
+    let index = db
+        .indexes()
+        .unwrap()
+        .into_iter()
+        .find(|index| index.name == name)
+        .unwrap();
+    let order = KeyOrder::for_columns(&index.def.columns).unwrap();
+    (index.entries().unwrap(), order)
+}
+
+#[test]
+fn stored_order_is_strictly_increasing() {
+    for name in INDEXES {
+        let (entries, order) = stored_entries(name);
+        assert_eq!(entries.len(), 441);
+        for pair in entries.windows(2) {
+            assert_eq!(
+                compare_records(&pair[0], &pair[1], &order),
+                Ordering::Less,
+                "{}: {:?} then {:?}",
+                name,
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+}
+
+#[test]
+fn keys_alone_tie_only_where_sqlite_considers_them_equal() {
+    let (entries, order) = stored_entries("mixed_v_nocase");
+    let key = |entry: &[FieldData]| entry[..1].to_vec();
+    let equal = |a: &str, b: &str| {
+        let find = |s: &str| {
+            entries
+                .iter()
+                .find(|entry| entry[0] == FieldData::Text(s.to_owned()))
+                .map(|entry| key(entry))
+                .unwrap()
+        };
+        compare_records(&find(a), &find(b), &order) == Ordering::Equal
+    };
+    assert!(equal("abc", "ABC"));
+    assert!(!equal("é", "É"));
+    assert!(!equal("a", "a "));
+
+    let (entries, order) = stored_entries("mixed_v");
+    let int = entries
+        .iter()
+        .find(|entry| entry[0] == FieldData::Integer(9007199254740992))
+        .unwrap();
+    let real = entries
+        .iter()
+        .find(|entry| entry[0] == FieldData::Real(9007199254740992.0))
+        .unwrap();
+    let above = entries
+        .iter()
+        .find(|entry| entry[0] == FieldData::Integer(9007199254740993))
+        .unwrap();
+    assert_eq!(
+        compare_records(&key(int), &key(real), &order),
+        Ordering::Equal
+    );
+    assert_eq!(
+        compare_records(&key(real), &key(above), &order),
+        Ordering::Less
+    );
+}
+
+// An index and its entries in some random order
+fn shuffled() -> impl Strategy<Value = (usize, Vec<Vec<FieldData>>)> {
+    (0..INDEXES.len()).prop_flat_map(|index| {
+        let (entries, _) = stored_entries(INDEXES[index]);
+        (Just(index), Just(entries).prop_shuffle())
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn sorting_restores_the_stored_order((index, mut entries) in shuffled()) {
+        let (stored, order) = stored_entries(INDEXES[index]);
+        entries.sort_by(|a, b| compare_records(a, b, &order));
+        prop_assert_eq!(entries, stored);
+    }
+}
@@ -0,0 +1,126 @@
+// A database keeping 32 reserved bytes at the end of each 1024-byte page, as encryption and
+// checksum extensions do, with rows long enough to spill onto overflow pages. Made with
+//
+//     PRAGMA page_size = 1024;
+//     .filectrl reserve_bytes 32
+//     CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT);
+//     CREATE INDEX notes_body ON notes(body);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+//     INSERT INTO notes SELECT i, printf('%d %.*c', i, i * 13 % 1500, 'r') FROM n;
+//     VACUUM;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::btree_page::{BtreePage, PageType};
+use sqrlite::cell::CellRef;
+use sqrlite::{execute, Database, FieldData, Select, SqrliteError};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/reserved.db");
+const USABLE_SIZE: u64 = 1024 - 32;
+
+fn body(id: i64) -> String {
+    format!("{} {}", id, "r".repeat((id * 13 % 1500) as usize))
+}
+
+// page numbers of every page of a b-tree, children after their parent
+fn tree_pages(db: &Database, root: u32) -> Vec<u32> {
+    let mut pages = vec![];
+    let mut pending = vec![root];
+    let mut buffer = vec![];
+    while let Some(page_num) = pending.pop() {
+        pages.push(page_num);
+        let mut page = BtreePage::default();
+        page.read_page(db, page_num, &mut buffer).unwrap();
+        if let PageType::InteriorTable | PageType::InteriorIndex = page.page_type {
+            for i in 0..page.cell_pointers.len() {
+                let cell = CellRef::get(&page, &buffer, i).unwrap();
+                pending.extend(cell.left_child_pointer());
+            }
+            pending.extend(page.rightmost_ptr);
+        }
+    }
+    pages
+}
+
+#[test]
+fn rows_and_index_entries_read_back() {
+    let db = Database::new(FIXTURE).unwrap();
+    assert_eq!(db.reserved_space, 32);
+    let rows = db.table("notes").unwrap().rows();
+    let rows = rows.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 200);
+    for row in rows {
+        let id = row.rowid.unwrap();
+        assert_eq!(row.get("body"), Some(&FieldData::Text(body(id))), "{}", id);
+    }
+
+    for id in [1, 77, 115, 200] {
+        let sql = format!("SELECT id FROM notes WHERE body = '{}'", body(id));
+        let result = execute(&db, &Select::parse(&sql).unwrap()).unwrap();
+        assert_eq!(result.rows, [[FieldData::Integer(id)]]);
+    }
+}
+
+#[test]
+fn cells_stay_within_the_usable_area() {
+    let db = Database::new(FIXTURE).unwrap();
+    let schema = db.schema().unwrap();
+    for name in ["notes", "notes_body"] {
+        let root = schema.find(name).unwrap().rootpage;
+        let stats = tree_stats(&db, root).unwrap();
+        assert!(stats.corruption.is_empty(), "{:?}", stats.corruption);
+        assert!(stats.overflow_pages > 0);
+
+        let mut buffer = vec![];
+        for page_num in tree_pages(&db, root) {
+            let mut page = BtreePage::default();
+            page.read_page(&db, page_num, &mut buffer).unwrap();
+            assert_eq!(page.usable_size(), USABLE_SIZE);
+            let cells = page.cells(&buffer).collect::<Result<Vec<_>, _>>().unwrap();
+            let used = cells.iter().map(|cell| cell.size as u64).sum::<u64>();
+            let free = page
+                .freeblocks
+                .iter()
+                .map(|&(_, size)| u64::from(size))
+                .sum::<u64>()
+                + u64::from(page.fragmented_bytes);
+            assert_eq!(
+                used + free,
+                USABLE_SIZE - u64::from(page.first_cell_start),
+                "page {}",
+                page_num
+            );
+        }
+    }
+}
+
+#[test]
+fn cell_running_into_the_reserved_bytes_is_an_error() {
+    let db = Database::new(FIXTURE).unwrap();
+    let root = db.table("notes").unwrap().rootpage;
+    let leaf = *tree_pages(&db, root).last().unwrap();
+    let mut page = BtreePage::default();
+    let mut buffer = vec![];
+    page.read_page(&db, leaf, &mut buffer).unwrap();
+    let (index, last) = page
+        .cells(&buffer)
+        .map(Result::unwrap)
+        .enumerate()
+        .max_by_key(|(_, cell)| cell.offset)
+        .unwrap();
+    assert_eq!(last.offset + last.size as u64, USABLE_SIZE);
+
+    // move the cell nearest the end of the page 16 bytes on, into the reserved bytes
+    let mut bytes = std::fs::read(FIXTURE).unwrap();
+    let pointer = (leaf as usize - 1) * 1024 + 8 + 2 * index;
+    let moved = last.offset as u16 + 16;
+    bytes[pointer..pointer + 2].copy_from_slice(&moved.to_be_bytes());
+    let db = Database::from_bytes(bytes).unwrap();
+    let result = page.read_page(&db, leaf, &mut buffer);
+    match result {
+        Err(SqrliteError::CorruptPage(e)) => {
+            assert_eq!(e.page, leaf);
+            assert!(e.reason.contains(&format!("cell {}", index)), "{}", e);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,73 @@
+// Scanning tables backwards: the three-level table of cursor.db, whose rowids are the even
+// numbers 2 to 10000, and the single leaf of shuffled.db, whose cells are stored out of key
+// order. See cursor.rs and cell_order.rs for how they were made.
+
+use sqrlite::{execute, Database, FieldData, ReverseTableScan, Select, SqrliteError, TableScan};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn open(name: &str) -> (Database, u32) {
+    let db = Database::new(format!("{}/{}", FIXTURES, name)).unwrap();
+    let root = db.table("t").unwrap().rootpage;
+    (db, root)
+}
+
+fn rowids(rows: impl Iterator<Item = Result<(i64, Vec<FieldData>), SqrliteError>>) -> Vec<i64> {
+    rows.map(|row| row.unwrap().0).collect()
+}
+
+#[test]
+fn reverses_across_leaf_boundaries() {
+    let (db, root) = open("cursor.db");
+    let forward = rowids(TableScan::new(&db, root).rows());
+    let mut backward = rowids(ReverseTableScan::new(&db, root).rows());
+    assert_eq!(backward.len(), 5000);
+    assert_eq!(backward[..3], [10000, 9998, 9996]);
+    backward.reverse();
+    assert_eq!(backward, forward);
+
+    // the values come with their rows
+    let (rowid, values) = ReverseTableScan::new(&db, root)
+        .rows()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(rowid, 10000);
+    assert_eq!(values[1], FieldData::Text("row 10000".into()));
+}
+
+#[test]
+fn reverses_a_single_leaf() {
+    let (db, root) = open("shuffled.db");
+    assert_eq!(
+        rowids(ReverseTableScan::new(&db, root).rows()),
+        [12, 11, 10, 9, 8, 7, 5, 4, 3, 2, 1]
+    );
+}
+
+#[test]
+fn order_by_rowid_desc_with_limit() {
+    let (db, _) = open("cursor.db");
+    for sql in [
+        "SELECT id FROM t ORDER BY id DESC LIMIT 5",
+        "SELECT id FROM t ORDER BY rowid DESC LIMIT 5",
+    ] {
+        let result = execute(&db, &Select::parse(sql).unwrap()).unwrap();
+        let ids = result
+            .rows
+            .iter()
+            .map(|row| row[0].as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [10000, 9998, 9996, 9994, 9992], "{}", sql);
+    }
+
+    let select = Select::parse("SELECT v FROM t WHERE id < 100 ORDER BY id DESC LIMIT 2").unwrap();
+    let result = execute(&db, &select).unwrap();
+    assert_eq!(
+        result.rows,
+        [
+            [FieldData::Text("row 98".into())],
+            [FieldData::Text("row 96".into())]
+        ]
+    );
+}
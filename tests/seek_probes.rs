@@ -0,0 +1,52 @@
+// Seeking by rowid through leaves holding around a thousand cells each, counting the pages
+// read and the keys compared. Made with
+//
+//     PRAGMA page_size = 8192;
+//     CREATE TABLE t(id INTEGER PRIMARY KEY);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 3000)
+//     INSERT INTO t SELECT i FROM n;
+
+use sqrlite::btree::tree_stats;
+use sqrlite::{Database, SeekResult, TableCursor};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wide.db");
+
+#[test]
+fn seeks_binary_search_each_page() {
+    let db = Database::new(FIXTURE).unwrap();
+    let root = db.table("t").unwrap().rootpage;
+    let stats = tree_stats(&db, root).unwrap();
+    assert!(stats.max_leaf_cells >= 500, "{:?}", stats.levels);
+
+    // a binary search over n cells compares at most floor(log2(n)) + 1 keys, and no page
+    // holds more cells than its whole level
+    let max_probes = stats
+        .levels
+        .iter()
+        .map(|level| (usize::BITS - level.cells.leading_zeros()) as usize)
+        .sum::<usize>();
+
+    let mut cursor = TableCursor::new(&db, root);
+    for rowid in 0..=3001 {
+        cursor.reset_stats();
+        let result = cursor.seek_rowid(rowid).unwrap();
+        let expected = match rowid {
+            0 => SeekResult::After,
+            3001 => SeekResult::PastEnd,
+            _ => SeekResult::Exact,
+        };
+        assert_eq!(result, expected, "{}", rowid);
+
+        let seek = cursor.stats();
+        assert!(
+            seek.key_probes <= max_probes,
+            "{} probes seeking {}",
+            seek.key_probes,
+            rowid
+        );
+        // past the end, the cursor also looks for a following leaf
+        if expected != SeekResult::PastEnd {
+            assert_eq!(seek.pages_read, stats.depth, "{}", rowid);
+        }
+    }
+}
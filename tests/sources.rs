@@ -0,0 +1,114 @@
+// The same databases read from a file path, a byte buffer and arbitrary readers. scan.db has
+// tables with indexes, long_text.db has chains of overflow pages; see overflow.rs for how
+// that one was made.
+
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use sqrlite::{Database, FieldData, SqrliteError};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+type Rows = Vec<(Option<i64>, Vec<FieldData>)>;
+
+// Every row of every table and every entry of every index, by name
+fn contents(db: &Database) -> Vec<(String, Rows)> {
+    let mut contents = vec![];
+    for table in db.tables().unwrap() {
+        let rows = table
+            .rows()
+            .map(|row| row.map(|row| (row.rowid, row.values().to_vec())))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        contents.push((table.name.clone(), rows));
+    }
+    for index in db.indexes().unwrap() {
+        let entries = index.entries().unwrap();
+        contents.push((
+            index.name.clone(),
+            entries.into_iter().map(|entry| (None, entry)).collect(),
+        ));
+    }
+    contents
+}
+
+// Counts the bytes read through it, to show page reads go through the reader
+#[derive(Debug)]
+struct CountingReader {
+    inner: Cursor<Vec<u8>>,
+    read: Arc<AtomicU64>,
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl Seek for CountingReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn every_source_reads_the_same() {
+    for name in ["scan.db", "long_text.db"] {
+        let path = format!("{}/{}", FIXTURES, name);
+        let expected = contents(&Database::new(&path).unwrap());
+        assert!(
+            expected.iter().all(|(_, rows)| !rows.is_empty()),
+            "{}",
+            name
+        );
+
+        let bytes = fs::read(&path).unwrap();
+        let sources = [
+            Database::from_bytes(bytes.clone()).unwrap(),
+            Database::from_reader(Cursor::new(bytes)).unwrap(),
+            Database::from_reader(File::open(&path).unwrap()).unwrap(),
+        ];
+        for db in sources {
+            assert_eq!(db.path(), None);
+            assert_eq!(db.wal_path(), None);
+            assert_eq!(contents(&db), expected, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn pages_are_read_through_the_reader() {
+    let bytes = fs::read(format!("{}/long_text.db", FIXTURES)).unwrap();
+    let read = Arc::new(AtomicU64::new(0));
+    let reader = CountingReader {
+        inner: Cursor::new(bytes),
+        read: Arc::clone(&read),
+    };
+    let db = Database::from_reader(reader).unwrap();
+    let before = read.load(Ordering::Relaxed);
+    let row = db.table("docs").unwrap().get(1).unwrap().unwrap();
+    assert!(matches!(row.get("body"), Some(FieldData::Text(body)) if body.len() == 300_000));
+    // the row's overflow chain alone is 300000 bytes
+    assert!(read.load(Ordering::Relaxed) - before >= 300_000);
+}
+
+#[test]
+fn truncated_buffer() {
+    let mut bytes = fs::read(format!("{}/scan.db", FIXTURES)).unwrap();
+    bytes.truncate(3 * 4096);
+    let db = Database::from_bytes(bytes).unwrap();
+    let result = db
+        .table("readings")
+        .and_then(|table| table.rows().collect::<Result<Vec<_>, _>>());
+    match result {
+        Err(SqrliteError::Truncated(e)) => assert_eq!(e.file_pages, 3),
+        other => panic!("expected a truncated database, got {:?}", other.map(|_| ())),
+    }
+
+    let result = Database::from_bytes(vec![]);
+    assert!(result.is_err());
+}
@@ -0,0 +1,80 @@
+// Values of every storage class, including the integers 0 and 1 that SQLite stores as
+// serial types 8 and 9 with no body, under no affinity, INTEGER and REAL. typeof() must name
+// them as SQLite does; types.txt is the output of sqlite3 3.51.2 for
+//
+//     SELECT id, typeof(v), typeof(i), typeof(r) FROM vals
+//
+// on the fixture, made with
+//
+//     CREATE TABLE vals(id INTEGER PRIMARY KEY, v, i INTEGER, r REAL);
+//     INSERT INTO vals(v, i, r) SELECT column1, column1, column1
+//     FROM (VALUES (0), (1), (2), (-1), (1.0), (0.5), ('1'), ('0'), (''), (x''), (x'01'),
+//         (NULL), (9223372036854775807), (1e308), ('one'));
+
+use std::fs;
+use std::process::Command;
+
+use sqrlite::{execute, Database, FieldData, Select};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn open() -> Database {
+    Database::new(format!("{}/types.db", FIXTURES)).unwrap()
+}
+
+#[test]
+fn constants_zero_and_one_are_integers() {
+    let db = open();
+    let table = db.table("vals").unwrap();
+    let zero = table.get(1).unwrap().unwrap();
+    let one = table.get(2).unwrap().unwrap();
+    assert_eq!(zero.get("v"), Some(&FieldData::BooleanFalse(0)));
+    assert_eq!(one.get("i"), Some(&FieldData::BooleanTrue(1)));
+    for value in [zero.get("v").unwrap(), one.get("i").unwrap()] {
+        assert_eq!(value.type_name(), "integer");
+    }
+}
+
+#[test]
+fn typeof_matches_sqlite() {
+    let db = open();
+    let select = Select::parse("SELECT id, typeof(v), typeof(i), typeof(r) FROM vals").unwrap();
+    let result = execute(&db, &select).unwrap();
+    assert_eq!(
+        result.columns,
+        ["id", "typeof(v)", "typeof(i)", "typeof(r)"]
+    );
+    let lines = result
+        .rows
+        .iter()
+        .map(|row| {
+            let values = row.iter().map(FieldData::to_string).collect::<Vec<_>>();
+            values.join("|")
+        })
+        .collect::<Vec<_>>();
+    let transcript = fs::read_to_string(format!("{}/types.txt", FIXTURES)).unwrap();
+    assert_eq!(lines, transcript.lines().collect::<Vec<_>>());
+}
+
+#[test]
+fn types_flag_follows_each_value_with_its_type() {
+    let output = Command::new(env!("CARGO_BIN_EXE_sqrlite"))
+        .arg(format!("{}/types.db", FIXTURES))
+        .arg("SELECT id, v FROM vals WHERE id < 4")
+        .arg("--types")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1 (integer)|0 (integer)\n2 (integer)|1 (integer)\n3 (integer)|2 (integer)\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sqrlite"))
+        .arg(format!("{}/types.db", FIXTURES))
+        .arg("SELECT v FROM vals")
+        .arg("--typed")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
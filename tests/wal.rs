@@ -0,0 +1,153 @@
+// A WAL-mode database copied, with its -wal file, while a connection held it open: the
+// database file has only the first, checkpointed transaction, and the WAL holds four more
+// committed transactions followed by the frames of one still being written. The `.dbinfo`
+// transcript was recorded with SQLite 3.51.2 from a copy of the pair. Made with
+//
+//     PRAGMA page_size = 1024;
+//     PRAGMA journal_mode = WAL;
+//     PRAGMA wal_autocheckpoint = 0;
+//     CREATE TABLE log(id INTEGER PRIMARY KEY, msg TEXT);
+//     WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+//     INSERT INTO log SELECT i, printf('entry %d', i) FROM n;
+//     PRAGMA wal_checkpoint;
+//     WITH RECURSIVE n(i) AS (SELECT 201 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+//     INSERT INTO log SELECT i, printf('entry %d', i) FROM n;
+//     UPDATE log SET msg = msg || ' (edited)' WHERE id % 7 = 0;
+//     DELETE FROM log WHERE id BETWEEN 100 AND 149;
+//     CREATE INDEX log_msg ON log(msg);
+//     PRAGMA cache_size = 2;
+//     BEGIN;
+//     WITH RECURSIVE n(i) AS (SELECT 501 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+//     INSERT INTO log SELECT i, printf('uncommitted %d', i) FROM n;
+//     .shell cp wal.db out.db; cp wal.db-wal out.db-wal
+//     ROLLBACK;
+
+use std::fs;
+use std::path::PathBuf;
+
+use sqrlite::{execute, DBInfo, Database, FieldData, Select};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const PAGE_SIZE: usize = 1024;
+const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+
+fn path() -> String {
+    format!("{}/wal.db", FIXTURES)
+}
+
+fn ids(db: &Database, sql: &str) -> Vec<i64> {
+    let result = execute(db, &Select::parse(sql).unwrap()).unwrap();
+    result
+        .rows
+        .iter()
+        .map(|row| row[0].as_i64().unwrap())
+        .collect()
+}
+
+// A copy of the fixture and its WAL in the temp directory, removed when dropped
+struct TempCopy(PathBuf);
+
+impl TempCopy {
+    fn wal(&self) -> PathBuf {
+        let mut wal = self.0.clone().into_os_string();
+        wal.push("-wal");
+        wal.into()
+    }
+}
+
+impl Drop for TempCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.wal());
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn committed_frames_are_read() {
+    let db = Database::new(path()).unwrap();
+    let info = db.wal_info().unwrap();
+    assert_eq!(info.page_size, 1024);
+    assert_eq!(info.frame_count, 110);
+    // some frames of the transaction being written check out, up to one caught half-written
+    // by the copy, but none of them commits it
+    assert_eq!(info.valid_frames, 39);
+    assert_eq!(info.committed_frames, 35);
+    assert_eq!(info.database_size, 20);
+    assert_eq!(db.page_count, 20);
+}
+
+#[test]
+fn dbinfo_matches_the_sqlite_shell() {
+    let db = Database::new(path()).unwrap();
+    let info = DBInfo::read_info(&db).unwrap();
+    let transcript = fs::read_to_string(format!("{}/wal.txt", FIXTURES)).unwrap();
+    let expected = transcript
+        .lines()
+        .filter(|line| !line.starts_with("data version"))
+        .collect::<Vec<_>>();
+    assert_eq!(info.to_string().lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn rows_are_those_sqlite_sees() {
+    let db = Database::new(path()).unwrap();
+    let table = db.table("log").unwrap();
+    let rows = table.rows().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(rows.len(), 450);
+    assert_eq!(rows.last().unwrap().rowid, Some(500));
+    let text_len = rows
+        .iter()
+        .map(|row| match row.get("msg") {
+            Some(FieldData::Text(msg)) => msg.len(),
+            other => panic!("unexpected value {:?}", other),
+        })
+        .sum::<usize>();
+    assert_eq!(text_len, 4518);
+
+    let msg = |id| table.get(id).unwrap().map(|row| row.get("msg").cloned());
+    assert_eq!(
+        msg(7),
+        Some(Some(FieldData::Text("entry 7 (edited)".into())))
+    );
+    assert_eq!(msg(99), Some(Some(FieldData::Text("entry 99".into()))));
+    assert_eq!(msg(100), None);
+    assert_eq!(msg(501), None);
+
+    // the index only exists in the WAL
+    assert_eq!(
+        ids(&db, "SELECT id FROM log WHERE msg = 'entry 203 (edited)'"),
+        [203]
+    );
+    assert!(db.schema().unwrap().find("log_msg").is_some());
+}
+
+#[test]
+fn database_file_alone_has_the_checkpointed_rows() {
+    let db = Database::new_ignoring_wal(path()).unwrap();
+    assert!(db.wal_info().is_none());
+    assert_eq!(db.page_count, 6);
+    assert_eq!(db.table("log").unwrap().count().unwrap(), 200);
+    assert!(db.schema().unwrap().find("log_msg").is_none());
+}
+
+#[test]
+fn reading_stops_at_a_frame_failing_its_checksum() {
+    let copy =
+        TempCopy(std::env::temp_dir().join(format!("sqrlite-wal-{}.db", std::process::id())));
+    fs::copy(path(), &copy.0).unwrap();
+    let mut wal = fs::read(format!("{}-wal", path())).unwrap();
+    // a byte of the page image in frame 30, inside the last committed transaction
+    let frame = WAL_HEADER_SIZE + 30 * (FRAME_HEADER_SIZE + PAGE_SIZE);
+    wal[frame + FRAME_HEADER_SIZE + 100] ^= 0xff;
+    fs::write(copy.wal(), wal).unwrap();
+
+    // the WAL ends at the commit before it, which left the database 12 pages long
+    let db = Database::new(&copy.0).unwrap();
+    let info = db.wal_info().unwrap();
+    assert_eq!(info.valid_frames, 30);
+    assert_eq!(info.committed_frames, 25);
+    assert_eq!(db.page_count, 12);
+    assert!(db.schema().unwrap().find("log_msg").is_none());
+    assert_eq!(db.table("log").unwrap().count().unwrap(), 450);
+}